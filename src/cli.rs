@@ -0,0 +1,814 @@
+//! Command line arguments, configuration file, and logger
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    path::Path,
+};
+use clap::{CommandFactory, FromArgMatches, Parser, parser::ValueSource};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use crate::input::DEFAULT_ALPHABET;
+use crate::links::SORT_CHUNK_SIZE;
+use crate::merge::CollapsePolicy;
+use crate::names::{NameScheme, RangeSyntax};
+use crate::open::{DECOMPRESS_BUDGET, is_url};
+use crate::output::{LineEnding, SEGMENT_BATCH};
+use crate::write::{FragmentNaming, NodeOrder, PathOrder};
+
+#[derive(Parser, Debug, Serialize)]
+#[clap(author, version, about)]
+pub(crate) struct Args {
+    /// TOML file with values for any of the options; options given on the command line take precedence
+    #[clap(long)]
+    #[serde(skip)]
+    pub(crate) config: Option<String>,
+
+    /// List of GFA file paths (or HTTP(S) URLs, with the 'http' feature) to combine, given after one
+    /// or more -g flags, comma-separated, or as trailing arguments, and taken in the order given.
+    /// Tar archives (possibly compressed) contribute each of their '*.gfa'/'*.gfa.gz' members
+    #[clap(short, long, value_parser, num_args = 1..)]
+    pub(crate) gfa_list: Vec<String>,
+
+    /// File with more GFA file paths, one per line (empty and '#' lines skipped), taken where the
+    /// option is given among the other inputs
+    #[clap(long)]
+    #[serde(skip)]
+    pub(crate) gfa_list_file: Option<String>,
+
+    /// GFA file paths, as for --gfa-list
+    #[clap(value_name = "GFA")]
+    #[serde(skip)]
+    pub(crate) inputs: Vec<String>,
+
+    /// Whether a --gfa-list value was split on spaces, which is deprecated
+    #[clap(skip)]
+    #[serde(skip)]
+    pub(crate) space_delimited_gfa_list: bool,
+
+    /// Paths split from a --gfa-list value that do not exist, to warn about
+    #[clap(skip)]
+    #[serde(skip)]
+    pub(crate) missing_split_inputs: Vec<String>,
+
+    /// Read the members of tar archives sorted by name instead of in archive order
+    #[clap(long)]
+    pub(crate) sort_archive: bool,
+
+    /// GAF files with more paths over the segments of the input GFAs, one per input (in the same order)
+    #[clap(long, num_args = 1.., value_delimiter = ' ')]
+    pub(crate) gaf: Vec<String>,
+
+    /// Output GFA file path for the combined graph
+    #[clap(short, long, value_parser)]
+    pub(crate) output: Option<String>,
+
+    /// Overwrite the output and auxiliary files (and the log file) if they exist, instead of refusing to run
+    #[clap(long)]
+    pub(crate) force: bool,
+
+    /// Write OUTPUT.sha256 for `sha256sum -c`, with the SHA-256 of the segments, links, and paths in a comment
+    #[clap(long)]
+    pub(crate) checksum: bool,
+
+    /// Log the histograms of the node in-degrees and out-degrees of the output graph, and their nodes of highest degree
+    #[clap(long)]
+    pub(crate) report_degrees: bool,
+
+    /// Log how fragmented the input path keys are: histograms of their ranges and gaps, and the keys with the most gaps and overlaps
+    #[clap(long)]
+    pub(crate) report_ranges: bool,
+
+    /// Name the output nodes after their input file and segment ID (e.g. b3_17 for segment 17 of the fourth input)
+    #[clap(long)]
+    pub(crate) id_prefix_by_file: bool,
+
+    /// Tag each segment with its input file, as `SF:Z:FILE`, or `SF:Z:gfalace` for the gap nodes and the nodes split by gfalace
+    #[clap(long)]
+    pub(crate) tag_origin: bool,
+
+    /// Append a `# block-group` comment per input with the output IDs of the nodes it contributed,
+    /// and one for the gap nodes
+    #[clap(long)]
+    pub(crate) emit_block_groups: bool,
+
+    /// Offsets added to the segment IDs of each input, one per input GFA in order, instead of the largest ID of the previous ones
+    #[clap(long, value_delimiter = ',')]
+    pub(crate) id_offsets: Option<Vec<u64>>,
+
+    /// Offset the segment IDs of the i-th input (from 0) by i times this, so that each input gets its own window of IDs
+    #[clap(long, value_parser = parse_nonzero)]
+    pub(crate) id_spacing: Option<usize>,
+
+    /// Number the output nodes from this ID instead of 1, e.g. to give the graphs of different chromosomes disjoint IDs
+    #[clap(long, default_value_t = 1, value_parser = parse_nonzero)]
+    pub(crate) first_id: usize,
+
+    /// Print shape metrics of the output graph: length, node lengths, edges, degrees, components, and paths
+    #[clap(long)]
+    pub(crate) graph_stats: bool,
+
+    /// TSV file mapping each stretch of the merged paths to its source GFA file, path, and coordinates
+    #[clap(long)]
+    pub(crate) liftover: Option<String>,
+
+    /// TSV file with the length, span, coverage, and gaps of the merged path of each path key
+    #[clap(long)]
+    pub(crate) sample_table: Option<String>,
+
+    /// TSV file with the fraction of its contig covered, the number of fragments, and the completeness of each path key, also summarized by sample in the log
+    #[clap(long)]
+    pub(crate) completeness: Option<String>,
+
+    /// Gap filling mode: 0=none, 1=middle gaps only, 2=all gaps (requires --fasta for end gaps)
+    #[clap(long, default_value = "0")]
+    pub(crate) fill_gaps: u8,
+
+    /// Skip the path keys whose ranges cover less than this fraction of their span
+    #[clap(long)]
+    pub(crate) min_coverage_fraction: Option<f64>,
+
+    /// Skip the path keys that are not a single gapless path over their whole contig (of --contig-lengths length, or else from 0 to the end of their last range)
+    #[clap(long)]
+    pub(crate) only_complete: bool,
+
+    /// Count the gaps filled with --fill-gaps as covered for --min-coverage-fraction
+    #[clap(long)]
+    pub(crate) count_filled_gaps: bool,
+
+    /// FASTA file containing sequences for gap filling
+    #[clap(long)]
+    pub(crate) fasta: Option<String>,
+
+    /// Write '*' with an LN tag instead of the segment sequences (topology-only output)
+    #[clap(long)]
+    pub(crate) no_sequences: bool,
+
+    /// Skip the links, writing only segments and paths (for consumers that rebuild adjacency from path steps)
+    #[clap(long)]
+    pub(crate) no_links: bool,
+
+    /// Write only the header and the paths, skipping the segments, links, and containments (for path-extraction workflows)
+    #[clap(long)]
+    pub(crate) paths_only: bool,
+
+    /// FASTA file with the sequence of each merged path, the concatenation of its steps (gzip or zstd compressed by extension)
+    #[clap(long)]
+    pub(crate) paths_fasta: Option<String>,
+
+    /// Line ending of the output GFA: `lf`, or `crlf` for Windows consumers
+    #[clap(long, value_enum, default_value_t = LineEnding::Lf)]
+    pub(crate) line_ending: LineEnding,
+
+    /// Split the output into shards of at most this many bytes, `<output>.0000.gfa`, ..., with the
+    /// links across shards in `<output>.links.gfa`, the paths in `<output>.paths.gfa`, and a
+    /// `<output>.manifest.json` describing them (the `.gfa` extension of the output is dropped)
+    #[clap(long, value_parser = parse_nonzero)]
+    pub(crate) shard_size: Option<usize>,
+
+    /// Naive joining of paths by name rather than range
+    #[clap(long)]
+    pub(crate) naive_join: bool,
+
+    /// Naming scheme of the path names: PanSN `sample#hap#contig`, minigraph-cactus `id=sample|contig`, detected (and required to be the same across inputs), or both
+    #[clap(long, value_enum, default_value_t = NameScheme::Auto)]
+    pub(crate) name_scheme: NameScheme,
+
+    /// Template of the input path names, e.g. `{sample}.{contig}_{start}_{end}`, with the placeholders {sample}, {hap}, {contig}, {start}, and {end}
+    #[clap(long)]
+    pub(crate) path_format: Option<String>,
+
+    /// TSV of exact path names with the key, start, and end to use for them, before parsing the names
+    #[clap(long)]
+    pub(crate) key_map: Option<String>,
+
+    /// Syntax of the range at the end of the path names, also used to name the fragments: `KEY:START-END`, `KEY_START_END`, or `KEY:START_END`
+    #[clap(long, value_enum, default_value_t = RangeSyntax::ColonDash)]
+    pub(crate) range_delimiters: RangeSyntax,
+
+    /// Collapse the haplotypes of each sample#hap#contig path key onto a single sample#contig path
+    #[clap(long)]
+    pub(crate) collapse_haplotypes: bool,
+
+    /// What to do when collapsing haplotypes whose ranges or steps differ: keep them as separate paths, or fail
+    #[clap(long, value_enum, default_value_t = CollapsePolicy::Bubble)]
+    pub(crate) collapse_policy: CollapsePolicy,
+
+    /// FASTA file with the sample assemblies to verify the sequence continuity at junctions between ranges
+    #[clap(long)]
+    pub(crate) check_junctions: Option<String>,
+
+    /// Report the adjacent steps of the merged paths without an edge between them (fatal under --strict)
+    #[clap(long)]
+    pub(crate) check_path_edges: bool,
+
+    /// Treat validation problems (e.g. junction mismatches, duplicate segment IDs in an input) as fatal errors
+    #[clap(long)]
+    pub(crate) strict: bool,
+
+    /// Warn about the segments with characters outside this alphabet, which are fatal under --strict [default: ACGTNacgtn]
+    #[clap(long, value_name = "ALPHABET", num_args = 0..=1, default_missing_value = DEFAULT_ALPHABET)]
+    pub(crate) check_alphabet: Option<String>,
+
+    /// Parse the output back and verify its segments, links, and paths against the combined graph
+    #[clap(long)]
+    pub(crate) check_roundtrip: bool,
+
+    /// Flag the gaps between the ranges of a path key longer than this, which often reveal a missing block (an error with --strict) [default: unlimited]
+    #[clap(long)]
+    pub(crate) max_gap: Option<usize>,
+
+    /// Overlap in bp of the tiled blocks: overlaps of exactly this size between ranges of different blocks are trimmed silently, and other overlaps are flagged (an error with --strict)
+    #[clap(long)]
+    pub(crate) expected_overlap: Option<usize>,
+
+    /// Contig lengths (a FASTA .fai, or a TSV of names and lengths) to report the gaps at the ends of the path keys and the missing contigs
+    #[clap(long)]
+    pub(crate) contig_lengths: Option<String>,
+
+    /// TSV of path keys, inputs (by index from 0 or by path), and ranks, to order the ranges of
+    /// these keys by rank instead of by the coordinates of their path names
+    #[clap(long)]
+    pub(crate) range_order: Option<String>,
+
+    /// Partitioning TSV, with a header, of the range of each contig (path key) in each input file.
+    /// These ranges override those of the path names, or name the paths without one. Without
+    /// --gfa-list, its files are the inputs, and without --contig-lengths, its largest ends are the contig lengths
+    #[clap(long)]
+    pub(crate) blocks_tsv: Option<String>,
+
+    /// Header names of the file, contig, start, and end columns of --blocks-tsv
+    #[clap(long, value_delimiter = ',', num_args = 4, default_value = "file,contig,start,end")]
+    pub(crate) blocks_tsv_columns: Vec<String>,
+
+    /// Maximum number of steps of a single merged path, to abort on runaway merges [default: unlimited]
+    #[clap(long)]
+    pub(crate) max_path_steps: Option<usize>,
+
+    /// Split the nodes longer than this into chains of nodes of at most this length (e.g. 1024 for vg) [default: unlimited]
+    #[clap(long, value_parser = parse_nonzero)]
+    pub(crate) max_node_length: Option<usize>,
+
+    /// Trim the bases duplicated by the overlapping links traversed by the paths, splitting nodes, so that the output is blunt
+    #[clap(long)]
+    pub(crate) blunt: bool,
+
+    /// Remove the edges that no merged path traverses
+    #[clap(long)]
+    pub(crate) drop_unused_edges: bool,
+
+    /// Skip the L lines of the inputs, and link the consecutive steps of the paths instead
+    #[clap(long)]
+    pub(crate) ignore_input_links: bool,
+
+    /// Remove the nodes that no merged path visits, with their edges
+    #[clap(long)]
+    pub(crate) prune_orphans: bool,
+
+    /// Copy the comment lines ('#') of the input files near the top of the output, tagged with their source file
+    #[clap(long)]
+    pub(crate) preserve_comments: bool,
+
+    /// Copy the lines of unrecognized record types of the input files verbatim to the output
+    #[clap(long)]
+    pub(crate) passthrough_unknown: bool,
+
+    /// List the path keys found in the input files, with their ranges and contributing files, and exit
+    #[clap(long)]
+    pub(crate) list_samples: bool,
+
+    /// Check that the path names of the input files can be parsed as NAME:START-END, report the failures, and exit
+    #[clap(long)]
+    pub(crate) validate_input: bool,
+
+    /// Keep the decompressed temporary file of a compressed input that fails to parse, and print its path
+    #[clap(long)]
+    pub(crate) keep_temp: bool,
+
+    /// Number of gzipped inputs decompressed on other threads ahead of their parsing (0 = decompress each when parsed)
+    #[clap(long, default_value = "4")]
+    pub(crate) decompress_ahead: usize,
+
+    /// Bytes of decompressed inputs waiting to be parsed, past which no other input is decompressed ahead
+    #[clap(long, default_value_t = DECOMPRESS_BUDGET)]
+    pub(crate) decompress_budget: u64,
+
+    /// Read uncompressed input files with buffered reads instead of memory-mapping them (e.g. on network filesystems).
+    /// A mapped input that another process truncates or modifies during the run can crash gfalace
+    #[clap(long)]
+    pub(crate) no_mmap: bool,
+
+    /// Read the inputs line by line into the combined graph, instead of parsing each whole into a block graph first,
+    /// for chromosome-scale single blocks
+    #[clap(long)]
+    pub(crate) stream_parse: bool,
+
+    /// Keep the node sequences in a temporary file instead of in memory, holding only the topology and the path steps
+    #[clap(long)]
+    pub(crate) low_memory: bool,
+
+    /// Directory of the temporary files of --low-memory and --external-sort (default: the system temporary directory)
+    #[clap(long, alias = "tmpdir")]
+    pub(crate) tmp_dir: Option<String>,
+
+    /// Number of retries of failed requests for input GFAs given as HTTP(S) URLs
+    #[cfg(feature = "http")]
+    #[clap(long, default_value = "3")]
+    pub(crate) http_retries: u32,
+
+    /// Number of threads for the range analysis (0 = all available cores)
+    #[clap(short, long, default_value = "0")]
+    pub(crate) threads: usize,
+
+    /// Order of the output paths: by name, by name and then numerically by coordinates, or unsorted
+    #[clap(long, value_enum, default_value_t = PathOrder::Name)]
+    pub(crate) sort_paths_by: PathOrder,
+
+    /// Naming of the paths of non-contiguous ranges: KEY:START-END, KEY#INDEX (PanSN fragment field), or KEY[START-END]
+    #[clap(long, value_enum, default_value_t = FragmentNaming::Range)]
+    pub(crate) fragment_naming: FragmentNaming,
+
+    /// Stream the segments and links in node ID order through a buffered writer, instead of sorting them in memory.
+    /// The output is byte-for-byte the same
+    #[clap(long)]
+    pub(crate) no_sort: bool,
+
+    /// Number of segments and links written to the output buffer between flushes
+    #[clap(long, default_value_t = SEGMENT_BATCH)]
+    pub(crate) segment_batch: usize,
+
+    /// Sort the links on disk, in sorted chunks of temporary files in --tmp-dir that are then merged, instead of in memory
+    #[clap(long)]
+    pub(crate) external_sort: bool,
+
+    /// Number of links sorted in memory at once, in each chunk of --external-sort
+    #[clap(long, default_value_t = SORT_CHUNK_SIZE)]
+    pub(crate) sort_chunk_size: usize,
+
+    /// Order of the output node IDs: that of the input IDs, or of the first visit by the paths (walked in output order)
+    #[clap(long, value_enum, default_value_t = NodeOrder::Id)]
+    pub(crate) node_order: NodeOrder,
+
+    /// Format of the log messages: human-readable text, or one JSON object per line
+    #[clap(long, value_enum, default_value_t = LogFormat::Human)]
+    pub(crate) log_format: LogFormat,
+
+    /// Also write every event up to the debug level (files parsed, gaps, overlaps, paths written,
+    /// and the final summary) to this file, one JSON object per line, whatever the verbosity
+    #[clap(long)]
+    pub(crate) log_file: Option<String>,
+
+    /// Verbosity level (0 = warning, 1 = info, 2 = debug)
+    #[clap(short, long, default_value = "0")]
+    pub(crate) verbose: u8,
+
+    /// Only log fatal errors and the final summary, or only fatal errors if repeated (-qq)
+    #[clap(short, long, action = clap::ArgAction::Count, conflicts_with = "verbose")]
+    pub(crate) quiet: u8,
+
+    #[clap(subcommand)]
+    #[serde(skip)]
+    pub(crate) command: Option<Command>,
+}
+
+/// Operations on laced graphs, run instead of lacing
+#[derive(clap::Subcommand, Debug)]
+pub(crate) enum Command {
+    /// Extract the part of a laced graph traversed by its paths within a region as a standalone GFA,
+    /// with the paths named after their extracted coordinates so that it can be laced again
+    Extract(ExtractArgs),
+    /// Compare two laced graphs by content: their path names, path sequences and node boundaries,
+    /// node and edge counts, and coverage by contig. Exits with 1 if they differ
+    Diff(DiffArgs),
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct ExtractArgs {
+    /// Laced GFA file
+    #[clap(short, long)]
+    pub(crate) input: String,
+
+    /// Region as CONTIG:START-END (0-based, end excluded, commas ignored), matching the path keys whose last field is CONTIG
+    #[clap(short, long)]
+    pub(crate) region: String,
+
+    /// Output GFA file for the extracted graph
+    #[clap(short, long)]
+    pub(crate) output: String,
+
+    /// Keep the nodes that cross the region boundaries whole instead of trimming them to the region
+    #[clap(long)]
+    pub(crate) whole_nodes: bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct DiffArgs {
+    /// Laced GFA file to compare against
+    pub(crate) old: String,
+
+    /// Laced GFA file to compare
+    pub(crate) new: String,
+
+    /// Also write the differences as JSON to this file
+    #[clap(long)]
+    pub(crate) json: Option<String>,
+
+    /// List the paths that differ, one line each, besides the summary
+    #[clap(long)]
+    pub(crate) detail: bool,
+}
+
+/// Target of the end-of-run summary, which is logged unless -qq is given
+pub(crate) const SUMMARY_TARGET: &str = "gfalace::summary";
+
+/// Configures the logger from the verbosity and quiet levels and the log format
+pub(crate) fn logger_builder(args: &Args) -> env_logger::Builder {
+    let mut logger = env_logger::Builder::new();
+    logger.filter_level(match (args.quiet, args.verbose) {
+        (1.., _) => log::LevelFilter::Error,
+        (_, 0) => log::LevelFilter::Warn,
+        (_, 1) => log::LevelFilter::Info,
+        _ => log::LevelFilter::Debug,
+    });
+    if args.quiet < 2 {
+        logger.filter_module(SUMMARY_TARGET, log::LevelFilter::Info);
+    }
+    if args.log_format == LogFormat::Json {
+        logger.format(|buf, record| writeln!(buf, "{}", format_json_record(record)));
+    }
+    logger
+}
+
+/// Logger writing to stderr as configured, and every event of gfalace up to the debug level to a
+/// file, one JSON object per line as with `--log-format json`
+pub(crate) struct FileLogger {
+    pub(crate) stderr: env_logger::Logger,
+    pub(crate) file: Mutex<File>,
+}
+
+impl log::Log for FileLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.stderr.enabled(metadata) || Self::logged_to_file(metadata)
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.stderr.matches(record) {
+            self.stderr.log(record);
+        }
+        if Self::logged_to_file(record.metadata()) {
+            // One write per event, so that lines are not interleaved or lost on exit
+            let line = format!("{}\n", format_json_record(record));
+            let _ = self.file.lock().unwrap_or_else(|e| e.into_inner()).write_all(line.as_bytes());
+        }
+    }
+
+    fn flush(&self) {
+        self.stderr.flush();
+        let _ = self.file.lock().unwrap_or_else(|e| e.into_inner()).flush();
+    }
+}
+
+impl FileLogger {
+    pub(crate) fn logged_to_file(metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::Level::Debug && metadata.target().starts_with(env!("CARGO_CRATE_NAME"))
+    }
+}
+
+/// Installs the logger, with the events also written to `--log-file` if given
+pub(crate) fn init_logger(args: &Args) -> io::Result<()> {
+    let Some(log_path) = &args.log_file else {
+        logger_builder(args).init();
+        return Ok(());
+    };
+    if let Some((_, input)) = input_collisions(args, std::slice::from_ref(log_path)).first() {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("'{}' is the input {}", log_path, input)));
+    }
+    if !args.force && Path::new(log_path).exists() {
+        return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("'{}' exists, use --force to overwrite it", log_path)));
+    }
+    let stderr = logger_builder(args).build();
+    let max_level = stderr.filter().max(log::LevelFilter::Debug);
+    log::set_boxed_logger(Box::new(FileLogger { stderr, file: Mutex::new(File::create(log_path)?) }))
+        .map_err(io::Error::other)?;
+    log::set_max_level(max_level);
+    Ok(())
+}
+
+/// Format of the log messages
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum LogFormat {
+    Human,
+    Json,
+}
+
+/// Formats a log record as a JSON object with its level, stage (parse, merge, or write),
+/// message, and the structured fields given at the call site (e.g. file, path_key)
+pub(crate) fn format_json_record(record: &log::Record) -> String {
+    struct Fields(serde_json::Map<String, serde_json::Value>);
+    impl<'kvs> log::kv::VisitSource<'kvs> for Fields {
+        fn visit_pair(&mut self, key: log::kv::Key<'kvs>, value: log::kv::Value<'kvs>) -> Result<(), log::kv::Error> {
+            let value = if let Some(number) = value.to_u64() {
+                number.into()
+            } else if let Some(number) = value.to_i64() {
+                number.into()
+            } else if let Some(flag) = value.to_bool() {
+                flag.into()
+            } else {
+                value.to_string().into()
+            };
+            self.0.insert(key.to_string(), value);
+            Ok(())
+        }
+    }
+
+    let mut fields = Fields(serde_json::Map::new());
+    fields.0.insert("level".to_string(), record.level().as_str().to_lowercase().into());
+    fields.0.insert("stage".to_string(), serde_json::Value::Null);
+    let _ = record.key_values().visit(&mut fields);
+    fields.0.insert("message".to_string(), record.args().to_string().trim().into());
+
+    serde_json::Value::Object(fields.0).to_string()
+}
+
+/// Options read from a `--config` TOML file, with the same names as the command line options
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub(crate) struct Config {
+    pub(crate) gfa_list: Option<Vec<String>>,
+    pub(crate) gfa_list_file: Option<String>,
+    pub(crate) sort_archive: Option<bool>,
+    pub(crate) gaf: Option<Vec<String>>,
+    pub(crate) output: Option<String>,
+    pub(crate) force: Option<bool>,
+    pub(crate) checksum: Option<bool>,
+    pub(crate) report_degrees: Option<bool>,
+    pub(crate) report_ranges: Option<bool>,
+    pub(crate) id_prefix_by_file: Option<bool>,
+    pub(crate) tag_origin: Option<bool>,
+    pub(crate) emit_block_groups: Option<bool>,
+    pub(crate) id_offsets: Option<Vec<u64>>,
+    pub(crate) id_spacing: Option<usize>,
+    pub(crate) first_id: Option<usize>,
+    pub(crate) graph_stats: Option<bool>,
+    pub(crate) liftover: Option<String>,
+    pub(crate) sample_table: Option<String>,
+    pub(crate) completeness: Option<String>,
+    pub(crate) fill_gaps: Option<u8>,
+    pub(crate) min_coverage_fraction: Option<f64>,
+    pub(crate) only_complete: Option<bool>,
+    pub(crate) count_filled_gaps: Option<bool>,
+    pub(crate) fasta: Option<String>,
+    pub(crate) no_sequences: Option<bool>,
+    pub(crate) no_links: Option<bool>,
+    pub(crate) paths_only: Option<bool>,
+    pub(crate) paths_fasta: Option<String>,
+    pub(crate) line_ending: Option<LineEnding>,
+    pub(crate) shard_size: Option<usize>,
+    pub(crate) naive_join: Option<bool>,
+    pub(crate) name_scheme: Option<NameScheme>,
+    pub(crate) path_format: Option<String>,
+    pub(crate) key_map: Option<String>,
+    pub(crate) range_delimiters: Option<RangeSyntax>,
+    pub(crate) collapse_haplotypes: Option<bool>,
+    pub(crate) collapse_policy: Option<CollapsePolicy>,
+    pub(crate) check_junctions: Option<String>,
+    pub(crate) check_path_edges: Option<bool>,
+    pub(crate) strict: Option<bool>,
+    pub(crate) check_alphabet: Option<String>,
+    pub(crate) check_roundtrip: Option<bool>,
+    pub(crate) max_gap: Option<usize>,
+    pub(crate) expected_overlap: Option<usize>,
+    pub(crate) contig_lengths: Option<String>,
+    pub(crate) range_order: Option<String>,
+    pub(crate) blocks_tsv: Option<String>,
+    pub(crate) blocks_tsv_columns: Option<Vec<String>>,
+    pub(crate) max_path_steps: Option<usize>,
+    pub(crate) max_node_length: Option<usize>,
+    pub(crate) blunt: Option<bool>,
+    pub(crate) drop_unused_edges: Option<bool>,
+    pub(crate) ignore_input_links: Option<bool>,
+    pub(crate) prune_orphans: Option<bool>,
+    pub(crate) preserve_comments: Option<bool>,
+    pub(crate) passthrough_unknown: Option<bool>,
+    pub(crate) list_samples: Option<bool>,
+    pub(crate) validate_input: Option<bool>,
+    pub(crate) keep_temp: Option<bool>,
+    pub(crate) decompress_ahead: Option<usize>,
+    pub(crate) decompress_budget: Option<u64>,
+    pub(crate) no_mmap: Option<bool>,
+    pub(crate) stream_parse: Option<bool>,
+    pub(crate) low_memory: Option<bool>,
+    pub(crate) tmp_dir: Option<String>,
+    #[cfg(feature = "http")]
+    pub(crate) http_retries: Option<u32>,
+    pub(crate) threads: Option<usize>,
+    pub(crate) sort_paths_by: Option<PathOrder>,
+    pub(crate) fragment_naming: Option<FragmentNaming>,
+    pub(crate) no_sort: Option<bool>,
+    pub(crate) segment_batch: Option<usize>,
+    pub(crate) external_sort: Option<bool>,
+    pub(crate) sort_chunk_size: Option<usize>,
+    pub(crate) node_order: Option<NodeOrder>,
+    pub(crate) log_format: Option<LogFormat>,
+    pub(crate) log_file: Option<String>,
+    pub(crate) verbose: Option<u8>,
+    pub(crate) quiet: Option<u8>,
+}
+
+pub(crate) fn read_config(config_path: &str) -> io::Result<Config> {
+    let content = std::fs::read_to_string(config_path).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("Failed to read config file '{}': {}", config_path, e)
+        )
+    })?;
+    toml::from_str(&content).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid config file '{}': {}", config_path, e)
+        )
+    })
+}
+
+/// Parses the command line and fills the options it does not set from the `--config` file, if any
+pub(crate) fn parse_args<I, T>(argv: I) -> io::Result<Args>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<std::ffi::OsString> + Clone,
+{
+    let matches = Args::command().get_matches_from(argv);
+    let mut args = Args::from_arg_matches(&matches).map_err(io::Error::other)?;
+    (args.gfa_list, args.space_delimited_gfa_list, args.missing_split_inputs) = command_line_inputs(&matches)?;
+    // A first trailing input named like a subcommand runs it, so inputs before it are a mistake
+    if args.command.is_some() && !args.gfa_list.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Input GFA files cannot be given with the extract or diff subcommands (write an input file named 'extract' or 'diff' as './extract' or './diff')"
+        ));
+    }
+    let Some(config_path) = args.config.clone() else {
+        return Ok(args);
+    };
+    let config = read_config(&config_path)?;
+
+    // Inputs given in any form on the command line replace those of the config file, whose list
+    // file entries follow its gfa-list
+    if args.gfa_list.is_empty() {
+        args.gfa_list = config.gfa_list.unwrap_or_default();
+        if let Some(list_path) = &config.gfa_list_file {
+            args.gfa_list.extend(read_gfa_list_file(list_path)?);
+        }
+    }
+
+    let on_command_line = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+    macro_rules! apply {
+        ($($field:ident),*) => {
+            $(if !on_command_line(stringify!($field)) && let Some(value) = config.$field {
+                args.$field = value.into();
+            })*
+        };
+    }
+    apply!(gfa_list_file, sort_archive, gaf, output, force, checksum, report_degrees, report_ranges, id_prefix_by_file, tag_origin, emit_block_groups, id_offsets, id_spacing, first_id, graph_stats, liftover, sample_table, completeness, fill_gaps, min_coverage_fraction, only_complete, count_filled_gaps, fasta, no_sequences, no_links, paths_only, paths_fasta, line_ending, shard_size, naive_join, name_scheme, path_format, key_map, range_delimiters,
+        collapse_haplotypes, collapse_policy,
+        check_junctions, check_path_edges, strict, check_alphabet, check_roundtrip, max_gap, expected_overlap, contig_lengths, range_order, blocks_tsv, blocks_tsv_columns, max_path_steps, max_node_length, blunt, drop_unused_edges, ignore_input_links, prune_orphans, preserve_comments, passthrough_unknown,
+        list_samples, validate_input, keep_temp, decompress_ahead, decompress_budget, no_mmap, stream_parse, low_memory, tmp_dir, threads, sort_paths_by, fragment_naming, no_sort, segment_batch, external_sort, sort_chunk_size, node_order, log_format, log_file, verbose, quiet);
+    #[cfg(feature = "http")]
+    apply!(http_retries);
+
+    Ok(args)
+}
+
+/// Merges the inputs given after -g, as trailing arguments, and in --gfa-list-file, in their order
+/// on the command line. Also returns whether a value was split on spaces, and the split paths that
+/// do not exist
+pub(crate) fn command_line_inputs(matches: &clap::ArgMatches) -> io::Result<(Vec<String>, bool, Vec<String>)> {
+    let mut positioned: Vec<(usize, Vec<String>)> = Vec::new();
+    let mut space_delimited = false;
+    let mut missing = Vec::new();
+    for id in ["gfa_list", "inputs"] {
+        if let (Some(values), Some(indices)) = (matches.get_many::<String>(id), matches.indices_of(id)) {
+            for (value, index) in values.zip(indices) {
+                let (paths, split_on_spaces) = split_gfa_list_value(value);
+                space_delimited |= split_on_spaces;
+                if paths.len() > 1 {
+                    missing.extend(paths.iter().filter(|path| !is_url(path) && !Path::new(path).exists()).cloned());
+                }
+                positioned.push((index, paths));
+            }
+        }
+    }
+    if let Some(list_path) = matches.get_one::<String>("gfa_list_file") {
+        let index = matches.index_of("gfa_list_file").unwrap_or(0);
+        positioned.push((index, read_gfa_list_file(list_path)?));
+    }
+    positioned.sort_by_key(|&(index, _)| index);
+    Ok((positioned.into_iter().flat_map(|(_, paths)| paths).collect(), space_delimited, missing))
+}
+
+/// Splits a --gfa-list value into paths. A value naming an existing file or a URL is kept whole, so
+/// that paths may contain commas or spaces; others are split on commas, or else on spaces, the
+/// deprecated form, which is reported by the returned flag
+pub(crate) fn split_gfa_list_value(value: &str) -> (Vec<String>, bool) {
+    if is_url(value) || Path::new(value).exists() {
+        (vec![value.to_string()], false)
+    } else if value.contains(',') {
+        (value.split(',').filter(|path| !path.is_empty()).map(String::from).collect(), false)
+    } else if value.contains(' ') {
+        (value.split_whitespace().map(String::from).collect(), true)
+    } else {
+        (vec![value.to_string()], false)
+    }
+}
+
+/// Reads the GFA file paths of a --gfa-list-file, one per line as given, skipping empty and '#' lines
+pub(crate) fn read_gfa_list_file(list_path: &str) -> io::Result<Vec<String>> {
+    let mut paths = Vec::new();
+    for line in BufReader::new(File::open(list_path)?).lines() {
+        let line = line?;
+        let path = line.strip_suffix('\r').unwrap_or(&line);
+        if !path.is_empty() && !path.starts_with('#') {
+            paths.push(path.to_string());
+        }
+    }
+    Ok(paths)
+}
+
+/// Files that the run would overwrite: the output GFA, or its shards with --shard-size, and the
+/// auxiliary outputs
+pub(crate) fn existing_outputs(args: &Args, output: &str) -> io::Result<Vec<String>> {
+    let mut existing: Vec<String> = output_files(args, output)?.into_iter().filter(|path| Path::new(path).exists()).collect();
+    existing.sort();
+    existing.dedup();
+    Ok(existing)
+}
+
+/// Files that the run writes: the output GFA, or its shards with --shard-size (including those of a
+/// previous run), and the auxiliary outputs
+pub(crate) fn output_files(args: &Args, output: &str) -> io::Result<Vec<String>> {
+    let mut outputs: Vec<String> = Vec::new();
+    if args.shard_size.is_some() {
+        let base = output.strip_suffix(".gfa").unwrap_or(output);
+        outputs.extend(["links.gfa", "paths.gfa", "manifest.json"].map(|suffix| format!("{}.{}", base, suffix)));
+        // Any numbered shard of a previous run, whatever their number
+        let base_path = Path::new(base);
+        let directory = base_path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        let prefix = format!("{}.", base_path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default());
+        if directory.is_dir() {
+            for entry in std::fs::read_dir(directory)? {
+                let file_name = entry?.file_name();
+                let is_shard = file_name.to_str()
+                    .and_then(|name| name.strip_prefix(&prefix))
+                    .and_then(|name| name.strip_suffix(".gfa"))
+                    .is_some_and(|index| index.len() == 4 && index.bytes().all(|byte| byte.is_ascii_digit()));
+                if is_shard {
+                    outputs.push(directory.join(file_name).to_string_lossy().into_owned());
+                }
+            }
+        }
+    } else {
+        outputs.push(output.to_string());
+    }
+    if args.checksum {
+        outputs.push(format!("{}.sha256", output));
+    }
+    outputs.extend([&args.liftover, &args.sample_table, &args.completeness, &args.paths_fasta].into_iter().flatten().cloned());
+    Ok(outputs)
+}
+
+/// Local files that the run reads: the input GFAs (or their archives), and the auxiliary inputs
+pub(crate) fn input_files(args: &Args) -> Vec<&str> {
+    let auxiliary = [&args.config, &args.gfa_list_file, &args.blocks_tsv, &args.fasta, &args.check_junctions, &args.contig_lengths, &args.range_order, &args.key_map];
+    args.gfa_list.iter().chain(&args.gaf)
+        .chain(auxiliary.into_iter().flatten())
+        .map(String::as_str)
+        .filter(|path| !is_url(path))
+        .collect()
+}
+
+/// Outputs that are also inputs of the run, as (output, input) pairs. The paths are compared once
+/// resolved, through symlinks and relative paths; outputs that do not exist yet cannot collide
+pub(crate) fn input_collisions(args: &Args, outputs: &[String]) -> Vec<(String, String)> {
+    let inputs: Vec<(std::path::PathBuf, &str)> = input_files(args).into_iter()
+        .filter_map(|input| std::fs::canonicalize(input).ok().map(|real_path| (real_path, input)))
+        .collect();
+    let mut collisions = Vec::new();
+    for output in outputs {
+        let Ok(real_path) = std::fs::canonicalize(output) else {
+            continue;
+        };
+        if let Some((_, input)) = inputs.iter().find(|(input_path, _)| *input_path == real_path) {
+            collisions.push((output.clone(), input.to_string()));
+        }
+    }
+    collisions
+}
+
+/// Parses a positive integer, such as a maximum node length or a first node ID
+pub(crate) fn parse_nonzero(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(0) => Err("must be at least 1".to_string()),
+        Ok(value) => Ok(value),
+        Err(e) => Err(e.to_string()),
+    }
+}
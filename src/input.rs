@@ -0,0 +1,1200 @@
+//! Reading of the input GFAs into one combined graph, and of the tables describing them
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    borrow::Cow,
+    path::Path,
+};
+use rustc_hash::{FxHashMap, FxHashSet};
+use handlegraph::{
+    handle::{Handle, NodeId, Edge},
+    handlegraph::*,
+    hashgraph::HashGraph,
+};
+use gfa::{gfa::GFA, optfields::{OptFields, OptFieldVal, OptionalFields}, parser::GFAParserBuilder};
+use log::{debug, info, warn};
+use crate::lace::LaceWarning;
+use crate::merge::{EdgeSet, LinkOverlaps, boundary_overlap, create_edge_if_missing, overlap_length, parse_path_step};
+use crate::names::{PathFormat, RangeSyntax, split_input_path_name};
+use crate::open::{DECOMPRESS_BUDGET, GfaInput, Prefetcher, for_each_input, is_prefetchable, is_tar_archive, is_url, open_input};
+use crate::ranges::{PathKey, RangeInfo};
+use crate::sequences::NodeSequences;
+use crate::write::node_source;
+
+/// Reads contig lengths from the first two columns of a FASTA index or of a TSV, skipping '#' lines
+pub(crate) fn read_contig_lengths(lengths_path: &str) -> io::Result<FxHashMap<String, usize>> {
+    let mut lengths = FxHashMap::default();
+    for (line_idx, line) in BufReader::new(File::open(lengths_path)?).lines().enumerate() {
+        let line = line?;
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut fields = line.split('\t');
+        let (Some(name), Some(Ok(length))) = (fields.next(), fields.next().map(|field| field.trim().parse::<usize>())) else {
+            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                format!("Line {} of '{}' is not a contig name and length", line_idx + 1, lengths_path)));
+        };
+        lengths.insert(name.to_string(), length);
+    }
+    Ok(lengths)
+}
+
+/// Reads the key and range of exact path names from a TSV of path names, keys, starts, and ends,
+/// skipping '#' lines
+pub(crate) fn read_key_map(key_map_path: &str) -> io::Result<FxHashMap<String, (String, usize, usize)>> {
+    let mut key_map = FxHashMap::default();
+    for (line_idx, line) in BufReader::new(File::open(key_map_path)?).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData,
+            format!("Line {} of '{}' {}", line_idx + 1, key_map_path, message));
+        let fields: Vec<&str> = line.split('\t').collect();
+        let [path_name, key, start, end] = fields[..] else {
+            return Err(invalid("is not a path name, a key, a start, and an end".to_string()));
+        };
+        let coordinate = |field: &str| field.trim().parse::<usize>().map_err(|_| invalid(format!("has an invalid coordinate '{}'", field)));
+        let (start, end) = (coordinate(start)?, coordinate(end)?);
+        if start > end {
+            return Err(invalid(format!("has a start {} after its end {}", start, end)));
+        }
+        if key.is_empty() {
+            return Err(invalid(format!("has an empty key for '{}'", path_name)));
+        }
+        if key_map.insert(path_name.to_string(), (key.to_string(), start, end)).is_some() {
+            return Err(invalid(format!("repeats path name '{}'", path_name)));
+        }
+    }
+    Ok(key_map)
+}
+
+/// Reads an explicit order of the ranges of some path keys from a TSV of path keys, inputs (by
+/// index from 0, or by path as given on the command line), and ranks, skipping '#' lines
+pub(crate) fn read_range_order(order_path: &str, gfa_names: &[String]) -> io::Result<FxHashMap<String, FxHashMap<usize, usize>>> {
+    let mut order: FxHashMap<String, FxHashMap<usize, usize>> = FxHashMap::default();
+    for (line_idx, line) in BufReader::new(File::open(order_path)?).lines().enumerate() {
+        let line = line?;
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData,
+            format!("Line {} of '{}' {}", line_idx + 1, order_path, message));
+        let fields: Vec<&str> = line.split('\t').map(str::trim).collect();
+        let [path_key, input, rank] = fields[..] else {
+            return Err(invalid("is not a path key, an input, and a rank"));
+        };
+        let gfa_id = gfa_names.iter().position(|name| name == input)
+            .or_else(|| input.parse::<usize>().ok().filter(|&gfa_id| gfa_id < gfa_names.len()))
+            .ok_or_else(|| invalid(&format!("names an unknown input '{}'", input)))?;
+        let rank = rank.parse::<usize>().map_err(|_| invalid(&format!("has an invalid rank '{}'", rank)))?;
+        if order.entry(path_key.to_string()).or_default().insert(gfa_id, rank).is_some() {
+            return Err(invalid(&format!("repeats input '{}' for path key '{}'", input, path_key)));
+        }
+    }
+    Ok(order)
+}
+
+/// Ranks the ranges of the path keys of the explicit order, so that they are sorted and linked
+/// by rank whatever the coordinates of the path names, which are kept. Ranges of an input not
+/// ranked for their key go after the ranked ones. Returns the path keys of the order without ranges
+pub(crate) fn apply_range_order(path_key_ranges: &mut FxHashMap<PathKey, Vec<RangeInfo>>, order: &FxHashMap<String, FxHashMap<usize, usize>>) -> Vec<String> {
+    let mut missing = Vec::new();
+    for (path_key, ranks) in order {
+        let Some(ranges) = path_key_ranges.get_mut(path_key.as_str()) else {
+            missing.push(path_key.clone());
+            continue;
+        };
+        for range in ranges.iter_mut() {
+            range.rank = Some(ranks.get(&range.gfa_id).copied().unwrap_or(usize::MAX));
+        }
+    }
+    missing.sort();
+    missing
+}
+
+/// Intended ranges of the path keys of the inputs, from a --blocks-tsv partitioning table
+#[derive(Debug, Default)]
+pub(crate) struct BlockTable {
+    /// Files in the order of their first row
+    pub(crate) files: Vec<String>,
+    /// Range of each path key (the contig column) of each file
+    pub(crate) ranges: FxHashMap<String, FxHashMap<String, (usize, usize)>>,
+    /// Files by their resolved path, to match the inputs however their path is written
+    pub(crate) resolved_files: FxHashMap<std::path::PathBuf, String>,
+}
+
+impl BlockTable {
+    /// Length of each contig: the largest end of its ranges
+    pub(crate) fn contig_lengths(&self) -> FxHashMap<String, usize> {
+        let mut lengths: FxHashMap<String, usize> = FxHashMap::default();
+        for (contig, &(_, end)) in self.ranges.values().flatten() {
+            let length = lengths.entry(contig.clone()).or_default();
+            *length = (*length).max(end);
+        }
+        lengths
+    }
+
+    /// Files that do not exist, other than URLs and members of the tar archives among the inputs
+    pub(crate) fn missing_files(&self, gfa_list: &[String]) -> Vec<&str> {
+        self.files.iter()
+            .filter(|file| !is_url(file) && !Path::new(file).exists()
+                && !gfa_list.iter().any(|input| is_archive_member(input, file)))
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Ranges of the file of an input, found by its path as written, or else by its resolved path
+    pub(crate) fn file_ranges(&self, gfa_path: &str) -> Option<&FxHashMap<String, (usize, usize)>> {
+        self.ranges.get(gfa_path).or_else(|| {
+            let file = self.resolved_files.get(&resolved_input_path(gfa_path)?)?;
+            self.ranges.get(file)
+        })
+    }
+
+    /// Files that match none of the inputs, nor a member of the tar archives among them
+    pub(crate) fn unmatched_files(&self, gfa_list: &[String]) -> Vec<&str> {
+        let resolved_inputs: FxHashSet<std::path::PathBuf> = gfa_list.iter().filter_map(|input| resolved_input_path(input)).collect();
+        self.files.iter()
+            .filter(|file| !gfa_list.contains(file) && !gfa_list.iter().any(|input| is_archive_member(input, file)))
+            .filter(|file| resolved_input_path(file).is_none_or(|resolved| {
+                !resolved_inputs.contains(&resolved) && !resolved_inputs.iter().any(|input| resolved.starts_with(input) && resolved != *input)
+            }))
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+/// Path of a local input or file of the blocks TSV with symlinks and relative parts resolved, so
+/// that different ways to write it compare equal. A member of a tar archive is resolved through its
+/// archive, as `archive/member`. None for URLs and files that do not exist
+pub(crate) fn resolved_input_path(path: &str) -> Option<std::path::PathBuf> {
+    if is_url(path) {
+        return None;
+    }
+    if let Ok(resolved) = std::fs::canonicalize(path) {
+        return Some(resolved);
+    }
+    path.match_indices('/')
+        .map(|(idx, _)| (&path[..idx], &path[idx + 1..]))
+        .find(|(archive, _)| is_tar_archive(archive) && Path::new(archive).is_file())
+        .and_then(|(archive, member)| Some(std::fs::canonicalize(archive).ok()?.join(member)))
+}
+
+/// Returns true if `file` names a member of the input `gfa_path`, a tar archive, as `archive/member`
+pub(crate) fn is_archive_member(gfa_path: &str, file: &str) -> bool {
+    is_tar_archive(gfa_path) && file.strip_prefix(gfa_path).is_some_and(|member| member.starts_with('/'))
+}
+
+/// Reads a partitioning TSV of files, contigs, starts, and ends, found by the names of `columns` in
+/// its header (which may start with '#'), skipping empty and '#' lines
+pub(crate) fn read_blocks_tsv(tsv_path: &str, columns: &[String]) -> io::Result<BlockTable> {
+    let mut lines = BufReader::new(File::open(tsv_path)?).lines().enumerate();
+    let header = loop {
+        match lines.next() {
+            Some((_, line)) => {
+                let line = line?;
+                if !line.trim().is_empty() {
+                    break line;
+                }
+            }
+            None => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("'{}' has no header", tsv_path))),
+        }
+    };
+    let header: Vec<&str> = header.trim_start_matches('#').split('\t').map(str::trim).collect();
+    let indices = columns.iter()
+        .map(|column| header.iter().position(|name| name == column).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData,
+            format!("The header of '{}' has no column '{}' (columns: {})", tsv_path, column, header.join(", ")))))
+        .collect::<io::Result<Vec<usize>>>()?;
+    let [file_idx, contig_idx, start_idx, end_idx] = indices[..] else {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+            format!("Expected the names of the file, contig, start, and end columns, got {}", columns.len())));
+    };
+
+    let mut table = BlockTable::default();
+    for (line_idx, line) in lines {
+        let line = line?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData,
+            format!("Line {} of '{}' {}", line_idx + 1, tsv_path, message));
+        let fields: Vec<&str> = line.split('\t').map(str::trim).collect();
+        let field = |idx: usize| fields.get(idx).copied().ok_or_else(|| invalid(format!("has no '{}' column", header[idx])));
+        let (file, contig) = (field(file_idx)?, field(contig_idx)?);
+        let coordinate = |idx: usize| field(idx)?.parse::<usize>().map_err(|_| invalid(format!("has an invalid {} '{}'", header[idx], fields[idx])));
+        let (start, end) = (coordinate(start_idx)?, coordinate(end_idx)?);
+        if start > end {
+            return Err(invalid(format!("has a start {} after its end {}", start, end)));
+        }
+        if !table.ranges.contains_key(file) {
+            table.files.push(file.to_string());
+            if let Some(resolved) = resolved_input_path(file) {
+                table.resolved_files.insert(resolved, file.to_string());
+            }
+        }
+        if table.ranges.entry(file.to_string()).or_default().insert(contig.to_string(), (start, end)).is_some() {
+            return Err(invalid(format!("repeats contig '{}' of file '{}'", contig, file)));
+        }
+    }
+    Ok(table)
+}
+
+/// Graph and path ranges collected from all the input GFAs
+pub(crate) struct CombinedInput {
+    pub(crate) graph: HashGraph,
+    pub(crate) path_key_ranges: FxHashMap<PathKey, Vec<RangeInfo>>,
+    pub(crate) link_overlaps: LinkOverlaps,
+    pub(crate) sequenceless: FxHashSet<NodeId>,
+    /// Name of each input, indexed by gfa_id
+    pub(crate) gfa_names: Vec<String>,
+    /// Inputs defining some segment IDs more than once, with those IDs
+    pub(crate) duplicate_segments: Vec<(String, Vec<usize>)>,
+    /// Tar archive members skipped because they are not GFA files
+    pub(crate) skipped_inputs: usize,
+    /// Paths skipped because their name cannot be parsed or they have no steps
+    pub(crate) dropped_paths: usize,
+    /// Offset added to the segment IDs of each input, indexed by gfa_id
+    pub(crate) id_translations: Vec<NodeId>,
+    /// C lines of the inputs, with translated IDs
+    pub(crate) containments: Vec<Containment>,
+    pub(crate) sequences: NodeSequences,
+    /// Findings about the inputs: their pathless blocks, skipped paths, and paths whose steps
+    /// miss a node or do not spell their range
+    pub(crate) warnings: Vec<LaceWarning>,
+    /// Paths whose name gives another range than the blocks TSV
+    pub(crate) block_range_conflicts: usize,
+    /// Raw bytes of the path names with replacement characters, to tell apart the names that are
+    /// not valid UTF-8
+    pub(crate) replaced_path_names: FxHashMap<String, Vec<u8>>,
+    /// Canonical edges of the graph, kept for the merge
+    pub(crate) edges: EdgeSet,
+}
+
+impl CombinedInput {
+    pub(crate) fn new() -> Self {
+        CombinedInput {
+            graph: HashGraph::new(),
+            path_key_ranges: FxHashMap::default(),
+            link_overlaps: FxHashMap::default(),
+            sequenceless: FxHashSet::default(),
+            gfa_names: Vec::new(),
+            duplicate_segments: Vec::new(),
+            skipped_inputs: 0,
+            dropped_paths: 0,
+            id_translations: Vec::new(),
+            containments: Vec::new(),
+            sequences: NodeSequences::InGraph,
+            warnings: Vec::new(),
+            block_range_conflicts: 0,
+            replaced_path_names: FxHashMap::default(),
+            edges: EdgeSet::default(),
+        }
+    }
+
+    /// Name of a path from its raw bytes. Names that are not valid UTF-8 are converted lossily,
+    /// which fails if two distinct names of the inputs convert to the same one
+    pub(crate) fn path_name(&mut self, raw: &[u8], gfa_path: &str) -> io::Result<String> {
+        let path_name = String::from_utf8_lossy(raw).into_owned();
+        if path_name.contains(char::REPLACEMENT_CHARACTER) {
+            let known = self.replaced_path_names.entry(path_name.clone()).or_insert_with(|| raw.to_vec());
+            if known != raw {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!(
+                    "Path name '{}' of '{}' is not valid UTF-8 and cannot be told apart from another path name", path_name, gfa_path)));
+            }
+        }
+        Ok(path_name)
+    }
+
+    /// Adds the nodes, edges, link overlaps, and path ranges of a block graph with translated IDs,
+    /// as the next gfa_id. Segments with a declared length are added as placeholders of that length.
+    /// The IDs are offset by `id_offset` if given, or else by the largest ID so far. The step ends
+    /// of the ranges do not count twice the bases shared by overlapping steps. The ranges keep the
+    /// optional fields given in `path_tags` for their path name. The ranges of `block_ranges`, by
+    /// path key or by whole path name, replace those of the path names or name the paths without one.
+    /// Returns the offset added to the IDs of the block
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn add_block(
+        &mut self,
+        block_graph: &HashGraph,
+        gfa_path: &str,
+        declared_lengths: &FxHashMap<usize, usize>,
+        block_overlaps: &LinkOverlaps,
+        path_tags: &FxHashMap<String, Vec<String>>,
+        block_ranges: &FxHashMap<String, (usize, usize)>,
+        options: &ReadOptions,
+        id_offset: Option<NodeId>,
+        edges: &mut EdgeSet
+    ) -> io::Result<NodeId> {
+        let gfa_id = self.gfa_names.len();
+        let dropped_paths = self.dropped_paths;
+        self.gfa_names.push(gfa_path.to_string());
+
+        // Record the id translation for this block. The offset is the largest ID used so far,
+        // since block IDs do not need to be compact and the node count could cause collisions.
+        let id_translation = id_offset.unwrap_or_else(|| self.graph.max_node_id());
+        self.id_translations.push(id_translation);
+
+        // Add nodes with translated IDs
+        for handle in block_graph.handles() {
+            let new_id = id_translation + handle.id().into();
+            if let Some(&length) = declared_lengths.get(&usize::from(handle.id())) {
+                // Keep a placeholder of the declared length, so that coordinates stay consistent
+                self.sequences.create_node(&mut self.graph, &vec![b'N'; length], new_id)?;
+                self.sequenceless.insert(new_id);
+            } else {
+                let sequence = block_graph.sequence(handle).collect::<Vec<_>>();
+                self.sequences.create_node(&mut self.graph, &sequence, new_id)?;
+            }
+        }
+
+        // Add edges with translated IDs
+        for edge in block_graph.edges() {
+            // Blocks may contain both a link and its reverse complement, which are added once
+            create_edge_if_missing(
+                &mut self.graph,
+                edges,
+                Handle::pack(id_translation + edge.0.id().into(), edge.0.is_reverse()),
+                Handle::pack(id_translation + edge.1.id().into(), edge.1.is_reverse())
+            );
+        }
+
+        let translate = |handle: Handle| Handle::pack(id_translation + handle.id().into(), handle.is_reverse());
+        for (&Edge(left, right), &overlap) in block_overlaps {
+            self.link_overlaps.insert(Edge(translate(left), translate(right)), overlap);
+        }
+
+        debug!(stage = "parse", file = gfa_path, gfa_id = gfa_id, nodes = block_graph.node_count(), edges = block_graph.edge_count();
+            "  GFA file {} ({}) processed: Added {} nodes and {} edges", gfa_id, gfa_path, block_graph.node_count(), block_graph.edge_count());
+
+        // Process paths and collect ranges with their steps
+        let mut num_ranges = 0;
+        let mut matched_block_ranges = FxHashSet::default();
+        for (_path_id, path_ref) in block_graph.paths.iter() {
+            let path_name = self.path_name(&path_ref.name, gfa_path)?;
+            let tags = path_tags.get(&*path_name).cloned().unwrap_or_default();
+            if self.add_path_range(gfa_id, gfa_path, &path_name, path_ref.nodes.iter().map(|&step| translate(step)), tags, block_ranges, &mut matched_block_ranges, options) {
+                num_ranges += 1;
+            }
+        }
+        self.finish_block(gfa_id, gfa_path, num_ranges, self.dropped_paths - dropped_paths, block_graph.node_count(), block_ranges, &matched_block_ranges);
+
+        Ok(id_translation)
+    }
+
+    /// Adds a block read line by line, as `add_block` does with a parsed block graph, but without
+    /// holding the parsed GFA nor a block graph: the segments go straight into the combined graph
+    /// (or the spill file), and only the links, containments, and paths are kept until the end of
+    /// the block, as they can come before their segments. Of a segment defined more than once, the
+    /// first definition is kept, and the L lines are skipped with `ignore_links`. Returns the largest
+    /// segment ID of the block, and the IDs defined more than once
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn stream_block<R: BufRead>(
+        &mut self,
+        mut lines: R,
+        gfa_path: &str,
+        block_ranges: &FxHashMap<String, (usize, usize)>,
+        options: &ReadOptions,
+        id_offset: Option<NodeId>,
+        edges: &mut EdgeSet
+    ) -> io::Result<(u64, Vec<usize>)> {
+        let gfa_id = self.gfa_names.len();
+        let dropped_paths = self.dropped_paths;
+        self.gfa_names.push(gfa_path.to_string());
+        let id_translation = id_offset.unwrap_or_else(|| self.graph.max_node_id());
+        self.id_translations.push(id_translation);
+        let translate = |handle: Handle| Handle::pack(u64::from(id_translation) + u64::from(handle.id()), handle.is_reverse());
+
+        let mut max_id = 0;
+        let mut num_segments = 0;
+        let mut duplicates = Vec::new();
+        let mut links: Vec<(Handle, Handle, usize)> = Vec::new();
+        let mut path_overlaps: Vec<(Edge, usize)> = Vec::new();
+        let mut paths: Vec<(String, Vec<Handle>, Vec<String>)> = Vec::new();
+        let mut containments: Vec<Containment> = Vec::new();
+        let mut num_walks = 0;
+        let mut buffer = Vec::new();
+        let mut line_idx = 0;
+        while lines.read_until(b'\n', &mut buffer)? > 0 {
+            line_idx += 1;
+            let line = String::from_utf8_lossy(&buffer);
+            let line = line.trim_end_matches(['\n', '\r']);
+            let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, format!("Line {} of '{}' {}", line_idx, gfa_path, message));
+            let oriented = |id: &str, orient: &str| -> Option<Handle> {
+                let id = id.parse::<u64>().ok()?;
+                match orient {
+                    "+" => Some(translate(Handle::pack(id, false))),
+                    "-" => Some(translate(Handle::pack(id, true))),
+                    _ => None,
+                }
+            };
+            let mut fields = line.split('\t');
+            match fields.next() {
+                Some("S") => {
+                    let (Some(id), Some(sequence)) = (fields.next().and_then(|id| id.parse::<u64>().ok()), fields.next()) else {
+                        return Err(invalid("is not a segment with a numeric ID and a sequence"));
+                    };
+                    max_id = max_id.max(id);
+                    let new_id = NodeId::from(u64::from(id_translation) + id);
+                    if self.graph.has_node(new_id) {
+                        duplicates.push(id as usize);
+                    } else if sequence == "*" {
+                        // Segments without sequence carry their length in the LN tag
+                        let length = match fields.find_map(|field| field.strip_prefix("LN:i:")).map(|length| length.parse::<usize>()) {
+                            Some(Ok(length)) => length,
+                            _ => {
+                                warn!(stage = "parse", file = gfa_path, segment = id;
+                                    "    Segment {} in '{}' has no sequence and no valid LN tag, assuming length 0", id, gfa_path);
+                                0
+                            }
+                        };
+                        self.sequences.create_node(&mut self.graph, &vec![b'N'; length], new_id)?;
+                        self.sequenceless.insert(new_id);
+                        num_segments += 1;
+                    } else {
+                        self.sequences.create_node(&mut self.graph, sequence.as_bytes(), new_id)?;
+                        num_segments += 1;
+                    }
+                }
+                Some("L") if !options.ignore_links => {
+                    let (Some(from), Some(from_orient), Some(to), Some(to_orient)) = (fields.next(), fields.next(), fields.next(), fields.next()) else {
+                        return Err(invalid("is not a link between two oriented segments"));
+                    };
+                    let (Some(left), Some(right)) = (oriented(from, from_orient), oriented(to, to_orient)) else {
+                        return Err(invalid("is not a link between two oriented segments"));
+                    };
+                    let overlap = fields.next().and_then(|cigar| overlap_length(cigar.as_bytes())).unwrap_or(0);
+                    links.push((left, right, overlap));
+                }
+                Some("C") => {
+                    let (Some(container), Some(container_orient), Some(contained), Some(contained_orient), Some(pos), overlap) =
+                        (fields.next(), fields.next(), fields.next(), fields.next(), fields.next(), fields.next()) else {
+                        return Err(invalid("is not a containment between two oriented segments"));
+                    };
+                    let (Some(container), Some(contained), Ok(pos)) = (oriented(container, container_orient), oriented(contained, contained_orient), pos.parse()) else {
+                        return Err(invalid("is not a containment between two oriented segments"));
+                    };
+                    containments.push(Containment {
+                        container,
+                        contained,
+                        pos,
+                        overlap: overlap.unwrap_or("*").to_string(),
+                        tags: fields.map(String::from).collect(),
+                    });
+                }
+                Some("P") => {
+                    let (Some(_), Some(segment_names)) = (fields.next(), fields.next()) else {
+                        return Err(invalid("is not a path with a name and steps"));
+                    };
+                    // The line is converted lossily, so the name is taken from its raw bytes
+                    let path_name = self.path_name(buffer.split(|&byte| byte == b'\t').nth(1).unwrap_or_default(), gfa_path)?;
+                    let steps = segment_names.split(',')
+                        .map(|step| parse_path_step(step).map(translate).ok_or_else(|| invalid(&format!("has an invalid step '{}' in path '{}'", step, path_name))))
+                        .collect::<io::Result<Vec<Handle>>>()?;
+                    // Keep the overlaps stated by the path, for the links that do not have one
+                    if let Some(overlaps) = fields.next().filter(|&overlaps| overlaps != "*") {
+                        for (pair, cigar) in steps.windows(2).zip(overlaps.split(',')) {
+                            if let Some(overlap) = overlap_length(cigar.as_bytes()).filter(|&overlap| overlap > 0) {
+                                path_overlaps.push((Edge(pair[0], pair[1]), overlap));
+                            }
+                        }
+                    }
+                    paths.push((path_name, steps, fields.map(String::from).collect()));
+                }
+                Some("W") => num_walks += 1,
+                _ => {}
+            }
+            buffer.clear();
+        }
+        if num_walks > 0 {
+            warn!(stage = "parse", file = gfa_path;
+                "    {} walks (W lines) in '{}' are skipped, only paths (P lines) are laced", num_walks, gfa_path);
+        }
+
+        let mut num_links = 0;
+        for (left, right, overlap) in links {
+            if !self.graph.has_node(left.id()) || !self.graph.has_node(right.id()) {
+                warn!(stage = "parse", file = gfa_path;
+                    "    Link from segment {} to {} in '{}' refers to an undefined segment and is skipped",
+                    u64::from(left.id()) - u64::from(id_translation), u64::from(right.id()) - u64::from(id_translation), gfa_path);
+                continue;
+            }
+            // Blocks may contain both a link and its reverse complement, which are added once
+            create_edge_if_missing(&mut self.graph, edges, left, right);
+            if overlap > 0 {
+                self.link_overlaps.insert(Edge(left, right), overlap);
+            }
+            num_links += 1;
+        }
+        for (edge, overlap) in path_overlaps {
+            self.link_overlaps.entry(edge).or_insert(overlap);
+        }
+        debug!(stage = "parse", file = gfa_path, gfa_id = gfa_id, nodes = num_segments, edges = num_links;
+            "  GFA file {} ({}) streamed: Added {} nodes and {} edges", gfa_id, gfa_path, num_segments, num_links);
+
+        let mut num_ranges = 0;
+        let mut matched_block_ranges = FxHashSet::default();
+        for (path_name, steps, tags) in paths {
+            if self.add_path_range(gfa_id, gfa_path, &path_name, steps.into_iter(), tags, block_ranges, &mut matched_block_ranges, options) {
+                num_ranges += 1;
+            }
+        }
+        self.finish_block(gfa_id, gfa_path, num_ranges, self.dropped_paths - dropped_paths, num_segments, block_ranges, &matched_block_ranges);
+
+        for containment in containments {
+            if !self.graph.has_node(containment.container.id()) || !self.graph.has_node(containment.contained.id()) {
+                warn!(stage = "parse", file = gfa_path;
+                    "    Containment of segment {} in {} in '{}' refers to an undefined segment and is skipped",
+                    u64::from(containment.contained.id()) - u64::from(id_translation), u64::from(containment.container.id()) - u64::from(id_translation), gfa_path);
+                continue;
+            }
+            self.containments.push(containment);
+        }
+        duplicates.sort_unstable();
+        duplicates.dedup();
+
+        Ok((max_id, duplicates))
+    }
+
+    /// Adds the range of a path of the block `gfa_id`, with its translated steps, if its name (or
+    /// the blocks TSV) gives one and it has steps. Returns true if the range was added
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn add_path_range<'b>(
+        &mut self,
+        gfa_id: usize,
+        gfa_path: &str,
+        path_name: &str,
+        steps: impl Iterator<Item = Handle>,
+        tags: Vec<String>,
+        block_ranges: &'b FxHashMap<String, (usize, usize)>,
+        matched_block_ranges: &mut FxHashSet<&'b String>,
+        options: &ReadOptions
+    ) -> bool {
+        let mut parsed = split_input_path_name(path_name, options);
+        let block_key = parsed.as_ref().map_or(path_name, |(key, _, _)| &**key);
+        if let Some((block_key, &(block_start, block_end))) = block_ranges.get_key_value(block_key) {
+            matched_block_ranges.insert(block_key);
+            match &parsed {
+                Some((_, start, end)) if (*start, *end) != (block_start, block_end) => {
+                    warn!(stage = "parse", file = gfa_path, path = path_name;
+                        "    Path '{}' has range {}-{}, but {}-{} in the blocks TSV, which is used", path_name, start, end, block_start, block_end);
+                    self.block_range_conflicts += 1;
+                }
+                Some(_) => {}
+                None => debug!(stage = "parse", file = gfa_path, path = path_name;
+                    "    Path '{}' has range {}-{} in the blocks TSV", path_name, block_start, block_end),
+            }
+            parsed = Some((Cow::Owned(block_key.clone()), block_start, block_end));
+        }
+
+        let Some((sample_hap_name, start, end)) = parsed else {
+            self.dropped_paths += 1;
+            debug!(stage = "parse", file = gfa_path, path = path_name;
+                "    Path '{}' is not named NAME:START-END and is skipped", path_name);
+            return false;
+        };
+
+        let mut translated_steps = Vec::new();
+        let mut step_ends = Vec::new();
+        let mut cumulative_pos = start;
+        for translated_step in steps {
+            if !self.graph.has_node(translated_step.id()) {
+                let node = u64::from(translated_step.id()) - u64::from(self.id_translations[gfa_id]);
+                self.warnings.push(LaceWarning::MissingNode { block: gfa_id, path_name: path_name.to_string(), node });
+                self.dropped_paths += 1;
+                return false;
+            }
+            // Record the end position of this step, past the bases it shares with the previous one
+            let node_length = self.sequences.node_len(&self.graph, translated_step);
+            let overlap = translated_steps.last()
+                .map_or(0, |&previous| boundary_overlap(&self.link_overlaps, previous, translated_step));
+            cumulative_pos += node_length.saturating_sub(overlap);
+            step_ends.push(cumulative_pos);
+            translated_steps.push(translated_step);
+        }
+
+        if cumulative_pos != end && !options.naive_join {
+            self.warnings.push(LaceWarning::ValidationMismatch {
+                block: gfa_id,
+                path_name: path_name.to_string(),
+                expected: end.saturating_sub(start),
+                spelled: cumulative_pos - start,
+            });
+        }
+
+        if translated_steps.is_empty() {
+            warn!(stage = "parse", file = gfa_path, path = path_name; "    Path '{}' has no steps", path_name);
+            self.dropped_paths += 1;
+            return false;
+        }
+        let range = RangeInfo {
+            start,
+            end,
+            gfa_id,
+            path_name: path_name.to_string(),
+            steps: translated_steps,
+            step_ends,
+            tags,
+            rank: None,
+        };
+        // Only the first range of a key allocates it
+        match self.path_key_ranges.get_mut(&*sample_hap_name) {
+            Some(ranges) => ranges.push(range),
+            None => {
+                self.path_key_ranges.insert(PathKey::from(sample_hap_name), vec![range]);
+            }
+        }
+        true
+    }
+
+    /// Records a block without path ranges or with skipped paths, and reports the contigs of the
+    /// blocks TSV without a path in it
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn finish_block(
+        &mut self,
+        gfa_id: usize,
+        gfa_path: &str,
+        num_ranges: usize,
+        num_dropped: usize,
+        num_nodes: usize,
+        block_ranges: &FxHashMap<String, (usize, usize)>,
+        matched_block_ranges: &FxHashSet<&String>
+    ) {
+        if num_dropped > 0 {
+            self.warnings.push(LaceWarning::DroppedPaths { block: gfa_id, count: num_dropped });
+        }
+        if num_ranges == 0 && num_nodes > 0 {
+            self.warnings.push(LaceWarning::PathlessBlock { block: gfa_id });
+        }
+        let mut unmatched: Vec<&String> = block_ranges.keys().filter(|key| !matched_block_ranges.contains(key)).collect();
+        unmatched.sort();
+        for path_key in unmatched {
+            warn!(stage = "parse", file = gfa_path, path_key = path_key.as_str();
+                "    Contig '{}' of the blocks TSV has no path in '{}'", path_key, gfa_path);
+        }
+    }
+
+    /// Records the segment IDs defined more than once by an input, which would silently be merged into a single node
+    pub(crate) fn add_duplicate_segments(&mut self, gfa_path: &str, duplicates: Vec<usize>) {
+        if !duplicates.is_empty() {
+            warn!(stage = "parse", file = gfa_path, duplicate_segments = duplicates.len();
+                "    {} segment IDs are defined more than once in '{}': {}", duplicates.len(), gfa_path, format_segment_ids(&duplicates));
+            self.duplicate_segments.push((gfa_path.to_string(), duplicates));
+        }
+    }
+}
+
+/// Offsets of the segment IDs of the inputs, instead of the largest ID of the previous inputs
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum IdOffsets<'a> {
+    /// One offset per input, in order
+    Explicit(&'a [u64]),
+    /// The i-th input (from 0) is offset by i times the spacing, and its segment IDs must not exceed it
+    Spacing(u64),
+}
+
+/// Options controlling how the input GFAs are read
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ReadOptions<'a> {
+    /// Use the whole path names as keys, without ranges
+    pub(crate) naive_join: bool,
+    /// Read the members of tar archives in name order
+    pub(crate) sort_archive: bool,
+    /// Offsets of the segment IDs of the inputs, instead of the largest ID of the previous inputs
+    pub(crate) id_offsets: Option<IdOffsets<'a>>,
+    /// Directory of the temporary file the node sequences are spilled to
+    pub(crate) spill_dir: Option<&'a Path>,
+    /// Intended ranges of the path keys of the inputs
+    pub(crate) block_table: Option<&'a BlockTable>,
+    /// Skip the L lines of the inputs, to rebuild the edges from the path steps
+    pub(crate) ignore_links: bool,
+    /// Keep the decompressed temporary file of inputs that fail to parse
+    pub(crate) keep_temp: bool,
+    /// Read the inputs line by line with `CombinedInput::stream_block`
+    pub(crate) stream_parse: bool,
+    /// Number of gzipped inputs decompressed on other threads ahead of their parsing
+    pub(crate) decompress_ahead: usize,
+    /// Bytes of decompressed inputs waiting to be parsed, past which no other input is decompressed ahead
+    pub(crate) decompress_budget: u64,
+    /// Parse the uncompressed local inputs with buffered reads instead of memory-mapping them
+    pub(crate) no_mmap: bool,
+    /// Syntax of the range at the end of the path names
+    pub(crate) range_syntax: RangeSyntax,
+    /// Template of the path names, instead of the range syntax
+    pub(crate) path_format: Option<&'a PathFormat>,
+    /// Keys and ranges of exact path names, which take precedence over the parsing of the names
+    pub(crate) key_map: Option<&'a FxHashMap<String, (String, usize, usize)>>,
+    /// Number of retries of failed HTTP requests
+    #[cfg(feature = "http")]
+    pub(crate) http_retries: u32,
+}
+
+impl Default for ReadOptions<'_> {
+    fn default() -> Self {
+        ReadOptions {
+            naive_join: false,
+            sort_archive: false,
+            id_offsets: None,
+            spill_dir: None,
+            block_table: None,
+            ignore_links: false,
+            keep_temp: false,
+            stream_parse: false,
+            decompress_ahead: 4,
+            decompress_budget: DECOMPRESS_BUDGET,
+            no_mmap: false,
+            range_syntax: RangeSyntax::ColonDash,
+            path_format: None,
+            key_map: None,
+            #[cfg(feature = "http")]
+            http_retries: 3,
+        }
+    }
+}
+
+/// Reads the input GFAs into a combined graph. With ID offsets, the segment IDs of each input are
+/// offset by its own value, and the ID spaces of the inputs (from their offset, excluded, to their
+/// offset plus their largest segment ID) must not overlap. With a spill directory, the node
+/// sequences are spilled to a temporary file in it
+pub(crate) fn read_gfa_files(gfa_list: &[String], options: &ReadOptions) -> io::Result<CombinedInput> {
+    let ReadOptions { id_offsets, spill_dir, block_table, ignore_links, .. } = *options;
+    let mut combined = CombinedInput::new();
+    if let Some(spill_dir) = spill_dir {
+        combined.sequences = NodeSequences::spilled(spill_dir)?;
+    }
+    let mut edges = EdgeSet::default();
+    let mut id_spaces: Vec<(u64, u64)> = Vec::new();
+
+    info!(stage = "parse"; "Reading {} GFA files", gfa_list.len());
+
+    // Process each GFA file, with the gzipped ones decompressed ahead on other threads
+    let mut parser_builder = GFAParserBuilder::all();
+    parser_builder.links(!ignore_links);
+    let parser = parser_builder.build();
+    let stream_parse = options.stream_parse;
+    let prefetcher = Prefetcher::new(gfa_list, if stream_parse { 0 } else { options.decompress_ahead }, options.decompress_budget);
+    let no_block_ranges = FxHashMap::default();
+    combined.skipped_inputs = for_each_input(gfa_list, options, |gfa_id, input| {
+        let gfa_path = input.name().to_string();
+        let block_ranges = block_table.and_then(|table| table.file_ranges(&gfa_path)).unwrap_or(&no_block_ranges);
+        if stream_parse {
+            let id_offset = block_id_offset(id_offsets, gfa_id, &gfa_path)?;
+            let (max_id, duplicates) = combined.stream_block(input.lines(options)?, &gfa_path, block_ranges, options, id_offset.map(NodeId::from), &mut edges)?;
+            if let Some(id_offset) = id_offset {
+                check_id_window(id_offsets, &gfa_path, id_offset, max_id, &mut id_spaces, &combined.gfa_names)?;
+            }
+            combined.add_duplicate_segments(&gfa_path, duplicates);
+            return Ok(());
+        }
+        let gfa = match (&input, &prefetcher) {
+            (GfaInput::Path(path), Some(prefetcher)) if is_prefetchable(path) => prefetcher.parse(path, &parser, options)?,
+            _ => input.parse(&parser, options)?,
+        };
+
+        // A segment defined twice would silently be merged into a single node
+        combined.add_duplicate_segments(&gfa_path, duplicate_segment_ids(&gfa));
+
+        let block_graph = HashGraph::from_gfa(&gfa);
+
+        // Segments without sequence ('*') carry their length in the LN tag
+        let mut declared_lengths: FxHashMap<usize, usize> = FxHashMap::default();
+        for segment in gfa.segments.iter().filter(|s| s.sequence.as_slice() == b"*") {
+            let length = match segment.optional.get_field(b"LN").map(|field| &field.value) {
+                Some(OptFieldVal::Int(length)) if *length >= 0 => *length as usize,
+                _ => {
+                    warn!(stage = "parse", file = gfa_path.as_str(), segment = segment.name;
+                        "    Segment {} in '{}' has no sequence and no valid LN tag, assuming length 0", segment.name, gfa_path);
+                    0
+                }
+            };
+            declared_lengths.insert(segment.name, length);
+        }
+
+        let id_offset = block_id_offset(id_offsets, gfa_id, &gfa_path)?;
+        if let Some(id_offset) = id_offset {
+            check_id_window(id_offsets, &gfa_path, id_offset, u64::from(block_graph.max_node_id()), &mut id_spaces, &combined.gfa_names)?;
+        }
+        // Keep the overlaps of non-blunt links, which HashGraph does not store
+        let mut block_overlaps = LinkOverlaps::default();
+        for link in gfa.links.iter() {
+            if let Some(overlap) = overlap_length(&link.overlap).filter(|&overlap| overlap > 0) {
+                let edge = Edge(
+                    Handle::pack(link.from_segment as u64, link.from_orient.is_reverse()),
+                    Handle::pack(link.to_segment as u64, link.to_orient.is_reverse())
+                );
+                block_overlaps.insert(edge, overlap);
+            }
+        }
+
+        // Keep the overlaps stated by the paths too, for the links that do not have one
+        for path in gfa.paths.iter().filter(|path| !path.overlaps.is_empty()) {
+            let segment_names = String::from_utf8_lossy(&path.segment_names);
+            let steps: Vec<Handle> = segment_names.split(',').filter_map(parse_path_step).collect();
+            for (pair, overlap) in steps.windows(2).zip(path.overlaps.iter()) {
+                let overlap = overlap.as_ref()
+                    .and_then(|cigar| overlap_length(cigar.to_string().as_bytes()))
+                    .unwrap_or(0);
+                if overlap > 0 {
+                    block_overlaps.entry(Edge(pair[0], pair[1])).or_insert(overlap);
+                }
+            }
+        }
+
+        let path_tags: FxHashMap<String, Vec<String>> = gfa.paths.iter()
+            .filter(|path| !path.optional.is_empty())
+            .map(|path| (path.path_name.to_string(), path.optional.iter().map(|field| field.to_string()).collect()))
+            .collect();
+
+        let id_translation = combined.add_block(&block_graph, &gfa_path, &declared_lengths, &block_overlaps, &path_tags, block_ranges, options, id_offset.map(NodeId::from), &mut edges)?;
+
+        for containment in gfa.containments.iter() {
+            let (container_id, contained_id) = (containment.container_name as u64, containment.contained_name as u64);
+            if !block_graph.has_node(container_id) || !block_graph.has_node(contained_id) {
+                warn!(stage = "parse", file = gfa_path.as_str();
+                    "    Containment of segment {} in {} in '{}' refers to an undefined segment and is skipped", contained_id, container_id, gfa_path);
+                continue;
+            }
+            combined.containments.push(Containment {
+                container: Handle::pack(u64::from(id_translation) + container_id, containment.container_orient.is_reverse()),
+                contained: Handle::pack(u64::from(id_translation) + contained_id, containment.contained_orient.is_reverse()),
+                pos: containment.pos,
+                overlap: String::from_utf8_lossy(&containment.overlap).into_owned(),
+                tags: containment.optional.iter().map(|field| field.to_string()).collect(),
+            });
+        }
+        Ok(())
+    })?;
+    if let Some(IdOffsets::Explicit(id_offsets)) = id_offsets
+        && id_offsets.len() != combined.gfa_names.len() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+            format!("Got {} ID offsets for {} input GFAs, expected one per input", id_offsets.len(), combined.gfa_names.len())));
+    }
+
+    info!(stage = "parse"; "Collected {} nodes, {} edges, and {} path keys",
+        combined.graph.node_count(), combined.graph.edge_count(), combined.path_key_ranges.len());
+    if !combined.sequenceless.is_empty() {
+        info!(stage = "parse"; "Collected {} segments without sequence", combined.sequenceless.len());
+    }
+
+    combined.edges = edges;
+    combined.sequences.finish()?;
+    if let Some(prefetcher) = &prefetcher {
+        debug!(stage = "parse"; "At most {} bytes of decompressed inputs were waiting to be parsed", prefetcher.peak_bytes());
+    }
+
+    Ok(combined)
+}
+
+/// Offset of the segment IDs of the input `gfa_id` given by the ID offsets, if any
+pub(crate) fn block_id_offset(id_offsets: Option<IdOffsets>, gfa_id: usize, gfa_path: &str) -> io::Result<Option<u64>> {
+    match id_offsets {
+        Some(IdOffsets::Explicit(id_offsets)) => {
+            let &id_offset = id_offsets.get(gfa_id).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput,
+                format!("Got {} ID offsets, but '{}' is input {}", id_offsets.len(), gfa_path, gfa_id + 1)))?;
+            Ok(Some(id_offset))
+        }
+        Some(IdOffsets::Spacing(id_spacing)) => {
+            let id_offset = (gfa_id as u64).checked_mul(id_spacing)
+                .filter(|id_offset| id_offset.checked_add(id_spacing).is_some())
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput,
+                format!("The ID window of '{}' (input {}) overflows the node IDs with a spacing of {}", gfa_path, gfa_id + 1, id_spacing)))?;
+            Ok(Some(id_offset))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Checks that the segment IDs of an input offset by `id_offset`, up to `max_id`, stay in its window:
+/// below the ID spacing, or clear of the IDs of the previous inputs (`id_spaces`) with explicit offsets
+pub(crate) fn check_id_window(id_offsets: Option<IdOffsets>, gfa_path: &str, id_offset: u64, max_id: u64, id_spaces: &mut Vec<(u64, u64)>, gfa_names: &[String]) -> io::Result<()> {
+    match id_offsets {
+        Some(IdOffsets::Explicit(_)) => {
+            let id_end = id_offset.checked_add(max_id).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!(
+                "The node IDs of '{}' offset by {} go past the largest node ID", gfa_path, id_offset)))?;
+            let id_space = (id_offset, id_end);
+            if let Some(other) = id_spaces.iter().position(|&(start, end)| start < id_space.1 && id_space.0 < end) {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!(
+                    "The node IDs of '{}' offset by {} ({}-{}) overlap those of '{}' ({}-{})",
+                    gfa_path, id_offset, id_space.0 + 1, id_space.1, gfa_names[other], id_spaces[other].0 + 1, id_spaces[other].1)));
+            }
+            id_spaces.push(id_space);
+        }
+        Some(IdOffsets::Spacing(id_spacing)) if max_id > id_spacing => {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!(
+                "The segment IDs of '{}' go up to {}, beyond the ID spacing of {}", gfa_path, max_id, id_spacing)));
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Parses a GAF path (e.g. `>1<2>3`) into oriented segment IDs. Returns None for
+/// stable coordinate paths, which are not supported
+pub(crate) fn parse_gaf_path(path: &str) -> Option<Vec<(u64, bool)>> {
+    if !path.starts_with(['>', '<']) {
+        return None;
+    }
+    let mut steps = Vec::new();
+    let mut rest = path;
+    while let Some(orient) = rest.chars().next() {
+        let next = rest[1..].find(['>', '<']).map_or(rest.len(), |i| i + 1);
+        steps.push((rest[1..next].parse().ok()?, orient == '<'));
+        rest = &rest[next..];
+    }
+    Some(steps)
+}
+
+/// Reads the alignments of a GAF file over the segments of the input `gfa_id` as path ranges.
+/// The query name is the path key, or the key and range of a `KEY:START-END` query, and the
+/// range is given by the query coordinates, extended to whole segments. Reverse strand
+/// alignments are reversed to follow the query. Returns the number of paths added
+pub(crate) fn read_gaf_paths(
+    gaf_path: &str,
+    gfa_id: usize,
+    id_translation: NodeId,
+    graph: &HashGraph,
+    sequences: &NodeSequences,
+    options: &ReadOptions,
+    path_key_ranges: &mut FxHashMap<PathKey, Vec<RangeInfo>>
+) -> io::Result<usize> {
+    let naive_join = options.naive_join;
+    let (reader, _format) = niffler::get_reader(open_input(gaf_path, options)?)
+        .map_err(io::Error::other)?;
+    let invalid = |line_number: usize, message: String| {
+        io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", line_number, message))
+    };
+
+    let mut num_paths = 0;
+    for (idx, line) in BufReader::new(reader).lines().enumerate() {
+        let line = line?;
+        let line_number = idx + 1;
+        if line.is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 12 {
+            return Err(invalid(line_number, format!("expected at least 12 columns, found {}", fields.len())));
+        }
+        let number = |column: usize| -> io::Result<usize> {
+            fields[column].parse().map_err(|_| invalid(line_number, format!("invalid number '{}' in column {}", fields[column], column + 1)))
+        };
+        let (query_name, query_start, strand, path) = (fields[0], number(2)?, fields[4], fields[5]);
+        let (path_length, path_start, path_end) = (number(6)?, number(7)?, number(8)?);
+        if path == "*" {
+            continue;
+        }
+
+        let Some(gaf_steps) = parse_gaf_path(path) else {
+            return Err(invalid(line_number, format!("unsupported path '{}', expected segment IDs with '>' or '<'", path)));
+        };
+        let mut steps = Vec::with_capacity(gaf_steps.len());
+        for (segment_id, is_reverse) in gaf_steps {
+            let node_id = id_translation + segment_id;
+            if !graph.has_node(node_id) {
+                return Err(invalid(line_number, format!("segment {} is not in the input GFA", segment_id)));
+            }
+            steps.push(Handle::pack(node_id, is_reverse));
+        }
+        // The part of the path before the query start depends on the strand
+        let unaligned_head = match strand {
+            "+" => path_start,
+            "-" => {
+                steps = steps.iter().rev().map(|handle| handle.flip()).collect();
+                path_length.saturating_sub(path_end)
+            }
+            _ => return Err(invalid(line_number, format!("invalid strand '{}'", strand))),
+        };
+
+        let (path_key, offset) = if naive_join {
+            (query_name.to_string(), None)
+        } else {
+            match split_input_path_name(query_name, options) {
+                Some((key, start, _)) => (key.into_owned(), Some(start)),
+                None => (query_name.to_string(), Some(0)),
+            }
+        };
+        let start = offset.map_or(usize::MIN, |offset| offset + query_start.saturating_sub(unaligned_head));
+        let mut step_ends = Vec::with_capacity(steps.len());
+        let mut cumulative_pos = start;
+        for &step in &steps {
+            cumulative_pos += sequences.node_len(graph, step);
+            step_ends.push(cumulative_pos);
+        }
+        let end = if offset.is_some() { cumulative_pos } else { usize::MAX };
+
+        path_key_ranges.entry(PathKey::from(path_key)).or_default().push(RangeInfo {
+            start,
+            end,
+            gfa_id,
+            path_name: query_name.to_string(),
+            steps,
+            step_ends,
+            tags: Vec::new(),
+            rank: None,
+        });
+        num_paths += 1;
+    }
+
+    Ok(num_paths)
+}
+
+/// Returns the segment IDs defined by more than one S line of a GFA, sorted
+pub(crate) fn duplicate_segment_ids(gfa: &GFA<usize, OptionalFields>) -> Vec<usize> {
+    let mut seen = FxHashSet::default();
+    let mut duplicates: Vec<usize> = gfa.segments.iter()
+        .map(|segment| segment.name)
+        .filter(|&id| !seen.insert(id))
+        .collect();
+    duplicates.sort_unstable();
+    duplicates.dedup();
+    duplicates
+}
+
+/// Maximum number of duplicate segment IDs listed per input file
+pub(crate) const MAX_REPORTED_SEGMENT_IDS: usize = 10;
+
+/// Characters expected in the segment sequences by --check-alphabet
+pub(crate) const DEFAULT_ALPHABET: &str = "ACGTNacgtn";
+
+/// Warns about the nodes whose sequence has a character outside `alphabet`, naming their input
+/// file and segment ID, and the first such character and its offset. Segments without sequence
+/// are skipped. Returns the offending nodes, in ID order, with their first offending character
+pub(crate) fn check_alphabet(
+    graph: &HashGraph,
+    sequences: &NodeSequences,
+    sequenceless: &FxHashSet<NodeId>,
+    alphabet: &[u8],
+    id_translations: &[NodeId],
+    gfa_names: &[String]
+) -> Vec<(NodeId, u8)> {
+    let mut allowed = [false; 256];
+    for &byte in alphabet {
+        allowed[byte as usize] = true;
+    }
+    let mut handles: Vec<Handle> = graph.handles().filter(|handle| !sequenceless.contains(&handle.id())).collect();
+    handles.sort_unstable();
+
+    let mut violations = Vec::new();
+    let mut sequence = Vec::new();
+    for handle in handles {
+        sequence.clear();
+        sequences.extend_with_sequence(graph, handle, &mut sequence);
+        if let Some((offset, &byte)) = sequence.iter().enumerate().find(|&(_, &byte)| !allowed[byte as usize]) {
+            let (gfa_id, segment_id) = node_source(handle.id(), id_translations, &FxHashMap::default());
+            warn!(stage = "parse", file = gfa_names[gfa_id].as_str(), segment = segment_id;
+                "Segment {} of '{}' has {:?}, outside the alphabet, at offset {}", segment_id, gfa_names[gfa_id], byte as char, offset);
+            violations.push((handle.id(), byte));
+        }
+    }
+    violations
+}
+
+/// Lists the first few segment IDs, for the diagnostics
+pub(crate) fn format_segment_ids(ids: &[usize]) -> String {
+    let mut listed = ids.iter().take(MAX_REPORTED_SEGMENT_IDS).map(|id| id.to_string()).collect::<Vec<_>>().join(", ");
+    if ids.len() > MAX_REPORTED_SEGMENT_IDS {
+        listed.push_str(", ...");
+    }
+    listed
+}
+
+/// Prints the distinct path keys of the input GFA files with their number of ranges
+/// and the contributing GFA files, without building the combined graph
+pub(crate) fn list_samples(gfa_list: &[String], options: &ReadOptions) -> io::Result<()> {
+    // Only paths are needed
+    let mut parser_builder = GFAParserBuilder::none();
+    parser_builder.paths(true);
+    let parser = parser_builder.build();
+
+    let mut key_gfa_ids: FxHashMap<String, Vec<usize>> = FxHashMap::default();
+    for_each_input(gfa_list, options, |gfa_id, input| {
+        let gfa = input.parse(&parser, options)?;
+        for path in gfa.paths.iter() {
+            let path_name = String::from_utf8_lossy(&path.path_name);
+            if let Some((key, _, _)) = split_input_path_name(&path_name, options) {
+                key_gfa_ids.entry(key.into_owned()).or_default().push(gfa_id);
+            }
+        }
+        Ok(())
+    })?;
+
+    let mut keys: Vec<_> = key_gfa_ids.keys().collect();
+    keys.sort();
+
+    let mut out = io::stdout().lock();
+    writeln!(out, "#path_key\tnum_ranges\tgfa_ids")?;
+    for key in keys {
+        let gfa_ids = &key_gfa_ids[key];
+        let mut distinct_ids = gfa_ids.clone();
+        distinct_ids.dedup();
+        let ids = distinct_ids.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(",");
+        writeln!(out, "{}\t{}\t{}", key, gfa_ids.len(), ids)?;
+    }
+
+    Ok(())
+}
+
+/// Number of unparseable path names listed per file by `validate_input`
+pub(crate) const MAX_REPORTED_PATH_NAMES: usize = 5;
+
+/// Returns the number of paths of a GFA file and the names of those that `split_path_name` cannot parse
+pub(crate) fn unparseable_path_names(gfa: &GFA<usize, OptionalFields>, options: &ReadOptions) -> (usize, Vec<String>) {
+    let unparseable = gfa.paths.iter()
+        .map(|path| String::from_utf8_lossy(&path.path_name).into_owned())
+        .filter(|path_name| split_input_path_name(path_name, options).is_none())
+        .collect();
+    (gfa.paths.len(), unparseable)
+}
+
+/// Reports, for each input GFA file, how many path names can be parsed into path key ranges
+/// and the first few that cannot. Returns false if any file has no parseable path names
+pub(crate) fn validate_input(gfa_list: &[String], options: &ReadOptions) -> io::Result<bool> {
+    // Only paths are needed
+    let mut parser_builder = GFAParserBuilder::none();
+    parser_builder.paths(true);
+    let parser = parser_builder.build();
+
+    let mut all_valid = true;
+    let mut out = io::stdout().lock();
+    for_each_input(gfa_list, options, |_gfa_id, input| {
+        let gfa_path = input.name().to_string();
+        let gfa = input.parse(&parser, options)?;
+        let (num_paths, unparseable) = unparseable_path_names(&gfa, options);
+        let num_parsed = num_paths - unparseable.len();
+        writeln!(out, "{}: {}/{} path names parsed", gfa_path, num_parsed, num_paths)?;
+        for path_name in unparseable.iter().take(MAX_REPORTED_PATH_NAMES) {
+            writeln!(out, "    unparseable: {}", path_name)?;
+        }
+        if unparseable.len() > MAX_REPORTED_PATH_NAMES {
+            writeln!(out, "    ... and {} more", unparseable.len() - MAX_REPORTED_PATH_NAMES)?;
+        }
+        if num_parsed == 0 {
+            all_valid = false;
+        }
+        Ok(())
+    })?;
+
+    Ok(all_valid)
+}
+
+/// Lines of the input GFA files that are not part of the graph, but are copied to the output
+#[derive(Debug, Default)]
+pub(crate) struct PassthroughLines {
+    /// Comment lines, tagged with their source file
+    pub(crate) comments: Vec<String>,
+    /// Lines of unrecognized record types, verbatim
+    pub(crate) unknown: Vec<String>,
+    /// C lines of the input files, written after the links
+    pub(crate) containments: Vec<Containment>,
+}
+
+/// A C line between translated nodes, with its position, overlap, and optional fields as in the input
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Containment {
+    pub(crate) container: Handle,
+    pub(crate) contained: Handle,
+    pub(crate) pos: usize,
+    pub(crate) overlap: String,
+    pub(crate) tags: Vec<String>,
+}
@@ -0,0 +1,777 @@
+//! Lacing of the input GFAs by the command line run, and by the library interface
+
+use std::io;
+use std::process::ExitCode;
+use rustc_hash::{FxHashMap, FxHashSet};
+use handlegraph::hashgraph::HashGraph;
+use log::{debug, info, warn, error};
+use rust_htslib::faidx;
+use crate::RunError;
+use crate::cli::{Args, SUMMARY_TARGET, existing_outputs, input_collisions, output_files};
+use crate::input::{BlockTable, CombinedInput, IdOffsets, PassthroughLines, ReadOptions, apply_range_order, check_alphabet, format_segment_ids, list_samples, read_blocks_tsv, read_contig_lengths, read_gaf_paths, read_gfa_files, read_range_order, validate_input};
+use crate::merge::{EdgeSet, LinkOverlaps, MAX_REPORTED_PATH_EDGES, blunt_overlaps, chop_long_nodes, collapse_haplotypes, drop_incomplete_keys, drop_low_coverage_keys, drop_unused_edges, link_path_steps, merge_path_ranges, missing_path_edges, prune_orphans};
+use crate::names::{RangeSyntax, check_name_scheme};
+use crate::open::{check_header_versions, collect_passthrough_lines};
+use crate::output::LineEnding;
+use crate::ranges::{PathKey, end_gaps, find_overlaps, large_gaps, unexpected_overlaps};
+use crate::report::{RangeReport, degree_histogram, write_completeness, write_sample_table};
+use crate::subcommands::stats::graph_stats;
+use crate::write::{FragmentNaming, LiftoverWriter, PathFastaWriter, WriteOptions, block_groups, check_roundtrip, file_prefixed_node_names, node_origins, path_group_names, path_groups, path_tag_conflicts, rename_output_nodes, write_checksum_file, write_graph_to_gfa};
+
+// use std::process::Command;
+
+// #[cfg(not(debug_assertions))]
+// fn log_memory_usage(stage: &str) {
+//     let output = Command::new("ps")
+//         .args(&["-o", "rss=", "-p", &std::process::id().to_string()])
+//         .output()
+//         .expect("Failed to execute ps command");
+    
+//     let memory_kb = String::from_utf8_lossy(&output.stdout)
+//         .trim()
+//         .parse::<u64>()
+//         .unwrap_or(0);
+    
+//     let memory_mb = memory_kb as f64 / 1024.0;
+//     info!("Memory usage at {}: {:.2} MB", stage, memory_mb);
+// }
+
+/// Laces the input GFAs into the output as the arguments say, or lists their samples or validates
+/// them instead. The failed checks under --strict log the summary of the run before failing
+pub(crate) fn run(mut args: Args, read_options: &ReadOptions) -> Result<ExitCode, RunError> {
+    let block_table = args.blocks_tsv.as_ref().map(|tsv_path| read_blocks_tsv(tsv_path, &args.blocks_tsv_columns))
+        .transpose().map_err(RunError::context("Failed to read the blocks TSV"))?;
+    if let Some(block_table) = &block_table {
+        let missing = block_table.missing_files(&args.gfa_list);
+        if !missing.is_empty() {
+            return Err(RunError::failed(format!("{} files of the blocks TSV do not exist: {}", missing.len(), missing.join(", "))));
+        }
+        if args.gfa_list.is_empty() {
+            args.gfa_list = block_table.files.clone();
+            info!(stage = "parse"; "Reading the {} files of the blocks TSV", args.gfa_list.len());
+        } else {
+            for file in block_table.unmatched_files(&args.gfa_list) {
+                warn!(stage = "parse", file = file; "{} is in the blocks TSV but not among the inputs", file);
+            }
+        }
+    }
+
+    if args.list_samples {
+        list_samples(&args.gfa_list, read_options).map_err(RunError::context("Error listing samples"))?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    if args.validate_input {
+        if !validate_input(&args.gfa_list, read_options).map_err(RunError::context("Error validating input"))? {
+            return Err(RunError::failed("Some input files have no parseable path names"));
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+    let output = args.output.as_deref().ok_or_else(|| RunError::failed("--output is required when not listing samples or validating input"))?;
+
+    let fasta_reader = args.fasta.as_ref().map(faidx::Reader::from_path).transpose().map_err(RunError::context("Failed to open FASTA file"))?;
+
+    let contig_lengths = match &args.contig_lengths {
+        Some(lengths_path) => Some(read_contig_lengths(lengths_path).map_err(RunError::context("Failed to read the contig lengths"))?),
+        None => block_table.as_ref().map(BlockTable::contig_lengths),
+    };
+
+    let junction_reader = args.check_junctions.as_ref().map(faidx::Reader::from_path).transpose().map_err(RunError::context("Failed to open junction FASTA file"))?;
+
+    let id_offsets = match (&args.id_offsets, args.id_spacing) {
+        (Some(_), Some(_)) => return Err(RunError::failed("--id-offsets and --id-spacing are mutually exclusive")),
+        (Some(id_offsets), None) => Some(IdOffsets::Explicit(id_offsets)),
+        (None, Some(0)) => return Err(RunError::failed("--id-spacing must be at least 1")),
+        (None, Some(id_spacing)) => Some(IdOffsets::Spacing(id_spacing as u64)),
+        (None, None) => None,
+    };
+    if args.first_id == 0 {
+        return Err(RunError::failed("--first-id must be at least 1"));
+    }
+    if args.first_id > 1 && id_offsets.is_some() {
+        return Err(RunError::failed("--first-id cannot be combined with --id-offsets or --id-spacing, whose node IDs are written as they are"));
+    }
+    if args.first_id > 1 && args.id_prefix_by_file {
+        return Err(RunError::failed("--first-id cannot be combined with --id-prefix-by-file, which names the nodes instead of numbering them"));
+    }
+    if args.paths_only && (args.check_roundtrip || args.graph_stats) {
+        return Err(RunError::failed("--paths-only cannot be combined with --check-roundtrip or --graph-stats, which parse the output as a graph"));
+    }
+    if args.line_ending == LineEnding::Crlf && (args.check_roundtrip || args.graph_stats || args.id_prefix_by_file) {
+        return Err(RunError::failed("--line-ending crlf cannot be combined with --check-roundtrip, --graph-stats, or --id-prefix-by-file, which read the output back"));
+    }
+    if args.emit_block_groups && (args.id_prefix_by_file || args.shard_size.is_some()) {
+        return Err(RunError::failed("--emit-block-groups cannot be combined with --id-prefix-by-file, whose node names already give their input, or --shard-size"));
+    }
+    if args.shard_size.is_some() && (args.check_roundtrip || args.graph_stats || args.id_prefix_by_file || args.checksum) {
+        return Err(RunError::failed("--shard-size cannot be combined with --check-roundtrip, --graph-stats, --id-prefix-by-file, or --checksum, which read the output back as one file"));
+    }
+    if args.tmp_dir.is_some() && !args.low_memory && !args.external_sort {
+        return Err(RunError::failed("--tmp-dir requires --low-memory or --external-sort"));
+    }
+    if args.external_sort && args.no_sort {
+        return Err(RunError::failed("--external-sort cannot be combined with --no-sort, which writes the links without sorting them"));
+    }
+    if args.no_sequences && (args.fasta.is_some() || args.paths_fasta.is_some()) {
+        return Err(RunError::failed("--no-sequences cannot be combined with --fasta or --paths-fasta, whose sequences it would not write"));
+    }
+    if args.external_sort && (args.report_degrees || args.check_roundtrip) {
+        return Err(RunError::failed("--external-sort cannot be combined with --report-degrees or --check-roundtrip, which collect all the links in memory"));
+    }
+    if args.shard_size.is_some() && (args.line_ending == LineEnding::Crlf || output.ends_with(".gz") || output.ends_with(".zst")) {
+        return Err(RunError::failed("--shard-size writes uncompressed shards with LF line endings; use an output name without .gz or .zst and --line-ending lf"));
+    }
+    // Never overwrite an input, even with --force, as the run would destroy what it reads
+    let outputs = output_files(&args, output).map_err(RunError::context("Failed to list the output files"))?;
+    let collisions = input_collisions(&args, &outputs);
+    if !collisions.is_empty() {
+        let described: Vec<String> = collisions.iter().map(|(output, input)| format!("{} (input {})", output, input)).collect();
+        return Err(RunError::failed(format!("Refusing to overwrite input files with outputs: {}", described.join(", "))));
+    }
+    // Refuse to clobber a previous result before the long parsing phase
+    if !args.force {
+        let existing = existing_outputs(&args, output).map_err(RunError::context("Failed to check for existing output files"))?;
+        if !existing.is_empty() {
+            return Err(RunError::failed(format!("Refusing to overwrite existing output files, use --force to overwrite them: {}", existing.join(", "))));
+        }
+    }
+
+    // log_memory_usage("start");
+
+    if args.verbose > 1
+        && let Err(e) = check_header_versions(&args.gfa_list, read_options) {
+        warn!("Could not check the header versions: {}", e);
+    }
+
+    // Create a single combined graph without paths and a map of path key to ranges
+    let spill_dir = args.low_memory.then(|| args.tmp_dir.as_ref().map_or_else(std::env::temp_dir, std::path::PathBuf::from));
+    if let Some(spill_dir) = &spill_dir {
+        info!(stage = "parse"; "Spilling the node sequences to a temporary file in {}", spill_dir.display());
+    }
+    let CombinedInput {
+        graph: mut combined_graph, mut path_key_ranges, mut link_overlaps, mut sequenceless,
+        gfa_names, duplicate_segments, skipped_inputs, dropped_paths, id_translations, containments, mut sequences, warnings,
+        block_range_conflicts, mut edges, ..
+    } = read_gfa_files(&args.gfa_list, &ReadOptions {
+        id_offsets,
+        spill_dir: spill_dir.as_deref(),
+        block_table: block_table.as_ref(),
+        ignore_links: args.ignore_input_links,
+        ..*read_options
+    }).map_err(RunError::context("Error reading the GFA files"))?;
+    if !args.gaf.is_empty() {
+        if args.gaf.len() != gfa_names.len() {
+            return Err(RunError::failed(format!("Got {} GAF files for {} input GFAs, expected one per input", args.gaf.len(), gfa_names.len())));
+        }
+        for (gfa_id, gaf_path) in args.gaf.iter().enumerate() {
+            let num_paths = read_gaf_paths(gaf_path, gfa_id, id_translations[gfa_id], &combined_graph, &sequences, read_options, &mut path_key_ranges)
+                .map_err(|e| RunError::failed(format!("Failed to read GAF file '{}': {}", gaf_path, e)))?;
+            info!(stage = "parse", file = gaf_path.as_str(); "Added {} paths from GAF file {} ({})", num_paths, gaf_path, gfa_names[gfa_id]);
+        }
+    }
+    check_name_scheme(&path_key_ranges, args.name_scheme, &gfa_names).map_err(|e| RunError::failed(e.to_string()))?;
+    // The paths of an input may come from its GAF file
+    let inputs_with_ranges: FxHashSet<usize> = path_key_ranges.values().flatten().map(|range| range.gfa_id).collect();
+    for warning in &warnings {
+        if !matches!(warning, LaceWarning::PathlessBlock { block } if inputs_with_ranges.contains(block)) {
+            warning.log(&gfa_names);
+        }
+    }
+    let mut summary = RunSummary {
+        parsed_files: gfa_names.len(),
+        skipped_files: skipped_inputs,
+        dropped_paths,
+        duplicate_segment_files: duplicate_segments.len(),
+        block_range_conflicts,
+        ..RunSummary::default()
+    };
+    if let Some(alphabet) = &args.check_alphabet {
+        summary.alphabet_violations = check_alphabet(&combined_graph, &sequences, &sequenceless, alphabet.as_bytes(), &id_translations, &gfa_names).len();
+    }
+    if args.strict && summary.strict_violations() > 0 {
+        for (gfa_path, duplicates) in &duplicate_segments {
+            error!("Segment IDs defined more than once in '{}': {}", gfa_path, format_segment_ids(duplicates));
+        }
+        if summary.alphabet_violations > 0 {
+            error!("Found {} segments with characters outside the alphabet", summary.alphabet_violations);
+        }
+        if summary.block_range_conflicts > 0 {
+            error!("Found {} paths whose range differs from the blocks TSV", summary.block_range_conflicts);
+        }
+        summary.log();
+        return Err(RunError::failed("The input files have issues that --strict does not allow"));
+    }
+
+    // log_memory_usage("after_reading_files");
+
+    if args.report_ranges || log::log_enabled!(log::Level::Debug) {
+        let report = RangeReport::new(&path_key_ranges);
+        for key in &report.keys {
+            debug!(stage = "parse", path_key = key.path_key.as_str(), ranges = key.ranges, gaps = key.gaps, overlaps = key.overlaps;
+                "Path key '{}' has {} ranges, {} gaps, and {} overlaps", key.path_key, key.ranges, key.gaps, key.overlaps);
+        }
+        if args.report_ranges {
+            report.log(&gfa_names);
+        }
+    }
+
+    if let Some(order_path) = &args.range_order {
+        let order = read_range_order(order_path, &gfa_names).map_err(RunError::context("Failed to read the range order"))?;
+        for path_key in apply_range_order(&mut path_key_ranges, &order) {
+            warn!(stage = "merge", path_key = path_key.as_str(); "Path key '{}' of the range order has no ranges", path_key);
+        }
+        info!(stage = "merge"; "Ordered the ranges of {} path keys by rank", order.len());
+    }
+
+    // The checks below report each finding as an error with --strict, before failing
+    let strict_level = if args.strict { log::Level::Error } else { log::Level::Warn };
+    let mut keys_with_unexpected_overlaps = None;
+    if let Some(expected_overlap) = args.expected_overlap {
+        let overlaps = unexpected_overlaps(&path_key_ranges, expected_overlap);
+        for (path_key, overlap) in &overlaps {
+            let describe = if overlap.first.gfa_id == overlap.second.gfa_id { "within a block" } else { "between blocks" };
+            log::log!(strict_level, stage = "merge", path_key = path_key.as_str(), position = overlap.second.start, overlap_bp = overlap.size;
+                "Path key '{}' has an overlap of {} bp at position {} {}, where only {} bp between blocks are expected", path_key, overlap.size, overlap.second.start, describe, expected_overlap);
+        }
+        summary.unexpected_overlaps = overlaps.len();
+        if summary.unexpected_overlaps > 0 && args.strict {
+            summary.log();
+            return Err(RunError::failed(format!("Found {} overlaps other than the expected {} bp between blocks", summary.unexpected_overlaps, expected_overlap)));
+        }
+        keys_with_unexpected_overlaps = Some(overlaps.iter().map(|(path_key, _)| path_key).collect::<FxHashSet<_>>().len());
+    }
+
+    // Sort, deduplicate, trim, and link path ranges
+    let mut split_origins = merge_path_ranges(&mut path_key_ranges, &mut combined_graph, &mut edges, &mut sequences, &mut sequenceless, junction_reader.as_ref(), &mut summary, args.verbose > 1);
+    if args.ignore_input_links {
+        let path_edges = link_path_steps(&path_key_ranges, &mut combined_graph, &mut edges);
+        info!(stage = "merge"; "Created {} edges between the consecutive steps of the paths", path_edges);
+    }
+    // The tiling overlaps are not issues
+    if let Some(keys) = keys_with_unexpected_overlaps {
+        summary.keys_with_overlaps = keys;
+    }
+
+    if args.collapse_haplotypes {
+        let collapsed = collapse_haplotypes(&mut path_key_ranges, args.collapse_policy).map_err(RunError::context("Failed to collapse haplotypes"))?;
+        info!(stage = "merge"; "Collapsed the haplotypes of {} sample contigs", collapsed);
+    }
+
+    if summary.junction_mismatches > 0 {
+        if args.strict {
+            summary.log();
+            return Err(RunError::failed(format!("Found {} junctions whose sequence does not match the FASTA", summary.junction_mismatches)));
+        }
+        warn!(stage = "merge"; "Found {} junctions whose sequence does not match the FASTA", summary.junction_mismatches);
+    }
+
+    summary.path_tag_conflicts = path_tag_conflicts(&path_key_ranges);
+    if summary.path_tag_conflicts > 0 && args.strict {
+        summary.log();
+        return Err(RunError::failed(format!("Found {} path keys whose ranges have conflicting P line tags", summary.path_tag_conflicts)));
+    }
+
+    if let Some(max_gap) = args.max_gap {
+        let gaps = large_gaps(&path_key_ranges, max_gap);
+        let hint = if args.strict { "" } else { ": is a block missing?" };
+        for (path_key, position, gap_bp) in &gaps {
+            log::log!(strict_level, stage = "merge", path_key = path_key.as_str(), position = position, gap_bp = gap_bp;
+                "Path key '{}' has a gap of {} bp at position {}, longer than --max-gap {}{}", path_key, gap_bp, position, max_gap, hint);
+        }
+        summary.large_gaps = gaps.len();
+        if summary.large_gaps > 0 && args.strict {
+            summary.log();
+            return Err(RunError::failed(format!("Found {} gaps longer than {} bp", summary.large_gaps, max_gap)));
+        }
+    }
+
+    if let Some(contig_lengths) = &contig_lengths {
+        let (gaps, missing_contigs) = end_gaps(&path_key_ranges, contig_lengths);
+        for (path_key, start, end) in &gaps {
+            let side = if *start == 0 { "start" } else { "end" };
+            log::log!(strict_level, stage = "merge", path_key = path_key.as_str(), position = start, gap_bp = end - start;
+                "Path key '{}' does not cover {}-{} ({} bp) at the {} of its contig", path_key, start, end, end - start, side);
+        }
+        if !missing_contigs.is_empty() {
+            warn!(stage = "merge", missing_contigs = missing_contigs.len();
+                "{} contigs of the contig lengths have no path key: {}", missing_contigs.len(), missing_contigs.join(", "));
+        }
+        summary.end_gaps = gaps.len();
+        summary.missing_contigs = missing_contigs.len();
+        if summary.end_gaps + summary.missing_contigs > 0 && args.strict {
+            summary.log();
+            return Err(RunError::failed(format!("Found {} gaps at contig ends and {} missing contigs", summary.end_gaps, summary.missing_contigs)));
+        }
+    }
+
+    if let Some(completeness_path) = &args.completeness {
+        let samples = write_completeness(completeness_path, &path_key_ranges, contig_lengths.as_ref(), args.fill_gaps)
+            .map_err(RunError::context("Error writing the completeness report"))?;
+        for ((sample, haplotype), counts) in &samples {
+            info!(stage = "merge", sample = sample.as_str(), haplotype = haplotype.as_str(), complete = counts.complete, fragmented = counts.fragmented;
+                "{} {}: {}/{} contigs complete, {} fragmented", sample, haplotype, counts.complete, counts.contigs, counts.fragmented);
+        }
+        info!(stage = "merge"; "Wrote the completeness report to {}", completeness_path);
+    }
+
+    if args.blunt {
+        let resolved = blunt_overlaps(&mut combined_graph, &mut sequences, &mut path_key_ranges, &mut sequenceless, &mut link_overlaps, &mut split_origins)
+            .map_err(RunError::context("Error making the overlapping links blunt"))?;
+        info!(stage = "merge"; "Trimmed the bases duplicated by {} overlapping links", resolved);
+    }
+
+    if let Some(max_node_length) = args.max_node_length {
+        let chopped = chop_long_nodes(&mut combined_graph, &mut sequences, &mut path_key_ranges, max_node_length, &mut sequenceless, &mut link_overlaps, &mut split_origins);
+        info!(stage = "merge"; "Split {} nodes longer than {} bp", chopped, max_node_length);
+    }
+
+    if let Some(min_coverage_fraction) = args.min_coverage_fraction {
+        summary.low_coverage_keys = drop_low_coverage_keys(&mut path_key_ranges, &combined_graph, &sequences, min_coverage_fraction, args.fill_gaps > 0 && args.count_filled_gaps);
+        info!(stage = "merge"; "Skipped {} path keys covering less than {} of their span", summary.low_coverage_keys, min_coverage_fraction);
+    }
+
+    if args.only_complete {
+        let incomplete = drop_incomplete_keys(&mut path_key_ranges, contig_lengths.as_ref());
+        summary.incomplete_keys = incomplete.len();
+        if incomplete.is_empty() {
+            info!(stage = "merge"; "All the path keys are complete");
+        } else {
+            warn!(stage = "merge", incomplete_keys = incomplete.len();
+                "Skipped {} path keys that are fragmented or do not cover their contig: {}", incomplete.len(), incomplete.join(", "));
+        }
+    }
+
+    if args.prune_orphans {
+        let removed = prune_orphans(&mut combined_graph, &path_key_ranges, &id_translations, &split_origins);
+        for (gfa_name, &count) in gfa_names.iter().zip(&removed).filter(|(_, count)| **count > 0) {
+            info!(stage = "merge", file = gfa_name.as_str(), removed_nodes = count; "Pruned {} nodes unused by the paths from {}", count, gfa_name);
+        }
+        info!(stage = "merge"; "Pruned {} nodes unused by the paths", removed.iter().sum::<usize>());
+    }
+
+    if args.drop_unused_edges {
+        let removed = drop_unused_edges(&mut combined_graph, &path_key_ranges, &id_translations, &split_origins);
+        for (gfa_name, &count) in gfa_names.iter().zip(&removed).filter(|(_, count)| **count > 0) {
+            info!(stage = "merge", file = gfa_name.as_str(), removed_edges = count; "Removed {} edges unused by the paths from {}", count, gfa_name);
+        }
+        info!(stage = "merge"; "Removed {} edges unused by the paths", removed.iter().sum::<usize>());
+    }
+
+    if args.check_path_edges {
+        let missing = missing_path_edges(&path_key_ranges, &combined_graph);
+        summary.missing_path_edges = missing.len();
+        for (path_key, position, step) in missing.iter().take(MAX_REPORTED_PATH_EDGES) {
+            warn!(stage = "merge", path_key = path_key.as_str(), position = position, step = step;
+                "Path key '{}' has no edge between its steps {} and {}, at position {}", path_key, step, step + 1, position);
+        }
+        if missing.is_empty() {
+            info!(stage = "merge"; "All the adjacent steps of the paths have an edge");
+        } else if args.strict {
+            summary.log();
+            return Err(RunError::failed(format!("Found {} adjacent path steps without an edge", missing.len())));
+        } else {
+            warn!(stage = "merge", missing_path_edges = missing.len(); "Found {} adjacent path steps without an edge", missing.len());
+        }
+    }
+
+    // log_memory_usage("before_writing");
+
+    let mut passthrough = if args.preserve_comments || args.passthrough_unknown {
+        collect_passthrough_lines(&args.gfa_list, read_options, args.preserve_comments, args.passthrough_unknown)
+            .map_err(RunError::context("Failed to collect pass-through lines"))?
+    } else {
+        PassthroughLines::default()
+    };
+    passthrough.containments = containments;
+
+    let origins = args.tag_origin.then(|| node_origins(&combined_graph, &id_translations, &split_origins, &gfa_names));
+    let write_options = WriteOptions {
+        fill_gaps: args.fill_gaps,
+        no_sequences: args.no_sequences,
+        no_links: args.no_links,
+        max_path_steps: args.max_path_steps.unwrap_or(usize::MAX),
+        sort_paths_by: args.sort_paths_by,
+        fragment_naming: args.fragment_naming,
+        range_syntax: args.range_delimiters,
+        no_sort: args.no_sort,
+        segment_batch: args.segment_batch.max(1),
+        external_sort: args.external_sort.then(|| args.tmp_dir.as_ref().map_or_else(std::env::temp_dir, std::path::PathBuf::from)),
+        sort_chunk_size: args.sort_chunk_size.max(1),
+        node_order: args.node_order,
+        first_id: args.first_id,
+        keep_ids: id_offsets.is_some(),
+        paths_only: args.paths_only,
+        line_ending: args.line_ending,
+        shard_size: args.shard_size.map(|size| size as u64),
+        origins: origins.as_deref(),
+        debug: args.verbose > 1,
+    };
+    let mut liftover = args.liftover.as_ref().map(|liftover_path| LiftoverWriter::new(liftover_path, &gfa_names))
+        .transpose().map_err(RunError::context("Failed to create the liftover file"))?;
+    let mut paths_fasta = args.paths_fasta.as_ref().map(|fasta_path| PathFastaWriter::new(fasta_path))
+        .transpose().map_err(RunError::context("Failed to create the paths FASTA file"))?;
+    let block_groups = args.emit_block_groups.then(|| block_groups(&combined_graph, &path_key_ranges, &write_options, &id_translations, &split_origins, &gfa_names));
+    let (split_keys, max_node_id) = write_graph_to_gfa(&combined_graph, &sequences, &path_key_ranges, &sequenceless, &link_overlaps, &passthrough, output, &fasta_reader, &write_options, liftover.as_mut(), paths_fasta.as_mut(), block_groups.as_ref())
+        .and_then(|written| liftover.map_or(Ok(()), LiftoverWriter::finish).map(|_| written))
+        .and_then(|written| paths_fasta.map_or(Ok(()), PathFastaWriter::finish).map(|_| written))
+        .map_err(RunError::context("Error writing the GFA file"))?;
+    summary.split_paths = split_keys;
+    summary.max_node_id = max_node_id;
+    info!(stage = "write", max_node_id = max_node_id; "Successfully wrote the combined graph to {} (largest node ID: {})", output, max_node_id);
+
+    if args.check_roundtrip {
+        let discrepancies = check_roundtrip(output, &combined_graph, &sequences, &path_key_ranges, &sequenceless, &write_options)
+            .map_err(RunError::context("Error parsing the written GFA file back"))?;
+        if !discrepancies.is_empty() {
+            for discrepancy in discrepancies.iter().take(20) {
+                error!(stage = "write"; "Round trip: {}", discrepancy);
+            }
+            return Err(RunError::failed(format!("The written graph does not match the combined graph ({} discrepancies)", discrepancies.len())));
+        }
+        info!(stage = "write"; "Verified the combined graph written to {}", output);
+    }
+
+    if let Some(sample_table_path) = &args.sample_table {
+        write_sample_table(sample_table_path, &combined_graph, &sequences, &path_key_ranges, &fasta_reader, args.fill_gaps)
+            .map_err(RunError::context("Error writing the sample table"))?;
+        info!(stage = "write"; "Wrote the sample table to {}", sample_table_path);
+    }
+
+    let node_names = args.id_prefix_by_file.then(|| file_prefixed_node_names(&combined_graph, &path_key_ranges, &write_options, &id_translations, &split_origins));
+    if let Some(names) = &node_names {
+        rename_output_nodes(output, names).map_err(RunError::context("Error renaming the nodes of the GFA file"))?;
+        info!(stage = "write"; "Named the nodes of the combined graph after their input files");
+    }
+
+    if args.checksum {
+        let checksum_path = format!("{}.sha256", output);
+        write_checksum_file(output, &checksum_path).map_err(RunError::context("Error writing the checksum"))?;
+        info!(stage = "write"; "Wrote the checksum of the combined graph to {}", checksum_path);
+    }
+
+    if args.report_degrees {
+        let degrees = degree_histogram(&combined_graph, &path_key_ranges, &write_options);
+        for (direction, counts, max_degree_node) in [
+            ("in", &degrees.in_counts, degrees.max_in_degree_node),
+            ("out", &degrees.out_counts, degrees.max_out_degree_node),
+        ] {
+            for (&degree, &count) in counts {
+                info!(stage = "write", direction = direction, degree = degree, nodes = count; "Nodes of {}-degree {}: {}", direction, degree, count);
+            }
+            if let Some((node_id, degree)) = max_degree_node {
+                let node = match &node_names {
+                    Some(names) => names[node_id].clone(),
+                    None => (node_id + args.first_id - 1).to_string(),
+                };
+                info!(stage = "write", direction = direction, degree = degree, node = node.as_str(); "Highest {}-degree: {} (node {})", direction, degree, node);
+            }
+        }
+    }
+
+    if args.graph_stats {
+        graph_stats(output).map_err(RunError::context("Error computing the graph statistics"))?.print();
+    }
+
+    summary.log();
+
+    // log_memory_usage("end");
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Counters of a run, reported at its end
+#[derive(Debug, Default)]
+pub(crate) struct RunSummary {
+    pub(crate) parsed_files: usize,
+    /// Tar archive members that are not GFA files
+    pub(crate) skipped_files: usize,
+    /// Paths with an unparseable name or without steps
+    pub(crate) dropped_paths: usize,
+    /// Inputs defining some segment IDs more than once
+    pub(crate) duplicate_segment_files: usize,
+    pub(crate) keys_with_gaps: usize,
+    /// Total length of the gaps between the ranges of the path keys
+    pub(crate) gap_bp: usize,
+    pub(crate) keys_with_overlaps: usize,
+    /// Edges created to link the ranges of different blocks
+    pub(crate) new_edges: usize,
+    /// Path keys written as several paths
+    pub(crate) split_paths: usize,
+    pub(crate) junction_mismatches: usize,
+    /// Path keys skipped for covering too little of their span
+    pub(crate) low_coverage_keys: usize,
+    /// Path keys skipped by --only-complete
+    pub(crate) incomplete_keys: usize,
+    /// Segments with characters outside the --check-alphabet alphabet
+    pub(crate) alphabet_violations: usize,
+    /// Path keys whose ranges give different values to the same P line tag
+    pub(crate) path_tag_conflicts: usize,
+    /// Gaps between ranges longer than --max-gap
+    pub(crate) large_gaps: usize,
+    /// Overlaps between ranges other than the --expected-overlap between blocks
+    pub(crate) unexpected_overlaps: usize,
+    /// Gaps before the first range or after the last one of the path keys, given --contig-lengths
+    pub(crate) end_gaps: usize,
+    /// Contigs of --contig-lengths without any path key
+    pub(crate) missing_contigs: usize,
+    /// Paths whose name gives another range than the --blocks-tsv
+    pub(crate) block_range_conflicts: usize,
+    /// Adjacent steps of the merged paths without an edge, found by --check-path-edges
+    pub(crate) missing_path_edges: usize,
+    /// Largest output node ID, gap nodes included, for the --first-id of a next run
+    pub(crate) max_node_id: usize,
+}
+
+impl RunSummary {
+    /// Number of problems that are fatal under --strict
+    pub(crate) fn strict_violations(&self) -> usize {
+        self.duplicate_segment_files + self.junction_mismatches + self.alphabet_violations + self.path_tag_conflicts
+            + self.large_gaps + self.unexpected_overlaps + self.end_gaps + self.missing_contigs + self.block_range_conflicts
+            + self.missing_path_edges
+    }
+
+    pub(crate) fn has_issues(&self) -> bool {
+        self.skipped_files + self.dropped_paths + self.keys_with_gaps + self.keys_with_overlaps
+            + self.split_paths + self.incomplete_keys + self.strict_violations() > 0
+    }
+
+    /// Logs the summary, as a warning if anything went wrong
+    pub(crate) fn log(&self) {
+        let level = if self.has_issues() { log::Level::Warn } else { log::Level::Info };
+        log::log!(
+            target: SUMMARY_TARGET,
+            level,
+            stage = "summary",
+            parsed_files = self.parsed_files, skipped_files = self.skipped_files, dropped_paths = self.dropped_paths,
+            duplicate_segment_files = self.duplicate_segment_files, keys_with_gaps = self.keys_with_gaps, gap_bp = self.gap_bp,
+            keys_with_overlaps = self.keys_with_overlaps, new_edges = self.new_edges, split_paths = self.split_paths,
+            junction_mismatches = self.junction_mismatches, low_coverage_keys = self.low_coverage_keys,
+            incomplete_keys = self.incomplete_keys, alphabet_violations = self.alphabet_violations, path_tag_conflicts = self.path_tag_conflicts,
+            large_gaps = self.large_gaps, unexpected_overlaps = self.unexpected_overlaps, end_gaps = self.end_gaps, missing_contigs = self.missing_contigs,
+            block_range_conflicts = self.block_range_conflicts, missing_path_edges = self.missing_path_edges, max_node_id = self.max_node_id;
+            "Summary: {} files parsed ({} skipped), {} paths dropped, {} files with duplicate segments, \
+            {} path keys with gaps ({} bp), {} path keys with overlaps, {} edges created between blocks, \
+            {} paths split into fragments, {} junction mismatches, {} path keys skipped for low coverage, \
+            {} incomplete path keys skipped, {} segments outside the alphabet, {} path keys with conflicting tags, \
+            {} gaps over --max-gap, {} unexpected overlaps, {} gaps at contig ends, {} missing contigs, {} ranges conflicting with the blocks TSV, {} path steps without an edge, largest node ID {}",
+            self.parsed_files, self.skipped_files, self.dropped_paths, self.duplicate_segment_files,
+            self.keys_with_gaps, self.gap_bp, self.keys_with_overlaps, self.new_edges,
+            self.split_paths, self.junction_mismatches, self.low_coverage_keys, self.incomplete_keys, self.alphabet_violations,
+            self.path_tag_conflicts, self.large_gaps, self.unexpected_overlaps, self.end_gaps, self.missing_contigs, self.block_range_conflicts, self.missing_path_edges, self.max_node_id
+        );
+    }
+}
+
+/// Options of `lace_graphs`
+#[derive(Debug, Clone)]
+pub struct LaceOptions {
+    /// Join the paths by name rather than by range
+    pub naive_join: bool,
+    /// Naming of the paths of non-contiguous ranges
+    pub fragment_naming: FragmentNaming,
+    /// Syntax of the range at the end of the path names, read and written
+    pub range_syntax: RangeSyntax,
+    /// Sequence lengths by path key, as read from the FASTA of the command line: a path key whose
+    /// ranges end before its length is named as a fragment
+    pub sequence_lengths: std::collections::HashMap<String, usize>,
+}
+
+impl Default for LaceOptions {
+    fn default() -> Self {
+        LaceOptions {
+            naive_join: false,
+            fragment_naming: FragmentNaming::Range,
+            range_syntax: RangeSyntax::ColonDash,
+            sequence_lengths: std::collections::HashMap::new(),
+        }
+    }
+}
+
+/// Error of `lace_graphs`
+#[derive(Debug)]
+pub enum LaceError {
+    /// Two paths of the laced graph would have this name, e.g. a path key named like a fragment of another
+    DuplicatePath(String),
+    /// Failed to store the node sequences
+    Io(io::Error),
+}
+
+impl std::fmt::Display for LaceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LaceError::DuplicatePath(path_name) => write!(f, "Path '{}' is defined more than once", path_name),
+            LaceError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for LaceError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LaceError::Io(e) => Some(e),
+            LaceError::DuplicatePath(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for LaceError {
+    fn from(e: io::Error) -> Self {
+        LaceError::Io(e)
+    }
+}
+
+/// Finding of `lace_graphs` about its input blocks, which the command line logs as warnings
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LaceWarning {
+    /// Two ranges of a path key overlap on `[start, end)`, before trimming
+    Overlap { path_key: String, start: usize, end: usize, first_block: usize, second_block: usize },
+    /// No range of a path key covers `[start, end)`, between two of its ranges
+    Gap { path_key: String, start: usize, end: usize },
+    /// Paths of a block skipped because their name is not `NAME:START-END` or they have no steps
+    DroppedPaths { block: usize, count: usize },
+    /// A block with nodes but without any path range
+    PathlessBlock { block: usize },
+    /// A path of a block visits a node that the block does not have, and is skipped
+    MissingNode { block: usize, path_name: String, node: u64 },
+    /// A path of a block spells `spelled` bp, while its name gives a range of `expected` bp
+    ValidationMismatch { block: usize, path_name: String, expected: usize, spelled: usize },
+}
+
+impl LaceWarning {
+    /// Logs the warning as the command line does, with the inputs named by `gfa_names`
+    pub(crate) fn log(&self, gfa_names: &[String]) {
+        match self {
+            LaceWarning::Overlap { path_key, start, end, first_block, second_block } => {
+                debug!(stage = "merge", path_key = path_key.as_str(), position = start, overlap_bp = end - start;
+                    "Path key '{}' has an overlap of {} bp at position {} between '{}' and '{}'", path_key, end - start, start, gfa_names[*first_block], gfa_names[*second_block]);
+            }
+            LaceWarning::Gap { path_key, start, end } => {
+                debug!(stage = "merge", path_key = path_key.as_str(), position = start, gap_bp = end - start;
+                    "Path key '{}' has a gap of {} bp at position {}", path_key, end - start, start);
+            }
+            LaceWarning::DroppedPaths { block, count } => {
+                debug!(stage = "parse", file = gfa_names[*block].as_str(), dropped_paths = count;
+                    "{} paths of '{}' are skipped", count, gfa_names[*block]);
+            }
+            LaceWarning::PathlessBlock { block } => {
+                warn!(stage = "parse", file = gfa_names[*block].as_str(); "{} has segments but no paths, so its nodes are not written", gfa_names[*block]);
+            }
+            LaceWarning::MissingNode { block, path_name, node } => {
+                warn!(stage = "parse", file = gfa_names[*block].as_str(), path = path_name.as_str(), segment = node;
+                    "Path '{}' of '{}' visits segment {}, which it does not define, and is skipped", path_name, gfa_names[*block], node);
+            }
+            LaceWarning::ValidationMismatch { block, path_name, expected, spelled } => {
+                debug!(stage = "parse", file = gfa_names[*block].as_str(), path = path_name.as_str();
+                    "Path '{}' of '{}' spells {} bp for a range of {} bp", path_name, gfa_names[*block], spelled, expected);
+            }
+        }
+    }
+}
+
+/// Counters of `lace_graphs`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LaceStats {
+    pub blocks: usize,
+    pub path_keys: usize,
+    /// Paths written, more than the path keys when some have gaps
+    pub paths: usize,
+    pub keys_with_overlaps: usize,
+    pub keys_with_gaps: usize,
+    /// Total length of the gaps between the ranges of the path keys
+    pub gap_bp: usize,
+    /// Edges created to link the ranges of different blocks
+    pub new_edges: usize,
+}
+
+/// Laced graph of `lace_graphs`, with its findings
+#[derive(Debug)]
+pub struct LaceResult {
+    pub graph: HashGraph,
+    pub warnings: Vec<LaceWarning>,
+    pub stats: LaceStats,
+}
+
+/// Laces in-memory block graphs, whose paths are named `NAME:START-END`, as the command line
+/// does with GFA files: the node IDs of each block are translated, and the path ranges are
+/// merged and linked. The returned graph has a path per path key, or per group of contiguous
+/// ranges (named as per `fragment_naming`) when there are gaps, and keeps the unused nodes.
+/// The overlaps, gaps, and skipped paths found on the way are returned as warnings instead of logged.
+///
+/// ```
+/// use gfalace::{LaceOptions, lace_graphs};
+/// use handlegraph::{handle::Edge, hashgraph::HashGraph, mutablehandlegraph::*};
+///
+/// let mut block_a = HashGraph::new();
+/// let a1 = block_a.create_handle(b"ACGT", 1);
+/// let a2 = block_a.create_handle(b"GG", 2);
+/// block_a.create_edge(Edge(a1, a2));
+/// let path = block_a.create_path(b"sample#1#chr1:0-6", false).unwrap();
+/// block_a.path_append_step(path, a1);
+/// block_a.path_append_step(path, a2);
+///
+/// let mut block_b = HashGraph::new();
+/// let b1 = block_b.create_handle(b"TTT", 1);
+/// let path = block_b.create_path(b"sample#1#chr1:6-9", false).unwrap();
+/// block_b.path_append_step(path, b1);
+///
+/// let laced = lace_graphs(&[block_a, block_b], &LaceOptions::default())?;
+/// assert!(laced.warnings.is_empty());
+/// assert_eq!(laced.graph.paths.len(), 1);
+/// assert_eq!(laced.graph.paths.values().next().unwrap().nodes.len(), 3);
+/// # Ok::<(), gfalace::LaceError>(())
+/// ```
+pub fn lace_graphs(blocks: &[HashGraph], options: &LaceOptions) -> Result<LaceResult, LaceError> {
+    let mut combined = CombinedInput::new();
+    let mut edges = EdgeSet::default();
+    let mut warnings = Vec::new();
+    let read_options = ReadOptions { naive_join: options.naive_join, range_syntax: options.range_syntax, ..ReadOptions::default() };
+    for (gfa_id, block_graph) in blocks.iter().enumerate() {
+        combined.add_block(block_graph, &format!("block {}", gfa_id), &FxHashMap::default(), &LinkOverlaps::default(), &FxHashMap::default(), &FxHashMap::default(), &read_options, None, &mut edges)?;
+    }
+    warnings.append(&mut combined.warnings);
+
+    let CombinedInput { mut graph, mut path_key_ranges, mut sequenceless, mut sequences, mut edges, .. } = combined;
+    let mut sorted_keys: Vec<&PathKey> = path_key_ranges.keys().collect();
+    sorted_keys.sort();
+    for path_key in sorted_keys {
+        warnings.extend(find_overlaps(&path_key_ranges[path_key]).into_iter().map(|overlap| LaceWarning::Overlap {
+            path_key: path_key.to_string(),
+            start: overlap.first.start.max(overlap.second.start),
+            end: overlap.first.end.min(overlap.second.end),
+            first_block: overlap.first.gfa_id,
+            second_block: overlap.second.gfa_id,
+        }));
+    }
+    let mut summary = RunSummary::default();
+    merge_path_ranges(&mut path_key_ranges, &mut graph, &mut edges, &mut sequences, &mut sequenceless, None, &mut summary, false);
+
+    let mut stats = LaceStats {
+        blocks: blocks.len(),
+        path_keys: path_key_ranges.len(),
+        keys_with_overlaps: summary.keys_with_overlaps,
+        keys_with_gaps: summary.keys_with_gaps,
+        gap_bp: summary.gap_bp,
+        new_edges: summary.new_edges,
+        ..LaceStats::default()
+    };
+    let mut path_keys: Vec<&PathKey> = path_key_ranges.keys().collect();
+    path_keys.sort();
+    for path_key in path_keys {
+        let ranges = &path_key_ranges[path_key];
+        warnings.extend(ranges.windows(2)
+            .filter(|pair| pair[1].start > pair[0].end)
+            .map(|pair| LaceWarning::Gap { path_key: path_key.to_string(), start: pair[0].end, end: pair[1].start }));
+        let groups = path_groups(ranges, false);
+        let sequence_length = options.sequence_lengths.get(&**path_key).copied();
+        let path_names = path_group_names(path_key, ranges, &groups, sequence_length, options.fragment_naming, options.range_syntax);
+        for (group, path_name) in groups.into_iter().zip(path_names) {
+            let path_id = graph.create_path(path_name.as_bytes(), false).ok_or(LaceError::DuplicatePath(path_name))?;
+            for range in &ranges[group] {
+                for &step in &range.steps {
+                    graph.path_append_step(path_id, step);
+                }
+            }
+            stats.paths += 1;
+        }
+    }
+
+    Ok(LaceResult { graph, warnings, stats })
+}
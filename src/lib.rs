@@ -0,0 +1,126 @@
+mod cli;
+mod input;
+mod lace;
+mod links;
+mod merge;
+mod names;
+mod open;
+mod output;
+mod ranges;
+mod report;
+mod sequences;
+mod subcommands;
+mod write;
+#[cfg(test)]
+mod tests;
+
+use std::process::ExitCode;
+use log::{info, warn, error};
+use crate::cli::{Command, init_logger, parse_args};
+use crate::input::{ReadOptions, read_key_map};
+use crate::names::PathFormat;
+
+pub use crate::lace::{LaceError, LaceOptions, LaceResult, LaceStats, LaceWarning, lace_graphs};
+pub use crate::names::RangeSyntax;
+pub use crate::ranges::{Coverage, RangeInfo, interval_coverage};
+pub use crate::write::FragmentNaming;
+
+/// Error that ends a run, reported by `report` before exiting with `exit_code`
+#[derive(Debug)]
+pub enum RunError {
+    /// Invalid arguments, or a log file that cannot be created, found before the logger is set up
+    Setup(String),
+    /// Failure of the run, with the exit code of the process
+    Failed { message: String, exit_code: u8 },
+}
+
+impl RunError {
+    /// Failure of the run, which exits with 1
+    fn failed(message: impl Into<String>) -> Self {
+        RunError::Failed { message: message.into(), exit_code: 1 }
+    }
+
+    /// Turns an error into a failure of the run, described after `context`
+    fn context<E: std::fmt::Display>(context: &str) -> impl FnOnce(E) -> Self + '_ {
+        move |e| RunError::failed(format!("{}: {}", context, e))
+    }
+
+    /// Reports the error on stderr if the logger is not set up yet, and logs it otherwise
+    pub fn report(&self) {
+        match self {
+            RunError::Setup(message) => eprintln!("Error: {}", message),
+            RunError::Failed { message, .. } => error!("{}", message),
+        }
+    }
+
+    /// Exit code of the process: 1, or as given by the failure
+    pub fn exit_code(&self) -> ExitCode {
+        match self {
+            RunError::Setup(_) => ExitCode::FAILURE,
+            RunError::Failed { exit_code, .. } => ExitCode::from(*exit_code),
+        }
+    }
+}
+
+impl std::fmt::Display for RunError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RunError::Setup(message) | RunError::Failed { message, .. } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for RunError {}
+
+/// Runs the command line tool on the arguments of the process: sets up the logger and the options
+/// shared by the subcommands, and hands over to the subcommand given, or to the lacing. Returns the
+/// exit code of a completed run, which is 1 for `diff` if the graphs differ
+pub fn run() -> Result<ExitCode, RunError> {
+    let mut args = parse_args(std::env::args_os()).map_err(|e| RunError::Setup(e.to_string()))?;
+
+    init_logger(&args).map_err(|e| RunError::Setup(format!("failed to create the log file: {}", e)))?;
+    if args.space_delimited_gfa_list {
+        warn!("Space-separated paths in a single --gfa-list value are deprecated: repeat -g, separate the paths with commas, or give them as trailing arguments");
+    }
+    for path in &args.missing_split_inputs {
+        warn!("Input '{}', split from a --gfa-list value that is not a file, does not exist", path);
+    }
+
+    if args.config.is_some() {
+        info!("Effective configuration:\n{}", toml::to_string(&args).unwrap_or_default());
+    }
+
+    if let Err(e) = rayon::ThreadPoolBuilder::new().num_threads(args.threads).build_global() {
+        warn!("Could not configure the thread pool: {}", e);
+    }
+    let path_format = args.path_format.as_deref().map(PathFormat::compile).transpose().map_err(RunError::context("Invalid --path-format"))?;
+    let key_map = match &args.key_map {
+        Some(key_map_path) => {
+            let key_map = read_key_map(key_map_path).map_err(|e| RunError::failed(format!("Failed to read the key map {}: {}", key_map_path, e)))?;
+            info!(stage = "parse"; "Read the keys and ranges of {} path names from {}", key_map.len(), key_map_path);
+            Some(key_map)
+        }
+        None => None,
+    };
+    let read_options = ReadOptions {
+        naive_join: args.naive_join,
+        sort_archive: args.sort_archive,
+        keep_temp: args.keep_temp,
+        stream_parse: args.stream_parse,
+        decompress_ahead: args.decompress_ahead,
+        decompress_budget: args.decompress_budget,
+        no_mmap: args.no_mmap,
+        range_syntax: args.range_delimiters,
+        path_format: path_format.as_ref(),
+        key_map: key_map.as_ref(),
+        #[cfg(feature = "http")]
+        http_retries: args.http_retries,
+        ..ReadOptions::default()
+    };
+
+    match args.command.take() {
+        Some(Command::Extract(extract_args)) => subcommands::extract::run(&extract_args, &read_options),
+        Some(Command::Diff(diff_args)) => subcommands::diff::run(&diff_args, &read_options),
+        None => lace::run(args, &read_options),
+    }
+}
@@ -0,0 +1,213 @@
+//! Links of the output GFA, sorted in memory or by an external merge sort
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Read, Seek, Write},
+    path::Path,
+};
+use rustc_hash::FxHashMap;
+use handlegraph::{
+    handle::Edge,
+    handlegraph::*,
+    hashgraph::HashGraph,
+};
+use bitvec::prelude::BitVec;
+use log::debug;
+use crate::merge::LinkOverlaps;
+use crate::write::{PathStep, write_link};
+
+/// Returns the canonical form of a link between two oriented output node IDs.
+/// A link and its reverse complement (B-, A-) describe the same adjacency in GFA,
+/// so the smaller of the two (from, to) tuples is used as the representative:
+/// the node with the smaller ID comes first, and for self-loops the forward orientation.
+/// This keeps the output stable whatever the order in which the edges were inserted.
+pub(crate) fn canonical_link<T: Ord + Copy>(from: (T, bool), to: (T, bool)) -> ((T, bool), (T, bool)) {
+    let flipped = ((to.0, !to.1), (from.0, !from.1));
+    std::cmp::min((from, to), flipped)
+}
+
+/// Overlaps of the links between used nodes, by canonical link on the output IDs. Links
+/// without one are blunt
+pub(crate) fn output_link_overlaps(
+    link_overlaps: &LinkOverlaps,
+    nodes_to_remove: &BitVec,
+    id_mapping: &[usize]
+) -> FxHashMap<(PathStep, PathStep), usize> {
+    link_overlaps.iter()
+        .filter(|(Edge(left, right), _)| !nodes_to_remove[usize::from(left.id())] && !nodes_to_remove[usize::from(right.id())])
+        .map(|(Edge(left, right), &overlap)| {
+            let link = canonical_link((id_mapping[usize::from(left.id())], left.is_reverse()), (id_mapping[usize::from(right.id())], right.is_reverse()));
+            (link, overlap)
+        })
+        .collect()
+}
+
+/// Collects the edges between used nodes as canonical links on the compacted IDs,
+/// sorted and without reverse-complement duplicates
+pub(crate) fn collect_output_links(
+    graph: &HashGraph,
+    nodes_to_remove: &BitVec,
+    id_mapping: &[usize]
+) -> Vec<((usize, bool), (usize, bool))> {
+    let mut links: Vec<_> = graph.edges()
+        .filter(|edge| {
+            !nodes_to_remove[u64::from(edge.0.id()) as usize] &&
+            !nodes_to_remove[u64::from(edge.1.id()) as usize]
+        })
+        .map(|edge| {
+            let from_id = id_mapping[u64::from(edge.0.id()) as usize];
+            let to_id = id_mapping[u64::from(edge.1.id()) as usize];
+            canonical_link((from_id, edge.0.is_reverse()), (to_id, edge.1.is_reverse()))
+        })
+        .collect();
+    links.sort_unstable();
+    links.dedup();
+
+    links
+}
+
+/// Number of links sorted in memory at once by --external-sort
+pub(crate) const SORT_CHUNK_SIZE: usize = 1 << 22;
+
+/// Size of a link in the chunks of --external-sort: the ID and the orientation of both nodes
+pub(crate) const SPILLED_LINK_BYTES: usize = 18;
+
+/// Largest number of chunks of links merged at once, which bounds the open temporary files
+pub(crate) const MERGE_FAN_IN: usize = 64;
+
+/// Writes the edges between used nodes as `collect_output_links` returns them, sorting them on
+/// disk: the links are sorted in chunks of `chunk_size`, each written to a temporary file in
+/// `tmp_dir`, and the chunks are then merged, `MERGE_FAN_IN` at a time and in as many passes as
+/// needed. Only the links of one chunk, and one link per chunk while merging, are held in memory.
+/// Returns the number of links written
+pub(crate) fn write_links_external<W: Write>(
+    out: &mut W,
+    graph: &HashGraph,
+    nodes_to_remove: &BitVec,
+    id_mapping: &[usize],
+    overlaps: &FxHashMap<(PathStep, PathStep), usize>,
+    tmp_dir: &Path,
+    chunk_size: usize
+) -> io::Result<usize> {
+    let mut chunk = Vec::with_capacity(chunk_size.min(graph.edge_count()));
+    let links = graph.edges()
+        .filter(|edge| {
+            !nodes_to_remove[u64::from(edge.0.id()) as usize] &&
+            !nodes_to_remove[u64::from(edge.1.id()) as usize]
+        })
+        .map(|edge| {
+            let from_id = id_mapping[u64::from(edge.0.id()) as usize];
+            let to_id = id_mapping[u64::from(edge.1.id()) as usize];
+            canonical_link((from_id, edge.0.is_reverse()), (to_id, edge.1.is_reverse()))
+        });
+    // Chunks by the number of merges they went through, merged into the next level once there are
+    // MERGE_FAN_IN of them, so that the open files stay few however many chunks there are
+    let mut levels: Vec<Vec<BufReader<File>>> = Vec::new();
+    let mut spill = |chunk: &mut Vec<(PathStep, PathStep)>| -> io::Result<()> {
+        let mut merged = spill_link_chunk(chunk, tmp_dir)?;
+        for level in 0.. {
+            if levels.len() == level {
+                levels.push(Vec::new());
+            }
+            levels[level].push(merged);
+            if levels[level].len() < MERGE_FAN_IN {
+                break;
+            }
+            merged = merge_link_chunks_to_file(std::mem::take(&mut levels[level]), tmp_dir)?;
+        }
+        Ok(())
+    };
+    for link in links {
+        chunk.push(link);
+        if chunk.len() == chunk_size {
+            spill(&mut chunk)?;
+        }
+    }
+    if !chunk.is_empty() {
+        spill(&mut chunk)?;
+    }
+    drop(chunk);
+
+    let mut chunks: Vec<_> = levels.into_iter().flatten().collect();
+    while chunks.len() > MERGE_FAN_IN {
+        let rest = chunks.split_off(MERGE_FAN_IN);
+        chunks = std::iter::once(merge_link_chunks_to_file(chunks, tmp_dir)).chain(rest.into_iter().map(Ok)).collect::<io::Result<_>>()?;
+    }
+    debug!(stage = "write", chunks = chunks.len(); "Merging {} sorted chunks of links", chunks.len());
+
+    let mut written = 0;
+    merge_link_chunks(chunks, |link| {
+        written += 1;
+        write_link(out, link.0, link.1, overlaps.get(&link).copied().unwrap_or(0))
+    })?;
+    Ok(written)
+}
+
+/// Merges sorted chunks of links through a heap of their smallest unmerged link, passing each
+/// distinct link to `emit` in order
+pub(crate) fn merge_link_chunks(
+    mut chunks: Vec<BufReader<File>>,
+    mut emit: impl FnMut((PathStep, PathStep)) -> io::Result<()>
+) -> io::Result<()> {
+    let mut heap = std::collections::BinaryHeap::with_capacity(chunks.len());
+    for (chunk_idx, chunk) in chunks.iter_mut().enumerate() {
+        if let Some(link) = read_spilled_link(chunk)? {
+            heap.push(std::cmp::Reverse((link, chunk_idx)));
+        }
+    }
+    let mut previous = None;
+    while let Some(std::cmp::Reverse((link, chunk_idx))) = heap.pop() {
+        if let Some(next) = read_spilled_link(&mut chunks[chunk_idx])? {
+            heap.push(std::cmp::Reverse((next, chunk_idx)));
+        }
+        // The same link can be in several chunks
+        if previous != Some(link) {
+            emit(link)?;
+            previous = Some(link);
+        }
+    }
+    Ok(())
+}
+
+/// Merges sorted chunks of links into a single chunk in `tmp_dir`, closing them. Returns a reader
+/// of the merged chunk from its start
+pub(crate) fn merge_link_chunks_to_file(chunks: Vec<BufReader<File>>, tmp_dir: &Path) -> io::Result<BufReader<File>> {
+    let mut out = BufWriter::new(tempfile::tempfile_in(tmp_dir)?);
+    merge_link_chunks(chunks, |link| write_spilled_link(&mut out, link))?;
+    let mut file = out.into_inner().map_err(io::IntoInnerError::into_error)?;
+    file.seek(io::SeekFrom::Start(0))?;
+    Ok(BufReader::new(file))
+}
+
+/// Sorts and deduplicates a chunk of links into an anonymous temporary file in `tmp_dir`, emptying
+/// the chunk. Returns a reader of the file from its start
+pub(crate) fn spill_link_chunk(chunk: &mut Vec<(PathStep, PathStep)>, tmp_dir: &Path) -> io::Result<BufReader<File>> {
+    chunk.sort_unstable();
+    chunk.dedup();
+    let mut out = BufWriter::new(tempfile::tempfile_in(tmp_dir)?);
+    for link in chunk.drain(..) {
+        write_spilled_link(&mut out, link)?;
+    }
+    let mut file = out.into_inner().map_err(io::IntoInnerError::into_error)?;
+    file.seek(io::SeekFrom::Start(0))?;
+    Ok(BufReader::new(file))
+}
+
+/// Writes a link to a chunk in `SPILLED_LINK_BYTES` bytes
+pub(crate) fn write_spilled_link<W: Write>(out: &mut W, ((from_id, from_reverse), (to_id, to_reverse)): (PathStep, PathStep)) -> io::Result<()> {
+    out.write_all(&(from_id as u64).to_le_bytes())?;
+    out.write_all(&[from_reverse as u8])?;
+    out.write_all(&(to_id as u64).to_le_bytes())?;
+    out.write_all(&[to_reverse as u8])
+}
+
+/// Reads the next link of a chunk written by `spill_link_chunk`, or None at its end
+pub(crate) fn read_spilled_link(chunk: &mut BufReader<File>) -> io::Result<Option<(PathStep, PathStep)>> {
+    if chunk.fill_buf()?.is_empty() {
+        return Ok(None);
+    }
+    let mut bytes = [0; SPILLED_LINK_BYTES];
+    chunk.read_exact(&mut bytes)?;
+    let id = |bytes: &[u8]| u64::from_le_bytes(bytes.try_into().unwrap()) as usize;
+    Ok(Some(((id(&bytes[..8]), bytes[8] != 0), (id(&bytes[9..17]), bytes[17] != 0))))
+}
@@ -0,0 +1,1111 @@
+//! Merging of the path ranges into the combined graph, and the changes to it before writing
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io,
+};
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::{Deserialize, Serialize};
+use handlegraph::{
+    handle::{Direction, Handle, NodeId, Edge},
+    handlegraph::*,
+    mutablehandlegraph::*,
+    hashgraph::HashGraph,
+};
+use bitvec::{bitvec, prelude::BitVec};
+use log::{debug, info, warn};
+use rust_htslib::faidx;
+use rayon::prelude::*;
+use crate::lace::RunSummary;
+use crate::ranges::{PathKey, RangeInfo, contig_length, find_overlaps, gap_length, interval_coverage, range_span};
+use crate::sequences::NodeSequences;
+use crate::write::node_source;
+
+/// Overlap (bp) of the non-blunt links of the input GFAs, keyed by translated edge
+pub(crate) type LinkOverlaps = FxHashMap<Edge, usize>;
+
+/// Canonical edges of the combined graph. Looking edges up here avoids `has_edge`,
+/// which scans the edge lists and gets slow on high-degree nodes
+pub(crate) type EdgeSet = FxHashSet<Edge>;
+
+/// Collects the edges of the graph in their canonical orientation
+pub(crate) fn canonical_edges(graph: &HashGraph) -> EdgeSet {
+    graph.edges().map(|Edge(left, right)| Edge::edge_handle(left, right)).collect()
+}
+
+/// Adds the edge between two handles unless it, or its reverse complement, is already in the graph.
+/// New edges are stored in the canonical orientation given by `Edge::edge_handle`.
+/// Returns true if the edge was created.
+pub(crate) fn create_edge_if_missing(graph: &mut HashGraph, edges: &mut EdgeSet, left: Handle, right: Handle) -> bool {
+    let edge = Edge::edge_handle(left, right);
+    if !edges.insert(edge) {
+        return false;
+    }
+    graph.create_edge(edge);
+    true
+}
+
+/// Returns the number of bases of the `to` segment covered by a link overlap CIGAR,
+/// or None if the overlap is unspecified (`*`) or malformed
+pub(crate) fn overlap_length(cigar: &[u8]) -> Option<usize> {
+    if cigar.is_empty() || cigar == b"*" {
+        return None;
+    }
+
+    let mut length = 0;
+    let mut count: Option<usize> = None;
+    for &byte in cigar {
+        if byte.is_ascii_digit() {
+            count = Some(count.unwrap_or(0) * 10 + (byte - b'0') as usize);
+        } else {
+            let op_len = count.take()?;
+            match byte {
+                // Operations consuming bases of the `to` segment
+                b'M' | b'I' | b'S' | b'=' | b'X' => length += op_len,
+                b'D' | b'N' | b'H' | b'P' => {}
+                _ => return None,
+            }
+        }
+    }
+
+    if count.is_some() { None } else { Some(length) }
+}
+
+/// Returns the overlap of the link between two handles, in either of its orientations
+pub(crate) fn boundary_overlap(link_overlaps: &LinkOverlaps, left: Handle, right: Handle) -> usize {
+    link_overlaps.get(&Edge(left, right))
+        .or_else(|| link_overlaps.get(&Edge(right.flip(), left.flip())))
+        .copied()
+        .unwrap_or(0)
+}
+
+/// Parses an oriented P line step (e.g. `12+`) into a handle
+pub(crate) fn parse_path_step(step: &str) -> Option<Handle> {
+    let (id, is_reverse) = match step.as_bytes().last()? {
+        b'+' => (&step[..step.len() - 1], false),
+        b'-' => (&step[..step.len() - 1], true),
+        _ => return None,
+    };
+    Some(Handle::pack(id.parse::<u64>().ok()?, is_reverse))
+}
+
+/// Number of adjacent path steps without an edge that --check-path-edges logs one by one; all are counted
+pub(crate) const MAX_REPORTED_PATH_EDGES: usize = 5;
+
+/// Adjacent steps of the merged paths without an edge between them, within a range or across the
+/// junction of contiguous ranges, as path key, position of the junction, and index of the first
+/// step in the path key, in path key order
+pub(crate) fn missing_path_edges(path_key_ranges: &FxHashMap<PathKey, Vec<RangeInfo>>, graph: &HashGraph) -> Vec<(String, usize, usize)> {
+    let edges = canonical_edges(graph);
+    let mut path_keys: Vec<&PathKey> = path_key_ranges.keys().collect();
+    path_keys.sort();
+    let mut missing = Vec::new();
+    for path_key in path_keys {
+        let mut previous: Option<(&RangeInfo, Handle, usize)> = None;
+        let mut step_idx = 0;
+        for range in &path_key_ranges[path_key] {
+            for (&step, &step_end) in range.steps.iter().zip(&range.step_ends) {
+                if let Some((previous_range, previous_step, previous_end)) = previous
+                    && (std::ptr::eq(previous_range, range) || previous_range.is_contiguous_with(range))
+                    && !edges.contains(&Edge::edge_handle(previous_step, step)) {
+                    missing.push((path_key.to_string(), previous_end, step_idx - 1));
+                }
+                previous = Some((range, step, step_end));
+                step_idx += 1;
+            }
+        }
+    }
+    missing
+}
+
+/// Creates the edges between the consecutive steps of the ranges, which are the only edges within
+/// the blocks with --ignore-input-links. Returns the number of edges created
+pub(crate) fn link_path_steps(path_key_ranges: &FxHashMap<PathKey, Vec<RangeInfo>>, graph: &mut HashGraph, edges: &mut EdgeSet) -> usize {
+    let mut path_keys: Vec<&PathKey> = path_key_ranges.keys().collect();
+    path_keys.sort();
+    path_keys.into_iter()
+        .flat_map(|path_key| &path_key_ranges[path_key])
+        .flat_map(|range| range.steps.windows(2))
+        .filter(|pair| create_edge_if_missing(graph, edges, pair[0], pair[1]))
+        .count()
+}
+
+/// Sorts, deduplicates, trims, and links the ranges of every path key, updating the graph, its
+/// canonical edges, and the merge counters of the summary. Returns the node that each node
+/// created by trimming was split from
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn merge_path_ranges(
+    path_key_ranges: &mut FxHashMap<PathKey, Vec<RangeInfo>>,
+    combined_graph: &mut HashGraph,
+    edges: &mut EdgeSet,
+    sequences: &mut NodeSequences,
+    sequenceless: &mut FxHashSet<NodeId>,
+    junction_reader: Option<&faidx::Reader>,
+    summary: &mut RunSummary,
+    debug: bool
+) -> FxHashMap<NodeId, NodeId> {
+    let mut split_origins = FxHashMap::default();
+    let input_edges = combined_graph.edge_count();
+    info!(stage = "merge"; "Sorting, deduplicating, trimming, and linking {} path ranges", path_key_ranges.values().map(|ranges| ranges.len()).sum::<usize>());
+    sort_and_filter_all_ranges(path_key_ranges, debug);
+
+    // The overlaps and the cuts that trim them only depend on the ranges of each key, so they are
+    // planned in parallel
+    let mut plans: Vec<(PathKey, bool, Vec<usize>)> = path_key_ranges.par_iter()
+        .map(|(path_key, ranges)| {
+            let has_overlaps = ranges.windows(2).any(|pair| pair[0].overlaps_with(&pair[1]));
+            (path_key.clone(), has_overlaps, overlap_cuts(ranges))
+        })
+        .collect();
+
+    // Update the graph one key at a time, in sorted order so that new node IDs are deterministic
+    plans.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+    for (path_key, has_overlaps, cuts) in &plans {
+        let ranges = path_key_ranges.get_mut(path_key).unwrap();
+        if *has_overlaps {
+            summary.keys_with_overlaps += 1;
+        }
+        trim_range_overlaps(path_key, ranges, cuts, combined_graph, sequences, edges, sequenceless, &mut split_origins, debug);
+        link_contiguous_ranges(path_key, ranges, combined_graph, edges, debug);
+        if let Some(reader) = junction_reader {
+            summary.junction_mismatches += check_junctions(path_key, ranges, combined_graph, sequences, sequenceless, reader);
+        }
+        let gap_bp = gap_length(ranges);
+        if gap_bp > 0 {
+            summary.keys_with_gaps += 1;
+            summary.gap_bp += gap_bp;
+        }
+    }
+    summary.new_edges = combined_graph.edge_count() - input_edges;
+    info!(stage = "merge"; "Created {} nodes and {} edges",
+        combined_graph.node_count(), combined_graph.edge_count());
+
+    split_origins
+}
+
+/// Handling of the haplotypes of a sample contig whose ranges or steps differ, when collapsing haplotypes
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum CollapsePolicy {
+    /// Keep the haplotypes as separate paths
+    Bubble,
+    /// Fail
+    Error,
+}
+
+/// Replaces the `sample#hap#contig` path keys of each sample contig by a single `sample#contig`
+/// key when all its haplotypes have ranges with the same coordinates and steps, keeping the ranges
+/// of the first haplotype. Other keys are left as they are. Returns the number of collapsed sample contigs
+pub(crate) fn collapse_haplotypes(path_key_ranges: &mut FxHashMap<PathKey, Vec<RangeInfo>>, policy: CollapsePolicy) -> io::Result<usize> {
+    let mut sample_contigs: BTreeMap<(String, String), Vec<PathKey>> = BTreeMap::new();
+    for path_key in path_key_ranges.keys() {
+        let fields: Vec<&str> = path_key.splitn(3, '#').collect();
+        if let [sample, _hap, contig] = fields[..] {
+            sample_contigs.entry((sample.to_string(), contig.to_string())).or_default().push(path_key.clone());
+        }
+    }
+
+    let mut collapsed = 0;
+    for ((sample, contig), mut path_keys) in sample_contigs {
+        path_keys.sort();
+        let coordinates = |path_key: &PathKey| -> Vec<(usize, usize)> {
+            path_key_ranges[path_key].iter().map(|range| (range.start, range.end)).collect()
+        };
+        let steps = |path_key: &PathKey| path_key_ranges[path_key].iter().flat_map(|range| &range.steps);
+        let first = coordinates(&path_keys[0]);
+        let difference = path_keys[1..].iter().find_map(|path_key| {
+            if coordinates(path_key) != first {
+                Some((path_key, "ranges"))
+            } else if !steps(path_key).eq(steps(&path_keys[0])) {
+                Some((path_key, "steps"))
+            } else {
+                None
+            }
+        });
+        if let Some((different, what)) = difference {
+            match policy {
+                CollapsePolicy::Bubble => {
+                    debug!("  Keeping the haplotypes of {}#{} separate, as the {} of {} and {} differ", sample, contig, what, path_keys[0], different);
+                    continue;
+                }
+                CollapsePolicy::Error => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("The {} of {} and {} differ", what, path_keys[0], different)
+                    ));
+                }
+            }
+        }
+
+        let collapsed_key = format!("{}#{}", sample, contig);
+        if path_key_ranges.contains_key(collapsed_key.as_str()) {
+            warn!(stage = "merge", path_key = collapsed_key.as_str(); "Not collapsing the haplotypes of {}, as the path key already exists", collapsed_key);
+            continue;
+        }
+        let ranges = path_key_ranges.remove(&path_keys[0]).unwrap();
+        for path_key in &path_keys[1..] {
+            path_key_ranges.remove(path_key);
+        }
+        path_key_ranges.insert(collapsed_key.into(), ranges);
+        collapsed += 1;
+    }
+
+    Ok(collapsed)
+}
+
+/// Reports the overlaps and sorts and filters the ranges of every path key, in parallel
+pub(crate) fn sort_and_filter_all_ranges(path_key_ranges: &mut FxHashMap<PathKey, Vec<RangeInfo>>, debug: bool) {
+    path_key_ranges.par_iter_mut().for_each(|(path_key, ranges)| {
+        debug!("Processing path key '{}' with {} ranges", path_key, ranges.len());
+
+        // Report before filtering, so that contained ranges are included
+        if debug {
+            for overlap in find_overlaps(ranges) {
+                debug!(
+                    stage = "merge", path_key = &**path_key,
+                    first_start = overlap.first.start, first_end = overlap.first.end, first_gfa_id = overlap.first.gfa_id,
+                    second_start = overlap.second.start, second_end = overlap.second.end, second_gfa_id = overlap.second.gfa_id,
+                    overlap_bp = overlap.size, containment = overlap.is_containment();
+                    "  key {}: [{},{}) (gfa {}) {} [{},{}) (gfa {}) by {} bp",
+                    path_key,
+                    overlap.first.start, overlap.first.end, overlap.first.gfa_id,
+                    if overlap.is_containment() { "contains or is contained in" } else { "overlaps" },
+                    overlap.second.start, overlap.second.end, overlap.second.gfa_id,
+                    overlap.size);
+            }
+        }
+        sort_and_filter_ranges(path_key, ranges, debug);
+    });
+}
+
+pub(crate) fn sort_and_filter_ranges(
+    path_key: &str,
+    ranges: &mut Vec<RangeInfo>,
+    debug: bool
+) {
+    // Sort ranges by rank, if the key has an explicit order, and start position
+    ranges.sort_by_key(|r| (r.rank, r.start, r.end));
+    if ranges.first().is_some_and(|r| r.rank.is_some()) {
+        // The coordinates of explicitly ordered ranges cannot tell which ones are redundant
+        return;
+    }
+
+    if debug {
+        debug!("  Path key '{}' at the beginning", path_key);
+        for range in ranges.iter() {
+            debug!("    Range: start={}, end={}, num.steps={}, gfa_id={}", range.start, range.end, range.steps.len(), range.gfa_id);
+        }
+
+        debug!("  Removing redundant ranges");
+    }
+
+    // Remove ranges that are contained within other ranges
+    let mut write_idx = 0;
+    for read_idx in 1..ranges.len() {
+        let (prev_start, prev_end) = (ranges[write_idx].start, ranges[write_idx].end);
+        let (curr_start, curr_end) = (ranges[read_idx].start, ranges[read_idx].end);
+    
+        if curr_start == prev_start && curr_end == prev_end {
+            // Skip duplicate range
+            // Current range is a duplicate of the previous range, skip it
+            debug!(
+                "    Duplicate range detected: Range [start={}, end={}] is identical to previous range and will be removed.",
+                curr_start, curr_end
+            );
+
+            continue;
+        } else if curr_start >= prev_start && curr_end <= prev_end {
+            // Skip range that is fully contained within previous range
+            debug!(
+                "    Contained range detected: Range [start={}, end={}] is fully contained within previous range [start={}, end={}] and will be removed.",
+                curr_start, curr_end, prev_start, prev_end
+            );
+
+            continue;
+        } else if prev_start >= curr_start && prev_end <= curr_end {
+            // Previous range is fully contained within current range
+            debug!(
+                "    Containing range detected: Previous range [start={}, end={}] is fully contained within current range [start={}, end={}] and will be removed.",
+                prev_start, prev_end, curr_start, curr_end
+            );
+
+            ranges.swap(write_idx, read_idx);
+        } else if curr_start < prev_end {
+            // Handle overlapping ranges - check both previous and next ranges
+            let mut should_skip = false;
+            
+            if read_idx < ranges.len() - 1 {
+                let next_start = ranges[read_idx + 1].start;
+                
+                // Check if current range is significantly overlapped by both neighbors
+                if curr_start > prev_start && next_start < curr_end {
+                    let overlap_with_prev = prev_end - curr_start;
+                    let overlap_with_next = curr_end - next_start;
+                    let range_length = curr_end - curr_start;
+                    
+                    // Skip if the range is mostly covered by its neighbors
+                    if overlap_with_prev + overlap_with_next > range_length {
+                        should_skip = true;
+                    }
+                }
+            }
+            
+            if !should_skip {
+                debug!(
+                    "    Overlapping range detected: Range [start={}, end={}] overlaps with previous range [start={}, end={}] and will be kept.",
+                    curr_start, curr_end, prev_start, prev_end
+                );
+                write_idx += 1;
+                if write_idx != read_idx {
+                    ranges.swap(write_idx, read_idx);
+                }
+            }
+        } else {
+            // No overlap - keep both ranges
+            write_idx += 1;
+            if write_idx != read_idx {
+                ranges.swap(write_idx, read_idx);
+            }
+        }
+    }
+    ranges.truncate(write_idx + 1);
+    
+    if debug {
+        debug!("  Path key '{}' without redundancy", path_key);
+        for range in ranges.iter() {
+            debug!("    Range: start={}, end={}, num.steps={}, gfa_id={}", range.start, range.end, range.steps.len(), range.gfa_id);
+        }
+    }
+}
+
+/// Returns the end of the bases of each sorted range already covered by the ranges sorted before
+/// it, including non-adjacent ones (e.g. a long range reaching past the next one). Empty for
+/// explicitly ordered ranges, which are laid end to end, not by their coordinates
+pub(crate) fn overlap_cuts(ranges: &[RangeInfo]) -> Vec<usize> {
+    if ranges.first().is_some_and(|r| r.rank.is_some()) {
+        return Vec::new();
+    }
+    let mut covered_until = vec![0; ranges.len()];
+    for overlap in find_overlaps(ranges) {
+        let (earlier, later) = (overlap.indices.0.min(overlap.indices.1), overlap.indices.0.max(overlap.indices.1));
+        covered_until[later] = covered_until[later].max(ranges[earlier].end);
+    }
+    covered_until
+}
+
+/// Trims the start of every range up to its cut, as returned by `overlap_cuts`
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn trim_range_overlaps(
+    path_key: &str,
+    ranges: &mut [RangeInfo],
+    covered_until: &[usize],
+    combined_graph: &mut HashGraph,
+    sequences: &mut NodeSequences,
+    edges: &mut EdgeSet,
+    sequenceless: &mut FxHashSet<NodeId>,
+    split_origins: &mut FxHashMap<NodeId, NodeId>,
+    debug: bool
+) {
+    // Trim overlaps
+    debug!("  Trimming overlapping ranges");
+    for (r2, &covered_end) in ranges.iter_mut().zip(covered_until).skip(1) {
+        if covered_end > r2.start {
+            // The overlap runs from the start of the range to the end of the bases covered before it
+            let overlap_start = r2.start;
+            let overlap_end = std::cmp::min(covered_end, r2.end);
+
+            debug!(
+                "    Overlap detected: Range [start={}, end={}] covered up to {}, Overlap [start={}, end={}, size={}]",
+                r2.start, r2.end, covered_end, overlap_start, overlap_end, overlap_end - overlap_start
+            );
+
+            trim_range_prefix(r2, overlap_end, combined_graph, sequences, edges, sequenceless, split_origins);
+
+            debug!("      Updated overlaps: Range2 [start={}, end={}]", r2.start, r2.end);
+        }
+    }
+
+    if debug {
+        debug!("  Path key '{}' without overlaps", path_key);
+        for range in ranges.iter() {
+            debug!("    Range: start={}, end={}, num.steps={}, gfa_id={}", range.start, range.end, range.steps.len(), range.gfa_id);
+        }
+    }
+}
+
+/// Splits a handle into new nodes spelling its consecutive pieces, in its orientation, between the
+/// offsets in `bounds`, and links them in order. The pieces inherit the origin of the node and its
+/// lack of sequence, if any. Returns their forward handles. Shared by the overlap trimming, the
+/// blunting of overlapping links, and the chopping of long nodes
+pub(crate) fn split_node(
+    combined_graph: &mut HashGraph,
+    sequences: &mut NodeSequences,
+    edges: &mut EdgeSet,
+    sequenceless: &mut FxHashSet<NodeId>,
+    split_origins: &mut FxHashMap<NodeId, NodeId>,
+    handle: Handle,
+    bounds: &[usize]
+) -> Vec<Handle> {
+    let pieces = sequences.create_pieces(combined_graph, handle, bounds, u64::from(combined_graph.max_node_id()) + 1);
+    for piece in &pieces {
+        if sequenceless.contains(&handle.id()) {
+            sequenceless.insert(piece.id());
+        }
+        split_origins.insert(piece.id(), handle.id());
+    }
+    for pair in pieces.windows(2) {
+        create_edge_if_missing(combined_graph, edges, pair[0], pair[1]);
+    }
+    pieces
+}
+
+/// Splits each node longer than `max_node_length` into a chain of nodes of at most that length,
+/// moving its edges to the ends of the chain and replacing its path steps with the chain,
+/// traversed backwards on reverse steps. The original nodes are left unused, and so are not written.
+/// Segments without sequence are kept whole, as they are written with only their length.
+/// Returns the number of split nodes
+pub(crate) fn chop_long_nodes(
+    combined_graph: &mut HashGraph,
+    sequences: &mut NodeSequences,
+    path_key_ranges: &mut FxHashMap<PathKey, Vec<RangeInfo>>,
+    max_node_length: usize,
+    sequenceless: &mut FxHashSet<NodeId>,
+    link_overlaps: &mut LinkOverlaps,
+    split_origins: &mut FxHashMap<NodeId, NodeId>
+) -> usize {
+    let mut edges = canonical_edges(combined_graph);
+    let mut long_nodes: Vec<Handle> = combined_graph.handles()
+        .filter(|&handle| !sequenceless.contains(&handle.id()) && sequences.node_len(combined_graph, handle) > max_node_length)
+        .collect();
+    long_nodes.sort_unstable();
+
+    // Forward handles of the pieces of each split node
+    let mut chains: FxHashMap<NodeId, Vec<Handle>> = FxHashMap::default();
+    for &handle in &long_nodes {
+        let node_len = sequences.node_len(combined_graph, handle);
+        let bounds: Vec<usize> = (0..node_len).step_by(max_node_length).chain(std::iter::once(node_len)).collect();
+        let chain = split_node(combined_graph, sequences, &mut edges, sequenceless, split_origins, handle, &bounds);
+        chains.insert(handle.id(), chain);
+    }
+
+    // An edge leaving a split node leaves the end of its chain in that orientation, and an edge
+    // entering it enters the start
+    let exit = |handle: Handle| match chains.get(&handle.id()) {
+        Some(chain) if handle.is_reverse() => chain[0].flip(),
+        Some(chain) => chain[chain.len() - 1],
+        None => handle,
+    };
+    let entry = |handle: Handle| match chains.get(&handle.id()) {
+        Some(chain) if handle.is_reverse() => chain[chain.len() - 1].flip(),
+        Some(chain) => chain[0],
+        None => handle,
+    };
+    for &handle in &long_nodes {
+        let right: Vec<Handle> = combined_graph.neighbors(handle, Direction::Right).collect();
+        let left: Vec<Handle> = combined_graph.neighbors(handle, Direction::Left).collect();
+        for next in right {
+            create_edge_if_missing(combined_graph, &mut edges, exit(handle), entry(next));
+        }
+        for previous in left {
+            create_edge_if_missing(combined_graph, &mut edges, exit(previous), entry(handle));
+        }
+    }
+    let moved_overlaps: Vec<(Edge, usize)> = link_overlaps.iter()
+        .filter(|(Edge(left, right), _)| chains.contains_key(&left.id()) || chains.contains_key(&right.id()))
+        .map(|(&Edge(left, right), &overlap)| (Edge(exit(left), entry(right)), overlap))
+        .collect();
+    link_overlaps.extend(moved_overlaps);
+
+    // Replace the steps through split nodes, whose ends follow the piece lengths
+    for range in path_key_ranges.values_mut().flatten() {
+        if !range.steps.iter().any(|step| chains.contains_key(&step.id())) {
+            continue;
+        }
+        let mut steps = Vec::with_capacity(range.steps.len());
+        let mut step_ends = Vec::with_capacity(range.step_ends.len());
+        let mut step_start = range.start;
+        for (&step, &step_end) in range.steps.iter().zip(&range.step_ends) {
+            match chains.get(&step.id()) {
+                Some(chain) => {
+                    let pieces: Vec<Handle> = if step.is_reverse() {
+                        chain.iter().rev().map(|piece| piece.flip()).collect()
+                    } else {
+                        chain.clone()
+                    };
+                    // The pieces start with the bases the step shares with the previous one, if any
+                    let mut piece_end = step_end.saturating_sub(sequences.node_len(combined_graph, step));
+                    for piece in pieces {
+                        piece_end += sequences.node_len(combined_graph, piece);
+                        steps.push(piece);
+                        step_ends.push(piece_end.max(step_start));
+                    }
+                }
+                None => {
+                    steps.push(step);
+                    step_ends.push(step_end);
+                }
+            }
+            step_start = step_end;
+        }
+        range.steps = steps;
+        range.step_ends = step_ends;
+    }
+
+    long_nodes.len()
+}
+
+/// Makes the links traversed by the paths blunt. The bases duplicated by an overlapping link are
+/// trimmed from one of its nodes, the right one in its canonical orientation (as per `Edge::edge_handle`),
+/// which is split at the overlap into a chain as in `chop_long_nodes`. Steps entering that node through
+/// the link skip the duplicated piece, while the others traverse the whole chain, and the edges are moved
+/// to the pieces. Overlaps as long as the trimmed node are kept. Returns the number of resolved links,
+/// or an error if the pieces of a node do not meet at a trimmed link
+pub(crate) fn blunt_overlaps(
+    combined_graph: &mut HashGraph,
+    sequences: &mut NodeSequences,
+    path_key_ranges: &mut FxHashMap<PathKey, Vec<RangeInfo>>,
+    sequenceless: &mut FxHashSet<NodeId>,
+    link_overlaps: &mut LinkOverlaps,
+    split_origins: &mut FxHashMap<NodeId, NodeId>
+) -> io::Result<usize> {
+    let mut edges = canonical_edges(combined_graph);
+
+    // Overlapping links between consecutive steps, and the forward offsets at which to cut the trimmed nodes
+    let mut resolved: FxHashMap<Edge, usize> = FxHashMap::default();
+    let mut cuts: FxHashMap<NodeId, BTreeSet<usize>> = FxHashMap::default();
+    for range in path_key_ranges.values().flatten() {
+        for pair in range.steps.windows(2) {
+            let overlap = boundary_overlap(link_overlaps, pair[0], pair[1]);
+            let link = Edge::edge_handle(pair[0], pair[1]);
+            if overlap == 0 || resolved.contains_key(&link) {
+                continue;
+            }
+            let trimmed = link.1;
+            let node_len = sequences.node_len(combined_graph, trimmed);
+            if overlap >= node_len {
+                warn!(stage = "merge"; "Cannot trim the {} bp overlap of a link into node {} of {} bp", overlap, u64::from(trimmed.id()), node_len);
+                continue;
+            }
+            resolved.insert(link, overlap);
+            cuts.entry(trimmed.id()).or_default().insert(if trimmed.is_reverse() { node_len - overlap } else { overlap });
+        }
+    }
+
+    // Forward handles of the pieces of each split node, with their forward start and end offsets
+    let mut chains: FxHashMap<NodeId, Vec<(Handle, usize, usize)>> = FxHashMap::default();
+    let mut split_nodes: Vec<NodeId> = cuts.keys().copied().collect();
+    split_nodes.sort_unstable();
+    for node_id in split_nodes {
+        let handle = Handle::pack(node_id, false);
+        let node_len = sequences.node_len(combined_graph, handle);
+        let bounds: Vec<usize> = std::iter::once(0).chain(cuts[&node_id].iter().copied()).chain(std::iter::once(node_len)).collect();
+        let pieces = split_node(combined_graph, sequences, &mut edges, sequenceless, split_origins, handle, &bounds);
+        let chain = pieces.into_iter().zip(bounds.windows(2)).map(|(piece, pair)| (piece, pair[0], pair[1])).collect();
+        chains.insert(node_id, chain);
+    }
+
+    // Pieces of a handle in its orientation, with their offsets on that strand
+    let graph: &HashGraph = combined_graph;
+    let sequences: &NodeSequences = sequences;
+    let oriented_pieces = |handle: Handle| -> Vec<(Handle, usize, usize)> {
+        let node_len = sequences.node_len(graph, handle);
+        match chains.get(&handle.id()) {
+            Some(chain) if handle.is_reverse() => chain.iter().rev().map(|&(piece, start, end)| (piece.flip(), node_len - end, node_len - start)).collect(),
+            Some(chain) => chain.clone(),
+            None => vec![(handle, 0, node_len)],
+        }
+    };
+    // Bases trimmed from the end of the left step and from the start of the right one
+    let trim = |left: Handle, right: Handle| -> (usize, usize) {
+        let link = Edge::edge_handle(left, right);
+        match resolved.get(&link) {
+            Some(&overlap) if link == Edge(left, right) => (0, overlap),
+            Some(&overlap) => (overlap, 0),
+            None => (0, 0),
+        }
+    };
+    // Pieces that a link between two steps leaves and enters, past the bases it trims
+    let new_edge = |left: Handle, right: Handle| -> io::Result<Edge> {
+        let (end_trim, start_trim) = trim(left, right);
+        let left_end = sequences.node_len(graph, left) - end_trim;
+        let no_piece = |node: Handle, side: &str, offset: usize| io::Error::new(io::ErrorKind::InvalidData, format!(
+            "Cannot move the link {}{} {}{} to the pieces of node {}: none of them {} at {} bp",
+            u64::from(left.id()), if left.is_reverse() { '-' } else { '+' }, u64::from(right.id()), if right.is_reverse() { '-' } else { '+' },
+            u64::from(node.id()), side, offset));
+        let exit = oriented_pieces(left).iter().find(|&&(_, _, end)| end == left_end).ok_or_else(|| no_piece(left, "ends", left_end))?.0;
+        let entry = oriented_pieces(right).iter().find(|&&(_, start, _)| start == start_trim).ok_or_else(|| no_piece(right, "starts", start_trim))?.0;
+        Ok(Edge(exit, entry))
+    };
+
+    let mut moved_edges = Vec::new();
+    for &node_id in chains.keys() {
+        for handle in [Handle::pack(node_id, false), Handle::pack(node_id, true)] {
+            for next in graph.neighbors(handle, Direction::Right) {
+                moved_edges.push(new_edge(handle, next)?);
+            }
+            for previous in graph.neighbors(handle, Direction::Left) {
+                moved_edges.push(new_edge(previous, handle)?);
+            }
+        }
+    }
+    let moved_overlaps: Vec<(Edge, usize)> = link_overlaps.iter()
+        .filter(|&(&Edge(left, right), _)| !resolved.contains_key(&Edge::edge_handle(left, right)))
+        .filter(|(Edge(left, right), _)| chains.contains_key(&left.id()) || chains.contains_key(&right.id()))
+        .map(|(&Edge(left, right), &overlap)| Ok((new_edge(left, right)?, overlap)))
+        .collect::<io::Result<_>>()?;
+
+    // Replace the steps through split nodes, skipping the trimmed pieces
+    let mut new_ranges = Vec::new();
+    for (path_key, ranges) in path_key_ranges.iter() {
+        for (idx, range) in ranges.iter().enumerate() {
+            if !range.steps.iter().any(|step| chains.contains_key(&step.id())) {
+                continue;
+            }
+            let mut steps = Vec::with_capacity(range.steps.len());
+            let mut step_ends = Vec::with_capacity(range.step_ends.len());
+            let mut step_end = range.start;
+            for (step_idx, &step) in range.steps.iter().enumerate() {
+                let start_trim = if step_idx > 0 { trim(range.steps[step_idx - 1], step).1 } else { 0 };
+                let end_trim = range.steps.get(step_idx + 1).map_or(0, |&next| trim(step, next).0);
+                let kept_end = sequences.node_len(graph, step) - end_trim;
+                for (piece, start, end) in oriented_pieces(step) {
+                    if start >= start_trim && end <= kept_end {
+                        step_end += end - start;
+                        steps.push(piece);
+                        step_ends.push(step_end);
+                    }
+                }
+            }
+            new_ranges.push((path_key.clone(), idx, steps, step_ends));
+        }
+    }
+
+    for Edge(left, right) in moved_edges {
+        create_edge_if_missing(combined_graph, &mut edges, left, right);
+    }
+    link_overlaps.retain(|&Edge(left, right), _| !resolved.contains_key(&Edge::edge_handle(left, right)));
+    link_overlaps.extend(moved_overlaps);
+    for (path_key, idx, steps, step_ends) in new_ranges {
+        let range = &mut path_key_ranges.get_mut(&path_key).unwrap()[idx];
+        range.steps = steps;
+        range.step_ends = step_ends;
+    }
+
+    Ok(resolved.len())
+}
+
+/// Removes the steps of a range that lie before the coordinate `cut`, splitting the step
+/// in which it falls. Used when the start of a range duplicates bases of earlier ranges.
+pub(crate) fn trim_range_prefix(
+    range: &mut RangeInfo,
+    cut: usize,
+    combined_graph: &mut HashGraph,
+    sequences: &mut NodeSequences,
+    edges: &mut EdgeSet,
+    sequenceless: &mut FxHashSet<NodeId>,
+    split_origins: &mut FxHashMap<NodeId, NodeId>
+) {
+    let cut = cut.clamp(range.start, range.end);
+    if cut == range.start {
+        return;
+    }
+
+    let mut new_steps = Vec::with_capacity(range.steps.len());
+    let mut new_step_ends = Vec::with_capacity(range.steps.len());
+    for (idx, (&step_handle, &step_end)) in range.steps.iter().zip(&range.step_ends).enumerate() {
+        let step_start = if idx == 0 { range.start } else { range.step_ends[idx - 1] };
+        if step_start >= cut {
+            // Past the overlap
+            new_steps.push(step_handle);
+            new_step_ends.push(step_end);
+        } else if step_end > cut {
+            // Split the step where the overlap ends, keeping its right part. The bases the step
+            // shares with the previous one, through an overlapping link, precede its start
+            let node_len = sequences.node_len(combined_graph, step_handle);
+            let lead = if idx == 0 { 0 } else { (step_start + node_len).saturating_sub(step_end) };
+            let offset = (cut - step_start + lead).min(node_len);
+            debug!("      Splitting step {} [start={}, end={}] at offset {} to remove overlap", idx, step_start, step_end, offset);
+
+            let new_node = split_node(combined_graph, sequences, edges, sequenceless, split_origins, step_handle, &[offset, node_len])[0];
+
+            new_steps.push(new_node);
+            new_step_ends.push(step_end);
+        }
+    }
+    range.steps = new_steps;
+    range.step_ends = new_step_ends;
+
+    for pair in range.steps.windows(2) {
+        create_edge_if_missing(combined_graph, edges, pair[0], pair[1]);
+    }
+
+    // If no steps remain, the range is empty at the end of the overlap
+    range.start = cut;
+    if let Some(&last_end) = range.step_ends.last() {
+        range.end = last_end;
+    } else {
+        range.end = cut;
+    }
+}
+
+pub(crate) fn link_contiguous_ranges(
+    path_key: &str,
+    ranges: &mut [RangeInfo],
+    combined_graph: &mut HashGraph,
+    edges: &mut EdgeSet,
+    debug: bool
+) {
+    // Trim overlaps
+    debug!("  Linking contiguous ranges");
+
+    for i in 1..ranges.len() {
+        let (left, right) = ranges.split_at_mut(i);
+        let r1 = &mut left[left.len()-1];
+        let r2 = &mut right[0];
+
+        // Check if ranges are contiguous
+        if r1.is_contiguous_with(r2) {
+            // Get last handle from previous range and first handle from current range
+            if let (Some(&last_handle), Some(&first_handle)) = (r1.steps.last(), r2.steps.first()) {
+                // Create edge if it doesn't exist
+                if create_edge_if_missing(combined_graph, edges, last_handle, first_handle) {
+                    debug!("    Created edge between contiguous ranges at position {}", r1.end);
+                }
+            }
+        }
+    }
+
+    if debug {
+        debug!("  Path key '{}' without overlaps", path_key);
+        for range in ranges.iter() {
+            debug!("    Range: start={}, end={}, num.steps={}, gfa_id={}", range.start, range.end, range.steps.len(), range.gfa_id);
+        }
+    }
+}
+
+/// Number of bases taken from each side of a junction by `check_junctions`
+pub(crate) const JUNCTION_WINDOW: usize = 16;
+
+/// Verifies that the sequence across each junction between contiguous ranges matches the
+/// sample assembly: the tail of the last node of a range followed by the head of the first
+/// node of the next range must equal the FASTA sequence around the junction coordinate.
+/// Junctions next to a segment without sequence, whose placeholder Ns cannot match, are skipped.
+/// Returns the number of mismatching junctions.
+pub(crate) fn check_junctions(
+    path_key: &str,
+    ranges: &[RangeInfo],
+    combined_graph: &HashGraph,
+    sequences: &NodeSequences,
+    sequenceless: &FxHashSet<NodeId>,
+    reader: &faidx::Reader
+) -> usize {
+    let mut mismatches = 0;
+
+    for window in ranges.windows(2) {
+        let (r1, r2) = (&window[0], &window[1]);
+        if !r1.is_contiguous_with(r2) {
+            continue;
+        }
+
+        if let (Some(&last_handle), Some(&first_handle)) = (r1.steps.last(), r2.steps.first()) {
+            if sequenceless.contains(&last_handle.id()) || sequenceless.contains(&first_handle.id()) {
+                debug!(stage = "merge", path_key = path_key, position = r1.end;
+                    "Not checking the junction of path key '{}' at position {}, next to a segment without sequence", path_key, r1.end);
+                continue;
+            }
+
+            // Sequences are oriented according to the handles
+            let last_seq = sequences.sequence_vec(combined_graph, last_handle);
+            let first_seq = sequences.sequence_vec(combined_graph, first_handle);
+            let tail = &last_seq[last_seq.len().saturating_sub(JUNCTION_WINDOW)..];
+            let head = &first_seq[..first_seq.len().min(JUNCTION_WINDOW)];
+            if tail.is_empty() && head.is_empty() {
+                continue;
+            }
+
+            let mut observed = tail.to_vec();
+            observed.extend_from_slice(head);
+
+            let window_start = r1.end.saturating_sub(tail.len());
+            let window_end = r1.end + head.len();
+            match reader.fetch_seq_string(path_key, window_start, window_end - 1) {
+                Ok(expected) => {
+                    if !expected.as_bytes().eq_ignore_ascii_case(&observed) {
+                        warn!(stage = "merge", path_key = path_key, position = r1.end;
+                            "Junction mismatch for path key '{}' at position {}: FASTA [{}-{}) is {}, graph has {}",
+                            path_key, r1.end, window_start, window_end, expected, String::from_utf8_lossy(&observed));
+                        mismatches += 1;
+                    }
+                }
+                Err(e) => {
+                    warn!(stage = "merge", path_key = path_key, position = r1.end;
+                        "Cannot check junction for path key '{}' at position {}: {}", path_key, r1.end, e);
+                }
+            }
+        }
+    }
+
+    mismatches
+}
+
+// fn create_paths_from_ranges(
+//     path_key: &str,
+//     ranges: &[RangeInfo],
+//     combined_graph: &mut HashGraph,
+//     debug: bool
+// ) {
+//     // Check for overlaps and contiguity
+//     let mut all_contiguous = true;
+    
+//     for window in ranges.windows(2) {
+//         let r1 = &window[0];
+//         let r2 = &window[1];
+
+//         if r1.overlaps_with(r2) {
+//             if debug {
+//                 debug!("Unresolved overlaps detected between ranges: [start={}, end={}] and [start={}, end={}]", 
+//                 r1.start, r1.end, r2.start, r2.end);
+//             }
+//             panic!("Unresolved overlaps detected in path key '{}'", path_key);
+//         }
+//         if !r1.is_contiguous_with(r2) {
+//             all_contiguous = false;
+//         }
+//     }
+            
+//     if !all_contiguous && debug {
+//         let mut current_start = ranges[0].start;
+//         let mut current_end = ranges[0].end;
+        
+//         for i in 1..ranges.len() {
+//             if ranges[i-1].is_contiguous_with(&ranges[i]) {
+//                 // Extend current merged range
+//                 current_end = ranges[i].end;
+//             } else {
+//                 // Print current merged range
+//                 debug!("    Merged range: start={}, end={}", 
+//                     current_start, current_end);
+                
+//                 if !ranges[i-1].overlaps_with(&ranges[i]) {
+//                     // Calculate and print gap
+//                     let gap = ranges[i].start - current_end;
+//                     debug!("      Gap to next range: {} positions", gap);
+//                 } else {
+//                     // Calculate and print overlap
+//                     let overlap = current_end - ranges[i].start;
+//                     debug!("      Overlap with next range: {} positions", overlap);
+//                 }
+
+//                 // Start new merged range
+//                 current_start = ranges[i].start;
+//                 current_end = ranges[i].end;
+//             }
+//         }
+        
+//         // Print final merged range
+//         debug!("    Merged range: start={}, end={}", 
+//             current_start, current_end);
+//     }
+
+//     if all_contiguous {
+//         // Create a single path with the original key
+//         let path_id = combined_graph.create_path(path_key.as_bytes(), false).unwrap();
+//         let mut prev_step = None;
+        
+//         // Add all steps from all ranges
+//         for range in ranges.iter() {
+//             for step in &range.steps {
+//                 combined_graph.path_append_step(path_id, *step);
+                
+//                 if let Some(prev) = prev_step {
+//                     if !combined_graph.has_edge(prev, *step) {
+//                         combined_graph.create_edge(Edge(prev, *step));
+//                     }
+//                 }
+//                 prev_step = Some(*step);
+//             }
+//         }
+//     } else {
+//         // Handle non-contiguous ranges by creating separate paths for each contiguous group
+//         let mut current_range_idx = 0;
+//         while current_range_idx < ranges.len() {
+//             let start_range = &ranges[current_range_idx];
+//             let mut steps = start_range.steps.clone();
+//             let mut next_idx = current_range_idx + 1;
+//             let mut end_range = start_range;
+            
+//             // Merge contiguous ranges
+//             while next_idx < ranges.len() && ranges[next_idx - 1].is_contiguous_with(&ranges[next_idx]) {
+//                 steps.extend(ranges[next_idx].steps.clone());
+//                 end_range = &ranges[next_idx];
+//                 next_idx += 1;
+//             }
+            
+//             // Create path name with range information
+//             let path_name = format!("{}:{}-{}", path_key, start_range.start, end_range.end);
+//             let path_id = combined_graph.create_path(path_name.as_bytes(), false).unwrap();
+            
+//             // Add steps to the path
+//             let mut prev_step = None;
+//             for step in steps {
+//                 combined_graph.path_append_step(path_id, step);
+                
+//                 if let Some(prev) = prev_step {
+//                     if !combined_graph.has_edge(prev, step) {
+//                         combined_graph.create_edge(Edge(prev, step));
+//                     }
+//                 }
+//                 prev_step = Some(step);
+//             }
+            
+//             current_range_idx = next_idx;
+//         }
+//     }
+// }
+
+pub(crate) fn mark_nodes_for_removal(
+    graph: &HashGraph,
+    path_key_ranges: &FxHashMap<PathKey, Vec<RangeInfo>>
+) -> BitVec {
+    // Create a bitvector with all nodes initially marked for removal
+    let max_node_id = u64::from(graph.max_node_id());
+    let mut nodes_to_remove = bitvec![1; max_node_id as usize + 1];
+    // Mark nodes used in path ranges as not to be removed (set bit to 0)
+    for ranges in path_key_ranges.values() {
+        for range in ranges {
+            for handle in &range.steps {
+                nodes_to_remove.set(u64::from(handle.id()) as usize, false);
+            }
+        }
+    }
+    
+    nodes_to_remove
+}
+
+/// Removes the nodes that no path visits and their edges, which are otherwise kept in the graph
+/// until they are skipped when writing. Returns the number of removed nodes by input file
+pub(crate) fn prune_orphans(
+    graph: &mut HashGraph,
+    path_key_ranges: &FxHashMap<PathKey, Vec<RangeInfo>>,
+    id_translations: &[NodeId],
+    split_origins: &FxHashMap<NodeId, NodeId>
+) -> Vec<usize> {
+    let unused = mark_nodes_for_removal(graph, path_key_ranges);
+    let mut orphans: Vec<Handle> = graph.handles().filter(|handle| unused[usize::from(handle.id())]).collect();
+    orphans.sort_unstable();
+    let mut removed = vec![0; id_translations.len()];
+    for handle in orphans {
+        let edges: Vec<Edge> = graph.neighbors(handle, Direction::Right).map(|next| Edge(handle, next))
+            .chain(graph.neighbors(handle, Direction::Left).map(|previous| Edge(previous, handle)))
+            .collect();
+        for edge in edges {
+            graph.remove_edge(edge);
+        }
+        graph.remove_handle(handle.id());
+        let (gfa_id, _) = node_source(handle.id(), id_translations, split_origins);
+        removed[gfa_id] += 1;
+    }
+    removed
+}
+
+/// Removes the edges that no path traverses, i.e. that join no consecutive steps of a range or
+/// the last and first steps of contiguous ranges, which are the only edges the paths are written
+/// through. Returns the number of removed edges by the input file of their first node
+pub(crate) fn drop_unused_edges(
+    graph: &mut HashGraph,
+    path_key_ranges: &FxHashMap<PathKey, Vec<RangeInfo>>,
+    id_translations: &[NodeId],
+    split_origins: &FxHashMap<NodeId, NodeId>
+) -> Vec<usize> {
+    let mut used_edges = EdgeSet::default();
+    for ranges in path_key_ranges.values() {
+        for range in ranges {
+            used_edges.extend(range.steps.windows(2).map(|pair| Edge::edge_handle(pair[0], pair[1])));
+        }
+        for pair in ranges.windows(2).filter(|pair| pair[0].is_contiguous_with(&pair[1])) {
+            if let (Some(&last), Some(&first)) = (pair[0].steps.last(), pair[1].steps.first()) {
+                used_edges.insert(Edge::edge_handle(last, first));
+            }
+        }
+    }
+
+    let mut unused_edges: Vec<Edge> = graph.edges()
+        .filter(|&Edge(left, right)| !used_edges.contains(&Edge::edge_handle(left, right)))
+        .collect();
+    unused_edges.sort_unstable_by_key(|&Edge(left, right)| (left, right));
+    let mut removed = vec![0; id_translations.len()];
+    for edge in unused_edges {
+        let (gfa_id, _) = node_source(edge.0.id(), id_translations, split_origins);
+        removed[gfa_id] += 1;
+        graph.remove_edge(edge);
+    }
+    removed
+}
+
+/// Coverage of the span of a path key, from the start of its first range to the end of its last one
+#[derive(Debug, PartialEq)]
+pub(crate) struct KeyCoverage {
+    /// Sum of the node lengths of the ranges, in bp
+    pub(crate) length: usize,
+    pub(crate) span: usize,
+    pub(crate) gaps: usize,
+    pub(crate) gap_bp: usize,
+}
+
+impl KeyCoverage {
+    pub(crate) fn new(graph: &HashGraph, sequences: &NodeSequences, ranges: &[RangeInfo]) -> Self {
+        let (start, end) = range_span(ranges);
+        let coverage = interval_coverage(ranges, start, end);
+        KeyCoverage {
+            length: ranges.iter().flat_map(|range| &range.steps).map(|&step| sequences.node_len(graph, step)).sum(),
+            span: end - start,
+            gaps: coverage.gaps.len(),
+            gap_bp: coverage.gap_length(),
+        }
+    }
+
+    /// Fraction of the span covered by the ranges, and by the gaps if they are filled
+    pub(crate) fn fraction(&self, count_gaps: bool) -> f64 {
+        let covered = if count_gaps { self.length + self.gap_bp } else { self.length };
+        if self.span > 0 { covered as f64 / self.span as f64 } else { 0.0 }
+    }
+}
+
+/// Removes the path keys whose ranges cover less than `min_fraction` of their span, counting the
+/// gaps as covered if requested. Returns the number of removed keys
+pub(crate) fn drop_low_coverage_keys(
+    path_key_ranges: &mut FxHashMap<PathKey, Vec<RangeInfo>>,
+    graph: &HashGraph,
+    sequences: &NodeSequences,
+    min_fraction: f64,
+    count_gaps: bool
+) -> usize {
+    let num_keys = path_key_ranges.len();
+    path_key_ranges.retain(|path_key, ranges| {
+        let fraction = KeyCoverage::new(graph, sequences, ranges).fraction(count_gaps);
+        if fraction < min_fraction {
+            debug!(stage = "merge", path_key = &**path_key; "Skipping path key '{}' covering {:.4} of its span", path_key, fraction);
+        }
+        fraction >= min_fraction
+    });
+    num_keys - path_key_ranges.len()
+}
+
+/// Removes the path keys that are not complete: fragmented, i.e. with a gap between ranges, or not
+/// covering their contig, from 0 to its length in `contig_lengths` if known, and else from 0 on.
+/// Returns the removed keys, sorted
+pub(crate) fn drop_incomplete_keys(path_key_ranges: &mut FxHashMap<PathKey, Vec<RangeInfo>>, contig_lengths: Option<&FxHashMap<String, usize>>) -> Vec<String> {
+    let mut incomplete = Vec::new();
+    path_key_ranges.retain(|path_key, ranges| {
+        let end = ranges.iter().map(|range| range.end).max().unwrap_or(0);
+        let is_complete = ranges.first().is_some_and(|first| first.start == 0)
+            && ranges.windows(2).all(|pair| pair[0].is_contiguous_with(&pair[1]))
+            && contig_lengths.and_then(|lengths| contig_length(lengths, path_key)).is_none_or(|length| end >= length);
+        if !is_complete {
+            incomplete.push(path_key.to_string());
+        }
+        is_complete
+    });
+    incomplete.sort();
+    incomplete
+}
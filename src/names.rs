@@ -0,0 +1,282 @@
+//! Parsing of the path names into path keys and ranges
+
+use std::{
+    io,
+    borrow::Cow,
+};
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+use crate::input::ReadOptions;
+use crate::ranges::{PathKey, RangeInfo};
+
+/// Splits a path name into its key and range, from either a name ending with a range in the given
+/// syntax (`KEY:START-END` by default) or a `KEY[START-END]` name
+pub(crate) fn split_path_name(path_name: &str, range_syntax: RangeSyntax) -> Option<(String, usize, usize)> {
+    split_path_key(path_name, range_syntax).map(|(key, start, end)| (key.to_string(), start, end))
+}
+
+/// As `split_path_name`, borrowing the key from the path name
+pub(crate) fn split_path_key(path_name: &str, range_syntax: RangeSyntax) -> Option<(&str, usize, usize)> {
+    // Bracketed range, as written by --fragment-naming brackets
+    if let Some(bracketed) = path_name.strip_suffix(']')
+        && let Some(open) = bracketed.rfind('[')
+        && let Some((start, end)) = parse_range(&bracketed[open + 1..]) {
+        return Some((&bracketed[..open], start, end));
+    }
+    range_syntax.split(path_name).map(|(key, start, end)| (strip_fragment_index(key), start, end))
+}
+
+/// Path key of a fragment named by --fragment-naming pansn, `KEY#INDEX` with a three-field PanSN
+/// key. Other keys are returned whole
+pub(crate) fn strip_fragment_index(key: &str) -> &str {
+    match key.rsplit_once('#') {
+        Some((path_key, index)) if path_key.matches('#').count() == 2 && !index.is_empty() && index.bytes().all(|b| b.is_ascii_digit()) => path_key,
+        _ => key,
+    }
+}
+
+/// Syntax of the range at the end of the path names. Only one is recognized in a run, as contig
+/// names may end with what looks like another syntax
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RangeSyntax {
+    /// `KEY:START-END`
+    ColonDash,
+    /// `KEY_START_END`
+    Underscores,
+    /// `KEY:START_END`
+    ColonUnderscore,
+}
+
+impl RangeSyntax {
+    /// Separators before the start and before the end
+    pub(crate) fn delimiters(self) -> (char, char) {
+        match self {
+            RangeSyntax::ColonDash => (':', '-'),
+            RangeSyntax::Underscores => ('_', '_'),
+            RangeSyntax::ColonUnderscore => (':', '_'),
+        }
+    }
+
+    /// Splits a name into its key and range, at the last range delimiter followed by a range
+    pub(crate) fn split(self, path_name: &str) -> Option<(&str, usize, usize)> {
+        let (key_delimiter, range_delimiter) = self.delimiters();
+        let (rest, end) = path_name.rsplit_once(range_delimiter)?;
+        let (key, start) = rest.rsplit_once(key_delimiter)?;
+        if start.is_empty() || end.is_empty() || !start.bytes().chain(end.bytes()).all(|byte| byte.is_ascii_digit()) {
+            return None;
+        }
+        Some((key, start.parse().ok()?, end.parse().ok()?))
+    }
+
+    pub(crate) fn format(self, path_key: &str, start: usize, end: usize) -> String {
+        let (key_delimiter, range_delimiter) = self.delimiters();
+        format!("{}{}{}{}{}", path_key, key_delimiter, start, range_delimiter, end)
+    }
+}
+
+/// Naming scheme of the path names, which all end with the range
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum NameScheme {
+    /// Detect the scheme of each name, requiring a single one across the inputs
+    Auto,
+    /// PanSN `sample#hap#contig` names, or plain contig names
+    Pansn,
+    /// minigraph-cactus `id=sample|contig` names
+    Cactus,
+    /// Accept both schemes in a run
+    Mixed,
+}
+
+/// Sample and contig of a minigraph-cactus `id=sample|contig` path key
+pub(crate) fn cactus_key(path_key: &str) -> Option<(&str, &str)> {
+    path_key.strip_prefix("id=")?.split_once('|')
+}
+
+/// Contig of a path key: the part after `|` of a minigraph-cactus key, and the last PanSN field otherwise
+pub(crate) fn key_contig(path_key: &str) -> &str {
+    match cactus_key(path_key) {
+        Some((_, contig)) => contig,
+        None => path_key.rsplit('#').next().unwrap_or(path_key),
+    }
+}
+
+/// Checks that the path keys follow the given naming scheme, or a single detected one. The keys
+/// are kept whole, so the output names follow the scheme of the input names
+pub(crate) fn check_name_scheme(path_key_ranges: &FxHashMap<PathKey, Vec<RangeInfo>>, scheme: NameScheme, gfa_names: &[String]) -> io::Result<()> {
+    if scheme == NameScheme::Mixed {
+        return Ok(());
+    }
+    // First key of each scheme, by name
+    let mut pansn: Option<&PathKey> = None;
+    let mut cactus: Option<&PathKey> = None;
+    for path_key in path_key_ranges.keys() {
+        let first = if cactus_key(path_key).is_some() { &mut cactus } else { &mut pansn };
+        if first.is_none_or(|first| path_key < first) {
+            *first = Some(path_key);
+        }
+    }
+    let describe = |path_key: &PathKey| format!("'{}' from {}", path_key,
+        path_key_ranges[path_key].first().map_or("no input", |range| gfa_names[range.gfa_id].as_str()));
+    match (scheme, pansn, cactus) {
+        (NameScheme::Pansn, _, Some(cactus)) => Err(io::Error::new(io::ErrorKind::InvalidData, format!(
+            "Path key {} follows the minigraph-cactus naming scheme instead of PanSN", describe(cactus)))),
+        (NameScheme::Cactus, Some(pansn), _) => Err(io::Error::new(io::ErrorKind::InvalidData, format!(
+            "Path key {} does not follow the minigraph-cactus naming scheme", describe(pansn)))),
+        (NameScheme::Auto, Some(pansn), Some(cactus)) => Err(io::Error::new(io::ErrorKind::InvalidData, format!(
+            "Path keys follow both the PanSN ({}) and the minigraph-cactus ({}) naming schemes, use --name-scheme mixed to merge them", describe(pansn), describe(cactus)))),
+        _ => Ok(()),
+    }
+}
+
+/// Splits an input path name into its key and range, from the key map entry of the name, or
+/// else with the path format of the options if given, or else their range syntax. The whole name
+/// is the key with `naive_join`. The key is borrowed from the path name unless the map or the
+/// template gives another
+pub(crate) fn split_input_path_name<'a>(path_name: &'a str, options: &ReadOptions) -> Option<(Cow<'a, str>, usize, usize)> {
+    if options.naive_join {
+        return Some((Cow::Borrowed(path_name), usize::MIN, usize::MAX));
+    }
+    if let Some((key, start, end)) = options.key_map.and_then(|key_map| key_map.get(path_name)) {
+        return Some((Cow::Owned(key.clone()), *start, *end));
+    }
+    match options.path_format {
+        Some(path_format) => path_format.parse(path_name).map(|(key, start, end)| (Cow::Owned(key), start, end)),
+        None => split_path_key(path_name, options.range_syntax).map(|(key, start, end)| (Cow::Borrowed(key), start, end)),
+    }
+}
+
+/// Placeholder of a path format template
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FormatField {
+    Sample,
+    Hap,
+    Contig,
+    Start,
+    End,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum FormatPart {
+    Literal(String),
+    Field(FormatField),
+}
+
+/// Path name template compiled from --path-format: placeholders capture the fields, and literal
+/// text is matched exactly. The key joins the sample, haplotype, and contig with `#`, as in PanSN
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct PathFormat {
+    pub(crate) parts: Vec<FormatPart>,
+}
+
+impl PathFormat {
+    /// Compiles a template such as `{sample}#{hap}#{contig}:{start}-{end}`. Errors point to the
+    /// offending part of the template
+    pub(crate) fn compile(template: &str) -> Result<Self, String> {
+        let pointer = |column: usize, message: String| format!("{} at column {}\n  {}\n  {}^", message, column + 1, template, " ".repeat(template[..column].chars().count()));
+        let mut parts = Vec::new();
+        let mut seen: Vec<FormatField> = Vec::new();
+        let mut literal = String::new();
+        let mut rest = template;
+        while let Some(open) = rest.find(['{', '}']) {
+            let column = template.len() - rest.len() + open;
+            if rest[open..].starts_with('}') {
+                return Err(pointer(column, "Unmatched '}'".to_string()));
+            }
+            let close = rest[open..].find('}').ok_or_else(|| pointer(column, "Unclosed '{'".to_string()))? + open;
+            let field = match &rest[open + 1..close] {
+                "sample" => FormatField::Sample,
+                "hap" => FormatField::Hap,
+                "contig" => FormatField::Contig,
+                "start" => FormatField::Start,
+                "end" => FormatField::End,
+                unknown => return Err(pointer(column, format!("Unknown placeholder '{{{}}}', expected one of {{sample}}, {{hap}}, {{contig}}, {{start}}, {{end}}", unknown))),
+            };
+            if seen.contains(&field) {
+                return Err(pointer(column, format!("Repeated placeholder '{}'", &rest[open..=close])));
+            }
+            literal.push_str(&rest[..open]);
+            if literal.is_empty() && matches!(parts.last(), Some(FormatPart::Field(_))) {
+                return Err(pointer(column, format!("Placeholder '{}' directly follows another one, without text to separate them", &rest[open..=close])));
+            }
+            if !literal.is_empty() {
+                parts.push(FormatPart::Literal(std::mem::take(&mut literal)));
+            }
+            parts.push(FormatPart::Field(field));
+            seen.push(field);
+            rest = &rest[close + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(FormatPart::Literal(rest.to_string()));
+        }
+        for (field, name) in [(FormatField::Start, "{start}"), (FormatField::End, "{end}")] {
+            if !seen.contains(&field) {
+                return Err(pointer(template.len(), format!("Missing placeholder '{}'", name)));
+            }
+        }
+        if !seen.iter().any(|field| matches!(field, FormatField::Sample | FormatField::Hap | FormatField::Contig)) {
+            return Err(pointer(template.len(), "Missing placeholder '{sample}', '{hap}', or '{contig}' for the path key".to_string()));
+        }
+        Ok(PathFormat { parts })
+    }
+
+    /// Splits a path name into its key and range, or returns None if it does not match the
+    /// template. Placeholders capture as much text as possible, and `{start}` and `{end}` digits only
+    pub(crate) fn parse(&self, path_name: &str) -> Option<(String, usize, usize)> {
+        let mut captures: [&str; 5] = [""; 5];
+        if !Self::match_parts(&self.parts, path_name, &mut captures) {
+            return None;
+        }
+        let key: Vec<&str> = [FormatField::Sample, FormatField::Hap, FormatField::Contig].iter()
+            .map(|&field| captures[field as usize])
+            .filter(|capture| !capture.is_empty())
+            .collect();
+        Some((key.join("#"), captures[FormatField::Start as usize].parse().ok()?, captures[FormatField::End as usize].parse().ok()?))
+    }
+
+    pub(crate) fn match_parts<'a>(parts: &[FormatPart], name: &'a str, captures: &mut [&'a str; 5]) -> bool {
+        match parts {
+            [] => name.is_empty(),
+            [FormatPart::Literal(literal), rest @ ..] => name.strip_prefix(literal.as_str())
+                .is_some_and(|name| Self::match_parts(rest, name, captures)),
+            [FormatPart::Field(field), rest @ ..] => {
+                let is_valid = |capture: &str| !capture.is_empty()
+                    && (!matches!(field, FormatField::Start | FormatField::End) || capture.bytes().all(|byte| byte.is_ascii_digit()));
+                match rest.first() {
+                    // The field ends at the last occurrence of the next literal that leaves a match
+                    Some(FormatPart::Literal(literal)) => name.rmatch_indices(literal.as_str()).any(|(end, _)| {
+                        is_valid(&name[..end]) && {
+                            captures[*field as usize] = &name[..end];
+                            Self::match_parts(rest, &name[end..], captures)
+                        }
+                    }),
+                    _ => {
+                        captures[*field as usize] = name;
+                        is_valid(name)
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Parses a `START-END` range
+pub(crate) fn parse_range(range_str: &str) -> Option<(usize, usize)> {
+    let (start_str, end_str) = range_str.split_once('-')?;
+    Some((start_str.parse().ok()?, end_str.parse().ok()?))
+}
+
+/// Sort key ordering the numbers within strings by value, so that chr2 precedes chr10
+pub(crate) fn natural_key(s: &str) -> Vec<(String, u64)> {
+    let mut key = Vec::new();
+    let mut rest = s;
+    while !rest.is_empty() {
+        let text_len = rest.find(|c: char| c.is_ascii_digit()).unwrap_or(rest.len());
+        let digits_len = rest[text_len..].find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len() - text_len);
+        let number = rest[text_len..text_len + digits_len].parse().unwrap_or(u64::MAX);
+        key.push((rest[..text_len].to_string(), number));
+        rest = &rest[text_len + digits_len..];
+    }
+    key
+}
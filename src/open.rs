@@ -0,0 +1,535 @@
+//! Opening of the inputs: plain or compressed files, tar archives, and URLs
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+use gfa::{gfa::GFA, optfields::OptionalFields, parser::GFAParser};
+use tempfile::NamedTempFile;
+use memmap2::Mmap;
+use log::{debug, info, warn};
+use std::sync::{Arc, Condvar, Mutex, mpsc::{self, Receiver}};
+use crate::input::{PassthroughLines, ReadOptions};
+
+/// Returns true if the input is an HTTP(S) URL rather than a local path
+pub(crate) fn is_url(gfa_path: &str) -> bool {
+    gfa_path.starts_with("http://") || gfa_path.starts_with("https://")
+}
+
+/// Opens an input GFA, either a local file or, with the `http` feature, an HTTP(S) URL
+#[cfg_attr(not(feature = "http"), allow(unused_variables))]
+pub(crate) fn open_input(gfa_path: &str, options: &ReadOptions) -> io::Result<Box<dyn Read>> {
+    if is_url(gfa_path) {
+        #[cfg(feature = "http")]
+        return fetch_url(gfa_path, options.http_retries);
+        #[cfg(not(feature = "http"))]
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("Cannot read '{}': gfalace was built without the 'http' feature", gfa_path)
+        ));
+    }
+    open_file(gfa_path)
+}
+
+/// Opens a local input file
+pub(crate) fn open_file(gfa_path: &str) -> io::Result<Box<dyn Read>> {
+    let file = File::open(gfa_path).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("Failed to open file '{}': {}", gfa_path, e)
+        )
+    })?;
+    Ok(Box::new(file))
+}
+
+/// Streams the body of an HTTP(S) URL. Requests failing with a transport error or a server
+/// error (5xx) are retried; a broken connection while the body is read is not
+#[cfg(feature = "http")]
+pub(crate) fn fetch_url(url: &str, retries: u32) -> io::Result<Box<dyn Read>> {
+    let mut attempt = 0;
+    loop {
+        let error = match ureq::get(url).call() {
+            Ok(response) => return Ok(Box::new(response.into_reader())),
+            Err(ureq::Error::Status(status, _)) => {
+                let error = io::Error::other(format!("Failed to fetch '{}': HTTP status {}", url, status));
+                if status < 500 {
+                    return Err(error);
+                }
+                error
+            }
+            Err(e) => io::Error::other(format!("Failed to fetch '{}': {}", url, e)),
+        };
+
+        if attempt >= retries {
+            return Err(error);
+        }
+        attempt += 1;
+        warn!("{}, retrying ({}/{})", error, attempt, retries);
+    }
+}
+
+/// Returns the distinct versions (`VN:Z` tag) of the H lines of a GFA file, in order of appearance.
+/// Files concatenated with `cat` contain several H lines, of which the parser keeps only the last
+pub(crate) fn header_versions<R: BufRead>(lines: R) -> io::Result<Vec<String>> {
+    let mut versions = Vec::new();
+    for line in lines.split(b'\n') {
+        let line = line?;
+        if !line.starts_with(b"H\t") {
+            continue;
+        }
+        let line = String::from_utf8_lossy(line.strip_suffix(b"\r").unwrap_or(&line)).into_owned();
+        if let Some(version) = line.split('\t').find_map(|field| field.strip_prefix("VN:Z:"))
+            && !versions.iter().any(|v| v == version) {
+            versions.push(version.to_string());
+        }
+    }
+    Ok(versions)
+}
+
+/// Warns if the H lines of the input GFA files state different versions.
+/// The output always gets a single `VN:Z:1.0` header
+pub(crate) fn check_header_versions(gfa_list: &[String], options: &ReadOptions) -> io::Result<()> {
+    let mut all_versions: Vec<String> = Vec::new();
+    for_each_input(gfa_list, options, |_gfa_id, input| {
+        let gfa_path = input.name().to_string();
+        let versions = header_versions(input.lines(options)?)?;
+        if versions.len() > 1 {
+            warn!(stage = "parse", file = gfa_path.as_str(); "File '{}' has H lines with different versions: {}", gfa_path, versions.join(", "));
+        }
+        for version in versions {
+            if !all_versions.contains(&version) {
+                all_versions.push(version);
+            }
+        }
+        Ok(())
+    })?;
+    if all_versions.len() > 1 {
+        warn!("Input files have different GFA versions ({}), the output is written as version 1.0", all_versions.join(", "));
+    }
+    Ok(())
+}
+
+/// Collects the comment lines and/or the lines of unrecognized record types of the input files
+pub(crate) fn collect_passthrough_lines(
+    gfa_list: &[String],
+    options: &ReadOptions,
+    comments: bool,
+    unknown: bool
+) -> io::Result<PassthroughLines> {
+    let mut passthrough = PassthroughLines::default();
+
+    for_each_input(gfa_list, options, |_gfa_id, input| {
+        let gfa_path = input.name().to_string();
+        for line in input.lines(options)?.split(b'\n') {
+            let line = line?;
+            let line = String::from_utf8_lossy(line.strip_suffix(b"\r").unwrap_or(&line)).into_owned();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some(comment) = line.strip_prefix('#') {
+                if comments {
+                    passthrough.comments.push(format!("# {}: {}", gfa_path, comment.trim_start()));
+                }
+            } else if unknown && !matches!(line.split('\t').next(), Some("H" | "S" | "L" | "C" | "P" | "W")) {
+                passthrough.unknown.push(line);
+            }
+        }
+        Ok(())
+    })?;
+
+    Ok(passthrough)
+}
+
+pub(crate) fn read_gfa(gfa_path: &str, parser: &GFAParser<usize, OptionalFields>, options: &ReadOptions) -> io::Result<GFA<usize, OptionalFields>> {
+    if gfa_path.ends_with(".gz") || gfa_path.ends_with(".zst") || is_url(gfa_path) {
+        // Create temporary file in the same directory as the input file for better performance
+        let temp_dir = if is_url(gfa_path) {
+            None
+        } else {
+            Some(Path::new(gfa_path).parent().unwrap_or(Path::new(".")))
+        };
+        parse_gfa_reader(gfa_path, open_input(gfa_path, options)?, temp_dir, parser, options.keep_temp)
+    } else if !options.no_mmap {
+        parse_mapped_gfa(gfa_path, parser)
+    } else {
+        parser.parse_file(gfa_path).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Failed to parse GFA file '{}': {}", gfa_path, e)
+            )
+        })
+    }
+}
+
+/// Parses an uncompressed GFA file by memory-mapping it and handing its lines to the parser
+/// as slices of the mapping, without copying them into a read buffer
+pub(crate) fn parse_mapped_gfa(gfa_path: &str, parser: &GFAParser<usize, OptionalFields>) -> io::Result<GFA<usize, OptionalFields>> {
+    let file = File::open(gfa_path)?;
+    // SAFETY: the mapping is only read, and dropped before returning. It is not protected against
+    // other processes: unlike a buffered read, which would see a short file and fail to parse,
+    // truncating the input meanwhile makes accesses past its new end raise SIGBUS and crash the
+    // process, and modifying it changes the bytes under the parser. --no-mmap avoids both
+    let mmap = unsafe { Mmap::map(&file)? };
+    #[cfg(unix)]
+    let _ = mmap.advise(memmap2::Advice::Sequential);
+
+    parser.parse_lines(bstr::ByteSlice::lines(&mmap[..])).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Failed to parse GFA file '{}': {}", gfa_path, e)
+        )
+    })
+}
+
+/// Parses a GFA, possibly compressed, from a reader by decompressing it to a temporary file
+/// in `temp_dir` (or the system temporary directory)
+pub(crate) fn parse_gfa_reader(
+    name: &str,
+    reader: Box<dyn Read + '_>,
+    temp_dir: Option<&Path>,
+    parser: &GFAParser<usize, OptionalFields>,
+    keep_temp: bool
+) -> io::Result<GFA<usize, OptionalFields>> {
+    let temp_file = decompress_to_temp(name, reader, temp_dir, None)?;
+    parse_temp_gfa(name, temp_file, parser, keep_temp)
+}
+
+/// Decompresses a GFA, possibly compressed, to a temporary file in `temp_dir`
+/// (or the system temporary directory). The data is streamed, so memory use is constant. The
+/// decompressed bytes are reserved in `in_flight` as the input of the given index, if given
+pub(crate) fn decompress_to_temp(name: &str, reader: Box<dyn Read + '_>, temp_dir: Option<&Path>, in_flight: Option<(&InFlightBytes, usize)>) -> io::Result<NamedTempFile> {
+    let (mut reader, _format) = niffler::get_reader(reader)
+        .map_err(io::Error::other)?;
+
+    let temp_file = match temp_dir {
+        Some(dir) => NamedTempFile::new_in(dir),
+        None => NamedTempFile::new(),
+    }.map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("Failed to create temporary file: {}", e)
+        )
+    })?;
+
+    // Write decompressed data
+    let mut writer = BufWriter::new(CountingWriter { inner: temp_file.as_file(), in_flight, written: 0 });
+    io::copy(&mut reader, &mut writer).and_then(|_| writer.flush()).map_err(|e| {
+        // The temporary file is removed, so the bytes it held are not in flight anymore
+        if let Some((in_flight, _)) = in_flight {
+            in_flight.release(writer.get_ref().written);
+        }
+        io::Error::new(
+            e.kind(),
+            format!("Failed to decompress file '{}' to a temporary file: {}", name, e)
+        )
+    })?;
+    drop(writer);
+
+    Ok(temp_file)
+}
+
+/// Parses a decompressed GFA from its temporary file, which is kept on failure with `keep_temp`
+pub(crate) fn parse_temp_gfa(
+    name: &str,
+    temp_file: NamedTempFile,
+    parser: &GFAParser<usize, OptionalFields>,
+    keep_temp: bool
+) -> io::Result<GFA<usize, OptionalFields>> {
+    // Parse GFA
+    let result = parser.parse_file(temp_file.path().to_str().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Invalid temporary file path"
+        )
+    })?);
+
+    result.map_err(|e| {
+        if keep_temp {
+            // Leave the decompressed file in place to inspect what is malformed
+            match temp_file.keep() {
+                Ok((_, kept_path)) => return io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Failed to parse GFA '{}' (decompressed copy kept at '{}'): {}", name, kept_path.display(), e)
+                ),
+                Err(keep_error) => warn!("Could not keep the temporary file: {}", keep_error),
+            }
+        }
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Failed to parse GFA '{}': {}", name, e)
+        )
+    })
+}
+
+/// Default bytes of decompressed inputs waiting to be parsed, past which no other input is decompressed ahead
+pub(crate) const DECOMPRESS_BUDGET: u64 = 4 << 30;
+
+/// Writer reserving the bytes written through it in `in_flight`, as the input of the given index
+pub(crate) struct CountingWriter<'a, W: Write> {
+    pub(crate) inner: W,
+    pub(crate) in_flight: Option<(&'a InFlightBytes, usize)>,
+    pub(crate) written: u64,
+}
+
+impl<W: Write> Write for CountingWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some((in_flight, index)) = self.in_flight {
+            in_flight.reserve(index, buf.len() as u64)?;
+        }
+        let written = self.inner.write(buf);
+        let count = *written.as_ref().unwrap_or(&0) as u64;
+        if let Some((in_flight, _)) = self.in_flight {
+            in_flight.release(buf.len() as u64 - count);
+        }
+        self.written += count;
+        written
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Bytes of the inputs decompressed ahead that are not parsed yet, shared by the prefetcher
+/// workers and the parsing thread. Past the budget, only the oldest unparsed input is written
+/// further, as the parsing thread waits for it, so that this cannot deadlock
+pub(crate) struct InFlightBytes {
+    pub(crate) budget: u64,
+    pub(crate) state: Mutex<InFlightState>,
+    pub(crate) released: Condvar,
+}
+
+#[derive(Default)]
+pub(crate) struct InFlightState {
+    pub(crate) bytes: u64,
+    pub(crate) peak: u64,
+    /// Index of the oldest input not parsed yet
+    pub(crate) parsed: usize,
+    /// Set once the prefetcher is dropped, so that its workers stop waiting
+    pub(crate) closed: bool,
+}
+
+impl InFlightBytes {
+    pub(crate) fn new(budget: u64) -> Self {
+        InFlightBytes { budget, state: Mutex::default(), released: Condvar::new() }
+    }
+
+    /// Waits until `bytes` more fit in the budget, or the input of `index` is the oldest unparsed one
+    pub(crate) fn reserve(&self, index: usize, bytes: u64) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        while index != state.parsed && state.bytes + bytes > self.budget {
+            if state.closed {
+                return Err(io::Error::other("Decompression ahead was stopped"));
+            }
+            state = self.released.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+        state.bytes += bytes;
+        state.peak = state.peak.max(state.bytes);
+        Ok(())
+    }
+
+    pub(crate) fn release(&self, bytes: u64) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.bytes = state.bytes.saturating_sub(bytes);
+        self.released.notify_all();
+    }
+
+    /// Releases the `bytes` of the oldest unparsed input once it is parsed
+    pub(crate) fn finish(&self, bytes: u64) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.bytes = state.bytes.saturating_sub(bytes);
+        state.parsed += 1;
+        self.released.notify_all();
+    }
+
+    pub(crate) fn close(&self) {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).closed = true;
+        self.released.notify_all();
+    }
+}
+
+/// Returns true for the gzipped local files that are decompressed ahead of their parsing
+pub(crate) fn is_prefetchable(gfa_path: &str) -> bool {
+    gfa_path.ends_with(".gz") && !is_url(gfa_path) && !is_tar_archive(gfa_path)
+}
+
+/// Decompresses the prefetchable inputs on worker threads, ahead of their parsing and in input
+/// order, as temporary files in the directory of their input. At most about `ahead` files are
+/// decompressed ahead, and past `budget` bytes of decompressed inputs waiting to be parsed, only
+/// the next one to parse is written further, so that the disk use does not depend on the number
+/// or size of the inputs. That one is always decompressed whole, even if larger than the budget
+pub(crate) struct Prefetcher {
+    pub(crate) queue: Receiver<(String, Receiver<io::Result<NamedTempFile>>)>,
+    pub(crate) in_flight: Arc<InFlightBytes>,
+}
+
+impl Prefetcher {
+    pub(crate) fn new(gfa_list: &[String], ahead: usize, budget: u64) -> Option<Self> {
+        let gfa_paths: Vec<String> = gfa_list.iter().filter(|path| is_prefetchable(path)).cloned().collect();
+        if ahead == 0 || gfa_paths.is_empty() {
+            return None;
+        }
+
+        let (sender, queue) = mpsc::sync_channel(ahead);
+        let in_flight = Arc::new(InFlightBytes::new(budget));
+        let worker_in_flight = Arc::clone(&in_flight);
+        std::thread::spawn(move || {
+            for (index, gfa_path) in gfa_paths.into_iter().enumerate() {
+                let (result_sender, result) = mpsc::channel();
+                let path = gfa_path.clone();
+                let in_flight = Arc::clone(&worker_in_flight);
+                std::thread::spawn(move || {
+                    let temp_dir = Path::new(&path).parent().unwrap_or(Path::new("."));
+                    let decompressed = open_file(&path).and_then(|reader| decompress_to_temp(&path, reader, Some(temp_dir), Some((&in_flight, index))));
+                    if let Err(e) = &decompressed {
+                        debug!(stage = "parse", file = path.as_str(); "Failed to decompress '{}' ahead: {}", path, e);
+                    }
+                    let _ = result_sender.send(decompressed);
+                });
+                // Blocks while `ahead` files are waiting, and stops once they are not needed
+                if sender.send((gfa_path, result)).is_err() {
+                    break;
+                }
+            }
+        });
+        Some(Prefetcher { queue, in_flight })
+    }
+
+    /// Largest number of bytes of decompressed inputs waiting to be parsed at once
+    pub(crate) fn peak_bytes(&self) -> u64 {
+        self.in_flight.state.lock().unwrap_or_else(|e| e.into_inner()).peak
+    }
+
+    /// Parses the next prefetched input, which must be `gfa_path`
+    pub(crate) fn parse(&self, gfa_path: &str, parser: &GFAParser<usize, OptionalFields>, options: &ReadOptions) -> io::Result<GFA<usize, OptionalFields>> {
+        let (prefetched_path, result) = self.queue.recv().map_err(io::Error::other)?;
+        if prefetched_path != gfa_path {
+            return Err(io::Error::other(format!("Expected '{}' to be decompressed, found '{}'", gfa_path, prefetched_path)));
+        }
+        let decompressed = result.recv().map_err(io::Error::other).and_then(|decompressed| decompressed);
+        let size = decompressed.as_ref().map_or(0, |temp_file| temp_file.as_file().metadata().map_or(0, |metadata| metadata.len()));
+        let parsed = decompressed.and_then(|temp_file| parse_temp_gfa(gfa_path, temp_file, parser, options.keep_temp));
+        self.in_flight.finish(size);
+        parsed
+    }
+}
+
+impl Drop for Prefetcher {
+    fn drop(&mut self) {
+        self.in_flight.close();
+    }
+}
+
+/// An input GFA, given either by path (or URL) or as a member of a tar archive
+pub(crate) enum GfaInput<'a> {
+    Path(&'a str),
+    Member { name: String, reader: Box<dyn Read + 'a> },
+}
+
+impl<'a> GfaInput<'a> {
+    /// Name of the input in diagnostics, with archive members named `archive/member`
+    pub(crate) fn name(&self) -> &str {
+        match self {
+            GfaInput::Path(path) => path,
+            GfaInput::Member { name, .. } => name,
+        }
+    }
+
+    pub(crate) fn parse(self, parser: &GFAParser<usize, OptionalFields>, options: &ReadOptions) -> io::Result<GFA<usize, OptionalFields>> {
+        match self {
+            GfaInput::Path(path) => read_gfa(path, parser, options),
+            GfaInput::Member { name, reader } => parse_gfa_reader(&name, reader, None, parser, options.keep_temp),
+        }
+    }
+
+    /// Opens the GFA, possibly compressed, for reading its raw lines
+    pub(crate) fn lines(self, options: &ReadOptions) -> io::Result<BufReader<Box<dyn Read + 'a>>> {
+        let reader = match self {
+            GfaInput::Path(path) => open_input(path, options)?,
+            GfaInput::Member { reader, .. } => reader,
+        };
+        let (reader, _format) = niffler::get_reader(reader)
+            .map_err(io::Error::other)?;
+
+        Ok(BufReader::new(reader))
+    }
+}
+
+/// Returns true if the input is a tar archive, possibly compressed
+pub(crate) fn is_tar_archive(gfa_path: &str) -> bool {
+    let uncompressed = [".gz", ".bz2", ".xz", ".zst"].iter()
+        .find_map(|ext| gfa_path.strip_suffix(ext))
+        .unwrap_or(gfa_path);
+    uncompressed.ends_with(".tar") || gfa_path.ends_with(".tgz")
+}
+
+/// Returns the name of an archive member holding a GFA (`*.gfa` or `*.gfa.gz`),
+/// or None for other members, which are skipped
+pub(crate) fn gfa_member_name<R: Read>(archive_path: &str, entry: &tar::Entry<R>) -> io::Result<Option<String>> {
+    if !entry.header().entry_type().is_file() {
+        return Ok(None);
+    }
+    let member = entry.path()?.to_string_lossy().into_owned();
+    if member.ends_with(".gfa") || member.ends_with(".gfa.gz") {
+        Ok(Some(format!("{}/{}", archive_path, member)))
+    } else {
+        info!(stage = "parse", file = archive_path; "Skipping member '{}' of '{}', which is not a GFA file", member, archive_path);
+        Ok(None)
+    }
+}
+
+/// Calls `f` on each input GFA with its ID, which follows the iteration order.
+/// Tar archives contribute their GFA members, in archive order or sorted by name.
+/// Returns the number of archive members skipped because they are not GFA files
+pub(crate) fn for_each_input<F>(gfa_list: &[String], options: &ReadOptions, mut f: F) -> io::Result<usize>
+where
+    F: FnMut(usize, GfaInput<'_>) -> io::Result<()>
+{
+    let mut gfa_id = 0;
+    let mut skipped = 0;
+    for gfa_path in gfa_list {
+        if !is_tar_archive(gfa_path) {
+            f(gfa_id, GfaInput::Path(gfa_path))?;
+            gfa_id += 1;
+            continue;
+        }
+
+        let (reader, _format) = niffler::get_reader(open_input(gfa_path, options)?)
+            .map_err(io::Error::other)?;
+        let mut archive = tar::Archive::new(reader);
+        if options.sort_archive {
+            // Archives can only be read sequentially, so the members are buffered to sort them
+            let mut members = Vec::new();
+            for entry in archive.entries()? {
+                let mut entry = entry?;
+                if let Some(name) = gfa_member_name(gfa_path, &entry)? {
+                    let mut content = Vec::new();
+                    entry.read_to_end(&mut content)?;
+                    members.push((name, content));
+                } else {
+                    skipped += 1;
+                }
+            }
+            members.sort_by(|a, b| a.0.cmp(&b.0));
+
+            for (name, content) in members {
+                f(gfa_id, GfaInput::Member { name, reader: Box::new(io::Cursor::new(content)) })?;
+                gfa_id += 1;
+            }
+        } else {
+            for entry in archive.entries()? {
+                let entry = entry?;
+                if let Some(name) = gfa_member_name(gfa_path, &entry)? {
+                    f(gfa_id, GfaInput::Member { name, reader: Box::new(entry) })?;
+                    gfa_id += 1;
+                } else {
+                    skipped += 1;
+                }
+            }
+        }
+    }
+
+    Ok(skipped)
+}
@@ -0,0 +1,384 @@
+//! Output files: written atomically, compressed, sharded, or with CRLF line endings
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+use serde::{Deserialize, Serialize};
+use log::warn;
+
+/// Line ending of the output GFA
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum LineEnding {
+    /// `\n`
+    Lf,
+    /// `\r\n`
+    Crlf,
+}
+
+/// Writer that ends the lines written through it with `\r\n` instead of `\n`
+pub(crate) struct CrlfWriter<W: Write>(pub(crate) W);
+
+impl<W: Write> Write for CrlfWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for (idx, line) in buf.split(|&byte| byte == b'\n').enumerate() {
+            if idx > 0 {
+                self.0.write_all(b"\r\n")?;
+            }
+            self.0.write_all(line)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Output split into shards of at most `shard_size` bytes, for consumers that load one shard at
+/// a time. Given `<base>` the output name without its `.gfa` extension:
+/// - the header lines (H and comments before the first segment) start every file;
+/// - the segments go in order into `<base>.0000.gfa`, `<base>.0001.gfa`, ..., starting a new
+///   shard when the next S line would exceed the limit (a shard always takes at least one
+///   segment, so a single oversized segment gets a shard of its own);
+/// - a link or containment goes into the shard declaring both of its segments if it still fits
+///   there, and into `<base>.links.gfa` otherwise;
+/// - the paths, and any other lines, go into `<base>.paths.gfa`;
+/// - `<base>.manifest.json` describes the files.
+pub(crate) struct ShardedWriter {
+    pub(crate) base: String,
+    pub(crate) shard_size: u64,
+    pub(crate) header: Vec<u8>,
+    pub(crate) shards: Vec<Shard>,
+    pub(crate) links: Option<ShardPart>,
+    pub(crate) paths: Option<ShardPart>,
+    pub(crate) line: Vec<u8>,
+}
+
+/// File of a sharded output with its byte and record counts
+pub(crate) struct ShardPart {
+    pub(crate) file: String,
+    pub(crate) out: AtomicFile,
+    pub(crate) bytes: u64,
+    pub(crate) records: usize,
+}
+
+/// Shard of segments, whose IDs are consecutive in the output
+pub(crate) struct Shard {
+    pub(crate) part: ShardPart,
+    pub(crate) first_segment: usize,
+    pub(crate) last_segment: usize,
+    pub(crate) segments: usize,
+}
+
+#[derive(Serialize)]
+pub(crate) struct ShardManifest {
+    pub(crate) shard_size: u64,
+    pub(crate) shards: Vec<ShardEntry>,
+    pub(crate) links: ShardEntry,
+    pub(crate) paths: ShardEntry,
+}
+
+#[derive(Serialize)]
+pub(crate) struct ShardEntry {
+    pub(crate) file: String,
+    pub(crate) bytes: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) first_segment: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) last_segment: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) segments: Option<usize>,
+    /// Links and containments, or paths for the paths file
+    pub(crate) records: usize,
+}
+
+impl ShardPart {
+    pub(crate) fn create(file: String, header: &[u8]) -> io::Result<Self> {
+        let mut out = AtomicFile::create(&file)?;
+        out.write_all(header)?;
+        Ok(ShardPart { file, out, bytes: header.len() as u64, records: 0 })
+    }
+
+    /// Renames the part into place once complete
+    pub(crate) fn commit(self) -> io::Result<()> {
+        self.out.finish()
+    }
+
+    pub(crate) fn write_record(&mut self, line: &[u8]) -> io::Result<()> {
+        self.out.write_all(line)?;
+        self.bytes += line.len() as u64;
+        self.records += 1;
+        Ok(())
+    }
+
+    pub(crate) fn entry(&self) -> ShardEntry {
+        ShardEntry { file: self.file.clone(), bytes: self.bytes, first_segment: None, last_segment: None, segments: None, records: self.records }
+    }
+}
+
+impl ShardedWriter {
+    pub(crate) fn new(output_path: &str, shard_size: u64) -> Self {
+        let base = output_path.strip_suffix(".gfa").unwrap_or(output_path).to_string();
+        ShardedWriter { base, shard_size, header: Vec::new(), shards: Vec::new(), links: None, paths: None, line: Vec::new() }
+    }
+
+    /// Name of the shard with the given index
+    pub(crate) fn shard_file(base: &str, index: usize) -> String {
+        format!("{}.{:04}.gfa", base, index)
+    }
+
+    /// Index of the shard declaring the segment, if any
+    pub(crate) fn segment_shard(&self, segment: usize) -> Option<usize> {
+        let index = self.shards.partition_point(|shard| shard.first_segment <= segment).checked_sub(1)?;
+        (segment <= self.shards[index].last_segment).then_some(index)
+    }
+
+    pub(crate) fn extra_part<'a>(part: &'a mut Option<ShardPart>, file: String, header: &[u8]) -> io::Result<&'a mut ShardPart> {
+        if part.is_none() {
+            *part = Some(ShardPart::create(file, header)?);
+        }
+        Ok(part.as_mut().unwrap())
+    }
+
+    pub(crate) fn route_line(&mut self, line: &[u8]) -> io::Result<()> {
+        let field = |idx: usize| line.split(|&byte| byte == b'\t' || byte == b'\n').nth(idx)
+            .and_then(|field| std::str::from_utf8(field).ok())
+            .and_then(|field| field.parse::<usize>().ok());
+        match line.first() {
+            Some(b'S') => {
+                let segment = field(1).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData,
+                    "--shard-size needs numeric segment IDs"))?;
+                let full = self.shards.last().is_none_or(|shard| shard.part.bytes + line.len() as u64 > self.shard_size);
+                if full {
+                    let part = ShardPart::create(Self::shard_file(&self.base, self.shards.len()), &self.header)?;
+                    self.shards.push(Shard { part, first_segment: segment, last_segment: segment, segments: 0 });
+                }
+                let shard = self.shards.last_mut().unwrap();
+                if segment < shard.last_segment {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "--shard-size needs the segments in ID order"));
+                }
+                shard.part.out.write_all(line)?;
+                shard.part.bytes += line.len() as u64;
+                shard.last_segment = segment;
+                shard.segments += 1;
+            }
+            Some(b'L' | b'C') => {
+                let from = field(1).and_then(|segment| self.segment_shard(segment));
+                let to = field(3).and_then(|segment| self.segment_shard(segment));
+                match (from, to) {
+                    (Some(from), Some(to)) if from == to && self.shards[from].part.bytes + line.len() as u64 <= self.shard_size => {
+                        self.shards[from].part.write_record(line)?;
+                    }
+                    _ => Self::extra_part(&mut self.links, format!("{}.links.gfa", self.base), &self.header)?.write_record(line)?,
+                }
+            }
+            Some(b'H' | b'#') if self.shards.is_empty() && self.links.is_none() && self.paths.is_none() => {
+                self.header.extend_from_slice(line);
+            }
+            _ => Self::extra_part(&mut self.paths, format!("{}.paths.gfa", self.base), &self.header)?.write_record(line)?,
+        }
+        Ok(())
+    }
+
+    /// Writes the remaining lines and the manifest, returning the number of shards
+    pub(crate) fn finish(mut self) -> io::Result<usize> {
+        if !self.line.is_empty() {
+            let line = std::mem::take(&mut self.line);
+            self.route_line(&line)?;
+        }
+        Self::extra_part(&mut self.links, format!("{}.links.gfa", self.base), &self.header)?;
+        Self::extra_part(&mut self.paths, format!("{}.paths.gfa", self.base), &self.header)?;
+        let (Some(links), Some(paths)) = (self.links, self.paths) else { unreachable!() };
+        let mut shards = Vec::with_capacity(self.shards.len());
+        for shard in self.shards {
+            shards.push(ShardEntry {
+                first_segment: Some(shard.first_segment),
+                last_segment: Some(shard.last_segment),
+                segments: Some(shard.segments),
+                ..shard.part.entry()
+            });
+            shard.part.commit()?;
+        }
+        let manifest = ShardManifest { shard_size: self.shard_size, shards, links: links.entry(), paths: paths.entry() };
+        links.commit()?;
+        paths.commit()?;
+        // The manifest comes last, once all the files it lists are in place
+        let mut manifest_file = AtomicFile::create(&format!("{}.manifest.json", self.base))?;
+        serde_json::to_writer_pretty(&mut manifest_file, &manifest)?;
+        manifest_file.finish()?;
+        Ok(manifest.shards.len())
+    }
+}
+
+impl Write for ShardedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut rest = buf;
+        while let Some(pos) = rest.iter().position(|&byte| byte == b'\n') {
+            self.line.extend_from_slice(&rest[..=pos]);
+            let line = std::mem::take(&mut self.line);
+            self.route_line(&line)?;
+            self.line = line;
+            self.line.clear();
+            rest = &rest[pos + 1..];
+        }
+        self.line.extend_from_slice(rest);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        for shard in &mut self.shards {
+            shard.part.out.flush()?;
+        }
+        for part in [&mut self.links, &mut self.paths].into_iter().flatten() {
+            part.out.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Default number of records written between flushes of the output buffer
+pub(crate) const SEGMENT_BATCH: usize = 1 << 16;
+
+/// Capacity of the output buffer, large enough for a batch of short segments
+pub(crate) const OUTPUT_BUFFER_CAPACITY: usize = 8 << 20;
+
+/// Compression level of the compressed output GFAs, from 1 (fastest) to 9 (smallest)
+pub(crate) const OUTPUT_COMPRESSION_LEVEL: i32 = 6;
+
+/// Output file written as `<path>.tmp.<pid>` in the same directory, through a buffer and compressed
+/// as per the extension of its path if created by `compressed`, and renamed to its path by `finish`
+/// once complete, so that a run killed mid-write never leaves a truncated but plausible file behind.
+/// The temporary file is removed if the output is dropped without being finished
+pub(crate) struct AtomicFile {
+    pub(crate) out: BufWriter<OutputWriter>,
+    pub(crate) temp: TempOutput,
+}
+
+impl AtomicFile {
+    /// Creates the temporary file of an output written as it is
+    pub(crate) fn create(path: &str) -> io::Result<Self> {
+        let temp = TempOutput::new(path);
+        let out = BufWriter::with_capacity(OUTPUT_BUFFER_CAPACITY, OutputWriter::Plain(File::create(&temp.temp_path)?));
+        Ok(AtomicFile { out, temp })
+    }
+
+    /// Creates the temporary file of an output compressed as per its path, as by `compressed_writer`
+    pub(crate) fn compressed(path: &str) -> io::Result<Self> {
+        let temp = TempOutput::new(path);
+        let out = BufWriter::with_capacity(OUTPUT_BUFFER_CAPACITY, compressed_writer(path, File::create(&temp.temp_path)?, OUTPUT_COMPRESSION_LEVEL)?);
+        Ok(AtomicFile { out, temp })
+    }
+
+    /// Flushes the buffer, finishes the compressed stream, syncs the temporary file, and renames it
+    /// to the final path. Where renaming fails, the file is copied next to the final path and that
+    /// copy renamed instead, with a warning
+    pub(crate) fn finish(self) -> io::Result<()> {
+        let file = self.out.into_inner().map_err(io::IntoInnerError::into_error)?.finish()?;
+        file.sync_all()?;
+        drop(file);
+        self.temp.commit()
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.out.write(buf)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.out.write_all(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Temporary file of an `AtomicFile`, removed unless committed
+pub(crate) struct TempOutput {
+    pub(crate) path: String,
+    pub(crate) temp_path: String,
+    pub(crate) committed: bool,
+}
+
+impl TempOutput {
+    pub(crate) fn new(path: &str) -> Self {
+        TempOutput { path: path.to_string(), temp_path: format!("{}.tmp.{}", path, std::process::id()), committed: false }
+    }
+
+    /// Renames the synced temporary file to the final path, or copies it to a temporary file in
+    /// the directory of the final path, on its filesystem, and renames that one
+    pub(crate) fn commit(mut self) -> io::Result<()> {
+        if let Err(e) = std::fs::rename(&self.temp_path, &self.path) {
+            warn!(stage = "write", file = self.path.as_str(); "Could not rename {} to {} ({}), copying it instead", self.temp_path, self.path, e);
+            let target_dir = Path::new(&self.path).parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+            let mut copy = tempfile::Builder::new().prefix(".gfalace-copy").tempfile_in(target_dir)?;
+            io::copy(&mut File::open(&self.temp_path)?, copy.as_file_mut())?;
+            copy.as_file().sync_all()?;
+            copy.persist(&self.path).map_err(|e| e.error)?;
+            std::fs::remove_file(&self.temp_path)?;
+        }
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Drop for TempOutput {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = std::fs::remove_file(&self.temp_path);
+        }
+    }
+}
+
+/// Stream of an output file, compressed or not, whose compressed stream is ended by `finish`
+pub(crate) enum OutputWriter {
+    Plain(File),
+    Gzip(flate2::write::GzEncoder<File>),
+    Zstd(zstd::stream::write::Encoder<'static, File>),
+}
+
+impl OutputWriter {
+    /// Ends the compressed stream, returning the file and any error writing its end
+    pub(crate) fn finish(self) -> io::Result<File> {
+        match self {
+            OutputWriter::Plain(file) => Ok(file),
+            OutputWriter::Gzip(encoder) => encoder.finish(),
+            OutputWriter::Zstd(encoder) => encoder.finish(),
+        }
+    }
+}
+
+impl Write for OutputWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            OutputWriter::Plain(file) => file.write(buf),
+            OutputWriter::Gzip(encoder) => encoder.write(buf),
+            OutputWriter::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            OutputWriter::Plain(file) => file.flush(),
+            OutputWriter::Gzip(encoder) => encoder.flush(),
+            OutputWriter::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Writes to `file` compressed with gzip if `path` ends with `.gz` and with zstd if it ends with
+/// `.zst`, at `level` (clamped to 1-9), and plain otherwise
+pub(crate) fn compressed_writer(path: &str, file: File, level: i32) -> io::Result<OutputWriter> {
+    let level = level.clamp(1, 9);
+    if path.ends_with(".gz") {
+        Ok(OutputWriter::Gzip(flate2::write::GzEncoder::new(file, flate2::Compression::new(level as u32))))
+    } else if path.ends_with(".zst") {
+        Ok(OutputWriter::Zstd(zstd::stream::write::Encoder::new(file, level)?))
+    } else {
+        Ok(OutputWriter::Plain(file))
+    }
+}
@@ -0,0 +1,245 @@
+//! Path ranges of the blocks, and their overlaps, gaps, and coverage
+
+use rustc_hash::{FxHashMap, FxHashSet};
+use handlegraph::handle::Handle;
+use std::sync::Arc;
+use crate::names::key_contig;
+
+/// Total length of the gaps between the ranges, within their span
+pub(crate) fn gap_length(ranges: &[RangeInfo]) -> usize {
+    let (start, end) = range_span(ranges);
+    interval_coverage(ranges, start, end).gap_length()
+}
+
+/// Start of the first range and end of the last one, or (0, 0) without ranges
+pub(crate) fn range_span(ranges: &[RangeInfo]) -> (usize, usize) {
+    let start = ranges.iter().map(|range| range.start).min().unwrap_or(0);
+    (start, ranges.iter().map(|range| range.end).max().unwrap_or(start))
+}
+
+/// Gaps between consecutive ranges longer than `max_gap`, as (path key, start, length), by path key
+pub(crate) fn large_gaps(path_key_ranges: &FxHashMap<PathKey, Vec<RangeInfo>>, max_gap: usize) -> Vec<(String, usize, usize)> {
+    let mut path_keys: Vec<&PathKey> = path_key_ranges.keys().collect();
+    path_keys.sort();
+    path_keys.into_iter()
+        .flat_map(|path_key| path_key_ranges[path_key].windows(2)
+            .filter(|pair| pair[1].start.saturating_sub(pair[0].end) > max_gap)
+            .map(move |pair| (path_key.to_string(), pair[0].end, pair[1].start - pair[0].end)))
+        .collect()
+}
+
+/// Length of the contig of a path key, looked up by the whole key, then by its contig
+pub(crate) fn contig_length(contig_lengths: &FxHashMap<String, usize>, path_key: &str) -> Option<usize> {
+    contig_lengths.get(path_key)
+        .or_else(|| contig_lengths.get(key_contig(path_key)))
+        .copied()
+}
+
+/// Gaps at the ends of the path keys of known contig length, as (path key, start, end) by path key:
+/// before their first range and after their furthest end. Also returns the contigs that no path
+/// key matches, sorted
+pub(crate) fn end_gaps(path_key_ranges: &FxHashMap<PathKey, Vec<RangeInfo>>, contig_lengths: &FxHashMap<String, usize>) -> (Vec<(String, usize, usize)>, Vec<String>) {
+    let mut path_keys: Vec<&PathKey> = path_key_ranges.keys().collect();
+    path_keys.sort();
+    let mut gaps = Vec::new();
+    let mut seen_contigs = FxHashSet::default();
+    for path_key in path_keys {
+        let ranges = &path_key_ranges[path_key];
+        let Some(length) = contig_length(contig_lengths, path_key) else { continue };
+        seen_contigs.insert(&**path_key);
+        seen_contigs.insert(key_contig(path_key));
+        if let Some(first) = ranges.first()
+            && first.start > 0 {
+            gaps.push((path_key.to_string(), 0, first.start.min(length)));
+        }
+        let end = ranges.iter().map(|range| range.end).max().unwrap_or(0);
+        if end < length {
+            gaps.push((path_key.to_string(), end, length));
+        }
+    }
+    let mut missing: Vec<String> = contig_lengths.keys().filter(|contig| !seen_contigs.contains(contig.as_str())).cloned().collect();
+    missing.sort();
+    (gaps, missing)
+}
+
+/// Path key, interned so that each unique key is stored once and is cheap to clone and to move
+/// across threads
+pub(crate) type PathKey = Arc<str>;
+
+#[derive(Debug, Clone)]
+pub struct RangeInfo {
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+    pub(crate) gfa_id: usize,
+    pub(crate) path_name: String,      // Name of the source path
+    pub(crate) steps: Vec<Handle>,     // Path steps for this range
+    pub(crate) step_ends: Vec<usize>,  // End positions of each step (start is either the range start (for index 0) or the previous step's end position)
+    pub(crate) tags: Vec<String>,      // Optional fields of the source P line
+    pub(crate) rank: Option<usize>,    // Rank of the input in the --range-order of the path key, if it has one
+}
+impl RangeInfo {
+    /// Range `[start, end)` of a path key from input `gfa_id`, without steps, e.g. to query
+    /// `interval_coverage`
+    pub fn new(start: usize, end: usize, gfa_id: usize) -> Self {
+        RangeInfo { start, end, gfa_id, path_name: String::new(), steps: Vec::new(), step_ends: Vec::new(), tags: Vec::new(), rank: None }
+    }
+
+    /// Range of the path `path_name` of input `gfa_id`, starting at `start`, with its steps and
+    /// their lengths in bp, past the bases shared with the previous step
+    pub fn from_steps(path_name: &str, start: usize, gfa_id: usize, steps: Vec<(Handle, usize)>) -> Self {
+        let mut end = start;
+        let step_ends = steps.iter().map(|&(_, length)| { end += length; end }).collect();
+        let steps = steps.into_iter().map(|(step, _)| step).collect();
+        RangeInfo { start, end, gfa_id, path_name: path_name.to_string(), steps, step_ends, tags: Vec::new(), rank: None }
+    }
+
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// Index of the input of the range
+    pub fn gfa_id(&self) -> usize {
+        self.gfa_id
+    }
+
+    /// Returns true if this range is immediately followed by another range
+    /// with no gap between them. The ranges of a key with an explicit order follow each other
+    /// whatever their coordinates
+    pub(crate) fn is_contiguous_with(&self, other: &Self) -> bool {
+        self.end == other.start || (self.rank.is_some() && other.rank.is_some())
+    }
+
+    /// Returns true if this range overlaps with another range
+    /// Two ranges overlap if one starts before the other ends
+    pub(crate) fn overlaps_with(&self, other: &Self) -> bool {
+        self.start < other.end && other.start < self.end && !(self.rank.is_some() && other.rank.is_some())
+    }
+}
+
+/// Coordinates and source of a range involved in an overlap
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RangeBounds {
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+    pub(crate) gfa_id: usize,
+}
+
+/// Two ranges of the same path key that overlap by `size` bp, with `first` starting no later than `second`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RangeOverlap {
+    pub(crate) first: RangeBounds,
+    pub(crate) second: RangeBounds,
+    pub(crate) size: usize,
+    /// Indices of the first and second ranges in the slice given to `find_overlaps`
+    pub(crate) indices: (usize, usize),
+}
+impl RangeOverlap {
+    /// Returns true if one of the ranges lies entirely within the other
+    pub(crate) fn is_containment(&self) -> bool {
+        self.size == self.first.end - self.first.start || self.size == self.second.end - self.second.start
+    }
+}
+
+/// Returns every pair of overlapping ranges, including non-adjacent ones (e.g. a long range
+/// spanning several short ones), with the overlap extent.
+/// Sweeps the ranges in start order, so it runs in O(n log n + k) for k overlapping pairs.
+pub(crate) fn find_overlaps(ranges: &[RangeInfo]) -> Vec<RangeOverlap> {
+    let mut order: Vec<usize> = (0..ranges.len()).collect();
+    order.sort_by_key(|&idx| (ranges[idx].start, ranges[idx].end));
+
+    let mut overlaps = Vec::new();
+    for (pos, &idx) in order.iter().enumerate() {
+        let r1 = &ranges[idx];
+        // Later ranges start no earlier than r1, so only those starting before its end can overlap it
+        for &other_idx in order[pos + 1..].iter().take_while(|&&other_idx| ranges[other_idx].start < r1.end) {
+            let r2 = &ranges[other_idx];
+            if r1.overlaps_with(r2) {
+                overlaps.push(RangeOverlap {
+                    first: RangeBounds { start: r1.start, end: r1.end, gfa_id: r1.gfa_id },
+                    second: RangeBounds { start: r2.start, end: r2.end, gfa_id: r2.gfa_id },
+                    size: std::cmp::min(r1.end, r2.end) - std::cmp::max(r1.start, r2.start),
+                    indices: (idx, other_idx),
+                });
+            }
+        }
+    }
+    overlaps
+}
+
+/// Overlaps between the ranges of each path key other than the tiling overlaps, of exactly
+/// `expected_overlap` bp between ranges of different blocks, by path key. Containments and
+/// overlaps within a block are never expected
+pub(crate) fn unexpected_overlaps(path_key_ranges: &FxHashMap<PathKey, Vec<RangeInfo>>, expected_overlap: usize) -> Vec<(String, RangeOverlap)> {
+    let mut path_keys: Vec<&PathKey> = path_key_ranges.keys().collect();
+    path_keys.sort();
+    path_keys.into_iter()
+        .flat_map(|path_key| find_overlaps(&path_key_ranges[path_key]).into_iter()
+            .filter(|overlap| overlap.size != expected_overlap || overlap.first.gfa_id == overlap.second.gfa_id || overlap.is_containment())
+            .map(move |overlap| (path_key.to_string(), overlap)))
+        .collect()
+}
+
+/// Coverage of a query interval by the ranges of a path key, as returned by `interval_coverage`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coverage {
+    /// Bases of the interval covered by at least one range
+    pub covered: usize,
+    /// Uncovered parts of the interval, as `(start, end)` path coordinates
+    pub gaps: Vec<(usize, usize)>,
+    /// Inputs of the ranges overlapping the interval, sorted
+    pub gfa_ids: Vec<usize>,
+}
+
+impl Coverage {
+    /// Returns true if the whole interval is covered
+    pub fn is_full(&self) -> bool {
+        self.gaps.is_empty()
+    }
+
+    /// Total length of the gaps, in bp
+    pub fn gap_length(&self) -> usize {
+        self.gaps.iter().map(|(start, end)| end - start).sum()
+    }
+}
+
+/// Reports how the ranges of a path key cover the interval `[start, end)`, merging the ranges as the
+/// command line does: overlapping ranges are counted once, and ranges that only touch the interval
+/// at its edges do not contribute. The ranges need not be sorted
+///
+/// ```
+/// use gfalace::{RangeInfo, interval_coverage};
+///
+/// let ranges = [RangeInfo::new(0, 100, 0), RangeInfo::new(50, 150, 1), RangeInfo::new(200, 300, 2)];
+/// let coverage = interval_coverage(&ranges, 0, 250);
+/// assert_eq!(coverage.covered, 200);
+/// assert_eq!(coverage.gaps, [(150, 200)]);
+/// assert_eq!(coverage.gfa_ids, [0, 1, 2]);
+/// ```
+pub fn interval_coverage(ranges: &[RangeInfo], start: usize, end: usize) -> Coverage {
+    let mut clipped: Vec<(usize, usize, usize)> = ranges.iter()
+        .map(|range| (range.start.max(start), range.end.min(end), range.gfa_id))
+        .filter(|&(clipped_start, clipped_end, _)| clipped_start < clipped_end)
+        .collect();
+    clipped.sort_unstable();
+
+    let mut coverage = Coverage { covered: 0, gaps: Vec::new(), gfa_ids: Vec::new() };
+    let mut position = start;
+    for &(clipped_start, clipped_end, gfa_id) in &clipped {
+        if clipped_start > position {
+            coverage.gaps.push((position, clipped_start));
+        }
+        coverage.covered += clipped_end.saturating_sub(clipped_start.max(position));
+        position = position.max(clipped_end);
+        coverage.gfa_ids.push(gfa_id);
+    }
+    if position < end {
+        coverage.gaps.push((position, end));
+    }
+    coverage.gfa_ids.sort_unstable();
+    coverage.gfa_ids.dedup();
+    coverage
+}
@@ -0,0 +1,258 @@
+//! Reports on the path keys and nodes of the combined graph
+
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+};
+use rustc_hash::FxHashMap;
+use handlegraph::hashgraph::HashGraph;
+use log::info;
+use rust_htslib::faidx;
+use crate::links::collect_output_links;
+use crate::merge::{KeyCoverage, mark_nodes_for_removal};
+use crate::names::{cactus_key, natural_key};
+use crate::output::AtomicFile;
+use crate::ranges::{PathKey, RangeInfo, contig_length, interval_coverage};
+use crate::sequences::NodeSequences;
+use crate::write::{WriteOptions, compact_id_mapping, is_full_path, path_groups};
+
+/// Bins of the number of ranges of a path key, by their largest value
+pub(crate) const RANGE_COUNT_BINS: [(usize, &str); 4] = [(1, "1"), (5, "2-5"), (20, "6-20"), (usize::MAX, "21+")];
+
+/// Fragmentation of a path key in the input
+#[derive(Debug, PartialEq)]
+pub(crate) struct KeyFragmentation {
+    pub(crate) path_key: String,
+    pub(crate) ranges: usize,
+    pub(crate) gaps: usize,
+    pub(crate) overlaps: usize,
+    /// Input files of the ranges, in gfa_id order
+    pub(crate) gfa_ids: Vec<usize>,
+}
+
+/// How fragmented the input path keys are, before their ranges are merged
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct RangeReport {
+    /// Number of path keys in each bin of RANGE_COUNT_BINS
+    pub(crate) keys_by_ranges: [usize; 4],
+    /// Number of ranges by length, binned by powers of ten (0 for ranges shorter than 10 bp)
+    pub(crate) ranges_by_length: BTreeMap<usize, usize>,
+    /// Number of path keys by number of gaps
+    pub(crate) keys_by_gaps: BTreeMap<usize, usize>,
+    /// Fragmentation of each path key, by name
+    pub(crate) keys: Vec<KeyFragmentation>,
+}
+
+impl RangeReport {
+    /// Computes the report from the unmerged ranges. Ranges are taken in coordinate order, and each one
+    /// starting past the furthest end so far opens a gap, while each one starting before it is an overlap
+    pub(crate) fn new(path_key_ranges: &FxHashMap<PathKey, Vec<RangeInfo>>) -> Self {
+        let mut report = RangeReport::default();
+        for (path_key, ranges) in path_key_ranges.iter().filter(|(_, ranges)| !ranges.is_empty()) {
+            let mut bounds: Vec<(usize, usize)> = ranges.iter().map(|range| (range.start, range.end)).collect();
+            bounds.sort_unstable();
+            let (mut gaps, mut overlaps) = (0, 0);
+            let mut max_end = bounds[0].1;
+            for &(start, end) in &bounds[1..] {
+                if start > max_end {
+                    gaps += 1;
+                } else if start < max_end {
+                    overlaps += 1;
+                }
+                max_end = max_end.max(end);
+            }
+            for (start, end) in bounds {
+                let length = end - start;
+                let bin = if length < 10 { 0 } else { 10usize.pow(length.ilog10()) };
+                *report.ranges_by_length.entry(bin).or_insert(0) += 1;
+            }
+            report.keys_by_ranges[RANGE_COUNT_BINS.iter().position(|&(max, _)| ranges.len() <= max).unwrap()] += 1;
+            *report.keys_by_gaps.entry(gaps).or_insert(0) += 1;
+
+            let mut gfa_ids: Vec<usize> = ranges.iter().map(|range| range.gfa_id).collect();
+            gfa_ids.sort_unstable();
+            gfa_ids.dedup();
+            report.keys.push(KeyFragmentation { path_key: path_key.to_string(), ranges: ranges.len(), gaps, overlaps, gfa_ids });
+        }
+        report.keys.sort_by(|a, b| a.path_key.cmp(&b.path_key));
+        report
+    }
+
+    /// The path keys with the most gaps, or overlaps, at most 10 and ties broken by name
+    pub(crate) fn worst_keys(&self, count: impl Fn(&KeyFragmentation) -> usize) -> Vec<&KeyFragmentation> {
+        let mut keys: Vec<_> = self.keys.iter().filter(|key| count(key) > 0).collect();
+        keys.sort_by_key(|key| std::cmp::Reverse(count(key)));
+        keys.truncate(10);
+        keys
+    }
+
+    /// Logs the report at the info level, one record per histogram bin and per worst key
+    pub(crate) fn log(&self, gfa_names: &[String]) {
+        for ((_, bin), keys) in RANGE_COUNT_BINS.iter().zip(self.keys_by_ranges) {
+            info!(stage = "parse", ranges = *bin, keys = keys; "Path keys with {} ranges: {}", bin, keys);
+        }
+        for (&bin, &ranges) in &self.ranges_by_length {
+            let length = if bin == 0 { "<10".to_string() } else { format!(">={}", bin) };
+            info!(stage = "parse", range_length = length.as_str(), ranges = ranges; "Ranges of length {} bp: {}", length, ranges);
+        }
+        for (&gaps, &keys) in &self.keys_by_gaps {
+            info!(stage = "parse", gaps = gaps, keys = keys; "Path keys with {} gaps: {}", gaps, keys);
+        }
+        for (label, worst) in [("gaps", self.worst_keys(|key| key.gaps)), ("overlaps", self.worst_keys(|key| key.overlaps))] {
+            for key in worst {
+                let files: Vec<&str> = key.gfa_ids.iter().map(|&gfa_id| gfa_names.get(gfa_id).map_or("?", String::as_str)).collect();
+                let count = if label == "gaps" { key.gaps } else { key.overlaps };
+                info!(stage = "parse", path_key = key.path_key.as_str(), count = count, files = files.join(",").as_str();
+                    "Path key '{}' has {} {}, from {}", key.path_key, count, label, files.join(","));
+            }
+        }
+    }
+}
+
+/// Distributions of the in-degrees and out-degrees of the nodes of the output graph, i.e. of the
+/// number of links entering the start of each node and leaving its end, in its forward
+/// orientation. Gap nodes created while writing are not included
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct DegreeHistogram {
+    /// Number of nodes by in-degree
+    pub(crate) in_counts: BTreeMap<usize, usize>,
+    /// Number of nodes by out-degree
+    pub(crate) out_counts: BTreeMap<usize, usize>,
+    /// Output ID and in-degree of the first node of highest in-degree
+    pub(crate) max_in_degree_node: Option<(usize, usize)>,
+    /// Output ID and out-degree of the first node of highest out-degree
+    pub(crate) max_out_degree_node: Option<(usize, usize)>,
+}
+
+pub(crate) fn degree_histogram(
+    graph: &HashGraph,
+    path_key_ranges: &FxHashMap<PathKey, Vec<RangeInfo>>,
+    options: &WriteOptions
+) -> DegreeHistogram {
+    let nodes_to_remove = mark_nodes_for_removal(graph, path_key_ranges);
+    let (id_mapping, num_nodes) = compact_id_mapping(graph, path_key_ranges, options);
+
+    // A link leaves the end of a forward step and enters the start of a forward step, and the
+    // other way around for reverse steps
+    let mut in_degrees = vec![0; num_nodes + 1];
+    let mut out_degrees = vec![0; num_nodes + 1];
+    for ((from, from_reverse), (to, to_reverse)) in collect_output_links(graph, &nodes_to_remove, &id_mapping) {
+        if from_reverse { in_degrees[from] += 1 } else { out_degrees[from] += 1 }
+        if to_reverse { out_degrees[to] += 1 } else { in_degrees[to] += 1 }
+    }
+
+    // Kept IDs leave holes, which are not nodes
+    let mut used = vec![false; num_nodes + 1];
+    for &new_id in id_mapping.iter().filter(|&&new_id| new_id > 0) {
+        used[new_id] = true;
+    }
+    let mut histogram = DegreeHistogram::default();
+    for (degrees, counts, max_degree_node) in [
+        (&in_degrees, &mut histogram.in_counts, &mut histogram.max_in_degree_node),
+        (&out_degrees, &mut histogram.out_counts, &mut histogram.max_out_degree_node),
+    ] {
+        for (node_id, &degree) in degrees.iter().enumerate().filter(|&(node_id, _)| used[node_id]) {
+            *counts.entry(degree).or_insert(0) += 1;
+            if max_degree_node.is_none_or(|(_, max_degree)| degree > max_degree) {
+                *max_degree_node = Some((node_id, degree));
+            }
+        }
+    }
+    histogram
+}
+
+/// Splits the path keys into their sample, haplotype, and contig (`*` for missing PanSN fields, and
+/// for the haplotype of minigraph-cactus keys), sorted by sample, contig, and haplotype with numbers
+/// ordered by value, skipping empty keys
+pub(crate) fn sample_table_rows(path_key_ranges: &FxHashMap<PathKey, Vec<RangeInfo>>) -> Vec<(&str, &str, &str, &str, &[RangeInfo])> {
+    let mut rows: Vec<(&str, &str, &str, &str, &[RangeInfo])> = path_key_ranges.iter()
+        .filter(|(_, ranges)| !ranges.is_empty())
+        .map(|(path_key, ranges)| {
+            let fields: Vec<&str> = path_key.splitn(3, '#').collect();
+            let (sample, haplotype, contig) = match (cactus_key(path_key), fields.as_slice()) {
+                (Some((sample, contig)), _) => (sample, "*", contig),
+                (None, [sample, haplotype, contig]) => (*sample, *haplotype, *contig),
+                (None, [sample, contig]) => (*sample, "*", *contig),
+                _ => (&**path_key, "*", "*"),
+            };
+            (&**path_key, sample, haplotype, contig, ranges.as_slice())
+        })
+        .collect();
+    rows.sort_by_cached_key(|&(_, sample, haplotype, contig, _)| (natural_key(sample), natural_key(contig), natural_key(haplotype)));
+    rows
+}
+
+/// Writes a TSV with one row per path key: its sample, haplotype, and contig (`*` when the key has
+/// fewer PanSN fields), number of ranges, length of the merged path in bp (the sum of its node
+/// lengths, gaps excluded), span from the first start to the last end, fraction of the span covered,
+/// number and length of the gaps, and whether the path is written `whole` or as `fragments`, as
+/// the writer decides it (a key starting after 0 or ending before its FASTA sequence is a fragment).
+/// Rows are sorted by sample, contig, and haplotype, with numbers ordered by value
+pub(crate) fn write_sample_table(
+    sample_table_path: &str,
+    graph: &HashGraph,
+    sequences: &NodeSequences,
+    path_key_ranges: &FxHashMap<PathKey, Vec<RangeInfo>>,
+    fasta_reader: &Option<faidx::Reader>,
+    fill_gaps: u8
+) -> io::Result<()> {
+    let rows = sample_table_rows(path_key_ranges);
+    let mut out = AtomicFile::create(sample_table_path)?;
+    writeln!(out, "sample\thaplotype\tcontig\tranges\tlength\tspan\tcovered\tgaps\tgap_bp\temitted")?;
+    for (path_key, sample, haplotype, contig, ranges) in rows {
+        let coverage = KeyCoverage::new(graph, sequences, ranges);
+        let sequence_length = fasta_reader.as_ref().map(|reader| reader.fetch_seq_len(path_key) as usize);
+        let groups = path_groups(ranges, fill_gaps > 0);
+        let emitted = if groups.first().is_some_and(|group| is_full_path(ranges, group, sequence_length)) { "whole" } else { "fragments" };
+        writeln!(out, "{}\t{}\t{}\t{}\t{}\t{}\t{:.4}\t{}\t{}\t{}",
+            sample, haplotype, contig, ranges.len(), coverage.length, coverage.span, coverage.fraction(false),
+            coverage.gaps, coverage.gap_bp, emitted)?;
+    }
+    out.finish()
+}
+
+/// Writes a TSV with one row per path key: `sample`, `haplotype`, and `contig` as in the sample table,
+/// the `length` the coverage is measured against, from the contig lengths (`denominator` `contig`)
+/// or else the span of the ranges from 0 (`span`, which cannot tell a missing end), the bases
+/// `covered` by the ranges and their `fraction`, the number of `fragments` the path is written as,
+/// and whether it is `complete`: a single contiguous path from 0 to the length. Returns the counts
+/// of each sample and haplotype
+pub(crate) fn write_completeness(
+    completeness_path: &str,
+    path_key_ranges: &FxHashMap<PathKey, Vec<RangeInfo>>,
+    contig_lengths: Option<&FxHashMap<String, usize>>,
+    fill_gaps: u8
+) -> io::Result<BTreeMap<(String, String), SampleCompleteness>> {
+    let mut samples: BTreeMap<(String, String), SampleCompleteness> = BTreeMap::new();
+    let mut out = AtomicFile::create(completeness_path)?;
+    writeln!(out, "sample\thaplotype\tcontig\tlength\tdenominator\tcovered\tfraction\tfragments\tcomplete")?;
+    for (path_key, sample, haplotype, contig, ranges) in sample_table_rows(path_key_ranges) {
+        let span_end = ranges.iter().map(|range| range.end).max().unwrap_or(0);
+        let (length, denominator) = match contig_lengths.and_then(|lengths| contig_length(lengths, path_key)) {
+            Some(length) => (length, "contig"),
+            None => (span_end, "span"),
+        };
+        let coverage = interval_coverage(ranges, 0, length);
+        let breaks = ranges.windows(2).filter(|pair| !pair[0].is_contiguous_with(&pair[1])).count();
+        let fragments = if fill_gaps > 0 { 1 } else { breaks + 1 };
+        let complete = coverage.is_full() && breaks == 0;
+        writeln!(out, "{}\t{}\t{}\t{}\t{}\t{}\t{:.4}\t{}\t{}", sample, haplotype, contig, length, denominator, coverage.covered,
+            if length > 0 { coverage.covered as f64 / length as f64 } else { 0.0 }, fragments, if complete { "yes" } else { "no" })?;
+
+        let counts = samples.entry((sample.to_string(), haplotype.to_string())).or_default();
+        counts.complete += usize::from(complete);
+        counts.fragmented += usize::from(fragments > 1);
+        counts.contigs += 1;
+    }
+    out.finish()?;
+    Ok(samples)
+}
+
+/// Numbers of contigs of a sample haplotype in the completeness report
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct SampleCompleteness {
+    pub(crate) complete: usize,
+    /// Contigs written as several paths
+    pub(crate) fragmented: usize,
+    pub(crate) contigs: usize,
+}
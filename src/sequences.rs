@@ -0,0 +1,168 @@
+//! Sequences of the combined nodes, held in the graph or spilled to a temporary file
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+use rustc_hash::FxHashMap;
+use handlegraph::{
+    handle::{Handle, NodeId},
+    handlegraph::*,
+    mutablehandlegraph::*,
+    hashgraph::HashGraph,
+};
+use tempfile::NamedTempFile;
+use memmap2::Mmap;
+use crate::output::OUTPUT_BUFFER_CAPACITY;
+
+/// Where the sequences of the combined nodes are kept: in the graph, or spilled to a temporary file
+/// by --low-memory, in which case the graph nodes are empty and only the topology is held in memory.
+/// Node lengths and sequences are looked up here rather than in the graph
+#[derive(Default)]
+pub(crate) enum NodeSequences {
+    #[default]
+    InGraph,
+    Spilled(SpilledSequences),
+}
+
+/// Node sequences appended to a temporary file, which is memory-mapped once the inputs are read
+pub(crate) struct SpilledSequences {
+    pub(crate) file: NamedTempFile,
+    pub(crate) writer: Option<BufWriter<File>>,
+    pub(crate) mmap: Option<Mmap>,
+    /// Bytes written to the file
+    pub(crate) written: u64,
+    /// Where the sequence of each node is in the file
+    pub(crate) entries: FxHashMap<NodeId, SpilledSequence>,
+}
+
+/// A node sequence, as a slice of the spilled file that is reverse complemented if `is_reverse`.
+/// The pieces of split nodes refer to a slice of the sequence of their origin
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SpilledSequence {
+    pub(crate) offset: u64,
+    pub(crate) length: usize,
+    pub(crate) is_reverse: bool,
+}
+
+impl NodeSequences {
+    pub(crate) fn spilled(tmp_dir: &Path) -> io::Result<Self> {
+        let file = NamedTempFile::new_in(tmp_dir)?;
+        let writer = BufWriter::with_capacity(OUTPUT_BUFFER_CAPACITY, file.reopen()?);
+        Ok(NodeSequences::Spilled(SpilledSequences { file, writer: Some(writer), mmap: None, written: 0, entries: FxHashMap::default() }))
+    }
+
+    /// Adds a node with the given sequence to the graph
+    pub(crate) fn create_node(&mut self, graph: &mut HashGraph, sequence: &[u8], node_id: NodeId) -> io::Result<Handle> {
+        match self {
+            NodeSequences::InGraph => Ok(graph.create_handle(sequence, node_id)),
+            NodeSequences::Spilled(spilled) => {
+                let writer = spilled.writer.as_mut().ok_or_else(|| io::Error::other("Node added after the spilled sequences were finished"))?;
+                writer.write_all(sequence)?;
+                spilled.entries.insert(node_id, SpilledSequence { offset: spilled.written, length: sequence.len(), is_reverse: false });
+                spilled.written += sequence.len() as u64;
+                Ok(graph.create_handle(b"", node_id))
+            }
+        }
+    }
+
+    /// Makes the spilled sequences readable, once all the input nodes are added
+    pub(crate) fn finish(&mut self) -> io::Result<()> {
+        if let NodeSequences::Spilled(spilled) = self
+            && let Some(mut writer) = spilled.writer.take() {
+            writer.flush()?;
+            if spilled.written > 0 {
+                // SAFETY: the temporary file is only written through `writer`, which is done
+                spilled.mmap = Some(unsafe { Mmap::map(spilled.file.as_file())? });
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn node_len(&self, graph: &HashGraph, handle: Handle) -> usize {
+        match self {
+            NodeSequences::InGraph => graph.node_len(handle),
+            NodeSequences::Spilled(spilled) => spilled.entries[&handle.id()].length,
+        }
+    }
+
+    /// Appends the sequence of a handle, in its orientation, to `out`
+    pub(crate) fn extend_with_sequence(&self, graph: &HashGraph, handle: Handle, out: &mut Vec<u8>) {
+        match self {
+            // Complemented here rather than by the graph, which only knows the ACGT bases
+            NodeSequences::InGraph if handle.is_reverse() => out.extend(reverse_complement(&graph.sequence_vec(handle.forward()))),
+            NodeSequences::InGraph => out.extend(graph.sequence(handle)),
+            NodeSequences::Spilled(spilled) => {
+                let entry = spilled.entries[&handle.id()];
+                if entry.length == 0 {
+                    return;
+                }
+                let mmap = spilled.mmap.as_ref().expect("spilled sequences read before being finished");
+                let bytes = &mmap[entry.offset as usize..entry.offset as usize + entry.length];
+                if entry.is_reverse != handle.is_reverse() {
+                    out.extend(reverse_complement(bytes));
+                } else {
+                    out.extend_from_slice(bytes);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn sequence_vec(&self, graph: &HashGraph, handle: Handle) -> Vec<u8> {
+        let mut sequence = Vec::new();
+        self.extend_with_sequence(graph, handle, &mut sequence);
+        sequence
+    }
+
+    /// Adds the nodes spelling the consecutive pieces of a handle, in its orientation, between
+    /// the offsets in `bounds`, with IDs from `first_id` on. Returns their forward handles
+    pub(crate) fn create_pieces(&mut self, graph: &mut HashGraph, handle: Handle, bounds: &[usize], first_id: u64) -> Vec<Handle> {
+        match self {
+            NodeSequences::InGraph => {
+                let sequence = NodeSequences::InGraph.sequence_vec(graph, handle);
+                bounds.windows(2).enumerate()
+                    .map(|(idx, pair)| graph.create_handle(&sequence[pair[0]..pair[1]], first_id + idx as u64))
+                    .collect()
+            }
+            NodeSequences::Spilled(spilled) => {
+                let entry = spilled.entries[&handle.id()];
+                let is_reverse = entry.is_reverse != handle.is_reverse();
+                bounds.windows(2).enumerate().map(|(idx, pair)| {
+                    // A piece of the reverse complement is the reverse complement of the mirrored slice
+                    let start = if is_reverse { entry.length - pair[1] } else { pair[0] };
+                    let node_id = NodeId::from(first_id + idx as u64);
+                    spilled.entries.insert(node_id, SpilledSequence { offset: entry.offset + start as u64, length: pair[1] - pair[0], is_reverse });
+                    graph.create_handle(b"", node_id)
+                }).collect()
+            }
+        }
+    }
+}
+
+/// Complement of a base, an IUPAC code in either case, other characters being kept as they are
+pub(crate) fn complement_base(base: u8) -> u8 {
+    let complement = match base.to_ascii_uppercase() {
+        b'A' => b'T',
+        b'C' => b'G',
+        b'G' => b'C',
+        b'T' | b'U' => b'A',
+        b'R' => b'Y',
+        b'Y' => b'R',
+        b'K' => b'M',
+        b'M' => b'K',
+        b'B' => b'V',
+        b'V' => b'B',
+        b'D' => b'H',
+        b'H' => b'D',
+        // S, W, and N are their own complement
+        _ => return base,
+    };
+    if base.is_ascii_lowercase() { complement.to_ascii_lowercase() } else { complement }
+}
+
+/// Reverse complement of a sequence, used for the reverse handles of both spilled and in-memory
+/// sequences so that they read the same
+pub(crate) fn reverse_complement(sequence: &[u8]) -> Vec<u8> {
+    sequence.iter().rev().map(|&base| complement_base(base)).collect()
+}
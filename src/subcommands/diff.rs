@@ -0,0 +1,238 @@
+//! The `diff` subcommand, which compares two laced GFAs
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io,
+};
+use std::process::ExitCode;
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::Serialize;
+use gfa::{optfields::{OptFields, OptFieldVal}, parser::GFAParser};
+use sha2::{Digest, Sha256};
+use crate::RunError;
+use crate::cli::DiffArgs;
+use crate::input::ReadOptions;
+use crate::links::canonical_link;
+use crate::names::{RangeSyntax, key_contig, split_path_name};
+use crate::open::read_gfa;
+use crate::output::AtomicFile;
+use crate::sequences::reverse_complement;
+use crate::write::PathStep;
+
+/// SHA-256 of a sequence
+pub(crate) type SequenceHash = [u8; 32];
+
+/// SHA-256 of the canonical sequence of a node, and whether the node is its reverse complement
+pub(crate) type OrientedHash = (SequenceHash, bool);
+
+/// Content of a GFA file that does not depend on its node numbering
+#[derive(Default)]
+pub(crate) struct GraphContent {
+    pub(crate) nodes: usize,
+    pub(crate) edges: usize,
+    /// Number of nodes by SHA-256 of their canonical sequence, the smaller of it and its reverse
+    /// complement, so that nodes only match when their sequences do
+    pub(crate) node_sequences: FxHashMap<SequenceHash, usize>,
+    /// Links by canonical link between the SHA-256 of the canonical sequences of their nodes, the
+    /// orientations being relative to these sequences
+    pub(crate) sequence_links: FxHashMap<(OrientedHash, OrientedHash), usize>,
+    /// Sequence and step ends, in bp, of each path by name. Sequenceless segments are spelled as Ns
+    pub(crate) paths: BTreeMap<String, (Vec<u8>, Vec<usize>)>,
+}
+
+impl GraphContent {
+    pub(crate) fn read(gfa_path: &str, options: &ReadOptions) -> io::Result<Self> {
+        let gfa = read_gfa(gfa_path, &GFAParser::new(), options)?;
+        let mut sequences: FxHashMap<usize, Vec<u8>> = FxHashMap::default();
+        for segment in &gfa.segments {
+            let sequence = if segment.sequence.as_slice() == b"*" {
+                match segment.optional.get_field(b"LN").map(|field| &field.value) {
+                    Some(OptFieldVal::Int(length)) if *length >= 0 => vec![b'N'; *length as usize],
+                    _ => Vec::new(),
+                }
+            } else {
+                segment.sequence.to_ascii_uppercase()
+            };
+            sequences.insert(segment.name, sequence);
+        }
+        let edges: FxHashSet<(PathStep, PathStep)> = gfa.links.iter()
+            .map(|link| canonical_link((link.from_segment, link.from_orient.is_reverse()), (link.to_segment, link.to_orient.is_reverse())))
+            .collect();
+
+        let mut content = GraphContent { nodes: sequences.len(), edges: edges.len(), ..GraphContent::default() };
+
+        // Identify the nodes by sequence, whatever their ID and orientation, so that a renumbering is no difference
+        let mut canonical_nodes: FxHashMap<usize, OrientedHash> = FxHashMap::default();
+        for (&segment_id, sequence) in &sequences {
+            let reverse = reverse_complement(sequence);
+            let is_flipped = reverse < *sequence;
+            let mut hasher = Sha256::new();
+            hasher.update(if is_flipped { &reverse } else { sequence });
+            let mut hash = [0; 32];
+            hash.copy_from_slice(&hasher.finalize());
+            *content.node_sequences.entry(hash).or_insert(0) += 1;
+            canonical_nodes.insert(segment_id, (hash, is_flipped));
+        }
+        for &((from, from_reverse), (to, to_reverse)) in &edges {
+            let (Some(&(from_hash, from_flipped)), Some(&(to_hash, to_flipped))) = (canonical_nodes.get(&from), canonical_nodes.get(&to)) else {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Link {} {} joins an undefined segment", from, to)));
+            };
+            let link = canonical_link((from_hash, from_reverse != from_flipped), (to_hash, to_reverse != to_flipped));
+            *content.sequence_links.entry(link).or_insert(0) += 1;
+        }
+
+        for path in &gfa.paths {
+            let path_name = String::from_utf8_lossy(&path.path_name).into_owned();
+            let mut sequence = Vec::new();
+            let mut step_ends = Vec::new();
+            for (segment_id, orientation) in path.iter() {
+                let segment = sequences.get(&segment_id).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidData, format!("Path '{}' visits the undefined segment {}", path_name, segment_id))
+                })?;
+                if orientation.is_reverse() {
+                    sequence.extend(reverse_complement(segment));
+                } else {
+                    sequence.extend_from_slice(segment);
+                }
+                step_ends.push(sequence.len());
+            }
+            content.paths.insert(path_name, (sequence, step_ends));
+        }
+        Ok(content)
+    }
+
+    /// Old and new counts of the keys of two multisets that are not matched in the other one
+    pub(crate) fn unmatched<K: Eq + std::hash::Hash>(old: &FxHashMap<K, usize>, new: &FxHashMap<K, usize>) -> (usize, usize) {
+        let only_old = old.iter().map(|(key, &count)| count.saturating_sub(new.get(key).copied().unwrap_or(0))).sum();
+        let only_new = new.iter().map(|(key, &count)| count.saturating_sub(old.get(key).copied().unwrap_or(0))).sum();
+        (only_old, only_new)
+    }
+
+    /// Length of the paths by the contig of their key, their range being in the given syntax
+    pub(crate) fn coverage(&self, range_syntax: RangeSyntax) -> BTreeMap<String, usize> {
+        let mut coverage = BTreeMap::new();
+        for (path_name, (sequence, _)) in &self.paths {
+            let path_key = split_path_name(path_name, range_syntax).map_or(path_name.clone(), |(path_key, _, _)| path_key);
+            let contig = key_contig(&path_key).to_string();
+            *coverage.entry(contig).or_insert(0) += sequence.len();
+        }
+        coverage
+    }
+}
+
+/// Differences between two laced graphs, compared by content since their node IDs are arbitrary
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub(crate) struct GraphDiff {
+    pub(crate) only_old: Vec<String>,
+    pub(crate) only_new: Vec<String>,
+    /// Paths whose sequences differ, with the first differing offset
+    pub(crate) sequences: Vec<(String, usize)>,
+    /// Paths with the same sequence but different node boundaries, with the first differing boundary
+    pub(crate) boundaries: Vec<(String, usize)>,
+    /// Old and new counts
+    pub(crate) nodes: (usize, usize),
+    pub(crate) edges: (usize, usize),
+    /// Old and new nodes that have no node of the same sequence (or reverse complement) in the other graph
+    pub(crate) unmatched_nodes: (usize, usize),
+    /// Old and new links that have no link between nodes of the same sequences in the other graph
+    pub(crate) unmatched_edges: (usize, usize),
+    /// Contigs whose paths differ in total length, with the old and new lengths
+    pub(crate) coverage: Vec<(String, usize, usize)>,
+}
+
+impl GraphDiff {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.only_old.is_empty() && self.only_new.is_empty() && self.sequences.is_empty() && self.boundaries.is_empty()
+            && self.nodes.0 == self.nodes.1 && self.edges.0 == self.edges.1 && self.coverage.is_empty()
+            && self.unmatched_nodes == (0, 0) && self.unmatched_edges == (0, 0)
+    }
+
+    /// Prints a summary of the differences, with a line per differing path if `detail` is set
+    pub(crate) fn print(&self, detail: bool) {
+        if detail {
+            for path_name in &self.only_old {
+                println!("- path {}", path_name);
+            }
+            for path_name in &self.only_new {
+                println!("+ path {}", path_name);
+            }
+            for (path_name, offset) in &self.sequences {
+                println!("! sequence {} at {}", path_name, offset);
+            }
+            for (path_name, offset) in &self.boundaries {
+                println!("! boundaries {} at {}", path_name, offset);
+            }
+        }
+        if !(self.only_old.is_empty() && self.only_new.is_empty() && self.sequences.is_empty() && self.boundaries.is_empty()) {
+            println!("! paths {} removed, {} added, {} with a different sequence, {} with different boundaries",
+                self.only_old.len(), self.only_new.len(), self.sequences.len(), self.boundaries.len());
+        }
+        for (contig, old, new) in &self.coverage {
+            println!("! coverage {} {} -> {}", contig, old, new);
+        }
+        if self.nodes.0 != self.nodes.1 || self.unmatched_nodes != (0, 0) {
+            println!("! nodes {} -> {} ({:+}), {} removed and {} added by sequence", self.nodes.0, self.nodes.1,
+                self.nodes.1 as i64 - self.nodes.0 as i64, self.unmatched_nodes.0, self.unmatched_nodes.1);
+        }
+        if self.edges.0 != self.edges.1 || self.unmatched_edges != (0, 0) {
+            println!("! edges {} -> {} ({:+}), {} removed and {} added by sequence", self.edges.0, self.edges.1,
+                self.edges.1 as i64 - self.edges.0 as i64, self.unmatched_edges.0, self.unmatched_edges.1);
+        }
+        if self.is_empty() {
+            println!("No differences");
+        }
+    }
+}
+
+/// Compares two laced GFA files by content: path names, path sequences and step lengths, node and
+/// edge counts (with links compared modulo orientation), nodes and links matched by sequence, and
+/// total path length by contig
+pub(crate) fn diff_graphs(old_path: &str, new_path: &str, options: &ReadOptions) -> io::Result<GraphDiff> {
+    let old = GraphContent::read(old_path, options)?;
+    let new = GraphContent::read(new_path, options)?;
+
+    let mut diff = GraphDiff {
+        only_old: old.paths.keys().filter(|name| !new.paths.contains_key(*name)).cloned().collect(),
+        only_new: new.paths.keys().filter(|name| !old.paths.contains_key(*name)).cloned().collect(),
+        nodes: (old.nodes, new.nodes),
+        edges: (old.edges, new.edges),
+        unmatched_nodes: GraphContent::unmatched(&old.node_sequences, &new.node_sequences),
+        unmatched_edges: GraphContent::unmatched(&old.sequence_links, &new.sequence_links),
+        ..GraphDiff::default()
+    };
+    for (path_name, (old_sequence, old_ends)) in &old.paths {
+        let Some((new_sequence, new_ends)) = new.paths.get(path_name) else { continue };
+        if old_sequence != new_sequence {
+            let offset = old_sequence.iter().zip(new_sequence).position(|(a, b)| a != b)
+                .unwrap_or_else(|| old_sequence.len().min(new_sequence.len()));
+            diff.sequences.push((path_name.clone(), offset));
+        } else if old_ends != new_ends {
+            let offset = old_ends.iter().zip(new_ends).find(|(a, b)| a != b)
+                .map_or_else(|| old_sequence.len(), |(a, b)| *a.min(b));
+            diff.boundaries.push((path_name.clone(), offset));
+        }
+    }
+    let (old_coverage, new_coverage) = (old.coverage(options.range_syntax), new.coverage(options.range_syntax));
+    let contigs: BTreeSet<&String> = old_coverage.keys().chain(new_coverage.keys()).collect();
+    for contig in contigs {
+        let (old_length, new_length) = (old_coverage.get(contig).copied().unwrap_or(0), new_coverage.get(contig).copied().unwrap_or(0));
+        if old_length != new_length {
+            diff.coverage.push((contig.clone(), old_length, new_length));
+        }
+    }
+    Ok(diff)
+}
+
+/// Runs `gfalace diff`, which exits with 1 if the graphs differ, and with 2 on errors
+pub(crate) fn run(args: &DiffArgs, read_options: &ReadOptions) -> Result<ExitCode, RunError> {
+    let failed = |context: &str, e: io::Error| RunError::Failed { message: format!("{}: {}", context, e), exit_code: 2 };
+    let diff = diff_graphs(&args.old, &args.new, read_options).map_err(|e| failed("Error comparing the graphs", e))?;
+    diff.print(args.detail);
+    if let Some(json_path) = &args.json {
+        AtomicFile::create(json_path).and_then(|mut file| {
+            serde_json::to_writer_pretty(&mut file, &diff)?;
+            file.finish()
+        }).map_err(|e| failed(&format!("Error writing the differences to {}", json_path), e))?;
+    }
+    Ok(if diff.is_empty() { ExitCode::SUCCESS } else { ExitCode::from(1) })
+}
@@ -0,0 +1,157 @@
+//! The `extract` subcommand, which writes the subgraph of a region of a laced GFA
+
+use std::io::{self, Write};
+use std::process::ExitCode;
+use rustc_hash::FxHashMap;
+use gfa::{optfields::{OptFields, OptFieldVal}, parser::GFAParser};
+use log::{info, warn};
+use crate::RunError;
+use crate::cli::ExtractArgs;
+use crate::input::ReadOptions;
+use crate::links::canonical_link;
+use crate::merge::overlap_length;
+use crate::names::{RangeSyntax, key_contig, split_path_name};
+use crate::open::read_gfa;
+use crate::output::AtomicFile;
+use crate::write::{FragmentNaming, PathStep, fragment_name, write_link, write_path, write_segment};
+
+/// Writes the part of a laced GFA traversed by the paths of the contig of a region within it: the
+/// segments, the links between them, and the subpaths, named `KEY:START-END` with their coordinates.
+/// Segments crossing the region boundaries are trimmed to it into new segments, unless `whole_nodes`
+/// is set, in which case the subpaths extend to their ends. Returns the number of subpaths
+pub(crate) fn extract_region(input: &str, region: &str, whole_nodes: bool, output: &str, options: &ReadOptions) -> io::Result<usize> {
+    let (contig, region_start, region_end) = split_path_name(&region.replace(',', ""), RangeSyntax::ColonDash)
+        .filter(|(_, start, end)| start < end)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid region '{}', expected CONTIG:START-END", region)))?;
+    let gfa = read_gfa(input, &GFAParser::new(), options)?;
+
+    // Sequence (None for '*') and length of each segment
+    let mut segments: FxHashMap<usize, (Option<&[u8]>, usize)> = FxHashMap::default();
+    for segment in &gfa.segments {
+        let entry = if segment.sequence.as_slice() == b"*" {
+            let length = match segment.optional.get_field(b"LN").map(|field| &field.value) {
+                Some(OptFieldVal::Int(length)) if *length >= 0 => *length as usize,
+                _ => 0,
+            };
+            (None, length)
+        } else {
+            (Some(segment.sequence.as_slice()), segment.sequence.len())
+        };
+        segments.insert(segment.name, entry);
+    }
+
+    // Subpaths as steps over (segment, is reverse, trimmed part of the segment if any)
+    type ExtractedStep = (usize, bool, Option<(usize, usize)>);
+    let mut subpaths: Vec<(String, Vec<ExtractedStep>)> = Vec::new();
+    for path in &gfa.paths {
+        let path_name = String::from_utf8_lossy(&path.path_name);
+        let (path_key, path_start) = match split_path_name(&path_name, options.range_syntax) {
+            Some((path_key, start, _)) => (path_key, start),
+            None => (path_name.to_string(), 0),
+        };
+        if key_contig(&path_key) != contig {
+            continue;
+        }
+
+        let mut steps = Vec::new();
+        let (mut subpath_start, mut subpath_end) = (usize::MAX, 0);
+        let mut step_start = path_start;
+        for (segment_id, orientation) in path.iter() {
+            let &(_, length) = segments.get(&segment_id).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("Path '{}' visits the undefined segment {}", path_name, segment_id))
+            })?;
+            let step_end = step_start + length;
+            if step_start < region_end && step_end > region_start {
+                let (start, end) = if whole_nodes {
+                    (step_start, step_end)
+                } else {
+                    (step_start.max(region_start), step_end.min(region_end))
+                };
+                // Part of the segment on its forward strand
+                let trimmed = (end - start < length).then(|| {
+                    let (from, to) = (start - step_start, end - step_start);
+                    if orientation.is_reverse() { (length - to, length - from) } else { (from, to) }
+                });
+                steps.push((segment_id, orientation.is_reverse(), trimmed));
+                subpath_start = subpath_start.min(start);
+                subpath_end = end;
+            }
+            step_start = step_end;
+        }
+        if !steps.is_empty() {
+            subpaths.push((fragment_name(&path_key, subpath_start, subpath_end, 0, FragmentNaming::Range, RangeSyntax::ColonDash), steps));
+        }
+    }
+
+    // Number the whole segments in ID order, then the trimmed ones
+    let mut whole: Vec<usize> = subpaths.iter().flat_map(|(_, steps)| steps)
+        .filter(|(_, _, trimmed)| trimmed.is_none())
+        .map(|&(segment_id, _, _)| segment_id)
+        .collect();
+    whole.sort_unstable();
+    whole.dedup();
+    let mut new_ids: FxHashMap<(usize, Option<(usize, usize)>), usize> = whole.iter().enumerate()
+        .map(|(idx, &segment_id)| ((segment_id, None), idx + 1))
+        .collect();
+    let mut pieces = Vec::new();
+    for &(segment_id, _, trimmed) in subpaths.iter().flat_map(|(_, steps)| steps) {
+        if trimmed.is_some() && !new_ids.contains_key(&(segment_id, trimmed)) {
+            new_ids.insert((segment_id, trimmed), whole.len() + pieces.len() + 1);
+            pieces.push((segment_id, trimmed));
+        }
+    }
+
+    let path_steps: Vec<(String, Vec<PathStep>)> = subpaths.into_iter()
+        .map(|(name, steps)| (name, steps.into_iter().map(|(segment_id, is_reverse, trimmed)| (new_ids[&(segment_id, trimmed)], is_reverse)).collect()))
+        .collect();
+    let mut links: Vec<(PathStep, PathStep)> = gfa.links.iter()
+        .filter_map(|link| {
+            let from = *new_ids.get(&(link.from_segment, None))?;
+            let to = *new_ids.get(&(link.to_segment, None))?;
+            Some(canonical_link((from, link.from_orient.is_reverse()), (to, link.to_orient.is_reverse())))
+        })
+        .chain(path_steps.iter().flat_map(|(_, steps)| steps.windows(2).map(|pair| canonical_link(pair[0], pair[1]))))
+        .collect();
+    links.sort_unstable();
+    links.dedup();
+
+    let mut out = AtomicFile::create(output)?;
+    writeln!(out, "H\tVN:Z:1.0")?;
+    for (segment_id, trimmed) in whole.iter().map(|&segment_id| (segment_id, None)).chain(pieces) {
+        let (sequence, length) = segments[&segment_id];
+        let (from, to) = trimmed.unwrap_or((0, length));
+        match sequence {
+            Some(sequence) if to > from => write_segment(&mut out, new_ids[&(segment_id, trimmed)], &sequence[from..to], None)?,
+            _ => writeln!(out, "S\t{}\t*\tLN:i:{}", new_ids[&(segment_id, trimmed)], to - from)?,
+        }
+    }
+    // Links between whole segments keep their overlap, those through the trimmed ends are blunt
+    let overlaps: FxHashMap<(PathStep, PathStep), usize> = gfa.links.iter()
+        .filter_map(|link| {
+            let from = *new_ids.get(&(link.from_segment, None))?;
+            let to = *new_ids.get(&(link.to_segment, None))?;
+            let overlap = overlap_length(link.overlap.as_ref())?;
+            Some((canonical_link((from, link.from_orient.is_reverse()), (to, link.to_orient.is_reverse())), overlap))
+        })
+        .collect();
+    for link in links {
+        write_link(&mut out, link.0, link.1, overlaps.get(&link).copied().unwrap_or(0))?;
+    }
+    for (name, steps) in &path_steps {
+        write_path(&mut out, name, steps.iter().copied(), &[], &[])?;
+    }
+    out.finish()?;
+
+    if path_steps.is_empty() {
+        warn!(stage = "write"; "No path of contig '{}' crosses {}:{}-{}", contig, contig, region_start, region_end);
+    }
+    Ok(path_steps.len())
+}
+
+/// Runs `gfalace extract`
+pub(crate) fn run(args: &ExtractArgs, read_options: &ReadOptions) -> Result<ExitCode, RunError> {
+    let num_paths = extract_region(&args.input, &args.region, args.whole_nodes, &args.output, read_options)
+        .map_err(RunError::context("Error extracting the region"))?;
+    info!(stage = "write"; "Extracted {} paths to {}", num_paths, args.output);
+    Ok(ExitCode::SUCCESS)
+}
@@ -0,0 +1,5 @@
+//! The subcommands of the command line tool, and the statistics of the written graph
+
+pub(crate) mod diff;
+pub(crate) mod extract;
+pub(crate) mod stats;
@@ -0,0 +1,131 @@
+//! Statistics of the written graph, given by --graph-stats
+
+use std::{
+    collections::BTreeMap,
+    io::{self, BufRead},
+};
+use rustc_hash::FxHashMap;
+use crate::input::ReadOptions;
+use crate::open::GfaInput;
+
+/// Shape metrics of a GFA file, in the spirit of `odgi stats`
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct GraphStats {
+    /// Total length of the segments, in bp
+    pub(crate) length: usize,
+    pub(crate) node_lengths: Vec<usize>,
+    pub(crate) edges: usize,
+    /// Number of nodes by degree, i.e. by number of links on either side, a self-loop counting twice
+    pub(crate) degrees: BTreeMap<usize, usize>,
+    pub(crate) components: usize,
+    /// Name, number of steps, and length in bp of each path
+    pub(crate) paths: Vec<(String, usize, usize)>,
+    /// Largest numeric segment ID, 0 if there is none
+    pub(crate) max_id: usize,
+}
+
+impl GraphStats {
+    /// Length of the shortest of the longest nodes that make up half of the graph length
+    pub(crate) fn node_n50(&self) -> usize {
+        let mut lengths = self.node_lengths.clone();
+        lengths.sort_unstable_by(|a, b| b.cmp(a));
+        let mut covered = 0;
+        for length in lengths {
+            covered += length;
+            if 2 * covered >= self.length {
+                return length;
+            }
+        }
+        0
+    }
+
+    pub(crate) fn node_mean(&self) -> f64 {
+        if self.node_lengths.is_empty() { 0.0 } else { self.length as f64 / self.node_lengths.len() as f64 }
+    }
+
+    pub(crate) fn print(&self) {
+        println!("metric\tvalue");
+        println!("length\t{}", self.length);
+        println!("nodes\t{}", self.node_lengths.len());
+        println!("node_n50\t{}", self.node_n50());
+        println!("node_mean\t{:.2}", self.node_mean());
+        println!("node_max\t{}", self.node_lengths.iter().max().unwrap_or(&0));
+        println!("edges\t{}", self.edges);
+        println!("components\t{}", self.components);
+        println!("max_id\t{}", self.max_id);
+        println!("\ndegree\tnodes");
+        for (degree, count) in &self.degrees {
+            println!("{}\t{}", degree, count);
+        }
+        println!("\npath\tsteps\tlength");
+        for (name, steps, length) in &self.paths {
+            println!("{}\t{}\t{}", name, steps, length);
+        }
+    }
+}
+
+/// Computes the shape metrics of a GFA file, reading its S, L, and P lines back.
+/// Components are those of the links taken as undirected edges, with paths ignored
+pub(crate) fn graph_stats(gfa_path: &str) -> io::Result<GraphStats> {
+    fn find(parents: &mut [usize], mut node: usize) -> usize {
+        while parents[node] != node {
+            parents[node] = parents[parents[node]];
+            node = parents[node];
+        }
+        node
+    }
+
+    let mut stats = GraphStats::default();
+    let mut node_indices: FxHashMap<String, usize> = FxHashMap::default();
+    let mut links = Vec::new();
+    let mut path_steps = Vec::new();
+    for line in GfaInput::Path(gfa_path).lines(&ReadOptions::default())?.lines() {
+        let line = line?;
+        let fields: Vec<&str> = line.split('\t').collect();
+        match fields.as_slice() {
+            ["S", name, sequence, tags @ ..] => {
+                let length = if *sequence != "*" {
+                    sequence.len()
+                } else {
+                    tags.iter().find_map(|tag| tag.strip_prefix("LN:i:")?.parse().ok()).unwrap_or(0)
+                };
+                stats.max_id = stats.max_id.max(name.parse().unwrap_or(0));
+                node_indices.insert(name.to_string(), stats.node_lengths.len());
+                stats.node_lengths.push(length);
+                stats.length += length;
+            }
+            ["L", from, _, to, ..] => links.push((from.to_string(), to.to_string())),
+            ["P", name, steps, ..] => path_steps.push((name.to_string(), steps.to_string())),
+            _ => {}
+        }
+    }
+
+    let invalid_node = |name: &str| io::Error::new(io::ErrorKind::InvalidData, format!("Unknown segment '{}' in '{}'", name, gfa_path));
+    let mut degrees = vec![0; stats.node_lengths.len()];
+    let mut parents: Vec<usize> = (0..stats.node_lengths.len()).collect();
+    for (from, to) in &links {
+        let from = *node_indices.get(from).ok_or_else(|| invalid_node(from))?;
+        let to = *node_indices.get(to).ok_or_else(|| invalid_node(to))?;
+        degrees[from] += 1;
+        degrees[to] += 1;
+        let (from_root, to_root) = (find(&mut parents, from), find(&mut parents, to));
+        parents[from_root] = to_root;
+    }
+    stats.edges = links.len();
+    for degree in degrees {
+        *stats.degrees.entry(degree).or_insert(0) += 1;
+    }
+    stats.components = (0..parents.len()).filter(|&node| find(&mut parents, node) == node).count();
+
+    for (name, steps) in path_steps {
+        let mut length = 0;
+        let mut num_steps = 0;
+        for step in steps.split(',') {
+            let node = &step[..step.len().saturating_sub(1)];
+            length += stats.node_lengths[*node_indices.get(node).ok_or_else(|| invalid_node(node))?];
+            num_steps += 1;
+        }
+        stats.paths.push((name, num_steps, length));
+    }
+    Ok(stats)
+}
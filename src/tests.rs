@@ -0,0 +1,3392 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    borrow::Cow,
+    path::Path,
+};
+use rustc_hash::{FxHashMap, FxHashSet};
+use clap::CommandFactory;
+use handlegraph::{
+    handle::{Direction, Handle, NodeId, Edge},
+    handlegraph::*,
+    mutablehandlegraph::*,
+    hashgraph::HashGraph,
+};
+use gfa::parser::{GFAParser, GFAParserBuilder};
+use bitvec::bitvec;
+use tempfile::NamedTempFile;
+use sha2::{Digest, Sha256};
+use crate::cli::{Args, SUMMARY_TARGET, existing_outputs, format_json_record, init_logger, input_collisions, logger_builder, output_files, parse_args};
+use crate::input::{CombinedInput, DEFAULT_ALPHABET, IdOffsets, PassthroughLines, ReadOptions, apply_range_order, check_alphabet, format_segment_ids, parse_gaf_path, read_blocks_tsv, read_contig_lengths, read_gaf_paths, read_gfa_files, read_key_map, read_range_order, unparseable_path_names, validate_input};
+use crate::lace::{LaceError, LaceOptions, LaceWarning, RunSummary, lace_graphs};
+use crate::links::{SORT_CHUNK_SIZE, canonical_link, collect_output_links};
+use crate::merge::{CollapsePolicy, EdgeSet, KeyCoverage, LinkOverlaps, blunt_overlaps, canonical_edges, chop_long_nodes, collapse_haplotypes, drop_incomplete_keys, drop_low_coverage_keys, drop_unused_edges, link_contiguous_ranges, link_path_steps, merge_path_ranges, missing_path_edges, overlap_cuts, overlap_length, prune_orphans, sort_and_filter_all_ranges, sort_and_filter_ranges, trim_range_overlaps};
+use crate::names::{FormatPart, NameScheme, PathFormat, RangeSyntax, cactus_key, check_name_scheme, key_contig, split_input_path_name, split_path_name};
+use crate::open::{DECOMPRESS_BUDGET, GfaInput, Prefetcher, check_header_versions, collect_passthrough_lines, for_each_input, header_versions, is_prefetchable, is_tar_archive, open_file, parse_mapped_gfa, read_gfa};
+#[cfg(feature = "http")]
+use crate::open::fetch_url;
+use crate::output::{AtomicFile, LineEnding};
+use crate::ranges::{Coverage, PathKey, RangeBounds, RangeInfo, contig_length, end_gaps, find_overlaps, gap_length, interval_coverage, large_gaps, unexpected_overlaps};
+use crate::report::{DegreeHistogram, KeyFragmentation, RangeReport, SampleCompleteness, degree_histogram, sample_table_rows, write_completeness, write_sample_table};
+use crate::sequences::{NodeSequences, reverse_complement};
+use crate::subcommands::diff::{GraphContent, GraphDiff, diff_graphs};
+use crate::subcommands::extract::extract_region;
+use crate::subcommands::stats::graph_stats;
+use crate::write::{FragmentNaming, GFALACE_ORIGIN, LiftoverWriter, NodeOrder, PathFastaWriter, PathOrder, PathStep, WriteOptions, block_groups, check_roundtrip, file_prefixed_node_names, format_id_runs, gfa_checksum, node_origins, node_source, path_tag_conflicts, rename_output_nodes, sort_path_keys, write_block_groups, write_checksum_file, write_graph_to_gfa, write_path, write_segment};
+
+// Helper function to write GFA content to a temporary file
+fn write_temp_gfa(content: &str) -> NamedTempFile {
+    let file = NamedTempFile::with_suffix(".gfa").unwrap();
+    file.as_file().write_all(content.as_bytes()).unwrap();
+    file
+}
+
+/// Writes a laced graph to a temporary GFA, without FASTA sequences, passthrough lines, or side outputs
+fn write_test_gfa_file(
+    graph: &HashGraph,
+    path_key_ranges: &FxHashMap<PathKey, Vec<RangeInfo>>,
+    sequenceless: &FxHashSet<NodeId>,
+    link_overlaps: &LinkOverlaps,
+    options: &WriteOptions
+) -> io::Result<NamedTempFile> {
+    let output = NamedTempFile::new()?;
+    write_graph_to_gfa(graph, &NodeSequences::default(), path_key_ranges, sequenceless, link_overlaps, &PassthroughLines::default(), output.path().to_str().unwrap(), &None, options, None, None, None)?;
+    Ok(output)
+}
+
+/// As `write_test_gfa_file`, returning the written GFA
+fn write_test_gfa(
+    graph: &HashGraph,
+    path_key_ranges: &FxHashMap<PathKey, Vec<RangeInfo>>,
+    sequenceless: &FxHashSet<NodeId>,
+    link_overlaps: &LinkOverlaps,
+    options: &WriteOptions
+) -> String {
+    let output = write_test_gfa_file(graph, path_key_ranges, sequenceless, link_overlaps, options).unwrap();
+    std::fs::read_to_string(output.path()).unwrap()
+}
+
+/// Reads and merges three blocks of one path: the second overlaps the first by 2 bp, which
+/// splits its first segment, and the third is separated by a gap. Returns the blocks, to keep
+/// them on disk, the merged input, and the origins of the split segments
+fn split_and_gap_fixture() -> (Vec<NamedTempFile>, CombinedInput, FxHashMap<NodeId, NodeId>) {
+    let blocks: Vec<_> = [
+        "S\t1\tACGT\nS\t2\tGG\nL\t1\t+\t2\t+\t0M\nP\tsample#1#chr1:0-6\t1+,2+\t*\n",
+        "S\t1\tGGTT\nS\t2\tA\nL\t1\t+\t2\t+\t0M\nP\tsample#1#chr1:4-9\t1+,2+\t*\n",
+        "S\t7\tCC\nP\tsample#1#chr1:12-14\t7+\t*\n",
+    ].iter().map(|block| write_temp_gfa(block)).collect();
+    let gfa_list: Vec<String> = blocks.iter().map(|block| block.path().to_str().unwrap().to_string()).collect();
+    let mut combined = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+    let split_origins = merge_path_ranges(&mut combined.path_key_ranges, &mut combined.graph, &mut combined.edges, &mut combined.sequences, &mut combined.sequenceless, None, &mut RunSummary::default(), false);
+    (blocks, combined, split_origins)
+}
+
+// Helper function to create a simple RangeInfo for testing
+fn create_range_info(start: usize, end: usize, gfa_id: usize) -> RangeInfo {
+    RangeInfo::new(start, end, gfa_id)
+}
+
+#[test]
+fn test_range_containment_removal() {
+    // Test cases
+    let test_cases = vec![
+        // Test case 1: Basic containment
+        (
+            vec![(10, 50, 0), (20, 30, 1)],  // Input ranges (start, end, gfa_id)
+            vec![(10, 50, 0)],               // Expected result
+            "Basic containment"
+        ),
+        
+        // Test case 2: No containment
+        (
+            vec![(10, 20, 0), (30, 40, 1)],
+            vec![(10, 20, 0), (30, 40, 1)],
+            "No containment"
+        ),
+        
+        // Test case 3: Multiple contained ranges
+        (
+            vec![(10, 100, 0), (20, 30, 1), (40, 50, 2), (60, 70, 3)],
+            vec![(10, 100, 0)],
+            "Multiple contained ranges"
+        ),
+        
+        // Test case 4: Identical ranges
+        (
+            vec![(10, 20, 0), (10, 20, 1)],
+            vec![(10, 20, 0)],
+            "Identical ranges"
+        ),
+        
+        // Test case 5: Nested containment
+        (
+            vec![(10, 100, 0), (20, 80, 1), (30, 40, 2)],
+            vec![(10, 100, 0)],
+            "Nested containment"
+        ),
+        
+        // Test case 6: Partial overlap (should keep both)
+        (
+            vec![(10, 30, 0), (20, 40, 1)],
+            vec![(10, 30, 0), (20, 40, 1)],
+            "Partial overlap"
+        ),
+        
+        // Test case 7: Edge cases - touching ranges
+        (
+            vec![(10, 20, 0), (20, 30, 1)],
+            vec![(10, 20, 0), (20, 30, 1)],
+            "Touching ranges"
+        ),
+
+        // Test case 8: Overlapping ranges from same GFA
+        (
+            vec![(0, 11742, 0), (9714, 13000, 1), (11000, 19000, 1)],
+            vec![(0, 11742, 0), (11000, 19000, 1)],
+            "Overlapping ranges from same GFA"
+        ),
+
+        // Test case 9: Overlapping ranges with different GFA IDs
+        (
+            vec![(0, 11742, 0), (9714, 13000, 1), (11000, 19000, 2)],
+            vec![(0, 11742, 0), (11000, 19000, 2)],
+            "Overlapping ranges"
+        ),
+
+        // Test case 10: Overlapping ranges with different GFA IDs 2
+        (
+            vec![(0, 10, 0), (8, 20, 1), (15, 30, 2)],
+            vec![(0, 10, 0), (8, 20, 1), (15, 30, 2)],
+            "Overlapping ranges"
+        ),
+
+        // Test case 11: Overlapping ranges with different GFA IDs 3
+        (
+            vec![(8000, 11000, 0), (9694, 12313, 1), (10908, 13908, 2)],
+            vec![(8000, 11000, 0), (10908, 13908, 2)],
+            "Overlapping ranges"
+        ),
+    ];
+
+    // Run each test case
+    for (case_index, (input_ranges, expected_ranges, case_name)) in test_cases.iter().enumerate() {
+        println!("Running test case {}: {}", case_index + 1, case_name);
+        
+        // Create input ranges
+        let mut ranges: Vec<RangeInfo> = input_ranges
+            .iter()
+            .map(|(start, end, gfa_id)| create_range_info(*start, *end, *gfa_id))
+            .collect();
+
+        // Sort ranges by start position
+        ranges.sort_by_key(|r| (r.start, r.end));
+
+        let mut write_idx = 0;
+        for read_idx in 1..ranges.len() {
+            let (prev_start, prev_end) = (ranges[write_idx].start, ranges[write_idx].end);
+            let (curr_start, curr_end) = (ranges[read_idx].start, ranges[read_idx].end);
+
+            if curr_start == prev_start && curr_end == prev_end {
+                // Skip duplicate range
+                continue;
+            } else if curr_start >= prev_start && curr_end <= prev_end {
+                // Skip range that is fully contained within previous range
+                continue;
+            } else if prev_start >= curr_start && prev_end <= curr_end {
+                // Previous range is fully contained within current range
+                ranges.swap(write_idx, read_idx);
+            } else if curr_start < prev_end {
+                // Handle overlapping ranges - check both previous and next ranges
+                let mut should_skip = false;
+                
+                if read_idx < ranges.len() - 1 {
+                    let next_start = ranges[read_idx + 1].start;
+                    
+                    // Check if current range is significantly overlapped by both neighbors
+                    if curr_start > prev_start && next_start < curr_end {
+                        let overlap_with_prev = prev_end - curr_start;
+                        let overlap_with_next = curr_end - next_start;
+                        let range_length = curr_end - curr_start;
+                        
+                        // Skip if the range is mostly covered by its neighbors
+                        if overlap_with_prev + overlap_with_next > range_length {
+                            should_skip = true;
+                        }
+                    }
+                }
+                
+                if !should_skip {
+                    write_idx += 1;
+                    if write_idx != read_idx {
+                        ranges.swap(write_idx, read_idx);
+                    }
+                }
+            } else {
+                // No overlap - keep both ranges
+                write_idx += 1;
+                if write_idx != read_idx {
+                    ranges.swap(write_idx, read_idx);
+                }
+            }
+        }
+        ranges.truncate(write_idx + 1);
+
+        // Create expected ranges
+        let expected: Vec<RangeInfo> = expected_ranges
+            .iter()
+            .map(|(start, end, gfa_id)| create_range_info(*start, *end, *gfa_id))
+            .collect();
+
+        // Compare results
+        assert_eq!(
+            ranges.len(),
+            expected.len(),
+            "Test case '{}': Wrong number of ranges after containment removal",
+            case_name
+        );
+
+        for (i, (result, expected)) in ranges.iter().zip(expected.iter()).enumerate() {
+            assert_eq!(
+                (result.start, result.end, result.gfa_id),
+                (expected.start, expected.end, expected.gfa_id),
+                "Test case '{}': Mismatch at position {}",
+                case_name,
+                i
+            );
+        }
+        
+        println!("Test case {} passed: {}", case_index + 1, case_name);
+    }
+}
+
+#[test]
+fn test_find_overlaps() {
+    let ranges = vec![
+        create_range_info(0, 100, 0),
+        create_range_info(90, 200, 1),
+        create_range_info(200, 300, 2),
+        create_range_info(250, 400, 3),
+    ];
+
+    let overlaps = find_overlaps(&ranges);
+    assert_eq!(overlaps.len(), 2);
+
+    assert_eq!(overlaps[0].first, RangeBounds { start: 0, end: 100, gfa_id: 0 });
+    assert_eq!(overlaps[0].second, RangeBounds { start: 90, end: 200, gfa_id: 1 });
+    assert_eq!(overlaps[0].size, 10);
+
+    // Touching ranges [90,200) and [200,300) do not overlap
+    assert_eq!(overlaps[1].first, RangeBounds { start: 200, end: 300, gfa_id: 2 });
+    assert_eq!(overlaps[1].second, RangeBounds { start: 250, end: 400, gfa_id: 3 });
+    assert_eq!(overlaps[1].size, 50);
+}
+
+#[test]
+fn test_find_overlaps_non_adjacent() {
+    // A long range spanning three short ones, given out of order
+    let ranges = vec![
+        create_range_info(10, 20, 1),
+        create_range_info(0, 100, 0),
+        create_range_info(50, 60, 3),
+        create_range_info(30, 40, 2),
+        create_range_info(100, 150, 4),
+    ];
+
+    let overlaps = find_overlaps(&ranges);
+    assert_eq!(overlaps.len(), 3);
+    for (overlap, (start, end, gfa_id)) in overlaps.iter().zip([(10, 20, 1), (30, 40, 2), (50, 60, 3)]) {
+        assert_eq!(overlap.first, RangeBounds { start: 0, end: 100, gfa_id: 0 });
+        assert_eq!(overlap.second, RangeBounds { start, end, gfa_id });
+        assert_eq!(overlap.size, 10);
+        assert!(overlap.is_containment());
+    }
+
+    // Partial overlaps are not containments
+    let overlaps = find_overlaps(&[create_range_info(0, 100, 0), create_range_info(90, 200, 1)]);
+    assert!(!overlaps[0].is_containment());
+}
+
+#[test]
+fn test_reverse_complement_links_written_once() {
+    let mut graph = HashGraph::new();
+    let a = graph.create_handle(b"ACGT", 1u64);
+    let b = graph.create_handle(b"TTGA", 2u64);
+    // (A+, B+) and its reverse complement (B-, A-) are the same link
+    graph.create_edge(Edge(a, b));
+    graph.create_edge(Edge(b.flip(), a.flip()));
+
+    let nodes_to_remove = bitvec![0; 3];
+    let id_mapping = vec![0, 1, 2];
+    let links = collect_output_links(&graph, &nodes_to_remove, &id_mapping);
+
+    assert_eq!(links, vec![((1, false), (2, false))]);
+    assert_eq!(canonical_link((2, true), (1, true)), ((1, false), (2, false)));
+}
+
+#[test]
+fn test_boundary_overlap_trimming() {
+    let mut graph = HashGraph::new();
+    let a = graph.create_handle(&[b'A'; 100], 1u64);
+    // The first 50 bp of the second range duplicate the end of the first one
+    let mut seq_b = vec![b'A'; 50];
+    seq_b.extend_from_slice(&[b'C'; 50]);
+    let b = graph.create_handle(&seq_b, 2u64);
+    let c = graph.create_handle(&[b'G'; 50], 3u64);
+    graph.create_edge(Edge(b, c));
+
+    // The coordinates of the second range include the 50 bp it shares with the first one
+    let mut ranges = vec![
+        RangeInfo { start: 0, end: 100, gfa_id: 0, path_name: String::new(), steps: vec![a], step_ends: vec![100], tags: vec![], rank: None },
+        RangeInfo { start: 50, end: 200, gfa_id: 1, path_name: String::new(), steps: vec![b, c], step_ends: vec![150, 200], tags: vec![], rank: None },
+    ];
+    let mut sequences = NodeSequences::default();
+    let mut edges = canonical_edges(&graph);
+    let cuts = overlap_cuts(&ranges);
+    trim_range_overlaps("sample#1#chr1", &mut ranges, &cuts, &mut graph, &mut sequences, &mut edges, &mut FxHashSet::default(), &mut FxHashMap::default(), false);
+    link_contiguous_ranges("sample#1#chr1", &mut ranges, &mut graph, &mut edges, false);
+
+    let trimmed = ranges[1].steps[0];
+    assert_ne!(trimmed, b);
+    assert_eq!(graph.sequence(trimmed).collect::<Vec<_>>(), vec![b'C'; 50]);
+    assert_eq!((ranges[1].start, ranges[1].end), (100, 200));
+    assert_eq!(ranges[1].step_ends, vec![150, 200]);
+    assert!(graph.has_edge(a, trimmed));
+    assert!(graph.has_edge(trimmed, c));
+
+    // Merged path length equals the coordinate span
+    let path_len: usize = ranges.iter()
+        .flat_map(|r| r.steps.iter())
+        .map(|&h| graph.sequence(h).count())
+        .sum();
+    assert_eq!(path_len, 200);
+}
+
+#[test]
+fn test_overlap_length() {
+    assert_eq!(overlap_length(b"0M"), Some(0));
+    assert_eq!(overlap_length(b"50M"), Some(50));
+    assert_eq!(overlap_length(b"10M2D5M"), Some(15));
+    assert_eq!(overlap_length(b"*"), None);
+    assert_eq!(overlap_length(b"M"), None);
+    assert_eq!(overlap_length(b"12"), None);
+}
+
+#[test]
+fn test_sequenceless_segments() {
+    let with_sequence = write_temp_gfa("H\tVN:Z:1.0\nS\t1\tACGT\nS\t2\tGG\nL\t1\t+\t2\t+\t0M\nP\tsample#1#chr1:0-6\t1+,2+\t*\n");
+    let without_sequence = write_temp_gfa("H\tVN:Z:1.0\nS\t1\t*\tLN:i:5\nS\t2\tTT\nL\t1\t+\t2\t+\t0M\nP\tsample#1#chr1:6-13\t1+,2+\t*\n");
+    let gfa_list = vec![
+        with_sequence.path().to_str().unwrap().to_string(),
+        without_sequence.path().to_str().unwrap().to_string(),
+    ];
+
+    let CombinedInput { graph, path_key_ranges, sequenceless, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+    assert_eq!(sequenceless.len(), 1);
+
+    let sequenceless_id = *sequenceless.iter().next().unwrap();
+    assert_eq!(graph.sequence(Handle::pack(sequenceless_id, false)).count(), 5);
+
+    // The declared length is used for the step coordinates
+    let ranges = &path_key_ranges["sample#1#chr1"];
+    let range = ranges.iter().find(|r| r.start == 6).unwrap();
+    assert_eq!(range.step_ends, vec![11, 13]);
+}
+
+#[test]
+fn test_no_sequences_only_changes_segment_sequences() {
+    let block_a = write_temp_gfa("S\t1\tACGT\nS\t2\tGG\nL\t1\t+\t2\t+\t0M\nP\tsample#1#chr1:0-6\t1+,2+\t*\n");
+    let block_b = write_temp_gfa("S\t1\tTTTTT\nP\tsample#1#chr1:10-15\t1+\t*\n");
+    let gfa_list = vec![
+        block_a.path().to_str().unwrap().to_string(),
+        block_b.path().to_str().unwrap().to_string(),
+    ];
+    let CombinedInput { graph, mut path_key_ranges, sequenceless, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+    for (path_key, ranges) in path_key_ranges.iter_mut() {
+        sort_and_filter_ranges(path_key, ranges, false);
+    }
+
+    let write = |no_sequences: bool| {
+        let options = WriteOptions { fill_gaps: 1, no_sequences, ..WriteOptions::default() };
+        write_test_gfa(&graph, &path_key_ranges, &sequenceless, &LinkOverlaps::default(), &options)
+    };
+    let golden = write(false);
+    let topology_only = write(true);
+
+    let golden_lines: Vec<_> = golden.lines().collect();
+    let topology_lines: Vec<_> = topology_only.lines().collect();
+    assert_eq!(golden_lines.len(), topology_lines.len());
+    for (expected, line) in golden_lines.iter().zip(topology_lines.iter()) {
+        if expected.starts_with("S\t") {
+            // Only the sequence column changes, with the length moved into the LN tag
+            let fields: Vec<_> = expected.split('\t').collect();
+            assert_eq!(*line, format!("S\t{}\t*\tLN:i:{}", fields[1], fields[2].len()));
+        } else {
+            assert_eq!(expected, line);
+        }
+    }
+}
+
+#[test]
+fn test_edge_id_translation() {
+    // A single block is laced with its own IDs
+    let block = write_temp_gfa("S\t1\tACGT\nS\t2\tGG\nL\t1\t+\t2\t-\t0M\nP\tsample#1#chr1:0-6\t1+,2-\t*\n");
+    let gfa_list = vec![block.path().to_str().unwrap().to_string()];
+    let CombinedInput { graph, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+    let edges: Vec<_> = graph.edges().collect();
+    assert_eq!(edges.len(), 1);
+    let Edge(from, to) = edges[0];
+    let mut ids = [u64::from(from.id()), u64::from(to.id())];
+    ids.sort();
+    assert_eq!(ids, [1, 2]);
+
+    // Blocks with non-compact IDs must not collide with the following blocks
+    let sparse_block = write_temp_gfa("S\t5\tACGT\nS\t6\tGG\nL\t5\t+\t6\t+\t0M\nP\tsample#1#chr1:0-6\t5+,6+\t*\n");
+    let next_block = write_temp_gfa("S\t1\tTT\nS\t2\tCC\nL\t1\t+\t2\t+\t0M\nP\tsample#1#chr1:6-10\t1+,2+\t*\n");
+    let gfa_list = vec![
+        sparse_block.path().to_str().unwrap().to_string(),
+        next_block.path().to_str().unwrap().to_string(),
+    ];
+    let CombinedInput { graph, path_key_ranges, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+    assert_eq!(graph.node_count(), 4);
+    assert!(graph.has_edge(Handle::pack(5u64, false), Handle::pack(6u64, false)));
+    assert!(graph.has_edge(Handle::pack(7u64, false), Handle::pack(8u64, false)));
+
+    let ranges = &path_key_ranges["sample#1#chr1"];
+    let next_range = ranges.iter().find(|r| r.gfa_id == 1).unwrap();
+    assert_eq!(next_range.steps, vec![Handle::pack(7u64, false), Handle::pack(8u64, false)]);
+}
+
+#[test]
+fn test_explicit_id_offsets() {
+    let block_a = write_temp_gfa("S\t1\tACGT\nS\t3\tGG\nL\t1\t+\t3\t+\t0M\nP\tsample#1#chr1:0-6\t1+,3+\t*\n");
+    let block_b = write_temp_gfa("S\t1\tTT\nS\t2\tCC\nL\t1\t+\t2\t+\t0M\nP\tsample#1#chr1:6-10\t1+,2+\t*\n");
+    let gfa_list: Vec<String> = [&block_a, &block_b].iter().map(|block| block.path().to_str().unwrap().to_string()).collect();
+
+    // Offsets in decreasing order, away from the automatic ones
+    let CombinedInput { mut graph, mut path_key_ranges, id_translations, link_overlaps, mut sequenceless, .. } = read_gfa_files(&gfa_list, &ReadOptions { id_offsets: Some(IdOffsets::Explicit(&[1000, 10])), ..ReadOptions::default() }).unwrap();
+    assert_eq!(id_translations, vec![NodeId::from(1000u64), NodeId::from(10u64)]);
+    assert!(graph.has_edge(Handle::pack(1001u64, false), Handle::pack(1003u64, false)));
+    assert!(graph.has_edge(Handle::pack(11u64, false), Handle::pack(12u64, false)));
+    let steps: Vec<_> = path_key_ranges["sample#1#chr1"].iter().map(|range| (range.gfa_id, range.steps.clone())).collect();
+    assert!(steps.contains(&(1, vec![Handle::pack(11u64, false), Handle::pack(12u64, false)])));
+    assert_eq!(node_source(NodeId::from(1003u64), &id_translations, &FxHashMap::default()), (0, 3));
+    assert_eq!(node_source(NodeId::from(12u64), &id_translations, &FxHashMap::default()), (1, 2));
+
+    // The offset IDs are written as they are by both writers, and checked as such
+    let mut edges = canonical_edges(&graph);
+    merge_path_ranges(&mut path_key_ranges, &mut graph, &mut edges, &mut NodeSequences::default(), &mut sequenceless, None, &mut RunSummary::default(), false);
+    for no_sort in [false, true] {
+        let options = WriteOptions { keep_ids: true, no_sort, ..WriteOptions::default() };
+        let output = write_test_gfa_file(&graph, &path_key_ranges, &sequenceless, &link_overlaps, &options).unwrap();
+        let output_path = output.path().to_str().unwrap();
+        let written = std::fs::read_to_string(output_path).unwrap();
+        assert!(written.contains("S\t11\tTT\n") && written.contains("S\t1003\tGG\n"), "{}", written);
+        assert!(written.contains("P\tsample#1#chr1\t1001+,1003+,11+,12+\t*\n"), "{}", written);
+        assert_eq!(check_roundtrip(output_path, &graph, &NodeSequences::default(), &path_key_ranges, &sequenceless, &options).unwrap(), Vec::<String>::new());
+    }
+
+    // With offset 2, block A spans IDs 3-5, which block B follows with offset 5 but overlaps with offset 3
+    assert!(read_gfa_files(&gfa_list, &ReadOptions { id_offsets: Some(IdOffsets::Explicit(&[2, 5])), ..ReadOptions::default() }).is_ok());
+    let error = read_gfa_files(&gfa_list, &ReadOptions { id_offsets: Some(IdOffsets::Explicit(&[2, 3])), ..ReadOptions::default() }).err().unwrap().to_string();
+    assert!(error.contains("offset by 3 (4-5) overlap those of"), "{}", error);
+    assert!(read_gfa_files(&gfa_list, &ReadOptions { id_offsets: Some(IdOffsets::Explicit(&[0])), ..ReadOptions::default() }).is_err());
+    assert!(read_gfa_files(&gfa_list, &ReadOptions { id_offsets: Some(IdOffsets::Explicit(&[0, 100, 200])), ..ReadOptions::default() }).is_err());
+    let error = read_gfa_files(&gfa_list, &ReadOptions { id_offsets: Some(IdOffsets::Explicit(&[u64::MAX - 1, 0])), ..ReadOptions::default() }).err().unwrap().to_string();
+    assert!(error.contains("go past the largest node ID"), "{}", error);
+}
+
+#[test]
+fn test_id_spacing() {
+    let block_a = write_temp_gfa("S\t1\tACGT\nS\t3\tGG\nL\t1\t+\t3\t+\t0M\nP\tsample#1#chr1:0-6\t1+,3+\t*\n");
+    let block_b = write_temp_gfa("S\t1\tTT\nS\t2\tCC\nL\t1\t+\t2\t+\t0M\nP\tsample#1#chr1:6-10\t1+,2+\t*\n");
+    let gfa_list: Vec<String> = [&block_a, &block_b].iter().map(|block| block.path().to_str().unwrap().to_string()).collect();
+
+    // Input i gets the IDs from i * 100 + 1 to (i + 1) * 100, whatever the size of the previous ones
+    let CombinedInput { graph, id_translations, .. } = read_gfa_files(&gfa_list, &ReadOptions { id_offsets: Some(IdOffsets::Spacing(100)), ..ReadOptions::default() }).unwrap();
+    assert_eq!(id_translations, vec![NodeId::from(0u64), NodeId::from(100u64)]);
+    assert!(graph.has_edge(Handle::pack(1u64, false), Handle::pack(3u64, false)));
+    assert!(graph.has_edge(Handle::pack(101u64, false), Handle::pack(102u64, false)));
+    assert_eq!(node_source(NodeId::from(102u64), &id_translations, &FxHashMap::default()), (1, 2));
+
+    // Block A has segment ID 3, which does not fit in a spacing of 2
+    assert!(read_gfa_files(&gfa_list, &ReadOptions { id_offsets: Some(IdOffsets::Spacing(3)), ..ReadOptions::default() }).is_ok());
+    let error = read_gfa_files(&gfa_list, &ReadOptions { id_offsets: Some(IdOffsets::Spacing(2)), ..ReadOptions::default() }).err().unwrap().to_string();
+    assert!(error.contains("go up to 3, beyond the ID spacing of 2"), "{}", error);
+    assert!(read_gfa_files(&gfa_list, &ReadOptions { id_offsets: Some(IdOffsets::Spacing(u64::MAX / 2 + 1)), ..ReadOptions::default() }).is_err());
+}
+
+#[test]
+fn test_check_alphabet() {
+    let block_a = write_temp_gfa("S\t1\tACGTN\nS\t2\tACRT\nS\t3\t*\tLN:i:4\nP\tsample#1#chr1:0-13\t1+,2+,3+\t*\n");
+    let block_b = write_temp_gfa("S\t4\tacgYt\nP\tsample#1#chr1:13-18\t4+\t*\n");
+    let gfa_list: Vec<String> = [&block_a, &block_b].iter().map(|block| block.path().to_str().unwrap().to_string()).collect();
+    let CombinedInput { graph, sequenceless, id_translations, gfa_names, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+
+    // The placeholder of the sequenceless segment is not reported
+    let violations = check_alphabet(&graph, &NodeSequences::default(), &sequenceless, DEFAULT_ALPHABET.as_bytes(), &id_translations, &gfa_names);
+    assert_eq!(violations, vec![(NodeId::from(2u64), b'R'), (NodeId::from(7u64), b'Y')]);
+    assert!(check_alphabet(&graph, &NodeSequences::default(), &sequenceless, b"ACGTNRYacgtn", &id_translations, &gfa_names).is_empty());
+}
+
+#[test]
+fn test_write_segment_raw_bytes() {
+    // IUPAC codes are written as they are
+    let mut out = Vec::new();
+    write_segment(&mut out, 1, b"ACGTRYKMN", None).unwrap();
+    assert_eq!(out, b"S\t1\tACGTRYKMN\n");
+
+    // Non-UTF-8 bytes are written unchanged rather than replaced
+    let mut out = Vec::new();
+    write_segment(&mut out, 2, &[b'A', 0xE9, b'C'], None).unwrap();
+    assert_eq!(out, [b"S\t2\t".as_slice(), &[b'A', 0xE9, b'C'], b"\n"].concat());
+
+    // Tabs and line breaks would break the format
+    let mut out = Vec::new();
+    assert!(write_segment(&mut out, 3, b"AC\tGT", None).is_err());
+    assert!(write_segment(&mut out, 3, b"AC\nGT", None).is_err());
+}
+
+#[test]
+fn test_zero_length_segments() {
+    // Node 2 is an empty connector segment
+    let block_a = write_temp_gfa("S\t1\tACGT\nS\t2\t*\tLN:i:0\nS\t3\tGG\nL\t1\t+\t2\t+\t0M\nL\t2\t+\t3\t+\t0M\nP\tsample#1#chr1:0-6\t1+,2+,3+\t*\n");
+    let block_b = write_temp_gfa("S\t1\tCC\nS\t2\t*\tLN:i:0\nS\t3\tTTT\nL\t1\t+\t2\t+\t0M\nL\t2\t+\t3\t+\t0M\nP\tsample#1#chr1:4-9\t1+,2+,3+\t*\n");
+    let gfa_list = vec![
+        block_a.path().to_str().unwrap().to_string(),
+        block_b.path().to_str().unwrap().to_string(),
+    ];
+    let CombinedInput { mut graph, mut path_key_ranges, mut sequenceless, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+
+    // Empty segments do not contribute to the path length
+    let ranges = path_key_ranges.get_mut("sample#1#chr1").unwrap();
+    ranges.sort_by_key(|r| r.start);
+    assert_eq!(ranges[0].step_ends, vec![4, 4, 6]);
+    assert_eq!(ranges[1].step_ends, vec![6, 6, 9]);
+
+    // The overlap [4, 6) covers the second range's first node, while its empty segment at 6 is kept
+    let mut edges = canonical_edges(&graph);
+    let cuts = overlap_cuts(ranges);
+    trim_range_overlaps("sample#1#chr1", ranges, &cuts, &mut graph, &mut NodeSequences::default(), &mut edges, &mut sequenceless, &mut FxHashMap::default(), false);
+    link_contiguous_ranges("sample#1#chr1", ranges, &mut graph, &mut edges, false);
+    assert_eq!((ranges[1].start, ranges[1].end), (6, 9));
+    assert_eq!(ranges[1].steps, vec![Handle::pack(5u64, false), Handle::pack(6u64, false)]);
+
+    let gfa = write_test_gfa(&graph, &path_key_ranges, &sequenceless, &LinkOverlaps::default(), &WriteOptions::default());
+
+    // Empty segments are written as '*' with a zero length
+    let empty_segments: Vec<_> = gfa.lines().filter(|l| l.starts_with("S\t") && l.ends_with("\t*\tLN:i:0")).collect();
+    assert_eq!(empty_segments.len(), 2);
+    let path_line = gfa.lines().find(|l| l.starts_with("P\t")).unwrap();
+    assert_eq!(path_line.split('\t').nth(2).unwrap().split(',').count(), 5);
+}
+
+#[test]
+fn test_trim_non_adjacent_overlap() {
+    // The first range reaches past the start of the third, which must not keep the bases both cover
+    let blocks: Vec<_> = [
+        "S\t1\tACGTACGTAC\nP\tsample#1#chr1:0-10\t1+\t*\n",
+        "S\t1\tCGTAC\nS\t2\tGG\nL\t1\t+\t2\t+\t0M\nP\tsample#1#chr1:5-12\t1+,2+\t*\n",
+        "S\t1\tTAC\nS\t2\tGTTT\nL\t1\t+\t2\t+\t0M\nP\tsample#1#chr1:8-15\t1+,2+\t*\n",
+    ].iter().map(|block| write_temp_gfa(block)).collect();
+    let gfa_list: Vec<String> = blocks.iter().map(|block| block.path().to_str().unwrap().to_string()).collect();
+    let CombinedInput { mut graph, mut path_key_ranges, mut sequenceless, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+    let ranges = path_key_ranges.get_mut("sample#1#chr1").unwrap();
+    ranges.sort_by_key(|r| r.start);
+
+    let mut edges = canonical_edges(&graph);
+    let cuts = overlap_cuts(ranges);
+    trim_range_overlaps("sample#1#chr1", ranges, &cuts, &mut graph, &mut NodeSequences::default(), &mut edges, &mut sequenceless, &mut FxHashMap::default(), false);
+    let bounds: Vec<_> = ranges.iter().map(|range| (range.start, range.end, range.step_ends.clone())).collect();
+    assert_eq!(bounds, vec![(0, 10, vec![10]), (10, 12, vec![12]), (12, 15, vec![15])]);
+    // Only the end of the last node of the third range is kept
+    assert_eq!(graph.sequence_vec(ranges[2].steps[0]), b"TTT");
+}
+
+#[test]
+fn test_passthrough_lines() {
+    let block = write_temp_gfa("# produced by chunker v1\nH\tVN:Z:1.0\nS\t1\tACGT\nX\tcustom\trecord\nP\tsample#1#chr1:0-4\t1+\t*\n#second comment\n");
+    let block_path = block.path().to_str().unwrap().to_string();
+    let gfa_list = vec![block_path.clone()];
+
+    let passthrough = collect_passthrough_lines(&gfa_list, &ReadOptions::default(), true, true).unwrap();
+    assert_eq!(passthrough.comments, vec![
+        format!("# {}: produced by chunker v1", block_path),
+        format!("# {}: second comment", block_path),
+    ]);
+    assert_eq!(passthrough.unknown, vec!["X\tcustom\trecord".to_string()]);
+
+    let CombinedInput { graph, path_key_ranges, sequenceless, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+    let output = NamedTempFile::new().unwrap();
+    write_graph_to_gfa(&graph, &NodeSequences::default(), &path_key_ranges, &sequenceless, &LinkOverlaps::default(), &passthrough, output.path().to_str().unwrap(), &None, &WriteOptions::default(), None, None, None).unwrap();
+    let gfa = std::fs::read_to_string(output.path()).unwrap();
+    let lines: Vec<_> = gfa.lines().collect();
+    assert_eq!(lines[1], format!("# {}: produced by chunker v1", block_path));
+    assert_eq!(*lines.last().unwrap(), "X\tcustom\trecord");
+
+    // Nothing is collected unless requested
+    let passthrough = collect_passthrough_lines(&gfa_list, &ReadOptions::default(), false, false).unwrap();
+    assert!(passthrough.comments.is_empty() && passthrough.unknown.is_empty());
+}
+
+#[test]
+fn test_containments() {
+    // Alleles contained in reference segments, one of which is not traversed by any path
+    let block_a = write_temp_gfa("S\t1\tACGTACGT\nS\t2\tGTAC\nC\t1\t+\t2\t+\t2\t4M\tID:Z:alt1\n\
+        P\tsample#1#chr1:0-8\t1+\t*\nP\tsample#2#chr1:0-4\t2+\t*\n");
+    let block_b = write_temp_gfa("S\t1\tTTTT\nS\t2\tAA\nS\t3\tTT\nC\t1\t-\t2\t+\t1\t2M\nC\t1\t+\t3\t+\t0\t2M\n\
+        P\tsample#1#chr1:8-12\t1+\t*\nP\tsample#2#chr1:4-6\t2+\t*\n");
+    let gfa_list: Vec<String> = [&block_a, &block_b].iter().map(|block| block.path().to_str().unwrap().to_string()).collect();
+    let CombinedInput { mut graph, mut path_key_ranges, link_overlaps, mut sequenceless, containments, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+    assert_eq!(containments.len(), 3);
+    assert_eq!(containments[1].container, Handle::pack(3u64, true));
+    let mut edges = canonical_edges(&graph);
+    merge_path_ranges(&mut path_key_ranges, &mut graph, &mut edges, &mut NodeSequences::default(), &mut sequenceless, None, &mut RunSummary::default(), false);
+
+    let passthrough = PassthroughLines { containments, ..PassthroughLines::default() };
+    let output = NamedTempFile::new().unwrap();
+    let output_path = output.path().to_str().unwrap();
+    write_graph_to_gfa(&graph, &NodeSequences::default(), &path_key_ranges, &sequenceless, &link_overlaps, &passthrough, output_path, &None, &WriteOptions::default(), None, None, None).unwrap();
+    let gfa = std::fs::read_to_string(output_path).unwrap();
+    let lines: Vec<_> = gfa.lines().collect();
+    let containment_lines: Vec<_> = lines.iter().filter(|line| line.starts_with("C\t")).collect();
+    assert_eq!(containment_lines, vec![&"C\t1\t+\t2\t+\t2\t4M\tID:Z:alt1", &"C\t3\t-\t4\t+\t1\t2M"]);
+    let first_containment = lines.iter().position(|line| line.starts_with("C\t")).unwrap();
+    assert!(lines[..first_containment].iter().all(|line| !line.starts_with("P\t")));
+    assert!(lines[first_containment..].iter().all(|line| !line.starts_with("S\t") && !line.starts_with("L\t")));
+    assert_eq!(check_roundtrip(output_path, &graph, &NodeSequences::default(), &path_key_ranges, &sequenceless, &WriteOptions::default()).unwrap(), Vec::<String>::new());
+}
+
+#[test]
+fn test_write_long_path() {
+    // A million steps, with a few known overlaps and tags, against the collect-and-join line
+    let path_elements: Vec<PathStep> = (1..=1_000_000).map(|id| (id, id % 3 == 0)).collect();
+    let path_overlaps = [(1, 5), (500_000, 12), (999_999, 1)];
+    let tags = vec!["RC:i:7".to_string(), "XY:Z:abc".to_string()];
+    for overlaps in [&path_overlaps[..], &[]] {
+        let mut out = Vec::new();
+        write_path(&mut out, "sample#1#chr1", path_elements.iter().copied(), overlaps, &tags).unwrap();
+
+        let steps: Vec<String> = path_elements.iter().map(|&(id, is_reverse)| format!("{}{}", id, if is_reverse { '-' } else { '+' })).collect();
+        let overlap_field = if overlaps.is_empty() {
+            "*".to_string()
+        } else {
+            let known: FxHashMap<usize, usize> = overlaps.iter().copied().collect();
+            (1..path_elements.len()).map(|idx| format!("{}M", known.get(&idx).unwrap_or(&0))).collect::<Vec<_>>().join(",")
+        };
+        let expected = format!("P\tsample#1#chr1\t{}\t{}\t{}\n", steps.join(","), overlap_field, tags.join("\t"));
+        assert!(out == expected.as_bytes());
+    }
+}
+
+#[test]
+fn test_path_tags() {
+    // The second block gives another value to RF, and a tag of its own
+    let block_a = write_temp_gfa("S\t1\tACGT\nP\tsample#1#chr1:0-4\t1+\t*\tSN:Z:chr1\tRF:i:1\nP\tsample#2#chr1:0-4\t1+\t*\n");
+    let block_b = write_temp_gfa("S\t1\tGG\nP\tsample#1#chr1:4-6\t1+\t*\tRF:i:0\tSR:Z:block_b\nP\tsample#2#chr1:4-6\t1+\t*\n");
+    let gfa_list: Vec<String> = [&block_a, &block_b].iter().map(|block| block.path().to_str().unwrap().to_string()).collect();
+    let CombinedInput { mut graph, mut path_key_ranges, link_overlaps, mut sequenceless, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+    let mut edges = canonical_edges(&graph);
+    merge_path_ranges(&mut path_key_ranges, &mut graph, &mut edges, &mut NodeSequences::default(), &mut sequenceless, None, &mut RunSummary::default(), false);
+    assert_eq!(path_tag_conflicts(&path_key_ranges), 1);
+
+    let output = write_test_gfa_file(&graph, &path_key_ranges, &sequenceless, &link_overlaps, &WriteOptions::default()).unwrap();
+    let output_path = output.path().to_str().unwrap();
+    let gfa = std::fs::read_to_string(output_path).unwrap();
+    let path_lines: Vec<_> = gfa.lines().filter(|line| line.starts_with("P\t")).collect();
+    assert_eq!(path_lines, vec!["P\tsample#1#chr1\t1+,2+\t*\tSN:Z:chr1\tRF:i:1\tSR:Z:block_b", "P\tsample#2#chr1\t1+,2+\t*"]);
+
+    // The tags survive lacing the output again
+    let CombinedInput { path_key_ranges, .. } = read_gfa_files(&[output_path.to_string()], &ReadOptions { naive_join: true, ..ReadOptions::default() }).unwrap();
+    assert_eq!(path_key_ranges["sample#1#chr1"][0].tags, vec!["SN:Z:chr1", "RF:i:1", "SR:Z:block_b"]);
+}
+
+#[test]
+fn test_max_path_steps() {
+    // The block reuses the same nodes for consecutive ranges of the key, which are merged into one path
+    let block = write_temp_gfa("S\t1\tAC\nS\t2\tGT\nL\t1\t+\t2\t+\t0M\nL\t2\t+\t1\t+\t0M\nP\tsample#1#chr1:0-4\t1+,2+\t*\nP\tsample#1#chr1:4-8\t1+,2+\t*\nP\tsample#1#chr1:8-12\t1+,2+\t*\n");
+    let gfa_list = vec![block.path().to_str().unwrap().to_string()];
+    let CombinedInput { graph, mut path_key_ranges, sequenceless, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+    for (path_key, ranges) in path_key_ranges.iter_mut() {
+        sort_and_filter_ranges(path_key, ranges, false);
+    }
+
+    let write = |max_path_steps: usize| {
+        let options = WriteOptions { max_path_steps, ..WriteOptions::default() };
+        write_test_gfa_file(&graph, &path_key_ranges, &sequenceless, &LinkOverlaps::default(), &options)
+    };
+
+    let err = write(5).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert!(err.to_string().contains("sample#1#chr1"));
+
+    assert!(write(6).is_ok());
+
+    // Filled gap nodes count as steps, and are checked before they are created
+    let block = write_temp_gfa("S\t1\tAC\nS\t2\tGT\nL\t1\t+\t2\t+\t0M\nP\tsample#1#chr1:0-4\t1+,2+\t*\nP\tsample#1#chr1:6-10\t1+,2+\t*\n");
+    let CombinedInput { graph, mut path_key_ranges, sequenceless, .. } = read_gfa_files(&[block.path().to_str().unwrap().to_string()], &ReadOptions::default()).unwrap();
+    for (path_key, ranges) in path_key_ranges.iter_mut() {
+        sort_and_filter_ranges(path_key, ranges, false);
+    }
+    let write = |max_path_steps: usize| {
+        let options = WriteOptions { max_path_steps, fill_gaps: 1, ..WriteOptions::default() };
+        write_test_gfa_file(&graph, &path_key_ranges, &sequenceless, &LinkOverlaps::default(), &options)
+    };
+    assert!(write(4).is_err());
+    assert!(write(5).is_ok());
+}
+
+#[test]
+fn test_reverse_complement_edges_added_once() {
+    // Both blocks state the same links in both orientations
+    let block_a = write_temp_gfa("S\t1\tACGT\nS\t2\tGG\nL\t1\t+\t2\t+\t0M\nL\t2\t-\t1\t-\t0M\nP\tsample#1#chr1:0-6\t1+,2+\t*\n");
+    let block_b = write_temp_gfa("S\t1\tTT\nS\t2\tCA\nL\t2\t-\t1\t-\t0M\nL\t1\t+\t2\t+\t0M\nP\tsample#1#chr1:6-10\t1+,2+\t*\n");
+    let gfa_list = vec![
+        block_a.path().to_str().unwrap().to_string(),
+        block_b.path().to_str().unwrap().to_string(),
+    ];
+    let CombinedInput { mut graph, mut path_key_ranges, sequenceless, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+    assert_eq!(graph.edges().count(), 2);
+
+    // The junction edge is already present in its flipped form, so it is not duplicated
+    graph.create_edge(Edge(Handle::pack(3u64, true), Handle::pack(2u64, true)));
+    let ranges = path_key_ranges.get_mut("sample#1#chr1").unwrap();
+    sort_and_filter_ranges("sample#1#chr1", ranges, false);
+    let mut edges = canonical_edges(&graph);
+    link_contiguous_ranges("sample#1#chr1", ranges, &mut graph, &mut edges, false);
+    assert_eq!(graph.edges().count(), 3);
+
+    let gfa = write_test_gfa(&graph, &path_key_ranges, &sequenceless, &LinkOverlaps::default(), &WriteOptions::default());
+    assert_eq!(gfa.lines().filter(|l| l.starts_with("L\t")).count(), 3);
+}
+
+#[test]
+fn test_unparseable_path_names() {
+    let block = write_temp_gfa("S\t1\tACGT\nP\tsample#1#chr1:0-4\t1+\t*\nP\tsample#1#chr1\t1+\t*\nP\tsample#2#chr1:a-4\t1+\t*\n");
+    let mut parser_builder = GFAParserBuilder::none();
+    parser_builder.paths(true);
+    let parser = parser_builder.build();
+    let gfa = read_gfa(block.path().to_str().unwrap(), &parser, &ReadOptions::default()).unwrap();
+
+    let (num_paths, unparseable) = unparseable_path_names(&gfa, &ReadOptions::default());
+    assert_eq!(num_paths, 3);
+    assert_eq!(unparseable, vec!["sample#1#chr1".to_string(), "sample#2#chr1:a-4".to_string()]);
+
+    // Every name is a key when joining naively
+    assert!(unparseable_path_names(&gfa, &ReadOptions { naive_join: true, ..ReadOptions::default() }).1.is_empty());
+
+    let list = vec![block.path().to_str().unwrap().to_string()];
+    assert!(validate_input(&list, &ReadOptions::default()).unwrap());
+    let invalid = write_temp_gfa("S\t1\tACGT\nP\tsample#1#chr1\t1+\t*\n");
+    let list = vec![invalid.path().to_str().unwrap().to_string()];
+    assert!(!validate_input(&list, &ReadOptions::default()).unwrap());
+}
+
+#[test]
+fn test_no_links() {
+    let block_a = write_temp_gfa("S\t1\tACGT\nS\t2\tGG\nL\t1\t+\t2\t+\t0M\nP\tsample#1#chr1:0-6\t1+,2+\t*\n");
+    let block_b = write_temp_gfa("S\t1\tTT\nP\tsample#1#chr1:8-10\t1+\t*\n");
+    let gfa_list = vec![
+        block_a.path().to_str().unwrap().to_string(),
+        block_b.path().to_str().unwrap().to_string(),
+    ];
+    let CombinedInput { graph, mut path_key_ranges, sequenceless, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+    sort_and_filter_ranges("sample#1#chr1", path_key_ranges.get_mut("sample#1#chr1").unwrap(), false);
+
+    let write = |no_links: bool| {
+        // Fill the middle gap, whose links are skipped too
+        let options = WriteOptions { fill_gaps: 1, no_links, ..WriteOptions::default() };
+        write_test_gfa(&graph, &path_key_ranges, &sequenceless, &LinkOverlaps::default(), &options)
+    };
+    let with_links = write(false);
+    let without_links = write(true);
+
+    assert_eq!(with_links.lines().filter(|l| l.starts_with("L\t")).count(), 3);
+    assert!(!without_links.lines().any(|l| l.starts_with("L\t")));
+    let other_lines = |gfa: &str| gfa.lines().filter(|l| !l.starts_with("L\t")).map(str::to_string).collect::<Vec<_>>();
+    assert_eq!(other_lines(&with_links), other_lines(&without_links));
+    let path_line = without_links.lines().find(|l| l.starts_with("P\tsample#1#chr1\t")).unwrap();
+    assert_eq!(path_line.split('\t').nth(2).unwrap().split(',').count(), 4);
+}
+
+#[test]
+fn test_multiple_header_lines() {
+    // Two blocks concatenated with cat, with disagreeing headers
+    let block = write_temp_gfa("H\tVN:Z:1.0\nS\t1\tACGT\nP\tsample#1#chr1:0-4\t1+\t*\nH\tVN:Z:1.1\nS\t2\tGG\nP\tsample#1#chr1:4-6\t2+\t*\n");
+    let block_path = block.path().to_str().unwrap().to_string();
+    assert_eq!(header_versions(GfaInput::Path(&block_path).lines(&ReadOptions::default()).unwrap()).unwrap(), vec!["1.0".to_string(), "1.1".to_string()]);
+    assert!(check_header_versions(std::slice::from_ref(&block_path), &ReadOptions::default()).is_ok());
+
+    let CombinedInput { graph, path_key_ranges, sequenceless, .. } = read_gfa_files(&[block_path], &ReadOptions::default()).unwrap();
+    assert_eq!(path_key_ranges["sample#1#chr1"].len(), 2);
+
+    let gfa = write_test_gfa(&graph, &path_key_ranges, &sequenceless, &LinkOverlaps::default(), &WriteOptions::default());
+    let headers: Vec<_> = gfa.lines().filter(|l| l.starts_with("H\t")).collect();
+    assert_eq!(headers, vec!["H\tVN:Z:1.0"]);
+    assert!(gfa.starts_with("H\t"));
+}
+
+#[test]
+fn test_link_orientation_is_deterministic() {
+    // The same blocks, with the links listed in a different order and orientation
+    let write_lace = |links: &str| {
+        let block_a = write_temp_gfa(&format!("S\t1\tACGT\nS\t2\tGG\nS\t3\tT\n{}P\tsample#1#chr1:0-7\t1+,2+,3-\t*\n", links));
+        let block_b = write_temp_gfa("S\t1\tCA\nP\tsample#1#chr1:7-9\t1+\t*\n");
+        let gfa_list = vec![
+            block_a.path().to_str().unwrap().to_string(),
+            block_b.path().to_str().unwrap().to_string(),
+        ];
+        let CombinedInput { mut graph, mut path_key_ranges, sequenceless, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+        let mut edges = canonical_edges(&graph);
+        let ranges = path_key_ranges.get_mut("sample#1#chr1").unwrap();
+        sort_and_filter_ranges("sample#1#chr1", ranges, false);
+        link_contiguous_ranges("sample#1#chr1", ranges, &mut graph, &mut edges, false);
+
+        write_test_gfa(&graph, &path_key_ranges, &sequenceless, &LinkOverlaps::default(), &WriteOptions::default())
+    };
+
+    let first = write_lace("L\t1\t+\t2\t+\t0M\nL\t2\t+\t3\t-\t0M\n");
+    let second = write_lace("L\t3\t+\t2\t-\t0M\nL\t2\t-\t1\t-\t0M\n");
+    assert_eq!(first, second);
+
+    let links: Vec<_> = first.lines().filter(|l| l.starts_with("L\t")).collect();
+    assert_eq!(links, vec!["L\t1\t+\t2\t+\t0M", "L\t2\t+\t3\t-\t0M", "L\t3\t-\t4\t+\t0M"]);
+}
+
+#[test]
+fn test_mmap_parsing() {
+    let parser = GFAParser::new();
+    for content in [
+        "H\tVN:Z:1.0\nS\t1\tACGT\nS\t2\tGG\nL\t1\t+\t2\t-\t0M\nP\tsample#1#chr1:0-6\t1+,2-\t*\n",
+        "S\t1\tACGT\r\nP\tsample#1#chr1:0-4\t1+\t*",
+        "",
+    ] {
+        let block = write_temp_gfa(content);
+        let block_path = block.path().to_str().unwrap();
+        assert_eq!(parse_mapped_gfa(block_path, &parser).unwrap(), parser.parse_file(block_path).unwrap());
+    }
+
+    let malformed = write_temp_gfa("S\t1\tACGT\nL\tnot\ta\tlink\n");
+    let error = parse_mapped_gfa(malformed.path().to_str().unwrap(), &parser).err().unwrap();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+}
+
+/// Compares the reads of an uncompressed GFA of GFALACE_BENCH_MB megabytes (default: 2048) with
+/// --no-mmap and with the default memory mapping. Run with
+/// `cargo test --release -- --ignored --nocapture bench_mmap_parsing`
+#[test]
+#[ignore]
+fn bench_mmap_parsing() {
+    let target_bytes = std::env::var("GFALACE_BENCH_MB").ok().and_then(|mb| mb.parse::<u64>().ok()).unwrap_or(2048) << 20;
+    let mut block = tempfile::Builder::new().suffix(".gfa").tempfile().unwrap();
+    {
+        let mut writer = BufWriter::new(block.as_file_mut());
+        let (mut written, mut id) = (0u64, 1u64);
+        while written < target_bytes {
+            let steps: Vec<String> = (id..id + 100).map(|step| format!("{}+", step)).collect();
+            let mut lines = String::new();
+            for node in id..id + 100 {
+                lines.push_str(&format!("S\t{}\t{}\n", node, "ACGT".repeat(16)));
+                lines.push_str(&format!("L\t{}\t+\t{}\t+\t0M\n", node, node + 1));
+            }
+            lines.push_str(&format!("P\tsample#1#chr1:{}-{}\t{}\t*\n", id * 64, (id + 100) * 64, steps.join(",")));
+            writer.write_all(lines.as_bytes()).unwrap();
+            written += lines.len() as u64;
+            id += 100;
+        }
+    }
+    let block_path = block.path().to_str().unwrap();
+    let parser = GFAParser::new();
+    // Warm the page cache so that neither run pays for the first read from disk
+    io::copy(&mut File::open(block_path).unwrap(), &mut io::sink()).unwrap();
+
+    let read = |no_mmap: bool| {
+        let start = std::time::Instant::now();
+        let segments = read_gfa(block_path, &parser, &ReadOptions { no_mmap, ..ReadOptions::default() }).unwrap().segments.len();
+        (segments, start.elapsed())
+    };
+    let (buffered, buffered_time) = read(true);
+    let (mapped, mapped_time) = read(false);
+
+    assert_eq!(buffered, mapped);
+    println!("{} MB, {} segments: buffered {:?}, mmap {:?}", target_bytes >> 20, mapped, buffered_time, mapped_time);
+}
+
+/// Times the writing of a laced graph with one segment per 64 bp of a block of the given size in
+/// megabytes (default: 256). Run with `cargo test --release -- --ignored --nocapture bench_write_graph`
+#[test]
+#[ignore]
+fn bench_write_graph() {
+    let target_bytes = std::env::var("GFALACE_BENCH_MB").ok().and_then(|mb| mb.parse::<u64>().ok()).unwrap_or(256) << 20;
+    let mut block = tempfile::Builder::new().suffix(".gfa").tempfile().unwrap();
+    {
+        let mut writer = BufWriter::new(block.as_file_mut());
+        let (mut written, mut id) = (0u64, 1u64);
+        while written < target_bytes {
+            let steps: Vec<String> = (id..id + 100).map(|step| format!("{}+", step)).collect();
+            let mut lines = String::new();
+            for node in id..id + 100 {
+                lines.push_str(&format!("S\t{}\t{}\n", node, "ACGT".repeat(16)));
+            }
+            lines.push_str(&format!("P\tsample#1#chr{}:0-6400\t{}\t*\n", id, steps.join(",")));
+            writer.write_all(lines.as_bytes()).unwrap();
+            written += lines.len() as u64;
+            id += 100;
+        }
+    }
+    let CombinedInput { graph, path_key_ranges, link_overlaps, sequenceless, .. } = read_gfa_files(&[block.path().to_str().unwrap().to_string()], &ReadOptions::default()).unwrap();
+
+    for no_sort in [false, true] {
+        let start = std::time::Instant::now();
+        let options = WriteOptions { no_sort, ..WriteOptions::default() };
+        write_test_gfa_file(&graph, &path_key_ranges, &sequenceless, &link_overlaps, &options).unwrap();
+        println!("{} MB, {} segments, no_sort={}: {:?}", target_bytes >> 20, graph.node_count(), no_sort, start.elapsed());
+    }
+}
+
+/// Times the writing of one path key of GFALACE_BENCH_STEPS steps (default: 20000000) split into
+/// 1000 contiguous ranges, and prints the peak resident memory of the process. Run with
+/// `cargo test --release -- --ignored --nocapture bench_write_fragmented_path`
+#[test]
+#[ignore]
+fn bench_write_fragmented_path() {
+    let num_steps = std::env::var("GFALACE_BENCH_STEPS").ok().and_then(|count| count.parse::<usize>().ok()).unwrap_or(20_000_000);
+    let mut graph = HashGraph::new();
+    let handles: Vec<Handle> = (1..=1000u64).map(|id| graph.create_handle(b"ACGT", id)).collect();
+    let range_steps = num_steps / 1000;
+    let ranges: Vec<RangeInfo> = (0..1000)
+        .map(|idx| {
+            let start = idx * range_steps * 4;
+            RangeInfo {
+                steps: (0..range_steps).map(|step| handles[(idx + step) % 1000]).collect(),
+                step_ends: (1..=range_steps).map(|step| start + step * 4).collect(),
+                path_name: "sample#1#chr1".to_string(),
+                ..RangeInfo::new(start, start + range_steps * 4, 0)
+            }
+        })
+        .collect();
+    let mut path_key_ranges: FxHashMap<PathKey, Vec<RangeInfo>> = FxHashMap::default();
+    path_key_ranges.insert("sample#1#chr1".into(), ranges);
+    let peak_rss = || std::fs::read_to_string("/proc/self/status").ok()
+        .and_then(|status| status.lines().find(|line| line.starts_with("VmHWM:")).map(|line| line.split_whitespace().nth(1).unwrap_or("").to_string()))
+        .unwrap_or_default();
+    let before = peak_rss();
+
+    let start = std::time::Instant::now();
+    write_test_gfa_file(&graph, &path_key_ranges, &FxHashSet::default(), &LinkOverlaps::default(), &WriteOptions::default()).unwrap();
+    println!("{} steps in 1000 ranges: write {:?}, peak RSS {} kB before writing, {} kB after", num_steps, start.elapsed(), before, peak_rss());
+}
+
+/// Times the grouping of GFALACE_BENCH_PATHS path records (default: 2000000) by GFALACE_BENCH_KEYS
+/// path keys (default: 10000) when reading a block. On one core, allocating the key only for its
+/// first range took the read from 5.5-6.4 s down to 4.9-5.6 s. Run with
+/// `cargo test --release -- --ignored --nocapture bench_path_grouping`
+#[test]
+#[ignore]
+fn bench_path_grouping() {
+    let env_count = |name: &str, default: usize| std::env::var(name).ok().and_then(|count| count.parse::<usize>().ok()).unwrap_or(default);
+    let (num_keys, num_paths) = (env_count("GFALACE_BENCH_KEYS", 10_000), env_count("GFALACE_BENCH_PATHS", 2_000_000));
+    let mut block = tempfile::Builder::new().suffix(".gfa").tempfile().unwrap();
+    {
+        let mut writer = BufWriter::new(block.as_file_mut());
+        for node in 1..=100 {
+            writeln!(writer, "S\t{}\tACGT", node).unwrap();
+        }
+        // Consecutive records share their key, as the ranges of a haplotype do in a block
+        for idx in 0..num_paths {
+            let (key, range) = (idx * num_keys / num_paths, idx % (num_paths / num_keys).max(1));
+            writeln!(writer, "P\tsample{}#1#chr1:{}-{}\t{}+\t*", key, range * 4, range * 4 + 4, idx % 100 + 1).unwrap();
+        }
+    }
+
+    let start = std::time::Instant::now();
+    let combined = read_gfa_files(&[block.path().to_str().unwrap().to_string()], &ReadOptions::default()).unwrap();
+    let read_time = start.elapsed();
+    let start = std::time::Instant::now();
+    let mut path_keys: Vec<&PathKey> = combined.path_key_ranges.keys().collect();
+    path_keys.sort();
+    let sort_time = start.elapsed();
+
+    assert_eq!(path_keys.len(), num_keys.min(num_paths));
+    assert_eq!(combined.path_key_ranges.values().map(Vec::len).sum::<usize>(), num_paths);
+    println!("{} path keys, {} paths: read {:?}, key sort {:?}", path_keys.len(), num_paths, read_time, sort_time);
+}
+
+/// Times the merge of GFALACE_BENCH_KEYS path keys (default: 200000), each with two overlapping
+/// ranges, on one thread and on all cores, and checks that both give the same output. Run with
+/// `cargo test --release -- --ignored --nocapture bench_merge_path_ranges`
+#[test]
+#[ignore]
+fn bench_merge_path_ranges() {
+    let num_keys = std::env::var("GFALACE_BENCH_KEYS").ok().and_then(|count| count.parse::<usize>().ok()).unwrap_or(200_000);
+    let write_block = |start: usize| {
+        let mut block = tempfile::Builder::new().suffix(".gfa").tempfile().unwrap();
+        {
+            let mut writer = BufWriter::new(block.as_file_mut());
+            for key in 0..num_keys {
+                writeln!(writer, "S\t{}\tACGT\nS\t{}\tACGT\nL\t{}\t+\t{}\t+\t0M", 2 * key + 1, 2 * key + 2, 2 * key + 1, 2 * key + 2).unwrap();
+                writeln!(writer, "P\tsample{}#1#chr1:{}-{}\t{}+,{}+\t*", key, start, start + 8, 2 * key + 1, 2 * key + 2).unwrap();
+            }
+        }
+        block
+    };
+    // The second range starts in the middle of a node of the first one, which is split
+    let blocks = [write_block(0), write_block(2)];
+    let gfa_list: Vec<String> = blocks.iter().map(|block| block.path().to_str().unwrap().to_string()).collect();
+
+    let mut checksums = Vec::new();
+    for threads in [1, 0] {
+        let CombinedInput { mut graph, mut path_key_ranges, link_overlaps, mut sequenceless, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+        let start = std::time::Instant::now();
+        let mut edges = canonical_edges(&graph);
+        pool.install(|| merge_path_ranges(&mut path_key_ranges, &mut graph, &mut edges, &mut NodeSequences::default(), &mut sequenceless, None, &mut RunSummary::default(), false));
+        println!("{} path keys, {} threads: merge {:?}", num_keys, pool.current_num_threads(), start.elapsed());
+
+        let output = write_test_gfa_file(&graph, &path_key_ranges, &sequenceless, &link_overlaps, &WriteOptions::default()).unwrap();
+        checksums.push(gfa_checksum(output.path().to_str().unwrap()).unwrap());
+    }
+    assert_eq!(checksums[0], checksums[1]);
+}
+
+/// Times the merge of GFALACE_BENCH_KEYS path keys (default: 50000) whose first range ends on
+/// the same hub segment, so that every junction adds an edge to it, and compares the lookup of
+/// the junction edges in the kept edge set with has_edge. Run with
+/// `cargo test --release -- --ignored --nocapture bench_merge_dense`
+#[test]
+#[ignore]
+fn bench_merge_dense() {
+    let num_keys = std::env::var("GFALACE_BENCH_KEYS").ok().and_then(|count| count.parse::<usize>().ok()).unwrap_or(50_000);
+    let mut blocks = [tempfile::Builder::new().suffix(".gfa").tempfile().unwrap(), tempfile::Builder::new().suffix(".gfa").tempfile().unwrap()];
+    {
+        let mut writer = BufWriter::new(blocks[0].as_file_mut());
+        writeln!(writer, "S\t1\tACGT").unwrap();
+        for key in 0..num_keys {
+            writeln!(writer, "S\t{}\tACGT\nL\t{}\t+\t1\t+\t0M\nP\tsample{}#1#chr1:0-8\t{}+,1+\t*", key + 2, key + 2, key, key + 2).unwrap();
+        }
+    }
+    {
+        let mut writer = BufWriter::new(blocks[1].as_file_mut());
+        for key in 0..num_keys {
+            writeln!(writer, "S\t{}\tACGT\nP\tsample{}#1#chr1:8-12\t{}+\t*", key + 1, key, key + 1).unwrap();
+        }
+    }
+    let gfa_list: Vec<String> = blocks.iter().map(|block| block.path().to_str().unwrap().to_string()).collect();
+
+    let CombinedInput { mut graph, mut path_key_ranges, mut sequenceless, mut edges, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+    let start = std::time::Instant::now();
+    merge_path_ranges(&mut path_key_ranges, &mut graph, &mut edges, &mut NodeSequences::default(), &mut sequenceless, None, &mut RunSummary::default(), false);
+    let merge_time = start.elapsed();
+    let start = std::time::Instant::now();
+    let rebuilt = canonical_edges(&graph);
+    let rebuild_time = start.elapsed();
+    assert_eq!(edges, rebuilt);
+
+    let junctions: Vec<(Handle, Handle)> = path_key_ranges.values()
+        .map(|ranges| (*ranges[0].steps.last().unwrap(), ranges[1].steps[0]))
+        .collect();
+    let start = std::time::Instant::now();
+    assert!(junctions.iter().all(|&(left, right)| edges.contains(&Edge::edge_handle(left, right))));
+    let set_time = start.elapsed();
+    let start = std::time::Instant::now();
+    assert!(junctions.iter().all(|&(left, right)| graph.has_edge(left, right)));
+    let has_edge_time = start.elapsed();
+    println!("{} path keys, hub degree {}: merge {:?}, edge set rebuild {:?}, junction lookups in the set {:?}, with has_edge {:?}",
+        num_keys, graph.degree(Handle::pack(1, false), Direction::Left) + graph.degree(Handle::pack(1, false), Direction::Right),
+        merge_time, rebuild_time, set_time, has_edge_time);
+}
+
+#[test]
+fn test_tar_archive_inputs() {
+    // Members in archive order: a GFA, a non-GFA file, and a gzipped GFA
+    let archive = tempfile::Builder::new().suffix(".tar").tempfile().unwrap();
+    {
+        let mut builder = tar::Builder::new(archive.reopen().unwrap());
+        let mut append = |name: &str, content: &[u8]| {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, content).unwrap();
+        };
+        append("z_block.gfa", b"# second\nS\t1\tGG\nP\tsample#1#chr1:4-6\t1+\t*\n");
+        append("README.txt", b"not a GFA");
+        let mut gzipped = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzipped.write_all(b"# first\nS\t1\tACGT\nP\tsample#1#chr1:0-4\t1+\t*\n").unwrap();
+        append("a_block.gfa.gz", &gzipped.finish().unwrap());
+        builder.finish().unwrap();
+    }
+    let archive_path = archive.path().to_str().unwrap().to_string();
+    let gfa_list = vec![archive_path.clone()];
+
+    let member_names = |sort_archive: bool| {
+        let mut names = Vec::new();
+        for_each_input(&gfa_list, &ReadOptions { sort_archive, ..ReadOptions::default() }, |gfa_id, input| {
+            names.push((gfa_id, input.name().to_string()));
+            Ok(())
+        }).unwrap();
+        names
+    };
+    assert_eq!(member_names(false), vec![
+        (0, format!("{}/z_block.gfa", archive_path)),
+        (1, format!("{}/a_block.gfa.gz", archive_path)),
+    ]);
+    assert_eq!(member_names(true), vec![
+        (0, format!("{}/a_block.gfa.gz", archive_path)),
+        (1, format!("{}/z_block.gfa", archive_path)),
+    ]);
+
+    // IDs follow the iteration order
+    let CombinedInput { path_key_ranges, skipped_inputs, .. } = read_gfa_files(&gfa_list, &ReadOptions { sort_archive: true, ..ReadOptions::default() }).unwrap();
+    assert_eq!(skipped_inputs, 1);
+    let mut ranges: Vec<_> = path_key_ranges["sample#1#chr1"].iter().map(|r| (r.start, r.gfa_id)).collect();
+    ranges.sort();
+    assert_eq!(ranges, vec![(0, 0), (4, 1)]);
+
+    // Diagnostics name the members
+    let passthrough = collect_passthrough_lines(&gfa_list, &ReadOptions::default(), true, false).unwrap();
+    assert_eq!(passthrough.comments, vec![
+        format!("# {}/z_block.gfa: second", archive_path),
+        format!("# {}/a_block.gfa.gz: first", archive_path),
+    ]);
+
+    assert!(is_tar_archive("blocks.tar.gz"));
+    assert!(is_tar_archive("blocks.tgz"));
+    assert!(!is_tar_archive("block.gfa.gz"));
+}
+
+#[test]
+fn test_decompress_ahead() {
+    // Gzipped blocks around a plain one, so that prefetched and directly parsed inputs interleave
+    let dir = tempfile::tempdir().unwrap();
+    let gfa_list: Vec<String> = (0..6).map(|i| {
+        let content = format!("S\t1\t{}\nS\t2\tAC\nL\t1\t+\t2\t+\t0M\nP\tsample#1#chr1:{}-{}\t1+,2+\t*\n",
+            "G".repeat(i + 1), i * 10, i * 10 + i + 3);
+        let path = dir.path().join(format!("block{}.gfa{}", i, if i == 2 { "" } else { ".gz" }));
+        if i == 2 {
+            std::fs::write(&path, content).unwrap();
+        } else {
+            let mut gzipped = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            gzipped.write_all(content.as_bytes()).unwrap();
+            std::fs::write(&path, gzipped.finish().unwrap()).unwrap();
+        }
+        path.to_str().unwrap().to_string()
+    }).collect();
+
+    let read = |decompress_ahead: usize, decompress_budget: u64| {
+        let options = ReadOptions { decompress_ahead, decompress_budget, ..ReadOptions::default() };
+        let CombinedInput { graph, path_key_ranges, gfa_names, id_translations, .. } = read_gfa_files(&gfa_list, &options).unwrap();
+        let ranges: Vec<_> = path_key_ranges["sample#1#chr1"].iter()
+            .map(|r| (r.start, r.end, r.gfa_id, r.steps.clone())).collect();
+        let sequences: Vec<_> = (1..=graph.node_count() as u64).map(|id| graph.sequence_vec(Handle::pack(id, false))).collect();
+        (ranges, gfa_names, id_translations, sequences)
+    };
+    let serial = read(0, DECOMPRESS_BUDGET);
+    let prefetched = read(2, DECOMPRESS_BUDGET);
+    let budgeted = read(4, 1);
+    assert_eq!(serial.0.len(), 6);
+    assert_eq!(serial, prefetched);
+    assert_eq!(serial, budgeted);
+
+    // With a budget of one byte, only the next input to parse is decompressed
+    let prefetcher = Prefetcher::new(&gfa_list, 4, 1).unwrap();
+    let gzipped: Vec<&String> = gfa_list.iter().filter(|path| is_prefetchable(path)).collect();
+    for gfa_path in &gzipped {
+        let gfa = prefetcher.parse(gfa_path, &GFAParser::new(), &ReadOptions::default()).unwrap();
+        assert_eq!(gfa.segments.len(), 2);
+    }
+    let largest = gzipped.iter()
+        .map(|path| io::copy(&mut flate2::read::MultiGzDecoder::new(open_file(path).unwrap()), &mut io::sink()).unwrap())
+        .max().unwrap();
+    assert!(prefetcher.peak_bytes() > 0 && prefetcher.peak_bytes() <= largest + 1);
+
+    assert!(is_prefetchable("block.gfa.gz"));
+    assert!(!is_prefetchable("block.gfa"));
+    assert!(!is_prefetchable("blocks.tar.gz"));
+    assert!(Prefetcher::new(&gfa_list, 0, DECOMPRESS_BUDGET).is_none());
+}
+
+#[test]
+fn test_concatenated_gzip_members() {
+    // Like `cat a.gz b.gz`: every member must be decompressed, not just the first
+    let mut concatenated = Vec::new();
+    for member in ["S\t1\tACGT\nS\t2\tGG\n", "S\t3\tTTA\nP\tsample#1#chr1:0-9\t1+,2+,3+\t*\n"] {
+        let mut gzipped = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzipped.write_all(member.as_bytes()).unwrap();
+        concatenated.extend(gzipped.finish().unwrap());
+    }
+    let block = tempfile::Builder::new().suffix(".gfa.gz").tempfile().unwrap();
+    block.as_file().write_all(&concatenated).unwrap();
+    let block_path = block.path().to_str().unwrap().to_string();
+
+    let gfa = read_gfa(&block_path, &GFAParser::new(), &ReadOptions::default()).unwrap();
+    assert_eq!(gfa.segments.len(), 3);
+    assert_eq!(gfa.paths.len(), 1);
+    let CombinedInput { graph, path_key_ranges, .. } = read_gfa_files(&[block_path], &ReadOptions::default()).unwrap();
+    assert_eq!(graph.node_count(), 3);
+    assert_eq!(path_key_ranges["sample#1#chr1"][0].steps.len(), 3);
+}
+
+#[test]
+fn test_keep_temp_on_parse_failure() {
+    let mut gzipped = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    gzipped.write_all(b"S\t1\tACGT\nL\tnot\ta\tlink\n").unwrap();
+    let block = tempfile::Builder::new().suffix(".gfa.gz").tempfile().unwrap();
+    block.as_file().write_all(&gzipped.finish().unwrap()).unwrap();
+    let parser = GFAParser::new();
+
+    let error = read_gfa(block.path().to_str().unwrap(), &parser, &ReadOptions { keep_temp: true, ..ReadOptions::default() }).err().unwrap().to_string();
+
+    let kept_path = error.split("decompressed copy kept at '").nth(1).unwrap().split('\'').next().unwrap();
+    assert_eq!(std::fs::read(kept_path).unwrap(), b"S\t1\tACGT\nL\tnot\ta\tlink\n");
+    std::fs::remove_file(kept_path).unwrap();
+}
+
+#[test]
+fn test_sort_and_filter_all_ranges_matches_serial() {
+    // Thousands of keys with overlapping, contained, and duplicate ranges
+    let mut path_key_ranges: FxHashMap<PathKey, Vec<RangeInfo>> = FxHashMap::default();
+    for key in 0..2000usize {
+        let ranges = (0..20usize)
+            .map(|idx| {
+                let start = (idx * 37 + key * 11) % 400;
+                create_range_info(start, start + 10 + (idx * key) % 90, idx)
+            })
+            .collect();
+        path_key_ranges.insert(format!("sample{}#1#chr1", key).into(), ranges);
+    }
+
+    let mut serial = path_key_ranges.clone();
+    for (path_key, ranges) in serial.iter_mut() {
+        sort_and_filter_ranges(path_key, ranges, false);
+    }
+    sort_and_filter_all_ranges(&mut path_key_ranges, false);
+
+    assert_eq!(path_key_ranges.len(), serial.len());
+    for (path_key, ranges) in &path_key_ranges {
+        let bounds = |ranges: &[RangeInfo]| ranges.iter().map(|r| (r.start, r.end, r.gfa_id)).collect::<Vec<_>>();
+        assert_eq!(bounds(ranges), bounds(&serial[path_key]));
+    }
+}
+
+#[test]
+fn test_config_precedence() {
+    let mut config = tempfile::Builder::new().suffix(".toml").tempfile().unwrap();
+    write!(config, "gfa-list = [\"a.gfa\", \"b.gfa\"]\noutput = \"config.gfa\"\nfill-gaps = 1\nno-links = true\nmax-path-steps = 1000\n").unwrap();
+    let config_path = config.path().to_str().unwrap();
+
+    // Command line options take precedence over the config file
+    let args = parse_args(["gfalace", "--config", config_path, "--fill-gaps", "2", "-o", "cli.gfa"]).unwrap();
+    assert_eq!(args.gfa_list, vec!["a.gfa".to_string(), "b.gfa".to_string()]);
+    assert_eq!(args.output.as_deref(), Some("cli.gfa"));
+    assert_eq!(args.fill_gaps, 2);
+    assert!(args.no_links);
+    assert_eq!(args.max_path_steps, Some(1000));
+    // Defaults are kept for options set nowhere
+    assert!(!args.no_sequences);
+    assert_eq!(args.verbose, 0);
+
+    let args = parse_args(["gfalace", "--config", config_path]).unwrap();
+    assert_eq!(args.output.as_deref(), Some("config.gfa"));
+    assert_eq!(args.fill_gaps, 1);
+
+    // A list file of the config file adds to its gfa-list, unless inputs are given on the command line
+    let list = tempfile::Builder::new().suffix(".txt").tempfile().unwrap();
+    std::fs::write(list.path(), "c.gfa\n").unwrap();
+    let mut with_list = tempfile::Builder::new().suffix(".toml").tempfile().unwrap();
+    write!(with_list, "gfa-list = [\"a.gfa\"]\ngfa-list-file = {:?}\n", list.path().to_str().unwrap()).unwrap();
+    let args = parse_args(["gfalace", "--config", with_list.path().to_str().unwrap()]).unwrap();
+    assert_eq!(args.gfa_list, ["a.gfa", "c.gfa"]);
+    assert_eq!(args.gfa_list_file.as_deref(), list.path().to_str());
+    let args = parse_args(["gfalace", "--config", with_list.path().to_str().unwrap(), "-g", "d.gfa"]).unwrap();
+    assert_eq!(args.gfa_list, ["d.gfa"]);
+
+    // Unknown keys are errors
+    let mut invalid = tempfile::Builder::new().suffix(".toml").tempfile().unwrap();
+    write!(invalid, "output = \"out.gfa\"\nfill-gap = 1\n").unwrap();
+    let error = parse_args(["gfalace", "--config", invalid.path().to_str().unwrap()]).err().unwrap();
+    assert!(error.to_string().contains("fill-gap"), "{}", error);
+}
+
+#[test]
+fn test_gfa_list_forms() {
+    let dir = tempfile::tempdir().unwrap();
+    let spaced = dir.path().join("block one.gfa");
+    std::fs::write(&spaced, "S\t1\tA\n").unwrap();
+    let spaced = spaced.to_str().unwrap();
+
+    // Repeated -g flags, comma-separated lists and trailing arguments, merged in order
+    let args = parse_args(["gfalace", "-g", "a.gfa", "-g", "b.gfa,c.gfa", "-o", "out.gfa", "d.gfa", "e.gfa"]).unwrap();
+    assert_eq!(args.gfa_list, ["a.gfa", "b.gfa", "c.gfa", "d.gfa", "e.gfa"]);
+    assert!(!args.space_delimited_gfa_list);
+
+    // An existing path is kept whole, while the deprecated space-separated form is still split
+    let args = parse_args(["gfalace", "-g", spaced, "x.gfa y.gfa"]).unwrap();
+    assert_eq!(args.gfa_list, [spaced, "x.gfa", "y.gfa"]);
+    assert!(args.space_delimited_gfa_list);
+
+    // Only the parts of a split value that do not exist are reported
+    let args = parse_args(["gfalace", "-g", &format!("{},typo.gfa", spaced), "-o", "out.gfa"]).unwrap();
+    assert_eq!(args.gfa_list, [spaced, "typo.gfa"]);
+    assert_eq!(args.missing_split_inputs, ["typo.gfa"]);
+    let args = parse_args(["gfalace", "-o", "out.gfa", spaced]).unwrap();
+    assert!(args.missing_split_inputs.is_empty());
+
+    // A trailing input named like a subcommand is an input after other inputs, or with a path
+    let args = parse_args(["gfalace", "-o", "out.gfa", "a.gfa", "diff"]).unwrap();
+    assert_eq!(args.gfa_list, ["a.gfa", "diff"]);
+    assert!(args.command.is_none());
+    let args = parse_args(["gfalace", "-o", "out.gfa", "./extract"]).unwrap();
+    assert_eq!(args.gfa_list, ["./extract"]);
+    assert!(parse_args(["gfalace", "-g", "a.gfa", "-o", "out.gfa", "diff", "old.gfa", "new.gfa"]).is_err());
+    assert!(parse_args(["gfalace", "diff", "old.gfa", "new.gfa"]).unwrap().command.is_some());
+
+    // List file entries are taken where the option is given, verbatim
+    let list = dir.path().join("inputs.txt");
+    std::fs::write(&list, format!("# blocks\nl1.gfa\n\n{}\n", spaced)).unwrap();
+    let list = list.to_str().unwrap();
+    let args = parse_args(["gfalace", "-g", "a.gfa", "--gfa-list-file", list, "-g", "b.gfa"]).unwrap();
+    assert_eq!(args.gfa_list, ["a.gfa", "l1.gfa", spaced, "b.gfa"]);
+
+    // Inputs on the command line replace those of a config file
+    let config = dir.path().join("config.toml");
+    std::fs::write(&config, "gfa-list = [\"config.gfa\"]\n").unwrap();
+    let config = config.to_str().unwrap();
+    let args = parse_args(["gfalace", "--config", config, "--gfa-list-file", list]).unwrap();
+    assert_eq!(args.gfa_list, ["l1.gfa", spaced]);
+    let args = parse_args(["gfalace", "--config", config]).unwrap();
+    assert_eq!(args.gfa_list, ["config.gfa"]);
+}
+
+#[test]
+fn test_blocks_tsv() {
+    let block_a = write_temp_gfa("S\t1\tACGT\nS\t2\tGG\nL\t1\t+\t2\t+\t0M\nP\tsample#1#chr1\t1+,2+\t*\nP\tsample#2#chr1:0-6\t1+,2+\t*\n");
+    let block_b = write_temp_gfa("S\t1\tTTT\nP\tsample#1#chr1:100-103\t1+\t*\nP\tsample#2#chr1:6-9\t1+\t*\n");
+    let (path_a, path_b) = (block_a.path().to_str().unwrap(), block_b.path().to_str().unwrap());
+    let mut tsv = NamedTempFile::new().unwrap();
+    write!(tsv, "#community\tseq\tgfa\tfrom\tto\n0\tsample#1#chr1\t{path_a}\t0\t6\n1\tsample#1#chr1\t{path_b}\t6\t9\n1\tsample#3#chr1\t{path_b}\t6\t9\n").unwrap();
+    let columns: Vec<String> = ["gfa", "seq", "from", "to"].map(String::from).to_vec();
+    let table = read_blocks_tsv(tsv.path().to_str().unwrap(), &columns).unwrap();
+    assert_eq!(table.files, [path_a, path_b]);
+    assert_eq!(table.contig_lengths(), FxHashMap::from_iter([("sample#1#chr1".to_string(), 9), ("sample#3#chr1".to_string(), 9)]));
+    assert!(table.missing_files(&[]).is_empty());
+
+    // The table names the path without a range and overrides the conflicting one, and other paths keep their ranges
+    let gfa_list = vec![path_a.to_string(), path_b.to_string()];
+    let combined = read_gfa_files(&gfa_list, &ReadOptions { block_table: Some(&table), ..ReadOptions::default() }).unwrap();
+    assert_eq!((combined.dropped_paths, combined.block_range_conflicts), (0, 1));
+    let bounds = |path_key: &str| -> Vec<(usize, usize, usize)> {
+        combined.path_key_ranges[path_key].iter().map(|range| (range.start, range.end, range.gfa_id)).collect()
+    };
+    assert_eq!(bounds("sample#1#chr1"), [(0, 6, 0), (6, 9, 1)]);
+    assert_eq!(bounds("sample#2#chr1"), [(0, 6, 0), (6, 9, 1)]);
+    assert_eq!(combined.path_key_ranges["sample#1#chr1"][1].step_ends, [9]);
+
+    // Columns are found by name, and the files that do not exist are reported
+    let error = read_blocks_tsv(tsv.path().to_str().unwrap(), &["file", "seq", "from", "to"].map(String::from)).err().unwrap();
+    assert!(error.to_string().contains("no column 'file'"), "{}", error);
+    let mut missing = NamedTempFile::new().unwrap();
+    write!(missing, "file\tcontig\tstart\tend\n{}\tchr1\t0\t6\nmissing.gfa\tchr1\t6\t9\n", path_a).unwrap();
+    let table = read_blocks_tsv(missing.path().to_str().unwrap(), &["file", "contig", "start", "end"].map(String::from)).unwrap();
+    assert_eq!(table.missing_files(&gfa_list), ["missing.gfa"]);
+    assert_eq!(table.unmatched_files(&gfa_list), ["missing.gfa"]);
+
+    // Files match the inputs by their resolved path, however it is written
+    let dotted = Path::new(path_a).parent().unwrap().join(".").join(Path::new(path_a).file_name().unwrap());
+    let mut dotted_tsv = NamedTempFile::new().unwrap();
+    write!(dotted_tsv, "file\tcontig\tstart\tend\n{}\tsample#1#chr1\t0\t6\n", dotted.display()).unwrap();
+    let table = read_blocks_tsv(dotted_tsv.path().to_str().unwrap(), &["file", "contig", "start", "end"].map(String::from)).unwrap();
+    assert!(table.unmatched_files(&gfa_list).is_empty());
+    let combined = read_gfa_files(&gfa_list, &ReadOptions { block_table: Some(&table), ..ReadOptions::default() }).unwrap();
+    assert_eq!(combined.dropped_paths, 0);
+    assert_eq!(combined.path_key_ranges["sample#1#chr1"][0].end, 6);
+}
+
+#[test]
+fn test_stream_parse() {
+    // A chain of segments walked by two haplotypes, one skipping every tenth segment, with the paths
+    // before the segments they visit, a link overlap, a containment, and a segment without sequence
+    let num_segments = 50_000;
+    let length = |id: usize| id % 7 + 1;
+    let steps = |skip: bool| (1..=num_segments).filter(|id| !skip || id % 10 != 0).map(|id| format!("{}+", id)).collect::<Vec<_>>();
+    let span = |skip: bool| (1..=num_segments).filter(|id| !skip || id % 10 != 0).map(length).sum::<usize>() - 1;
+    let mut gfa = String::from("H\tVN:Z:1.0\n");
+    gfa.push_str(&format!("P\tsample#1#chr1:0-{}\t{}\t*\tXX:Z:one\n", span(false), steps(false).join(",")));
+    gfa.push_str(&format!("P\tsample#2#chr1:0-{}\t{}\t*\n", span(true), steps(true).join(",")));
+    for id in 1..=num_segments {
+        let sequence: String = (0..length(id)).map(|offset| b"ACGT"[(id + offset) % 4] as char).collect();
+        gfa.push_str(&format!("S\t{}\t{}\n", id, sequence));
+    }
+    gfa.push_str("L\t1\t+\t2\t+\t1M\n");
+    for id in 2..num_segments {
+        gfa.push_str(&format!("L\t{}\t+\t{}\t+\t0M\n", id, id + 1));
+        if id % 10 == 9 && id + 2 <= num_segments {
+            gfa.push_str(&format!("L\t{}\t+\t{}\t+\t0M\n", id, id + 2));
+        }
+    }
+    gfa.push_str(&format!("S\t{}\t*\tLN:i:5\nP\tsample#3#chr1:0-5\t{}+\t*\nC\t1\t+\t2\t-\t0\t2M\n", num_segments + 1, num_segments + 1));
+    let block = write_temp_gfa(&gfa);
+    let block_path = block.path().to_str().unwrap();
+
+    let parsed = read_gfa_files(&[block_path.to_string()], &ReadOptions::default()).unwrap();
+    let mut streamed = CombinedInput::new();
+    let (max_id, duplicates) = streamed.stream_block(BufReader::new(File::open(block_path).unwrap()), block_path, &FxHashMap::default(), &ReadOptions::default(), None, &mut EdgeSet::default()).unwrap();
+    assert_eq!((max_id, duplicates), (num_segments as u64 + 1, vec![]));
+
+    // The streamed block is the parsed one
+    assert_eq!(streamed.graph.node_count(), parsed.graph.node_count());
+    assert_eq!(canonical_edges(&streamed.graph), canonical_edges(&parsed.graph));
+    assert_eq!(parsed.edges, canonical_edges(&parsed.graph));
+    for handle in parsed.graph.handles() {
+        assert!(streamed.graph.sequence(handle).eq(parsed.graph.sequence(handle)), "{:?}", handle);
+    }
+    assert_eq!(streamed.link_overlaps, parsed.link_overlaps);
+    assert_eq!(streamed.sequenceless, parsed.sequenceless);
+    assert_eq!(streamed.containments, parsed.containments);
+    assert_eq!(streamed.containments.len(), 1);
+    let ranges = |combined: &CombinedInput| {
+        let mut ranges: Vec<_> = combined.path_key_ranges.iter()
+            .flat_map(|(path_key, ranges)| ranges.iter().map(move |range| (path_key.to_string(), range.start, range.end, range.steps.clone(), range.step_ends.clone(), range.tags.clone())))
+            .collect();
+        ranges.sort();
+        ranges
+    };
+    let streamed_ranges = ranges(&streamed);
+    assert_eq!(streamed_ranges, ranges(&parsed));
+    assert_eq!(streamed_ranges.len(), 3);
+    assert_eq!(streamed_ranges[0].4.last(), Some(&span(false)));
+    assert_eq!(streamed_ranges[0].5, ["XX:Z:one"]);
+
+    // A step that does not parse fails the block instead of being dropped, while walks are skipped
+    let stream = |gfa: &str| CombinedInput::new().stream_block(gfa.as_bytes(), "block.gfa", &FxHashMap::default(), &ReadOptions::default(), None, &mut EdgeSet::default());
+    let error = stream("S\t1\tA\nS\t2\tC\nP\tsample#1#chr1:0-2\t1+,2\t*\n").unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    assert!(error.to_string().contains("invalid step '2'"), "{}", error);
+    assert!(stream("S\t1\tA\nW\tsample\t1\tchr1\t0\t1\t>1\n").is_ok());
+}
+
+#[test]
+fn test_ignore_input_links() {
+    // Links that no path traverses, including one between blocks, are not recreated
+    let block_a = write_temp_gfa("S\t1\tACGT\nS\t2\tGG\nS\t3\tT\nL\t1\t+\t2\t+\t0M\nL\t1\t+\t3\t+\t0M\nL\t3\t+\t2\t+\t0M\nP\tsample#1#chr1:0-6\t1+,2+\t*\nP\tsample#2#chr1:0-7\t1+,3+,2+\t*\n");
+    let block_b = write_temp_gfa("S\t1\tTTT\nS\t2\tCA\nL\t1\t+\t2\t-\t0M\nL\t2\t+\t1\t+\t0M\nP\tsample#1#chr1:6-11\t1+,2-\t*\nP\tsample#2#chr1:7-10\t1+\t*\n");
+    let gfa_list = vec![block_a.path().to_str().unwrap().to_string(), block_b.path().to_str().unwrap().to_string()];
+    let CombinedInput { mut graph, mut path_key_ranges, link_overlaps, mut sequenceless, .. } = read_gfa_files(&gfa_list, &ReadOptions { ignore_links: true, ..ReadOptions::default() }).unwrap();
+    assert_eq!(graph.edge_count(), 0);
+    let mut edges = canonical_edges(&graph);
+    merge_path_ranges(&mut path_key_ranges, &mut graph, &mut edges, &mut NodeSequences::default(), &mut sequenceless, None, &mut RunSummary::default(), false);
+    assert_eq!(link_path_steps(&path_key_ranges, &mut graph, &mut edges), 4);
+
+    let gfa = write_test_gfa(&graph, &path_key_ranges, &sequenceless, &link_overlaps, &WriteOptions::default());
+    let orient = |reverse: bool| if reverse { "-" } else { "+" };
+    let canonical = |(from, from_reverse): (u64, bool), (to, to_reverse): (u64, bool)| {
+        let edge = Edge::edge_handle(Handle::pack(from, from_reverse), Handle::pack(to, to_reverse));
+        format!("{}{}{}{}", u64::from(edge.0.id()), orient(edge.0.is_reverse()), u64::from(edge.1.id()), orient(edge.1.is_reverse()))
+    };
+    let mut links: Vec<String> = gfa.lines().filter(|line| line.starts_with("L\t"))
+        .map(|line| {
+            let fields: Vec<&str> = line.split('\t').collect();
+            canonical((fields[1].parse().unwrap(), fields[2] == "-"), (fields[3].parse().unwrap(), fields[4] == "-"))
+        })
+        .collect();
+    let mut path_links: Vec<String> = gfa.lines().filter(|line| line.starts_with("P\t"))
+        .flat_map(|line| {
+            let steps: Vec<(u64, bool)> = line.split('\t').nth(2).unwrap().split(',')
+                .map(|step| (step[..step.len() - 1].parse().unwrap(), step.ends_with('-')))
+                .collect();
+            steps.windows(2).map(|pair| canonical(pair[0], pair[1])).collect::<Vec<_>>()
+        })
+        .collect();
+    links.sort();
+    path_links.sort();
+    path_links.dedup();
+    assert_eq!(links, path_links);
+    assert_eq!(links.len(), 5);
+}
+
+#[test]
+fn test_existing_outputs() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+    let output = path("out.gfa");
+    let args = parse_args(["gfalace", "-g", "a.gfa", "-o", &output, "--checksum", "--liftover", &path("liftover.tsv")]).unwrap();
+    assert!(existing_outputs(&args, &output).unwrap().is_empty());
+
+    // The output and the auxiliary files of a previous run are found
+    for name in ["out.gfa", "out.gfa.sha256", "liftover.tsv", "unrelated.tsv"] {
+        std::fs::write(path(name), "").unwrap();
+    }
+    assert_eq!(existing_outputs(&args, &output).unwrap(), [path("liftover.tsv"), path("out.gfa"), path("out.gfa.sha256")]);
+
+    // Shards are found by number, rather than the output itself
+    let args = parse_args(["gfalace", "-g", "a.gfa", "-o", &output, "--shard-size", "1000"]).unwrap();
+    assert!(existing_outputs(&args, &output).unwrap().is_empty());
+    for name in ["out.0012.gfa", "out.manifest.json", "out.12.gfa"] {
+        std::fs::write(path(name), "").unwrap();
+    }
+    assert_eq!(existing_outputs(&args, &output).unwrap(), [path("out.0012.gfa"), path("out.manifest.json")]);
+}
+
+#[test]
+fn test_input_collisions() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+    std::fs::write(path("a.gfa"), "").unwrap();
+    std::fs::write(path("lengths.tsv"), "").unwrap();
+    std::fs::create_dir(path("sub")).unwrap();
+    let collisions = |output: &str, extra: &[&str]| {
+        let args = parse_args(["gfalace", "-g", &path("a.gfa"), "-o", output, "--force"].iter().chain(extra)).unwrap();
+        input_collisions(&args, &output_files(&args, output).unwrap())
+    };
+    assert!(collisions(&path("out.gfa"), &[]).is_empty());
+    assert!(collisions(&path("b.gfa"), &[]).is_empty());
+
+    // Through a path with '..'
+    assert_eq!(collisions(&path("sub/../a.gfa"), &[]), [(path("sub/../a.gfa"), path("a.gfa"))]);
+
+    // Auxiliary outputs are checked against the auxiliary inputs too
+    assert_eq!(collisions(&path("out.gfa"), &["--contig-lengths", &path("lengths.tsv"), "--liftover", &path("sub/../lengths.tsv")]),
+        [(path("sub/../lengths.tsv"), path("lengths.tsv"))]);
+}
+
+#[test]
+#[cfg(unix)]
+fn test_input_collisions_unix() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = |name: &str| dir.path().join(name).to_str().unwrap().to_string();
+    std::fs::write(path("a.gfa"), "").unwrap();
+    std::os::unix::fs::symlink(path("a.gfa"), path("link.gfa")).unwrap();
+    let args = parse_args(["gfalace", "-g", &path("a.gfa"), "-o", &path("link.gfa"), "--force"]).unwrap();
+    assert_eq!(input_collisions(&args, &output_files(&args, &path("link.gfa")).unwrap()), [(path("link.gfa"), path("a.gfa"))]);
+
+    // Through a path relative to the working directory, climbing to the root
+    let absolute = std::fs::canonicalize(path("a.gfa")).unwrap();
+    let depth = std::env::current_dir().unwrap().components().count() - 1;
+    let relative = format!("{}{}", "../".repeat(depth), absolute.strip_prefix("/").unwrap().to_str().unwrap());
+    let absolute = absolute.to_str().unwrap().to_string();
+    let args = parse_args(["gfalace", "-g", &absolute, "-o", &relative]).unwrap();
+    assert_eq!(input_collisions(&args, &output_files(&args, &relative).unwrap()), [(relative.clone(), absolute)]);
+}
+
+#[test]
+fn test_check_path_edges() {
+    // The first block lacks its internal link from 2 to 3, while the blocks are linked by the merge
+    let block_a = write_temp_gfa("S\t1\tACGT\nS\t2\tGG\nS\t3\tT\nL\t1\t+\t2\t+\t0M\nP\tsample#1#chr1:0-7\t1+,2+,3+\t*\nP\tsample#2#chr1:0-6\t1+,2+\t*\n");
+    let block_b = write_temp_gfa("S\t1\tTTT\nS\t2\tCA\nL\t1\t+\t2\t-\t0M\nP\tsample#1#chr1:7-12\t1+,2-\t*\nP\tsample#2#chr1:6-9\t1+\t*\n");
+    let gfa_list = vec![block_a.path().to_str().unwrap().to_string(), block_b.path().to_str().unwrap().to_string()];
+    let CombinedInput { mut graph, mut path_key_ranges, mut sequenceless, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+    let mut edges = canonical_edges(&graph);
+    merge_path_ranges(&mut path_key_ranges, &mut graph, &mut edges, &mut NodeSequences::default(), &mut sequenceless, None, &mut RunSummary::default(), false);
+    assert_eq!(missing_path_edges(&path_key_ranges, &graph), [("sample#1#chr1".to_string(), 6, 1)]);
+
+    // With the edge, every path is consistent
+    graph.create_edge(Edge(Handle::pack(2, false), Handle::pack(3, false)));
+    assert!(missing_path_edges(&path_key_ranges, &graph).is_empty());
+}
+
+#[test]
+fn test_atomic_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let output_path = dir.path().join("out.gfa").to_str().unwrap().to_string();
+    let files = || -> Vec<String> {
+        let mut files: Vec<String> = std::fs::read_dir(dir.path()).unwrap().map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned()).collect();
+        files.sort();
+        files
+    };
+    std::fs::write(&output_path, "previous\n").unwrap();
+
+    // A write error, injected through a wrapper writer, leaves the previous file alone
+    struct FailingWriter<W: Write> { inner: W, remaining: usize }
+    impl<W: Write> Write for FailingWriter<W> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if buf.len() > self.remaining {
+                return Err(io::Error::other("injected write error"));
+            }
+            self.remaining -= buf.len();
+            self.inner.write(buf)
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            self.inner.flush()
+        }
+    }
+    let write = |remaining: usize| -> io::Result<()> {
+        let mut output = AtomicFile::create(&output_path)?;
+        let mut out = FailingWriter { inner: &mut output, remaining };
+        out.write_all(b"S\t1\tACGT\n")?;
+        out.write_all(b"S\t2\tGG\n")?;
+        output.finish()
+    };
+    assert!(write(12).is_err());
+    assert_eq!(files(), ["out.gfa"]);
+    assert_eq!(std::fs::read_to_string(&output_path).unwrap(), "previous\n");
+
+    // So does a failure of the graph writer
+    let block = write_temp_gfa("S\t1\tACGT\nS\t2\tGG\nL\t1\t+\t2\t+\t0M\nP\tsample#1#chr1:0-6\t1+,2+\t*\n");
+    let CombinedInput { graph, path_key_ranges, link_overlaps, sequenceless, .. } = read_gfa_files(&[block.path().to_str().unwrap().to_string()], &ReadOptions::default()).unwrap();
+    let write_graph = |options: &WriteOptions| write_graph_to_gfa(&graph, &NodeSequences::default(), &path_key_ranges, &sequenceless, &link_overlaps, &PassthroughLines::default(), &output_path, &None, options, None, None, None);
+    assert!(write_graph(&WriteOptions { max_path_steps: 1, ..WriteOptions::default() }).is_err());
+    assert_eq!(files(), ["out.gfa"]);
+    assert_eq!(std::fs::read_to_string(&output_path).unwrap(), "previous\n");
+
+    // A complete output replaces it
+    write(100).unwrap();
+    assert_eq!(std::fs::read_to_string(&output_path).unwrap(), "S\t1\tACGT\nS\t2\tGG\n");
+    write_graph(&WriteOptions::default()).unwrap();
+    assert_eq!(files(), ["out.gfa"]);
+    assert!(std::fs::read_to_string(&output_path).unwrap().contains("P\tsample#1#chr1\t1+,2+"));
+}
+
+#[test]
+fn test_key_map() {
+    let mut key_map_file = NamedTempFile::new().unwrap();
+    writeln!(key_map_file, "# path name\tkey\tstart\tend\nHG002.chr1.block7\tHG002#1#chr1\t0\t4\nweird_HG002_blk2\tHG002#1#chr1\t4\t6\nHG002#1#chr2:0-3\tHG002#1#chr2\t10\t13").unwrap();
+    key_map_file.flush().unwrap();
+    let key_map = read_key_map(key_map_file.path().to_str().unwrap()).unwrap();
+    assert_eq!(key_map.len(), 3);
+    let options = ReadOptions { key_map: Some(&key_map), ..ReadOptions::default() };
+
+    // Mapped names use the map, even where they parse, and the others are parsed
+    let block = write_temp_gfa("S\t1\tACGT\nS\t2\tGG\nS\t3\tTTT\nP\tHG002.chr1.block7\t1+\t*\nP\tweird_HG002_blk2\t2+\t*\nP\tHG002#1#chr2:0-3\t3+\t*\nP\tHG002#2#chr1:0-6\t1+,2+\t*\nP\tunmapped_name\t3+\t*\n");
+    let gfa_list = vec![block.path().to_str().unwrap().to_string()];
+    let CombinedInput { path_key_ranges, dropped_paths, .. } = read_gfa_files(&gfa_list, &options).unwrap();
+    let ranges = |key: &str| -> Vec<(String, usize, usize)> {
+        let mut ranges: Vec<(String, usize, usize)> = path_key_ranges[key].iter().map(|range| (range.path_name.clone(), range.start, range.end)).collect();
+        ranges.sort_by_key(|range| range.1);
+        ranges
+    };
+    assert_eq!(path_key_ranges.len(), 3);
+    assert_eq!(ranges("HG002#1#chr1"), vec![("HG002.chr1.block7".to_string(), 0, 4), ("weird_HG002_blk2".to_string(), 4, 6)]);
+    assert_eq!(ranges("HG002#1#chr2"), vec![("HG002#1#chr2:0-3".to_string(), 10, 13)]);
+    assert_eq!(ranges("HG002#2#chr1"), vec![("HG002#2#chr1:0-6".to_string(), 0, 6)]);
+    assert_eq!(dropped_paths, 1);
+    // The map only applies to the reads given it
+    assert_eq!(read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap().dropped_paths, 3);
+
+    let mut invalid = NamedTempFile::new().unwrap();
+    writeln!(invalid, "a\tkey\t0\t4\na\tkey\t4\t8").unwrap();
+    invalid.flush().unwrap();
+    assert!(read_key_map(invalid.path().to_str().unwrap()).unwrap_err().to_string().contains("repeats path name 'a'"));
+}
+
+#[test]
+fn test_path_overlaps() {
+    // Overlaps stated by the P line, and by an L line for a path without them
+    let block_a = write_temp_gfa("S\t1\tACGT\nS\t2\tGTA\nS\t3\tC\nP\tsample#1#chr1:0-6\t1+,2+,3+\t2M,0M\n");
+    let block_b = write_temp_gfa("S\t1\tAAC\nS\t2\tCGG\nL\t1\t+\t2\t+\t1M\nP\tsample#2#chr1:0-5\t1+,2+\t*\nS\t3\tT\nP\tsample#3#chr1:0-1\t3+\t*\n");
+    let gfa_list = vec![
+        block_a.path().to_str().unwrap().to_string(),
+        block_b.path().to_str().unwrap().to_string(),
+    ];
+    let CombinedInput { graph, path_key_ranges, link_overlaps, sequenceless, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+
+    let gfa = write_test_gfa(&graph, &path_key_ranges, &sequenceless, &link_overlaps, &WriteOptions::default());
+    let overlaps = |path_key: &str| {
+        let line = gfa.lines().find(|l| l.starts_with(&format!("P\t{}\t", path_key))).unwrap();
+        line.split('\t').nth(3).unwrap().to_string()
+    };
+
+    assert_eq!(overlaps("sample#1#chr1"), "2M,0M");
+    assert_eq!(overlaps("sample#2#chr1"), "1M");
+    // No overlap is known
+    assert_eq!(overlaps("sample#3#chr1"), "*");
+
+    // The L line of block B keeps its overlap, whichever way the links are written
+    let tmp_dir = tempfile::tempdir().unwrap();
+    for options in [
+        WriteOptions::default(),
+        WriteOptions { no_sort: true, ..WriteOptions::default() },
+        WriteOptions { external_sort: Some(tmp_dir.path().to_path_buf()), sort_chunk_size: 1, ..WriteOptions::default() },
+    ] {
+        let links: Vec<String> = write_test_gfa(&graph, &path_key_ranges, &sequenceless, &link_overlaps, &options).lines().filter(|line| line.starts_with("L\t")).map(str::to_string).collect();
+        assert_eq!(links, ["L\t4\t+\t5\t+\t1M"]);
+    }
+}
+
+#[test]
+fn test_duplicate_segments() {
+    let clean = write_temp_gfa("S\t1\tACGT\nP\tsample#1#chr1:0-4\t1+\t*\n");
+    let broken = write_temp_gfa("S\t1\tACGT\nS\t2\tGG\nS\t1\tTTTT\nS\t2\tGG\nS\t2\tGG\nP\tsample#1#chr1:4-10\t1+,2+\t*\n");
+    let gfa_list = vec![
+        clean.path().to_str().unwrap().to_string(),
+        broken.path().to_str().unwrap().to_string(),
+    ];
+    let CombinedInput { duplicate_segments, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+
+    // Only the broken block is reported, with each duplicate ID once
+    assert_eq!(duplicate_segments, vec![(gfa_list[1].clone(), vec![1, 2])]);
+    assert_eq!(format_segment_ids(&duplicate_segments[0].1), "1, 2");
+    assert_eq!(format_segment_ids(&(1..=12).collect::<Vec<_>>()), "1, 2, 3, 4, 5, 6, 7, 8, 9, 10, ...");
+}
+
+#[test]
+fn test_relace_fragments() {
+    let block_a = write_temp_gfa("S\t1\tACGT\nS\t2\tGG\nL\t1\t+\t2\t+\t0M\nP\tsample#1#chr:1:0-6\t1+,2+\t*\n");
+    let block_b = write_temp_gfa("S\t1\tTTT\nP\tsample#1#chr:1:10-13\t1+\t*\n");
+    let gfa_list = vec![
+        block_a.path().to_str().unwrap().to_string(),
+        block_b.path().to_str().unwrap().to_string(),
+    ];
+    let CombinedInput { graph, mut path_key_ranges, sequenceless, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+    for (path_key, ranges) in path_key_ranges.iter_mut() {
+        sort_and_filter_ranges(path_key, ranges, false);
+    }
+
+    let write = |fragment_naming: FragmentNaming| {
+        let options = WriteOptions { fragment_naming, ..WriteOptions::default() };
+        write_test_gfa_file(&graph, &path_key_ranges, &sequenceless, &LinkOverlaps::default(), &options).unwrap()
+    };
+    let path_names = |output: &NamedTempFile| -> Vec<String> {
+        std::fs::read_to_string(output.path()).unwrap().lines()
+            .filter(|l| l.starts_with("P\t"))
+            .map(|l| l.split('\t').nth(1).unwrap().to_string())
+            .collect()
+    };
+
+    // The coordinates of the fragments are recovered when lacing the output again
+    for (naming, expected_names) in [
+        (FragmentNaming::Pansn, ["sample#1#chr:1#1:0-6", "sample#1#chr:1#2:10-13"]),
+        (FragmentNaming::Range, ["sample#1#chr:1:0-6", "sample#1#chr:1:10-13"]),
+        (FragmentNaming::Brackets, ["sample#1#chr:1[0-6]", "sample#1#chr:1[10-13]"]),
+    ] {
+        let output = write(naming);
+        assert_eq!(path_names(&output), expected_names);
+
+        let CombinedInput { path_key_ranges: relaced, .. } = read_gfa_files(&[output.path().to_str().unwrap().to_string()], &ReadOptions::default()).unwrap();
+        assert_eq!(relaced.len(), 1);
+        let mut coordinates: Vec<_> = relaced["sample#1#chr:1"].iter().map(|r| (r.start, r.end, r.step_ends.clone())).collect();
+        coordinates.sort();
+        assert_eq!(coordinates, vec![(0, 6, vec![4, 6]), (10, 13, vec![13])]);
+    }
+}
+
+#[test]
+fn test_run_summary() {
+    let ranges = vec![
+        create_range_info(0, 100, 0),
+        create_range_info(100, 200, 1),
+        create_range_info(250, 300, 2),
+        create_range_info(310, 400, 3),
+    ];
+    assert_eq!(gap_length(&ranges), 60);
+    assert_eq!(gap_length(&ranges[..2]), 0);
+
+    let clean = RunSummary { parsed_files: 4, new_edges: 3, ..RunSummary::default() };
+    assert!(!clean.has_issues());
+    assert_eq!(clean.strict_violations(), 0);
+
+    // Gaps are reported, but only duplicate segments and junction mismatches fail under --strict
+    let gaps = RunSummary { keys_with_gaps: 1, gap_bp: 60, ..RunSummary::default() };
+    assert!(gaps.has_issues());
+    assert_eq!(gaps.strict_violations(), 0);
+    let mismatches = RunSummary { junction_mismatches: 2, duplicate_segment_files: 1, ..RunSummary::default() };
+    assert!(mismatches.has_issues());
+    assert_eq!(mismatches.strict_violations(), 3);
+}
+
+#[test]
+fn test_large_gaps() {
+    // A 5 Mb gap, as left by a missing block, and a small one
+    let mut path_key_ranges = FxHashMap::default();
+    path_key_ranges.insert("sample#1#chr1".into(), vec![
+        create_range_info(0, 100, 0),
+        create_range_info(5_000_100, 5_000_200, 1),
+        create_range_info(5_000_250, 5_000_300, 2),
+    ]);
+    path_key_ranges.insert("sample#1#chr2".into(), vec![create_range_info(0, 100, 0), create_range_info(100, 200, 1)]);
+
+    let gaps = large_gaps(&path_key_ranges, 1_000_000);
+    assert_eq!(gaps, vec![("sample#1#chr1".to_string(), 100, 5_000_000)]);
+    assert_eq!(large_gaps(&path_key_ranges, 10).len(), 2);
+    assert!(large_gaps(&path_key_ranges, usize::MAX).is_empty());
+
+    // They fail the run under --strict
+    let summary = RunSummary { large_gaps: gaps.len(), ..RunSummary::default() };
+    assert_eq!(summary.strict_violations(), 1);
+}
+
+#[test]
+fn test_expected_overlap() {
+    // Blocks tiled with a 1000 bp overlap, one 3 bp overlap, and a 1000 bp overlap within a block
+    let mut path_key_ranges = FxHashMap::default();
+    path_key_ranges.insert("sample#1#chr1".into(), vec![
+        create_range_info(0, 3000, 0),
+        create_range_info(2000, 5000, 1),
+        create_range_info(4000, 7000, 2),
+    ]);
+    path_key_ranges.insert("sample#1#chr2".into(), vec![create_range_info(0, 3000, 0), create_range_info(2997, 6000, 1)]);
+    path_key_ranges.insert("sample#1#chr3".into(), vec![create_range_info(0, 3000, 0), create_range_info(2000, 5000, 0)]);
+
+    let unexpected = unexpected_overlaps(&path_key_ranges, 1000);
+    let described: Vec<(&str, usize, usize)> = unexpected.iter().map(|(path_key, overlap)| (path_key.as_str(), overlap.second.start, overlap.size)).collect();
+    assert_eq!(described, vec![("sample#1#chr2", 2997, 3), ("sample#1#chr3", 2000, 1000)]);
+    // Contained ranges are never expected
+    path_key_ranges.insert("sample#1#chr4".into(), vec![create_range_info(0, 3000, 0), create_range_info(1000, 2000, 1)]);
+    assert_eq!(unexpected_overlaps(&path_key_ranges, 1000).len(), 3);
+    assert_eq!(unexpected_overlaps(&path_key_ranges, 3).len(), 4);
+
+    let summary = RunSummary { unexpected_overlaps: unexpected.len(), ..RunSummary::default() };
+    assert_eq!(summary.strict_violations(), 2);
+}
+
+#[test]
+fn test_end_gaps() {
+    let lengths_file = write_temp_gfa("#name\tlength\nchr1\t1000\t6\t60\t61\nchr2\t500\nchrM\t16569\nsample#2#chr3\t50\n");
+    let contig_lengths = read_contig_lengths(lengths_file.path().to_str().unwrap()).unwrap();
+    assert_eq!(contig_lengths.len(), 4);
+    assert_eq!(contig_length(&contig_lengths, "sample#1#chr1"), Some(1000));
+    assert_eq!(contig_length(&contig_lengths, "sample#2#chr3"), Some(50));
+
+    // chr1 lacks its first 10 bp for one haplotype and its last block for the other, chr2 is
+    // complete, chrM is missing, and chr4 has no known length
+    let mut path_key_ranges = FxHashMap::default();
+    path_key_ranges.insert("sample#1#chr1".into(), vec![create_range_info(10, 600, 0), create_range_info(600, 1000, 1)]);
+    path_key_ranges.insert("sample#2#chr1".into(), vec![create_range_info(0, 600, 0)]);
+    path_key_ranges.insert("sample#1#chr2".into(), vec![create_range_info(0, 500, 0)]);
+    path_key_ranges.insert("sample#2#chr3".into(), vec![create_range_info(0, 50, 0)]);
+    path_key_ranges.insert("sample#1#chr4".into(), vec![create_range_info(5, 50, 0)]);
+    let (gaps, missing) = end_gaps(&path_key_ranges, &contig_lengths);
+    assert_eq!(gaps, vec![("sample#1#chr1".to_string(), 0, 10), ("sample#2#chr1".to_string(), 600, 1000)]);
+    assert_eq!(missing, vec!["chrM".to_string()]);
+
+    // They fail the run under --strict
+    let summary = RunSummary { end_gaps: gaps.len(), missing_contigs: missing.len(), ..RunSummary::default() };
+    assert_eq!(summary.strict_violations(), 3);
+
+    let invalid = write_temp_gfa("chr1\tlong\n");
+    assert!(read_contig_lengths(invalid.path().to_str().unwrap()).is_err());
+}
+
+#[test]
+fn test_only_complete() {
+    let mut path_key_ranges = FxHashMap::default();
+    path_key_ranges.insert("sample#1#chr1".into(), vec![create_range_info(0, 600, 0), create_range_info(600, 1000, 1)]);
+    path_key_ranges.insert("sample#2#chr1".into(), vec![create_range_info(0, 600, 0)]);
+    path_key_ranges.insert("sample#3#chr1".into(), vec![create_range_info(0, 500, 0), create_range_info(600, 1000, 1)]);
+    path_key_ranges.insert("sample#1#chr2".into(), vec![create_range_info(10, 500, 0)]);
+    path_key_ranges.insert("sample#1#chr4".into(), vec![create_range_info(0, 50, 0)]);
+
+    // Without lengths, a single gapless path from 0 is complete, however short
+    let mut unknown_lengths = path_key_ranges.clone();
+    assert_eq!(drop_incomplete_keys(&mut unknown_lengths, None), vec!["sample#1#chr2", "sample#3#chr1"]);
+    assert_eq!(unknown_lengths.len(), 3);
+
+    // With lengths, the path must also reach the end of its contig
+    let contig_lengths = FxHashMap::from_iter([("chr1".to_string(), 1000), ("chr2".to_string(), 500)]);
+    assert_eq!(drop_incomplete_keys(&mut path_key_ranges, Some(&contig_lengths)), vec!["sample#1#chr2", "sample#2#chr1", "sample#3#chr1"]);
+    let mut kept: Vec<&str> = path_key_ranges.keys().map(|key| &**key).collect();
+    kept.sort();
+    assert_eq!(kept, vec!["sample#1#chr1", "sample#1#chr4"]);
+
+    // The skipped keys are reported
+    let summary = RunSummary { incomplete_keys: 3, ..RunSummary::default() };
+    assert!(summary.has_issues());
+}
+
+#[test]
+fn test_streaming_writer_matches_sorted_writer() {
+    let block_a = write_temp_gfa("S\t1\tACGT\nS\t2\tGG\nS\t3\tTT\nS\t4\tC\nL\t1\t+\t2\t-\t0M\nL\t3\t-\t2\t+\t0M\nL\t3\t+\t3\t+\t0M\nL\t4\t-\t1\t-\t0M\nP\tsample#1#chr1:0-9\t1+,2-,3+,4+\t*\n");
+    let block_b = write_temp_gfa("S\t7\tTTTTT\nS\t9\tA\nL\t9\t-\t7\t+\t0M\nP\tsample#1#chr1:9-15\t9-,7+\t*\nP\tsample#1#chr2:0-5\t7+\t*\n");
+    let block_c = write_temp_gfa("S\t1\tCCC\nP\tsample#1#chr1:20-23\t1+\t*\n");
+    let gfa_list: Vec<String> = [&block_a, &block_b, &block_c].iter()
+        .map(|block| block.path().to_str().unwrap().to_string())
+        .collect();
+    let CombinedInput { mut graph, mut path_key_ranges, link_overlaps, mut sequenceless, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+    let mut edges = canonical_edges(&graph);
+    sort_and_filter_all_ranges(&mut path_key_ranges, false);
+    for (path_key, ranges) in path_key_ranges.iter_mut() {
+        let cuts = overlap_cuts(ranges);
+        trim_range_overlaps(path_key, ranges, &cuts, &mut graph, &mut NodeSequences::default(), &mut edges, &mut sequenceless, &mut FxHashMap::default(), false);
+        link_contiguous_ranges(path_key, ranges, &mut graph, &mut edges, false);
+    }
+
+    for (fill_gaps, no_links) in [(0, false), (1, false), (1, true)] {
+        let write = |no_sort: bool| {
+            let options = WriteOptions { fill_gaps, no_links, no_sort, ..WriteOptions::default() };
+            write_test_gfa(&graph, &path_key_ranges, &sequenceless, &link_overlaps, &options)
+        };
+        let sorted = write(false);
+        assert!(no_links || sorted.lines().filter(|l| l.starts_with("L\t")).count() >= 6);
+        // Byte for byte, so --no-sort only changes the memory use
+        assert_eq!(write(true), sorted);
+    }
+}
+
+#[test]
+fn test_external_sort() {
+    // Random links in both orientations, with reverse-complement and exact duplicates
+    let num_nodes = 200;
+    let mut gfa = String::new();
+    for node_id in 1..=num_nodes {
+        gfa.push_str(&format!("S\t{}\tA\n", node_id));
+    }
+    let mut state: u64 = 7;
+    let mut next = |bound: u64| {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (state >> 33) % bound
+    };
+    let orient = |reverse: bool| if reverse { "-" } else { "+" };
+    for _ in 0..1000 {
+        let (from, to, from_reverse, to_reverse) = (1 + next(num_nodes), 1 + next(num_nodes), next(2) == 1, next(2) == 1);
+        gfa.push_str(&format!("L\t{}\t{}\t{}\t{}\t0M\n", from, orient(from_reverse), to, orient(to_reverse)));
+        if next(4) == 0 {
+            gfa.push_str(&format!("L\t{}\t{}\t{}\t{}\t0M\n", to, orient(!to_reverse), from, orient(!from_reverse)));
+        }
+    }
+    let steps: Vec<String> = (1..=num_nodes).map(|node_id| format!("{}+", node_id)).collect();
+    gfa.push_str(&format!("P\tsample#1#chr1:0-{}\t{}\t*\n", num_nodes, steps.join(",")));
+    let block = write_temp_gfa(&gfa);
+    let CombinedInput { graph, mut path_key_ranges, link_overlaps, sequenceless, .. } = read_gfa_files(&[block.path().to_str().unwrap().to_string()], &ReadOptions::default()).unwrap();
+    sort_and_filter_all_ranges(&mut path_key_ranges, false);
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let write = |options: WriteOptions| {
+        write_test_gfa(&graph, &path_key_ranges, &sequenceless, &link_overlaps, &options)
+    };
+    let sorted = write(WriteOptions::default());
+    assert!(sorted.lines().filter(|line| line.starts_with("L\t")).count() > 900);
+    // Chunks of 1 and 7 links are more than MERGE_FAN_IN, and take several merge passes
+    for sort_chunk_size in [1, 7, 100, SORT_CHUNK_SIZE] {
+        let options = WriteOptions { external_sort: Some(tmp_dir.path().to_path_buf()), sort_chunk_size, ..WriteOptions::default() };
+        assert_eq!(write(options), sorted, "chunks of {}", sort_chunk_size);
+    }
+    // The chunks do not outlive the run
+    assert_eq!(std::fs::read_dir(tmp_dir.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn test_path_node_order() {
+    let block = write_temp_gfa("S\t1\tAC\nS\t2\tG\nS\t3\tTTA\nS\t4\tC\nL\t3\t+\t1\t+\t0M\nL\t1\t+\t2\t+\t0M\nP\tsample#1#chr1:0-6\t3+,1+,2+\t*\n");
+    let CombinedInput { graph, mut path_key_ranges, link_overlaps, sequenceless, .. } = read_gfa_files(&[block.path().to_str().unwrap().to_string()], &ReadOptions::default()).unwrap();
+    sort_and_filter_all_ranges(&mut path_key_ranges, false);
+
+    for no_sort in [false, true] {
+        let options = WriteOptions { no_sort, node_order: NodeOrder::PathOrder, ..WriteOptions::default() };
+        let gfa = write_test_gfa(&graph, &path_key_ranges, &sequenceless, &link_overlaps, &options);
+        let segments: Vec<_> = gfa.lines().filter(|l| l.starts_with("S\t")).collect();
+        assert_eq!(segments, vec!["S\t1\tTTA", "S\t2\tAC", "S\t3\tG"]);
+        let steps: Vec<u64> = gfa.lines().find(|l| l.starts_with("P\t")).unwrap().split('\t').nth(2).unwrap()
+            .split(',').map(|step| step.trim_end_matches(['+', '-']).parse().unwrap())
+            .collect();
+        assert!(steps.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+}
+
+#[test]
+fn test_quiet_logging() {
+    let enabled = |argv: &[&str], level: log::Level, target: &str| {
+        let args = parse_args(["gfalace"].iter().chain(argv)).unwrap();
+        let logger = logger_builder(&args).build();
+        log::Log::enabled(&logger, &log::Metadata::builder().level(level).target(target).build())
+    };
+
+    // -qq < -q < default < -v 1 < -v 2
+    assert!(!enabled(&["-q"], log::Level::Warn, "gfalace"));
+    assert!(enabled(&["-q"], log::Level::Error, "gfalace"));
+    assert!(enabled(&["-q"], log::Level::Warn, SUMMARY_TARGET));
+    assert!(enabled(&["-q"], log::Level::Info, SUMMARY_TARGET));
+    assert!(!enabled(&["-qq"], log::Level::Warn, SUMMARY_TARGET));
+    assert!(enabled(&["-qq"], log::Level::Error, "gfalace"));
+    assert!(enabled(&[], log::Level::Warn, "gfalace"));
+    assert!(!enabled(&[], log::Level::Info, "gfalace"));
+    assert!(enabled(&["-v", "1"], log::Level::Info, "gfalace"));
+    assert!(enabled(&["-v", "2"], log::Level::Debug, "gfalace"));
+
+    assert!(Args::command().try_get_matches_from(["gfalace", "-q", "-v", "1"]).is_err());
+}
+
+#[test]
+fn test_log_file() {
+    // The only test installing a global logger; events of concurrent tests are valid lines too. The log
+    // file exists already, so it is only overwritten with --force
+    let log_file = NamedTempFile::new().unwrap();
+    let args = parse_args(["gfalace", "-qq", "--force", "--log-file", log_file.path().to_str().unwrap()]).unwrap();
+    init_logger(&args).unwrap();
+    let block = write_temp_gfa("S\t1\tACGT\nS\t2\tGG\nL\t1\t+\t2\t+\t0M\nP\tsample#1#chr1:0-6\t1+,2+\t*\n");
+    read_gfa_files(&[block.path().to_str().unwrap().to_string()], &ReadOptions::default()).unwrap();
+    RunSummary { parsed_files: 1, ..RunSummary::default() }.log();
+    log::logger().flush();
+
+    let events: Vec<serde_json::Value> = std::fs::read_to_string(log_file.path()).unwrap().lines()
+        .map(|line| serde_json::from_str(line).unwrap_or_else(|e| panic!("Invalid log line {:?}: {}", line, e)))
+        .collect();
+    assert!(events.iter().all(|event| event.is_object() && event["level"].is_string() && event["message"].is_string()));
+    let parsed = events.iter().find(|event| event["file"] == block.path().to_str().unwrap() && event["nodes"].is_u64()).unwrap();
+    assert_eq!((parsed["nodes"].as_u64(), parsed["edges"].as_u64()), (Some(2), Some(1)));
+    let summary = events.iter().find(|event| event["stage"] == "summary").unwrap();
+    assert_eq!(summary["parsed_files"], 1);
+    assert_eq!(summary["level"], "info");
+}
+
+#[test]
+fn test_gaf_paths() {
+    assert_eq!(parse_gaf_path(">1<2>10"), Some(vec![(1, false), (2, true), (10, false)]));
+    assert_eq!(parse_gaf_path("chr1"), None);
+    assert_eq!(parse_gaf_path(">1<"), None);
+
+    let block_a = write_temp_gfa("S\t1\tACGT\nS\t2\tGG\nL\t1\t+\t2\t+\t0M\nP\tsample#1#chr1:0-6\t1+,2+\t*\n");
+    let block_b = write_temp_gfa("S\t1\tTTT\nS\t2\tCA\nS\t3\tG\nL\t1\t+\t2\t+\t0M\nL\t2\t+\t3\t+\t0M\n");
+    let gfa_list = vec![
+        block_a.path().to_str().unwrap().to_string(),
+        block_b.path().to_str().unwrap().to_string(),
+    ];
+    let CombinedInput { graph, mut path_key_ranges, id_translations, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+
+    // Forward alignment of a subsequence query starting 1 bp into its first segment,
+    // reverse alignment of a whole contig, and an unmapped record
+    let gaf = write_temp_gfa(concat!(
+        "sample#1#chr1:6-100\t94\t1\t6\t+\t>1>2\t5\t1\t5\t5\t5\t60\n",
+        "sample#2#chr1\t6\t0\t6\t-\t>1>2>3\t6\t0\t6\t6\t6\t60\n",
+        "sample#3#chr1\t6\t0\t0\t*\t*\t0\t0\t0\t0\t0\t0\n",
+    ));
+    let num_paths = read_gaf_paths(gaf.path().to_str().unwrap(), 1, id_translations[1], &graph, &NodeSequences::default(), &ReadOptions::default(), &mut path_key_ranges).unwrap();
+    assert_eq!(num_paths, 2);
+
+    let translate = |id: u64, is_reverse: bool| Handle::pack(id_translations[1] + id, is_reverse);
+    let ranges = &path_key_ranges["sample#1#chr1"];
+    assert_eq!(ranges.len(), 2);
+    let gaf_range = ranges.iter().find(|r| r.gfa_id == 1).unwrap();
+    assert_eq!((gaf_range.start, gaf_range.end), (6, 11));
+    assert_eq!(gaf_range.steps, vec![translate(1, false), translate(2, false)]);
+    assert_eq!(gaf_range.step_ends, vec![9, 11]);
+    assert_eq!(gaf_range.path_name, "sample#1#chr1:6-100");
+
+    let reverse = &path_key_ranges["sample#2#chr1"][0];
+    assert_eq!((reverse.start, reverse.end), (0, 6));
+    assert_eq!(reverse.steps, vec![translate(3, true), translate(2, true), translate(1, true)]);
+
+    // Segments must exist in the input the GAF belongs to
+    let missing = write_temp_gfa("sample#1#chr1\t4\t0\t4\t+\t>7\t4\t0\t4\t4\t4\t60\n");
+    let error = read_gaf_paths(missing.path().to_str().unwrap(), 1, id_translations[1], &graph, &NodeSequences::default(), &ReadOptions::default(), &mut path_key_ranges).unwrap_err();
+    assert!(error.to_string().contains("line 1: segment 7"), "{}", error);
+}
+
+#[test]
+fn test_collapse_haplotypes() {
+    let ranges = |coordinates: &[(usize, usize)], gfa_id: usize| -> Vec<RangeInfo> {
+        coordinates.iter().map(|&(start, end)| create_range_info(start, end, gfa_id)).collect()
+    };
+    let mut path_key_ranges: FxHashMap<PathKey, Vec<RangeInfo>> = FxHashMap::default();
+    path_key_ranges.insert("HG002#1#chr1".into(), ranges(&[(0, 100), (100, 200)], 0));
+    path_key_ranges.insert("HG002#2#chr1".into(), ranges(&[(0, 100), (100, 200)], 1));
+    path_key_ranges.insert("HG002#1#chr2".into(), ranges(&[(0, 50)], 0));
+    path_key_ranges.insert("HG002#2#chr2".into(), ranges(&[(0, 60)], 1));
+    path_key_ranges.insert("chrM".into(), ranges(&[(0, 10)], 0));
+    // Same coordinates, but not the same steps
+    let with_steps = |steps: &[u64]| vec![RangeInfo { steps: steps.iter().map(|&id| Handle::pack(id, false)).collect(), ..create_range_info(0, 30, 0) }];
+    path_key_ranges.insert("HG002#1#chr3".into(), with_steps(&[1, 2]));
+    path_key_ranges.insert("HG002#2#chr3".into(), with_steps(&[1, 3]));
+
+    let mut bubbled = path_key_ranges.clone();
+    assert_eq!(collapse_haplotypes(&mut bubbled, CollapsePolicy::Bubble).unwrap(), 1);
+    let mut keys: Vec<&str> = bubbled.keys().map(|key| &**key).collect();
+    keys.sort();
+    assert_eq!(keys, vec!["HG002#1#chr2", "HG002#1#chr3", "HG002#2#chr2", "HG002#2#chr3", "HG002#chr1", "chrM"]);
+    // The ranges of the first haplotype are kept
+    assert!(bubbled["HG002#chr1"].iter().all(|range| range.gfa_id == 0));
+
+    let mut chr3 = FxHashMap::default();
+    chr3.insert("HG002#1#chr3".into(), path_key_ranges["HG002#1#chr3"].clone());
+    chr3.insert("HG002#2#chr3".into(), path_key_ranges["HG002#2#chr3"].clone());
+    let error = collapse_haplotypes(&mut path_key_ranges, CollapsePolicy::Error).unwrap_err();
+    assert!(error.to_string().contains("ranges of HG002#1#chr2"), "{}", error);
+    let error = collapse_haplotypes(&mut chr3, CollapsePolicy::Error).unwrap_err();
+    assert_eq!(error.to_string(), "The steps of HG002#1#chr3 and HG002#2#chr3 differ");
+}
+
+#[test]
+fn test_paths_fasta_reverse_steps() {
+    assert_eq!(reverse_complement(b"ACGTURYKMSWBDHVN-"), b"-NBDHVWSKMRYAACGT");
+    assert_eq!(reverse_complement(b"acgtrykmbdhvn"), b"nbdhvkmryacgt");
+
+    // Two contiguous blocks, with reverse steps over IUPAC codes
+    let block_a = write_temp_gfa("S\t1\tACGT\nS\t2\tGRKN\nL\t1\t+\t2\t-\t0M\nP\tsample#1#chr1:0-8\t1+,2-\t*\n");
+    let block_b = write_temp_gfa("S\t1\tbdac\nS\t2\tTTT\nL\t1\t-\t2\t+\t0M\nP\tsample#1#chr1:8-15\t1-,2+\t*\n");
+    let gfa_list: Vec<String> = [&block_a, &block_b].iter()
+        .map(|block| block.path().to_str().unwrap().to_string())
+        .collect();
+    let CombinedInput { graph, mut path_key_ranges, sequenceless, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+    sort_and_filter_all_ranges(&mut path_key_ranges, false);
+
+    let output = NamedTempFile::new().unwrap();
+    let fasta_file = NamedTempFile::new().unwrap();
+    let mut paths_fasta = PathFastaWriter::new(fasta_file.path().to_str().unwrap()).unwrap();
+    write_graph_to_gfa(&graph, &NodeSequences::default(), &path_key_ranges, &sequenceless, &LinkOverlaps::default(), &PassthroughLines::default(), output.path().to_str().unwrap(), &None, &WriteOptions::default(), None, Some(&mut paths_fasta), None).unwrap();
+    paths_fasta.finish().unwrap();
+    assert_eq!(std::fs::read_to_string(fasta_file.path()).unwrap(), ">sample#1#chr1\nACGTNMYCgthvTTT\n");
+
+    // The bases shared through an overlapping link are written once
+    let block = write_temp_gfa("S\t1\tACGT\nS\t2\tGTCA\nL\t1\t+\t2\t+\t2M\nP\tsample#1#chr1:0-6\t1+,2+\t*\n");
+    let CombinedInput { graph, mut path_key_ranges, sequenceless, link_overlaps, .. } = read_gfa_files(&[block.path().to_str().unwrap().to_string()], &ReadOptions::default()).unwrap();
+    sort_and_filter_all_ranges(&mut path_key_ranges, false);
+    let mut paths_fasta = PathFastaWriter::new(fasta_file.path().to_str().unwrap()).unwrap();
+    write_graph_to_gfa(&graph, &NodeSequences::default(), &path_key_ranges, &sequenceless, &link_overlaps, &PassthroughLines::default(), output.path().to_str().unwrap(), &None, &WriteOptions::default(), None, Some(&mut paths_fasta), None).unwrap();
+    paths_fasta.finish().unwrap();
+    assert_eq!(std::fs::read_to_string(fasta_file.path()).unwrap(), ">sample#1#chr1\nACGTCA\n");
+}
+
+#[test]
+fn test_name_scheme() {
+    // The range of minigraph-cactus names parses as for PanSN names, and the key is kept whole
+    assert_eq!(split_path_name("id=HG002.1|JAHKSE010000001.1:12000-36000", RangeSyntax::ColonDash), Some(("id=HG002.1|JAHKSE010000001.1".to_string(), 12000, 36000)));
+    assert_eq!(split_path_name("id=HG002.1|chr1[0-10]", RangeSyntax::ColonDash), Some(("id=HG002.1|chr1".to_string(), 0, 10)));
+    // A numeric fourth PanSN field is the index of a --fragment-naming pansn fragment, not part of the key
+    assert_eq!(split_path_name("HG002#1#chr1#2:5-9", RangeSyntax::ColonDash), Some(("HG002#1#chr1".to_string(), 5, 9)));
+    assert_eq!(split_path_name("HG002#1#2:5-9", RangeSyntax::ColonDash), Some(("HG002#1#2".to_string(), 5, 9)));
+    assert_eq!(split_path_name("HG002#1#chr1#alt:5-9", RangeSyntax::ColonDash), Some(("HG002#1#chr1#alt".to_string(), 5, 9)));
+    assert_eq!(cactus_key("id=HG002.1|JAHKSE010000001.1"), Some(("HG002.1", "JAHKSE010000001.1")));
+    assert_eq!(cactus_key("HG002#1#chr1"), None);
+    assert_eq!(cactus_key("id=HG002.1"), None);
+    assert_eq!(key_contig("id=HG002.1|JAHKSE010000001.1"), "JAHKSE010000001.1");
+    assert_eq!(key_contig("HG002#1#chr1"), "chr1");
+    assert_eq!(key_contig("chrM"), "chrM");
+
+    // End to end, from the input files to the output path names
+    let block_a = write_temp_gfa("S\t1\tACGT\nS\t2\tGG\nL\t1\t+\t2\t+\t0M\nP\tid=HG002.1|chr1:0-6\t1+,2+\t*\n");
+    let block_b = write_temp_gfa("S\t1\tTTT\nP\tid=HG002.1|chr1:6-9\t1+\t*\nP\tid=HG002.1|chr2:20-23\t1+\t*\n");
+    let gfa_list: Vec<String> = [&block_a, &block_b].iter()
+        .map(|block| block.path().to_str().unwrap().to_string())
+        .collect();
+    let CombinedInput { graph, mut path_key_ranges, sequenceless, gfa_names, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+    check_name_scheme(&path_key_ranges, NameScheme::Auto, &gfa_names).unwrap();
+    check_name_scheme(&path_key_ranges, NameScheme::Cactus, &gfa_names).unwrap();
+    let error = check_name_scheme(&path_key_ranges, NameScheme::Pansn, &gfa_names).unwrap_err();
+    assert!(error.to_string().contains("id=HG002.1|chr1"), "{}", error);
+    sort_and_filter_all_ranges(&mut path_key_ranges, false);
+
+    let samples: Vec<(&str, &str, &str)> = sample_table_rows(&path_key_ranges).into_iter()
+        .map(|(_, sample, haplotype, contig, _)| (sample, haplotype, contig))
+        .collect();
+    assert_eq!(samples, vec![("HG002.1", "*", "chr1"), ("HG002.1", "*", "chr2")]);
+
+    let output = write_test_gfa_file(&graph, &path_key_ranges, &sequenceless, &LinkOverlaps::default(), &WriteOptions::default()).unwrap();
+    let path_names: Vec<String> = std::fs::read_to_string(output.path()).unwrap().lines()
+        .filter(|line| line.starts_with("P\t"))
+        .map(|line| line.split('\t').nth(1).unwrap().to_string())
+        .collect();
+    assert_eq!(path_names, vec!["id=HG002.1|chr1", "id=HG002.1|chr2:20-23"]);
+
+    // Mixing the schemes across inputs is an error unless allowed
+    let block_c = write_temp_gfa("S\t1\tAAA\nP\tHG002#1#chr1:0-3\t1+\t*\n");
+    let gfa_list = vec![gfa_list[0].clone(), block_c.path().to_str().unwrap().to_string()];
+    let CombinedInput { path_key_ranges, gfa_names, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+    let error = check_name_scheme(&path_key_ranges, NameScheme::Auto, &gfa_names).unwrap_err();
+    assert!(error.to_string().contains("'HG002#1#chr1' from ") && error.to_string().contains("'id=HG002.1|chr1' from "), "{}", error);
+    let error = check_name_scheme(&path_key_ranges, NameScheme::Cactus, &gfa_names).unwrap_err();
+    assert!(error.to_string().contains(&gfa_list[1]), "{}", error);
+    check_name_scheme(&path_key_ranges, NameScheme::Mixed, &gfa_names).unwrap();
+}
+
+#[test]
+fn test_range_delimiters() {
+    assert_eq!(RangeSyntax::ColonDash.split("HG005#2#chr11:1080000-2090000"), Some(("HG005#2#chr11", 1080000, 2090000)));
+    assert_eq!(RangeSyntax::Underscores.split("HG005#2#chr11_1080000_2090000"), Some(("HG005#2#chr11", 1080000, 2090000)));
+    assert_eq!(RangeSyntax::ColonUnderscore.split("HG005#2#chr11:1080000_2090000"), Some(("HG005#2#chr11", 1080000, 2090000)));
+    // Contig names that end like another syntax are not split unless it is chosen
+    assert_eq!(RangeSyntax::ColonDash.split("HG005#2#chrUn_KI270742v1_1_2"), None);
+    assert_eq!(RangeSyntax::Underscores.split("HG005#2#chrUn_KI270742v1_1_2"), Some(("HG005#2#chrUn_KI270742v1", 1, 2)));
+    assert_eq!(RangeSyntax::Underscores.split("HG005#2#chr11:1080000-2090000"), None);
+    assert_eq!(RangeSyntax::ColonUnderscore.split("HG005#2#chr11:1080000-2090000"), None);
+    assert_eq!(RangeSyntax::ColonDash.split("chr1:HLA-1:10-20"), Some(("chr1:HLA-1", 10, 20)));
+    assert_eq!(RangeSyntax::ColonDash.split("chr1:1-2-3"), None);
+
+    // Fragment names are parsed back with the same syntax
+    for syntax in [RangeSyntax::ColonDash, RangeSyntax::Underscores, RangeSyntax::ColonUnderscore] {
+        let name = syntax.format("HG005#2#chr11_random", 10, 20);
+        assert_eq!(syntax.split(&name), Some(("HG005#2#chr11_random", 10, 20)), "{}", name);
+    }
+    assert_eq!(RangeSyntax::Underscores.format("sample#1#chr1", 10, 20), "sample#1#chr1_10_20");
+
+    // The syntax only applies to the reads given it
+    let underscores = ReadOptions { range_syntax: RangeSyntax::Underscores, ..ReadOptions::default() };
+    assert_eq!(split_input_path_name("sample#1#chr1_10_20", &underscores), Some((Cow::Borrowed("sample#1#chr1"), 10, 20)));
+    assert_eq!(split_input_path_name("sample#1#chr1_10_20", &ReadOptions::default()), None);
+    assert_eq!(split_input_path_name("sample#1#chr1:10-20", &ReadOptions::default()), Some((Cow::Borrowed("sample#1#chr1"), 10, 20)));
+}
+
+#[test]
+fn test_non_utf8_path_names() {
+    // A Latin-1 contig name, in two abutting blocks
+    let blocks: Vec<NamedTempFile> = [
+        &b"S\t1\tACGT\nP\tsample#1#chr\xe91:0-4\t1+\t*\nP\tsample#1#chr2:0-4\t1+\t*\n"[..],
+        &b"S\t1\tGG\nP\tsample#1#chr\xe91:4-6\t1+\t*\n"[..],
+    ].iter().map(|content| {
+        let file = NamedTempFile::with_suffix(".gfa").unwrap();
+        file.as_file().write_all(content).unwrap();
+        file
+    }).collect();
+    let gfa_list: Vec<String> = blocks.iter().map(|block| block.path().to_str().unwrap().to_string()).collect();
+    let CombinedInput { mut graph, mut path_key_ranges, link_overlaps, mut sequenceless, dropped_paths, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+    assert_eq!(dropped_paths, 0);
+    let mut path_keys: Vec<&str> = path_key_ranges.keys().map(|key| &**key).collect();
+    path_keys.sort();
+    assert_eq!(path_keys, ["sample#1#chr2", "sample#1#chr\u{FFFD}1"]);
+    assert_eq!(split_input_path_name("sample#1#chr\u{FFFD}1:4-6", &ReadOptions::default()), Some((Cow::Borrowed("sample#1#chr\u{FFFD}1"), 4, 6)));
+
+    let mut edges = canonical_edges(&graph);
+    merge_path_ranges(&mut path_key_ranges, &mut graph, &mut edges, &mut NodeSequences::default(), &mut sequenceless, None, &mut RunSummary::default(), false);
+    let gfa = write_test_gfa(&graph, &path_key_ranges, &sequenceless, &link_overlaps, &WriteOptions::default());
+    assert!(gfa.lines().any(|line| line.starts_with("P\tsample#1#chr\u{FFFD}1\t1+,2+\t")), "{}", gfa);
+
+    // Distinct names that convert to the same one are not merged
+    let mut clashing = NamedTempFile::with_suffix(".gfa").unwrap();
+    clashing.write_all(b"S\t1\tACGT\nP\tsample#1#chr\xe91:0-4\t1+\t*\nP\tsample#1#chr\xe81:0-4\t1+\t*\n").unwrap();
+    let clashing_list = vec![clashing.path().to_str().unwrap().to_string()];
+    for stream_parse in [false, true] {
+        let options = ReadOptions { stream_parse, ..ReadOptions::default() };
+        let error = read_gfa_files(&clashing_list, &options).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
+
+#[test]
+fn test_path_format() {
+    let pansn = PathFormat::compile("{sample}#{hap}#{contig}:{start}-{end}").unwrap();
+    assert_eq!(pansn.parse("HG002#1#chr1:100-200"), Some(("HG002#1#chr1".to_string(), 100, 200)));
+    assert_eq!(pansn.parse("HG002#1#chr1:HLA:100-200"), Some(("HG002#1#chr1:HLA".to_string(), 100, 200)));
+    assert_eq!(pansn.parse("HG002#1#chr1:100-2x0"), None);
+    assert_eq!(pansn.parse("HG002#chr1:100-200"), None);
+    let dotted = PathFormat::compile("{sample}.{contig}_{start}_{end}").unwrap();
+    assert_eq!(dotted.parse("HG002.chr1_0_36000"), Some(("HG002#chr1".to_string(), 0, 36000)));
+    assert_eq!(dotted.parse("HG002.chr1_un_0_36000"), Some(("HG002#chr1_un".to_string(), 0, 36000)));
+    assert_eq!(dotted.parse("HG002.chr1:0-36000"), None);
+
+    // Errors point to the offending part of the template
+    let error = PathFormat::compile("{sample}#{haplotype}#{contig}:{start}-{end}").unwrap_err();
+    assert!(error.starts_with("Unknown placeholder '{haplotype}'") && error.ends_with("\n           ^"), "{}", error);
+    assert!(PathFormat::compile("{contig}:{start}").unwrap_err().starts_with("Missing placeholder '{end}'"));
+    assert!(PathFormat::compile("{contig}:{end}").unwrap_err().starts_with("Missing placeholder '{start}'"));
+    assert!(PathFormat::compile("x:{start}-{end}").unwrap_err().starts_with("Missing placeholder '{sample}'"));
+    assert!(PathFormat::compile("{contig}{start}-{end}").unwrap_err().contains("directly follows"));
+    assert!(PathFormat::compile("{contig}:{start}-{end}:{start}").unwrap_err().starts_with("Repeated placeholder"));
+    assert!(PathFormat::compile("{contig:{start}-{end}").unwrap_err().starts_with("Unknown placeholder"));
+    assert!(PathFormat::compile("{contig}:{start}-{end").unwrap_err().starts_with("Unclosed '{'"));
+
+    // Generated names round trip through the templates, with fields that avoid the literal text
+    let templates = ["{sample}#{hap}#{contig}:{start}-{end}", "{sample}.{contig}_{start}_{end}", "chr={contig}|{start}..{end}|{sample}", "{contig}[{start}-{end}]"];
+    let alphabet: Vec<char> = "ACGTacgt0123456789.#_:-|=[]".chars().collect();
+    let mut state: u64 = 42;
+    let mut next = |bound: usize| {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        (state >> 33) as usize % bound
+    };
+    for template in templates {
+        let path_format = PathFormat::compile(template).unwrap();
+        let literals: String = path_format.parts.iter().filter_map(|part| match part {
+            FormatPart::Literal(literal) => Some(literal.as_str()),
+            FormatPart::Field(_) => None,
+        }).collect();
+        let allowed: Vec<char> = alphabet.iter().copied().filter(|c| !literals.contains(*c)).collect();
+        for _ in 0..200 {
+            let field = |next: &mut dyn FnMut(usize) -> usize| (0..1 + next(8)).map(|_| allowed[next(allowed.len())]).collect::<String>();
+            let (sample, hap, contig) = (field(&mut next), (1 + next(2)).to_string(), field(&mut next));
+            let (start, end) = (next(1_000_000), next(1_000_000));
+            let name = template.replace("{sample}", &sample).replace("{hap}", &hap).replace("{contig}", &contig)
+                .replace("{start}", &start.to_string()).replace("{end}", &end.to_string());
+            let key: Vec<&str> = [("{sample}", sample.as_str()), ("{hap}", hap.as_str()), ("{contig}", contig.as_str())].iter()
+                .filter(|(placeholder, _)| template.contains(placeholder))
+                .map(|(_, value)| *value)
+                .collect();
+            assert_eq!(path_format.parse(&name), Some((key.join("#"), start, end)), "{} from {}", name, template);
+        }
+    }
+}
+
+#[test]
+fn test_lace_graphs() {
+    let block = |segments: &[&[u8]], path_name: &str| {
+        let mut graph = HashGraph::new();
+        let handles: Vec<Handle> = segments.iter().enumerate()
+            .map(|(idx, sequence)| graph.create_handle(sequence, idx as u64 + 1))
+            .collect();
+        for pair in handles.windows(2) {
+            graph.create_edge(Edge(pair[0], pair[1]));
+        }
+        let path_id = graph.create_path(path_name.as_bytes(), false).unwrap();
+        for &handle in &handles {
+            graph.path_append_step(path_id, handle);
+        }
+        graph
+    };
+    let path_steps = |graph: &HashGraph| -> Vec<(String, usize)> {
+        let mut paths: Vec<_> = graph.paths.values()
+            .map(|path| (String::from_utf8_lossy(&path.name).into_owned(), path.nodes.len()))
+            .collect();
+        paths.sort();
+        paths
+    };
+
+    let blocks = [
+        block(&[b"ACGT", b"GG"], "sample#1#chr1:0-6"),
+        block(&[b"TTT"], "sample#1#chr1:6-9"),
+    ];
+    let laced = lace_graphs(&blocks, &LaceOptions::default()).unwrap();
+    assert_eq!(path_steps(&laced.graph), vec![("sample#1#chr1".to_string(), 3)]);
+    assert_eq!(laced.graph.node_count(), 3);
+    assert_eq!(laced.graph.edge_count(), 2);
+    assert!(laced.warnings.is_empty());
+    assert_eq!(laced.stats.new_edges, 1);
+
+    // Ranges separated by a gap are split into fragments
+    let blocks = [
+        block(&[b"ACGT", b"GG"], "sample#1#chr1:0-6"),
+        block(&[b"TTT"], "sample#1#chr1:10-13"),
+    ];
+    let options = LaceOptions { fragment_naming: FragmentNaming::Brackets, ..LaceOptions::default() };
+    let laced = lace_graphs(&blocks, &options).unwrap();
+    assert_eq!(path_steps(&laced.graph), vec![
+        ("sample#1#chr1[0-6]".to_string(), 2),
+        ("sample#1#chr1[10-13]".to_string(), 1),
+    ]);
+    assert_eq!(laced.warnings, vec![LaceWarning::Gap { path_key: "sample#1#chr1".to_string(), start: 6, end: 10 }]);
+    assert_eq!((laced.stats.paths, laced.stats.keys_with_gaps, laced.stats.gap_bp), (2, 1, 4));
+
+    // As with the FASTA of the command line, a key ending before its sequence is a fragment
+    let blocks = [block(&[b"ACGT", b"GG"], "sample#1#chr1:0-6")];
+    let sequence_lengths = [("sample#1#chr1".to_string(), 8)].into_iter().collect();
+    let laced = lace_graphs(&blocks, &LaceOptions { sequence_lengths, ..LaceOptions::default() }).unwrap();
+    assert_eq!(path_steps(&laced.graph), vec![("sample#1#chr1:0-6".to_string(), 2)]);
+
+    // Paths that would have the same name are an error
+    let blocks = [block(&[b"ACGT"], "sample#1#chr1:4-8"), block(&[b"GG"], "sample#1#chr1:4-8:0-2")];
+    assert!(matches!(lace_graphs(&blocks, &LaceOptions::default()), Err(LaceError::DuplicatePath(path_name)) if path_name == "sample#1#chr1:4-8"));
+}
+
+#[test]
+fn test_lace_warnings() {
+    let block = |sequence: &[u8], path_name: &str| {
+        let mut graph = HashGraph::new();
+        let handle = graph.create_handle(sequence, 1);
+        let path_id = graph.create_path(path_name.as_bytes(), false).unwrap();
+        graph.path_append_step(path_id, handle);
+        graph
+    };
+
+    // Overlapping ranges are reported before being trimmed, and unparseable paths by block
+    let mut unnamed = block(b"TTTT", "sample#1#chr1:6-10");
+    let handle = unnamed.create_handle(b"A", 2);
+    let path_id = unnamed.create_path(b"sample#1#chr1", false).unwrap();
+    unnamed.path_append_step(path_id, handle);
+    let blocks = [block(b"ACGTGG", "sample#1#chr1:0-6"), block(b"GGTTT", "sample#1#chr1:4-9"), unnamed];
+    let laced = lace_graphs(&blocks, &LaceOptions::default()).unwrap();
+    assert_eq!(laced.warnings, vec![
+        LaceWarning::DroppedPaths { block: 2, count: 1 },
+        LaceWarning::Overlap { path_key: "sample#1#chr1".to_string(), start: 4, end: 6, first_block: 0, second_block: 1 },
+        LaceWarning::Overlap { path_key: "sample#1#chr1".to_string(), start: 6, end: 9, first_block: 1, second_block: 2 },
+    ]);
+    assert_eq!((laced.stats.blocks, laced.stats.path_keys, laced.stats.keys_with_overlaps), (3, 1, 1));
+
+    // A path spelling another length than its range is reported, but kept
+    let laced = lace_graphs(&[block(b"ACGTG", "sample#1#chr2:0-4")], &LaceOptions::default()).unwrap();
+    assert_eq!(laced.warnings, vec![
+        LaceWarning::ValidationMismatch { block: 0, path_name: "sample#1#chr2:0-4".to_string(), expected: 4, spelled: 5 },
+    ]);
+    assert_eq!(laced.stats.paths, 1);
+
+    // A path visiting a segment its block does not define is skipped
+    let missing = write_temp_gfa("H\tVN:Z:1.0\nS\t1\tACGT\nP\tsample#1#chr1:0-6\t1+,2+\t*\n");
+    let gfa_list = vec![missing.path().to_str().unwrap().to_string()];
+    let CombinedInput { path_key_ranges, dropped_paths, warnings, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+    assert!(path_key_ranges.is_empty());
+    assert_eq!(dropped_paths, 1);
+    assert_eq!(warnings, vec![
+        LaceWarning::MissingNode { block: 0, path_name: "sample#1#chr1:0-6".to_string(), node: 2 },
+        LaceWarning::DroppedPaths { block: 0, count: 1 },
+        LaceWarning::PathlessBlock { block: 0 },
+    ]);
+}
+
+#[test]
+fn test_interval_coverage() {
+    // Two overlapping ranges, then a gap before a range of another input
+    let ranges = vec![create_range_info(100, 200, 0), create_range_info(150, 250, 1), create_range_info(300, 400, 2)];
+
+    let full = interval_coverage(&ranges, 100, 250);
+    assert_eq!(full, Coverage { covered: 150, gaps: vec![], gfa_ids: vec![0, 1] });
+    assert!(full.is_full());
+
+    let partial = interval_coverage(&ranges, 50, 350);
+    assert_eq!(partial, Coverage { covered: 200, gaps: vec![(50, 100), (250, 300)], gfa_ids: vec![0, 1, 2] });
+    assert!(!partial.is_full());
+
+    // Ranges ending at the start or starting at the end of the interval do not cover it
+    assert_eq!(interval_coverage(&ranges, 250, 300), Coverage { covered: 0, gaps: vec![(250, 300)], gfa_ids: vec![] });
+    assert_eq!(interval_coverage(&ranges, 199, 201), Coverage { covered: 2, gaps: vec![], gfa_ids: vec![0, 1] });
+    assert_eq!(interval_coverage(&ranges, 400, 400), Coverage { covered: 0, gaps: vec![], gfa_ids: vec![] });
+    assert_eq!(partial.gap_length(), 100);
+
+    // Overlapping ranges do not count as gaps, even past the end of the next range
+    let nested = vec![create_range_info(0, 100, 0), create_range_info(20, 30, 1), create_range_info(40, 100, 2)];
+    assert_eq!(gap_length(&nested), 0);
+
+    let range = RangeInfo::from_steps("sample#1#chr1:10-16", 10, 3, vec![(Handle::pack(1, false), 4), (Handle::pack(2, true), 2)]);
+    assert_eq!((range.start(), range.end(), range.gfa_id()), (10, 16, 3));
+    assert_eq!(range.step_ends, [14, 16]);
+}
+
+#[test]
+fn test_checksum() {
+    let checksum = |block_a: &str, comments: Vec<String>| {
+        let block_a = write_temp_gfa(block_a);
+        let block_b = write_temp_gfa("S\t1\tTTT\nP\tsample#1#chr1:6-9\t1+\t*\n");
+        let gfa_list = vec![
+            block_a.path().to_str().unwrap().to_string(),
+            block_b.path().to_str().unwrap().to_string(),
+        ];
+        let CombinedInput { mut graph, mut path_key_ranges, link_overlaps, mut sequenceless, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+        let mut edges = canonical_edges(&graph);
+        merge_path_ranges(&mut path_key_ranges, &mut graph, &mut edges, &mut NodeSequences::default(), &mut sequenceless, None, &mut RunSummary::default(), false);
+
+        let output = NamedTempFile::new().unwrap();
+        let passthrough = PassthroughLines { comments, ..PassthroughLines::default() };
+        write_graph_to_gfa(&graph, &NodeSequences::default(), &path_key_ranges, &sequenceless, &link_overlaps, &passthrough, output.path().to_str().unwrap(), &None, &WriteOptions::default(), None, None, None).unwrap();
+        gfa_checksum(output.path().to_str().unwrap()).unwrap()
+    };
+    let block = "S\t1\tACGT\nS\t2\tGG\nL\t1\t+\t2\t+\t0M\nP\tsample#1#chr1:0-6\t1+,2+\t*\n";
+
+    let golden = checksum(block, vec![]);
+    assert_eq!(golden.len(), 64);
+    assert_eq!(checksum(block, vec![]), golden);
+    // Comments are not covered
+    assert_eq!(checksum(block, vec!["# kept comment".to_string()]), golden);
+    // A single base is
+    assert_ne!(checksum(&block.replace("ACGT", "ACGA"), vec![]), golden);
+
+    // The sidecar can be checked by sha256sum -c, which skips its comment
+    let dir = tempfile::tempdir().unwrap();
+    let output = dir.path().join("out.gfa");
+    std::fs::write(&output, format!("H\tVN:Z:1.0\n{}", block)).unwrap();
+    let (output, checksum_path) = (output.to_str().unwrap(), dir.path().join("out.gfa.sha256"));
+    write_checksum_file(output, checksum_path.to_str().unwrap()).unwrap();
+    let mut hasher = Sha256::new();
+    hasher.update(std::fs::read(output).unwrap());
+    let file_checksum: String = hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect();
+    assert_eq!(std::fs::read_to_string(&checksum_path).unwrap(),
+        format!("# SHA-256 of the S, L, and P lines: {}\n{}  out.gfa\n", gfa_checksum(output).unwrap(), file_checksum));
+}
+
+#[test]
+fn test_range_report() {
+    let mut path_key_ranges: FxHashMap<PathKey, Vec<RangeInfo>> = FxHashMap::default();
+    path_key_ranges.insert("a#1#chr1".into(), vec![create_range_info(0, 5, 0)]);
+    // Unsorted, with a contained range, an overlap, and two gaps
+    path_key_ranges.insert("b#1#chr1".into(), vec![create_range_info(200, 1500, 1), create_range_info(0, 100, 0),
+        create_range_info(10, 20, 2), create_range_info(90, 150, 1), create_range_info(2000, 2050, 2)]);
+    path_key_ranges.insert("c#1#chr1".into(), (0..21).map(|idx| create_range_info(idx * 10, idx * 10 + 10, 0)).collect());
+
+    let report = RangeReport::new(&path_key_ranges);
+    assert_eq!(report.keys_by_ranges, [1, 1, 0, 1]);
+    assert_eq!(report.ranges_by_length, BTreeMap::from([(0, 1), (10, 24), (100, 1), (1000, 1)]));
+    assert_eq!(report.keys_by_gaps, BTreeMap::from([(0, 2), (2, 1)]));
+    assert_eq!(report.keys[1], KeyFragmentation { path_key: "b#1#chr1".to_string(), ranges: 5, gaps: 2, overlaps: 2, gfa_ids: vec![0, 1, 2] });
+    let worst: Vec<&str> = report.worst_keys(|key| key.gaps).iter().map(|key| key.path_key.as_str()).collect();
+    assert_eq!(worst, vec!["b#1#chr1"]);
+}
+
+#[test]
+fn test_degree_histogram() {
+    // Node 2 joins three others, and node 5 is not on any path
+    let block = write_temp_gfa("S\t1\tA\nS\t2\tC\nS\t3\tG\nS\t4\tT\nS\t5\tTT\n\
+        L\t1\t+\t2\t+\t0M\nL\t2\t+\t3\t+\t0M\nL\t2\t+\t4\t-\t0M\nL\t4\t+\t5\t+\t0M\n\
+        P\tsample#1#chr1:0-3\t1+,2+,3+\t*\nP\tsample#2#chr1:0-3\t1+,2+,4-\t*\n");
+    let CombinedInput { graph, path_key_ranges, .. } = read_gfa_files(&[block.path().to_str().unwrap().to_string()], &ReadOptions::default()).unwrap();
+
+    // Node 2 has one link entering it and two leaving it, and the link to 4- joins the end of 4, as an out-link
+    let degrees = degree_histogram(&graph, &path_key_ranges, &WriteOptions::default());
+    assert_eq!(degrees.in_counts, BTreeMap::from([(0, 2), (1, 2)]));
+    assert_eq!(degrees.out_counts, BTreeMap::from([(0, 1), (1, 2), (2, 1)]));
+    assert_eq!(degrees.max_in_degree_node, Some((2, 1)));
+    assert_eq!(degrees.max_out_degree_node, Some((2, 2)));
+
+    assert_eq!(degree_histogram(&HashGraph::new(), &FxHashMap::default(), &WriteOptions::default()), DegreeHistogram::default());
+}
+
+#[test]
+fn test_tag_origin() {
+    let (_blocks, CombinedInput { graph, path_key_ranges, link_overlaps, sequenceless, id_translations, gfa_names, .. }, split_origins) = split_and_gap_fixture();
+
+    // The streaming writer tags the segments as well
+    for (no_sequences, no_sort) in [(false, false), (true, false), (false, true)] {
+        let origins = node_origins(&graph, &id_translations, &split_origins, &gfa_names);
+        let options = WriteOptions { fill_gaps: 1, no_sequences, no_sort, origins: Some(&origins), ..WriteOptions::default() };
+        let output = write_test_gfa_file(&graph, &path_key_ranges, &sequenceless, &link_overlaps, &options).unwrap();
+        let output_path = output.path().to_str().unwrap();
+        // The tags are kept when renaming the nodes
+        rename_output_nodes(output_path, &file_prefixed_node_names(&graph, &path_key_ranges, &options, &id_translations, &split_origins)).unwrap();
+
+        let gfa = std::fs::read_to_string(output_path).unwrap();
+        let tags: FxHashMap<&str, &str> = gfa.lines().filter(|line| line.starts_with("S\t")).map(|line| {
+            let fields: Vec<&str> = line.split('\t').collect();
+            assert_eq!(fields.len(), if no_sequences { 5 } else { 4 }, "{}", line);
+            (fields[1], fields[fields.len() - 1])
+        }).collect();
+        assert_eq!(tags.len(), 6);
+        assert_eq!(tags["b0_1"], format!("SF:Z:{}", gfa_names[0]));
+        assert_eq!(tags["b1_2"], format!("SF:Z:{}", gfa_names[1]));
+        assert_eq!(tags["b2_7"], format!("SF:Z:{}", gfa_names[2]));
+        assert_eq!(tags["b1_1_1"], "SF:Z:gfalace");
+        assert_eq!(tags["gap_1"], "SF:Z:gfalace");
+    }
+}
+
+#[test]
+fn test_emit_block_groups() {
+    let (_blocks, CombinedInput { graph, path_key_ranges, link_overlaps, sequenceless, id_translations, gfa_names, .. }, split_origins) = split_and_gap_fixture();
+    for (first_id, suffix) in [(1, ".gfa"), (10, ".gfa.gz")] {
+        let output = tempfile::Builder::new().suffix(suffix).tempfile().unwrap();
+        let output_path = output.path().to_str().unwrap();
+        let options = WriteOptions { fill_gaps: 1, first_id, ..WriteOptions::default() };
+        let groups = block_groups(&graph, &path_key_ranges, &options, &id_translations, &split_origins, &gfa_names);
+        let (_, max_node_id) = write_graph_to_gfa(&graph, &NodeSequences::default(), &path_key_ranges, &sequenceless, &link_overlaps, &PassthroughLines::default(), output_path, &None, &options, None, None, Some(&groups)).unwrap();
+        let mut buffer = Vec::new();
+        assert_eq!(write_block_groups(&mut buffer, &groups, max_node_id).unwrap(), 4);
+
+        let mut gfa = String::new();
+        GfaInput::Path(output_path).lines(&ReadOptions::default()).unwrap().read_to_string(&mut gfa).unwrap();
+        let mut group_of: FxHashMap<usize, String> = FxHashMap::default();
+        for line in gfa.lines().filter_map(|line| line.strip_prefix("# block-group\t")) {
+            let fields: Vec<&str> = line.split('\t').collect();
+            for run in fields[2].split(',') {
+                let (start, end) = run.split_once('-').unwrap_or((run, run));
+                for id in start.parse::<usize>().unwrap()..=end.parse().unwrap() {
+                    assert!(group_of.insert(id, fields[1].to_string()).is_none(), "Node {} in several groups", id);
+                }
+            }
+        }
+        // Every node is in exactly one group, including the piece split from the second block
+        let segments: Vec<usize> = gfa.lines().filter_map(|line| line.strip_prefix("S\t")).map(|line| line.split('\t').next().unwrap().parse().unwrap()).collect();
+        assert_eq!(segments.len(), 6);
+        assert_eq!(group_of.len(), segments.len());
+        let members = |name: &str| group_of.values().filter(|group| *group == name).count();
+        assert_eq!((members(&gfa_names[0]), members(&gfa_names[1]), members(&gfa_names[2]), members(GFALACE_ORIGIN)), (2, 2, 1, 1));
+        assert_eq!(group_of[&max_node_id], GFALACE_ORIGIN);
+        assert!(segments.iter().all(|id| group_of.contains_key(id)));
+    }
+    assert_eq!(format_id_runs(&[1, 2, 3, 5, 7, 8]), "1-3,5,7-8");
+}
+
+#[test]
+fn test_id_prefix_by_file() {
+    let (_blocks, CombinedInput { graph, path_key_ranges, link_overlaps, sequenceless, id_translations, .. }, split_origins) = split_and_gap_fixture();
+    let options = WriteOptions { fill_gaps: 1, ..WriteOptions::default() };
+    let output = write_test_gfa_file(&graph, &path_key_ranges, &sequenceless, &link_overlaps, &options).unwrap();
+    let output_path = output.path().to_str().unwrap();
+    let unprefixed = std::fs::read_to_string(output_path).unwrap();
+    rename_output_nodes(output_path, &file_prefixed_node_names(&graph, &path_key_ranges, &options, &id_translations, &split_origins)).unwrap();
+    let gfa = std::fs::read_to_string(output_path).unwrap();
+
+    let segments: FxHashMap<&str, &str> = gfa.lines().filter_map(|line| {
+        let fields: Vec<&str> = line.split('\t').collect();
+        (fields[0] == "S").then(|| (fields[1], fields[2]))
+    }).collect();
+    let mut names: Vec<&str> = segments.keys().copied().collect();
+    names.sort();
+    assert_eq!(names, vec!["b0_1", "b0_2", "b1_1_1", "b1_2", "b2_7", "gap_1"]);
+    assert_eq!(segments["b1_1_1"], "TT");
+
+    // Links and paths reference the renamed segments, and the path spells the same sequence
+    for line in gfa.lines().filter(|line| line.starts_with("L\t")) {
+        let fields: Vec<&str> = line.split('\t').collect();
+        assert!(segments.contains_key(fields[1]) && segments.contains_key(fields[3]), "{}", line);
+    }
+    let path_sequence = |gfa: &str| {
+        let segments: FxHashMap<String, String> = gfa.lines().filter(|line| line.starts_with("S\t"))
+            .map(|line| { let fields: Vec<&str> = line.split('\t').collect(); (fields[1].to_string(), fields[2].to_string()) })
+            .collect();
+        let path = gfa.lines().find(|line| line.starts_with("P\t")).unwrap().split('\t').nth(2).unwrap().to_string();
+        path.split(',').map(|step| segments[step.strip_suffix('+').unwrap()].clone()).collect::<String>()
+    };
+    assert_eq!(path_sequence(&gfa), "ACGTGGTTANNNCC");
+    assert_eq!(path_sequence(&gfa), path_sequence(&unprefixed));
+}
+
+#[test]
+fn test_sample_table() {
+    let blocks: Vec<_> = [
+        "S\t1\tACGT\nP\tHG002#1#chr10:0-4\t1+\t*\nP\tHG002#2#chr2:0-4\t1+\t*\nP\tHG002#1#chr2:0-4\t1+\t*\n",
+        "S\t1\tGG\nP\tHG002#1#chr2:4-6\t1+\t*\nP\tHG002#2#chr2:10-12\t1+\t*\nP\tgrch38#chr1:5-7\t1+\t*\n",
+    ].iter().map(|block| write_temp_gfa(block)).collect();
+    let gfa_list: Vec<String> = blocks.iter().map(|block| block.path().to_str().unwrap().to_string()).collect();
+    let CombinedInput { mut graph, mut path_key_ranges, mut sequenceless, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+    let mut edges = canonical_edges(&graph);
+    merge_path_ranges(&mut path_key_ranges, &mut graph, &mut edges, &mut NodeSequences::default(), &mut sequenceless, None, &mut RunSummary::default(), false);
+
+    let table = NamedTempFile::new().unwrap();
+    write_sample_table(table.path().to_str().unwrap(), &graph, &NodeSequences::default(), &path_key_ranges, &None, 0).unwrap();
+    assert_eq!(std::fs::read_to_string(table.path()).unwrap(), "\
+        sample\thaplotype\tcontig\tranges\tlength\tspan\tcovered\tgaps\tgap_bp\temitted\n\
+        HG002\t1\tchr2\t2\t6\t6\t1.0000\t0\t0\twhole\n\
+        HG002\t2\tchr2\t2\t6\t12\t0.5000\t1\t6\tfragments\n\
+        HG002\t1\tchr10\t1\t4\t4\t1.0000\t0\t0\twhole\n\
+        grch38\t*\tchr1\t1\t2\t2\t1.0000\t0\t0\tfragments\n");
+
+    // Filled gaps do not split the path
+    write_sample_table(table.path().to_str().unwrap(), &graph, &NodeSequences::default(), &path_key_ranges, &None, 1).unwrap();
+    assert!(std::fs::read_to_string(table.path()).unwrap().contains("HG002\t2\tchr2\t2\t6\t12\t0.5000\t1\t6\twhole\n"));
+}
+
+#[test]
+fn test_completeness() {
+    // chr1 is complete for haplotype 1 and lacks its end for haplotype 2, chr2 has a gap, and
+    // chr3 has no known length
+    let mut path_key_ranges = FxHashMap::default();
+    path_key_ranges.insert("HG002#1#chr1".into(), vec![create_range_info(0, 600, 0), create_range_info(600, 1000, 1)]);
+    path_key_ranges.insert("HG002#2#chr1".into(), vec![create_range_info(0, 600, 0)]);
+    path_key_ranges.insert("HG002#1#chr2".into(), vec![create_range_info(0, 200, 0), create_range_info(300, 500, 1)]);
+    path_key_ranges.insert("HG002#1#chr3".into(), vec![create_range_info(0, 80, 0)]);
+    let contig_lengths = FxHashMap::from_iter([("chr1".to_string(), 1000), ("chr2".to_string(), 500)]);
+
+    let report = NamedTempFile::new().unwrap();
+    let samples = write_completeness(report.path().to_str().unwrap(), &path_key_ranges, Some(&contig_lengths), 0).unwrap();
+    assert_eq!(std::fs::read_to_string(report.path()).unwrap(),
+        "sample\thaplotype\tcontig\tlength\tdenominator\tcovered\tfraction\tfragments\tcomplete\n\
+        HG002\t1\tchr1\t1000\tcontig\t1000\t1.0000\t1\tyes\n\
+        HG002\t2\tchr1\t1000\tcontig\t600\t0.6000\t1\tno\n\
+        HG002\t1\tchr2\t500\tcontig\t400\t0.8000\t2\tno\n\
+        HG002\t1\tchr3\t80\tspan\t80\t1.0000\t1\tyes\n");
+    assert_eq!(samples, BTreeMap::from([
+        (("HG002".to_string(), "1".to_string()), SampleCompleteness { complete: 2, fragmented: 1, contigs: 3 }),
+        (("HG002".to_string(), "2".to_string()), SampleCompleteness { complete: 0, fragmented: 0, contigs: 1 }),
+    ]));
+
+    // Filled gaps make a single path, which is still not complete
+    let samples = write_completeness(report.path().to_str().unwrap(), &path_key_ranges, None, 1).unwrap();
+    assert_eq!(samples[&("HG002".to_string(), "1".to_string())], SampleCompleteness { complete: 2, fragmented: 0, contigs: 3 });
+}
+
+#[test]
+fn test_chop_long_nodes() {
+    // A 10 bp node traversed forward, backward, and in a self-loop, next to a short node
+    let block = write_temp_gfa("S\t1\tACGTTGCAAC\nS\t2\tGG\n\
+        L\t1\t+\t2\t+\t0M\nL\t1\t+\t1\t+\t0M\n\
+        P\tsample#1#chr1:0-12\t1+,2+\t*\nP\tsample#2#chr1:0-12\t2-,1-\t*\nP\tsample#3#chr1:0-20\t1+,1+\t*\n");
+    let CombinedInput { mut graph, mut path_key_ranges, mut link_overlaps, mut sequenceless, .. } = read_gfa_files(&[block.path().to_str().unwrap().to_string()], &ReadOptions::default()).unwrap();
+    let spell = |graph: &HashGraph, ranges: &FxHashMap<PathKey, Vec<RangeInfo>>| {
+        let mut sequences: Vec<(String, Vec<u8>)> = ranges.iter()
+            .map(|(key, ranges)| (key.to_string(), ranges.iter().flat_map(|range| &range.steps).flat_map(|&step| graph.sequence_vec(step)).collect()))
+            .collect();
+        sequences.sort();
+        sequences
+    };
+    let before = spell(&graph, &path_key_ranges);
+
+    let mut split_origins = FxHashMap::default();
+    let chopped = chop_long_nodes(&mut graph, &mut NodeSequences::default(), &mut path_key_ranges, 4, &mut sequenceless, &mut link_overlaps, &mut split_origins);
+    assert_eq!(chopped, 1);
+    assert_eq!(spell(&graph, &path_key_ranges), before);
+    for range in path_key_ranges.values().flatten() {
+        assert!(range.steps.iter().all(|&step| graph.node_len(step) <= 4));
+        let mut step_start = range.start;
+        for (&step, &step_end) in range.steps.iter().zip(&range.step_ends) {
+            assert_eq!(step_end - step_start, graph.node_len(step));
+            step_start = step_end;
+        }
+        assert_eq!(step_start, range.end);
+    }
+    let reverse: Vec<_> = path_key_ranges["sample#2#chr1"][0].steps.iter().map(|step| (u64::from(step.id()), step.is_reverse())).collect();
+    assert_eq!(reverse, vec![(2, true), (5, true), (4, true), (3, true)]);
+    assert!(split_origins.values().all(|&origin| origin == NodeId::from(1u64)));
+
+    // The edges of the original node join the ends of its chain, which is linked internally
+    let gfa = write_test_gfa(&graph, &path_key_ranges, &sequenceless, &link_overlaps, &WriteOptions::default());
+    let segments: Vec<_> = gfa.lines().filter(|l| l.starts_with("S\t")).collect();
+    assert_eq!(segments, vec!["S\t1\tGG", "S\t2\tACGT", "S\t3\tTGCA", "S\t4\tAC"]);
+    let links: Vec<_> = gfa.lines().filter(|l| l.starts_with("L\t")).collect();
+    assert_eq!(links, vec!["L\t1\t-\t4\t-\t0M", "L\t2\t+\t3\t+\t0M", "L\t2\t-\t4\t-\t0M", "L\t3\t+\t4\t+\t0M"]);
+
+    // A segment without sequence is kept whole
+    let block = write_temp_gfa("S\t1\t*\tLN:i:10\nP\tsample#1#chr1:0-10\t1+\t*\n");
+    let CombinedInput { mut graph, mut path_key_ranges, mut link_overlaps, mut sequenceless, .. } = read_gfa_files(&[block.path().to_str().unwrap().to_string()], &ReadOptions::default()).unwrap();
+    assert_eq!(chop_long_nodes(&mut graph, &mut NodeSequences::default(), &mut path_key_ranges, 4, &mut sequenceless, &mut link_overlaps, &mut FxHashMap::default()), 0);
+    assert_eq!(path_key_ranges["sample#1#chr1"][0].steps, vec![Handle::pack(1, false)]);
+}
+
+#[test]
+fn test_blunt_overlaps() {
+    // Overlapping unitigs, traversed forward and backward, next to a blunt block
+    let block_a = write_temp_gfa("S\t1\tACGTAC\nS\t2\tTACGG\nS\t3\tGGTT\nL\t1\t+\t2\t+\t3M\nL\t2\t+\t3\t+\t2M\n\
+        P\tsample#1#chr1:0-10\t1+,2+,3+\t*\nP\tsample#2#chr1:0-10\t3-,2-,1-\t*\n");
+    let block_b = write_temp_gfa("S\t1\tAAAA\nP\tsample#1#chr1:10-14\t1+\t*\n");
+    let gfa_list: Vec<String> = [&block_a, &block_b].iter().map(|block| block.path().to_str().unwrap().to_string()).collect();
+    let CombinedInput { mut graph, mut path_key_ranges, mut link_overlaps, mut sequenceless, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+    assert_eq!(path_key_ranges["sample#1#chr1"][0].step_ends, vec![6, 8, 10]);
+    assert_eq!(path_key_ranges["sample#2#chr1"][0].step_ends, vec![4, 7, 10]);
+    let mut edges = canonical_edges(&graph);
+    merge_path_ranges(&mut path_key_ranges, &mut graph, &mut edges, &mut NodeSequences::default(), &mut sequenceless, None, &mut RunSummary::default(), false);
+
+    let output = write_test_gfa_file(&graph, &path_key_ranges, &sequenceless, &link_overlaps, &WriteOptions::default()).unwrap();
+    let output_path = output.path().to_str().unwrap();
+    let gfa = std::fs::read_to_string(output_path).unwrap();
+    assert!(gfa.lines().any(|line| line.starts_with("P\tsample#1#chr1") && line.ends_with("\t3M,2M,0M")));
+
+    let mut split_origins = FxHashMap::default();
+    assert_eq!(blunt_overlaps(&mut graph, &mut NodeSequences::default(), &mut path_key_ranges, &mut sequenceless, &mut link_overlaps, &mut split_origins).unwrap(), 2);
+    assert!(link_overlaps.is_empty());
+    assert_eq!(path_key_ranges["sample#1#chr1"][0].step_ends, vec![6, 8, 10]);
+    assert_eq!(path_key_ranges["sample#2#chr1"][0].step_ends, vec![2, 4, 10]);
+    write_graph_to_gfa(&graph, &NodeSequences::default(), &path_key_ranges, &sequenceless, &link_overlaps, &PassthroughLines::default(), output_path, &None, &WriteOptions::default(), None, None, None).unwrap();
+    assert_eq!(check_roundtrip(output_path, &graph, &NodeSequences::default(), &path_key_ranges, &sequenceless, &WriteOptions::default()).unwrap(), Vec::<String>::new());
+    let content = GraphContent::read(output_path, &ReadOptions::default()).unwrap();
+    let spelled: Vec<&[u8]> = content.paths.values().map(|(sequence, _)| sequence.as_slice()).collect();
+    assert_eq!(spelled, vec![b"ACGTACGGTTAAAA".as_slice(), b"AACCGTACGT".as_slice()]);
+    let gfa = std::fs::read_to_string(output_path).unwrap();
+    assert!(gfa.lines().filter(|line| line.starts_with("P\t")).all(|line| line.ends_with("\t*")));
+}
+
+#[test]
+fn test_low_memory() {
+    // Overlapping ranges traversed in both orientations, with soft-masked and ambiguous bases, a
+    // segment without sequence, and 3M links, then blunted and chopped: nodes are split from
+    // pieces of reverse steps, which the spilled store keeps as slices of their origin
+    let blocks: Vec<_> = [
+        "S\t1\tACGTacRT\nS\t2\t*\tLN:i:3\nS\t3\tGGTTAC\nL\t1\t+\t2\t+\t0M\nL\t2\t+\t3\t-\t0M\n\
+            P\tsample#1#chr1:0-17\t1+,2+,3-\t*\nP\tsample#2#chr1:0-14\t3+,1-\t*\n",
+        "S\t1\tCCATG\nS\t2\tGGAnTTG\nL\t1\t-\t2\t+\t0M\nP\tsample#1#chr1:14-26\t1-,2+\t*\nP\tsample#2#chr1:14-21\t2-\t*\n",
+        "S\t1\tACGTAC\nS\t2\tTACGG\nS\t3\tGGTT\nL\t1\t+\t2\t+\t3M\nL\t2\t+\t3\t+\t2M\nP\tsample#1#chr2:0-10\t1+,2+,3+\t*\nP\tsample#2#chr2:0-10\t3-,2-,1-\t*\n",
+    ].iter().map(|block| write_temp_gfa(block)).collect();
+    let gfa_list: Vec<String> = blocks.iter().map(|block| block.path().to_str().unwrap().to_string()).collect();
+    let spill_dir = tempfile::tempdir().unwrap();
+
+    let lace = |spill_dir: Option<&Path>| -> (String, String) {
+        let CombinedInput { mut graph, mut path_key_ranges, mut link_overlaps, mut sequenceless, mut sequences, .. } = read_gfa_files(&gfa_list, &ReadOptions { spill_dir, ..ReadOptions::default() }).unwrap();
+        assert_eq!(matches!(sequences, NodeSequences::Spilled(_)), spill_dir.is_some());
+        let mut edges = canonical_edges(&graph);
+        let mut split_origins = merge_path_ranges(&mut path_key_ranges, &mut graph, &mut edges, &mut sequences, &mut sequenceless, None, &mut RunSummary::default(), false);
+        blunt_overlaps(&mut graph, &mut sequences, &mut path_key_ranges, &mut sequenceless, &mut link_overlaps, &mut split_origins).unwrap();
+        chop_long_nodes(&mut graph, &mut sequences, &mut path_key_ranges, 4, &mut sequenceless, &mut link_overlaps, &mut split_origins);
+
+        let output = NamedTempFile::new().unwrap();
+        let output_path = output.path().to_str().unwrap();
+        let table = NamedTempFile::new().unwrap();
+        write_graph_to_gfa(&graph, &sequences, &path_key_ranges, &sequenceless, &link_overlaps, &PassthroughLines::default(), output_path, &None, &WriteOptions::default(), None, None, None).unwrap();
+        write_sample_table(table.path().to_str().unwrap(), &graph, &sequences, &path_key_ranges, &None, 0).unwrap();
+        assert_eq!(check_roundtrip(output_path, &graph, &sequences, &path_key_ranges, &sequenceless, &WriteOptions::default()).unwrap(), Vec::<String>::new());
+        (std::fs::read_to_string(output_path).unwrap(), std::fs::read_to_string(table.path()).unwrap())
+    };
+
+    let in_memory = lace(None);
+    // The end of CCATG kept past the overlap, on a reverse step, is split from the reverse complement
+    assert!(in_memory.0.contains("S\t2\tGG\n") && in_memory.0.contains("P\tsample#1#chr1\t5+,6+,1+,8-,7-,2+,9+,10+\t*\n"));
+    assert_eq!(lace(Some(spill_dir.path())), in_memory);
+    // The spilled file is removed with the store
+    assert_eq!(std::fs::read_dir(spill_dir.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn test_min_coverage_fraction() {
+    // sample#2 covers 4 bp of a 40 bp span
+    let blocks: Vec<_> = [
+        "S\t1\tACGT\nP\tsample#1#chr1:0-4\t1+\t*\nP\tsample#2#chr1:0-4\t1+\t*\n",
+        "S\t1\tGGGG\nP\tsample#1#chr1:4-8\t1+\t*\nP\tsample#2#chr1:36-40\t1+\t*\n",
+    ].iter().map(|block| write_temp_gfa(block)).collect();
+    let gfa_list: Vec<String> = blocks.iter().map(|block| block.path().to_str().unwrap().to_string()).collect();
+    let CombinedInput { mut graph, mut path_key_ranges, link_overlaps, mut sequenceless, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+    let mut edges = canonical_edges(&graph);
+    merge_path_ranges(&mut path_key_ranges, &mut graph, &mut edges, &mut NodeSequences::default(), &mut sequenceless, None, &mut RunSummary::default(), false);
+
+    let coverage = KeyCoverage::new(&graph, &NodeSequences::default(), &path_key_ranges["sample#2#chr1"]);
+    assert_eq!(coverage, KeyCoverage { length: 8, span: 40, gaps: 1, gap_bp: 32 });
+    assert_eq!(coverage.fraction(false), 0.2);
+    assert_eq!(coverage.fraction(true), 1.0);
+
+    let mut with_gaps_filled = path_key_ranges.clone();
+    assert_eq!(drop_low_coverage_keys(&mut with_gaps_filled, &graph, &NodeSequences::default(), 0.5, true), 0);
+    assert_eq!(drop_low_coverage_keys(&mut path_key_ranges, &graph, &NodeSequences::default(), 0.5, false), 1);
+    assert_eq!(path_key_ranges.keys().map(|key| &**key).collect::<Vec<_>>(), vec!["sample#1#chr1"]);
+
+    // The nodes of the skipped key are not written
+    let gfa = write_test_gfa(&graph, &path_key_ranges, &sequenceless, &link_overlaps, &WriteOptions::default());
+    assert_eq!(gfa.lines().filter(|l| l.starts_with("S\t")).count(), 2);
+    assert!(!gfa.contains("sample#2"));
+}
+
+#[test]
+fn test_merge_same_block_ranges() {
+    // A single block tiled into two abutting ranges of a key, with the boundary edge given in
+    // its reverse complement orientation, and without it
+    for (links, expected_new_edges) in [("L\t3\t-\t2\t-\t0M\n", 0), ("", 1)] {
+        let block = write_temp_gfa(&format!("S\t1\tACGT\nS\t2\tGG\nS\t3\tTTT\nL\t1\t+\t2\t+\t0M\n{}\
+            P\tsample#1#chr1:0-6\t1+,2+\t*\nP\tsample#1#chr1:6-9\t3+\t*\n", links));
+        let gfa_list = vec![block.path().to_str().unwrap().to_string()];
+        let CombinedInput { mut graph, mut path_key_ranges, mut sequenceless, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+        let mut summary = RunSummary::default();
+        let mut edges = canonical_edges(&graph);
+        merge_path_ranges(&mut path_key_ranges, &mut graph, &mut edges, &mut NodeSequences::default(), &mut sequenceless, None, &mut summary, false);
+        assert_eq!(summary.new_edges, expected_new_edges);
+        assert_eq!(graph.edge_count(), 2);
+        assert!(graph.has_edge(Handle::pack(2u64, false), Handle::pack(3u64, false)));
+
+        let output = NamedTempFile::new().unwrap();
+        let (split_keys, _) = write_graph_to_gfa(&graph, &NodeSequences::default(), &path_key_ranges, &sequenceless, &LinkOverlaps::default(), &PassthroughLines::default(), output.path().to_str().unwrap(), &None, &WriteOptions::default(), None, None, None).unwrap();
+        assert_eq!(split_keys, 0);
+        let gfa = std::fs::read_to_string(output.path()).unwrap();
+        let links: Vec<&str> = gfa.lines().filter(|line| line.starts_with("L\t")).collect();
+        assert_eq!(links, vec!["L\t1\t+\t2\t+\t0M", "L\t2\t+\t3\t+\t0M"]);
+        let paths: Vec<&str> = gfa.lines().filter(|line| line.starts_with("P\t")).collect();
+        assert_eq!(paths, vec!["P\tsample#1#chr1\t1+,2+,3+\t*"]);
+    }
+}
+
+#[test]
+fn test_drop_unused_edges() {
+    // Each block has an edge that no path traverses, and the blocks are joined at 6
+    let blocks: Vec<_> = [
+        "S\t1\tACGT\nS\t2\tGG\nS\t3\tC\nL\t1\t+\t2\t+\t0M\nL\t1\t+\t3\t+\t0M\nP\tsample#1#chr1:0-6\t1+,2+\t*\n",
+        "S\t1\tTT\nS\t2\tA\nL\t1\t+\t2\t+\t0M\nL\t2\t+\t1\t-\t0M\nP\tsample#1#chr1:6-9\t1+,2+\t*\n",
+    ].iter().map(|block| write_temp_gfa(block)).collect();
+    let gfa_list: Vec<String> = blocks.iter().map(|block| block.path().to_str().unwrap().to_string()).collect();
+    let CombinedInput { mut graph, mut path_key_ranges, mut sequenceless, id_translations, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+    let mut edges = canonical_edges(&graph);
+    let split_origins = merge_path_ranges(&mut path_key_ranges, &mut graph, &mut edges, &mut NodeSequences::default(), &mut sequenceless, None, &mut RunSummary::default(), false);
+    assert_eq!(graph.edge_count(), 5);
+
+    assert_eq!(drop_unused_edges(&mut graph, &path_key_ranges, &id_translations, &split_origins), vec![1, 1]);
+    assert_eq!(graph.edge_count(), 3);
+
+    // Every remaining edge, including the one between the blocks, is witnessed by a path
+    let path_steps: Vec<Handle> = path_key_ranges["sample#1#chr1"].iter().flat_map(|range| range.steps.clone()).collect();
+    let witnessed: EdgeSet = path_steps.windows(2).map(|pair| Edge::edge_handle(pair[0], pair[1])).collect();
+    assert!(graph.edges().all(|Edge(left, right)| witnessed.contains(&Edge::edge_handle(left, right))));
+    assert!(witnessed.contains(&Edge::edge_handle(path_steps[1], path_steps[2])));
+}
+
+#[test]
+fn test_prune_orphans() {
+    // The first block has a node that its path skips, and the second block has no paths
+    let blocks: Vec<_> = [
+        "S\t1\tACGT\nS\t2\tGG\nS\t3\tC\nL\t1\t+\t2\t+\t0M\nL\t2\t+\t3\t+\t0M\nP\tsample#1#chr1:0-6\t1+,2+\t*\n",
+        "S\t1\tTT\nS\t2\tA\nL\t1\t+\t2\t+\t0M\n",
+    ].iter().map(|block| write_temp_gfa(block)).collect();
+    let gfa_list: Vec<String> = blocks.iter().map(|block| block.path().to_str().unwrap().to_string()).collect();
+    let CombinedInput { mut graph, mut path_key_ranges, mut sequenceless, id_translations, warnings, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+    assert_eq!(warnings, [LaceWarning::PathlessBlock { block: 1 }]);
+    let mut edges = canonical_edges(&graph);
+    let split_origins = merge_path_ranges(&mut path_key_ranges, &mut graph, &mut edges, &mut NodeSequences::default(), &mut sequenceless, None, &mut RunSummary::default(), false);
+
+    let write = |graph: &HashGraph| {
+        write_test_gfa(graph, &path_key_ranges, &sequenceless, &LinkOverlaps::default(), &WriteOptions::default())
+    };
+    let unpruned = write(&graph);
+
+    assert_eq!(prune_orphans(&mut graph, &path_key_ranges, &id_translations, &split_origins), vec![1, 2]);
+    assert_eq!(graph.node_count(), 2);
+    assert_eq!(graph.edge_count(), 1);
+    // The orphans were not written anyway
+    assert_eq!(write(&graph), unpruned);
+}
+
+#[test]
+fn test_extract_region() {
+    // A laced graph with a full path, a reverse one, and a fragment of another contig
+    let laced = write_temp_gfa("H\tVN:Z:1.0\nS\t1\tACGT\nS\t2\tGGCC\nS\t3\tTTAG\nS\t4\t*\tLN:i:3\n\
+        L\t1\t+\t2\t+\t0M\nL\t2\t+\t3\t+\t0M\nL\t3\t+\t4\t+\t0M\n\
+        P\tsample#1#chr1\t1+,2+,3+,4+\t*\nP\tsample#2#chr1:10-22\t3-,2-,1-\t*\nP\tsample#1#chr2\t1+\t*\n");
+    let extract = |region: &str, whole_nodes: bool| {
+        let output = NamedTempFile::new().unwrap();
+        let num_paths = extract_region(laced.path().to_str().unwrap(), region, whole_nodes, output.path().to_str().unwrap(), &ReadOptions::default()).unwrap();
+        (num_paths, std::fs::read_to_string(output.path()).unwrap(), output)
+    };
+
+    let (num_paths, gfa, _output) = extract("chr1:6-1,3", false);
+    assert_eq!(num_paths, 2);
+    // The reverse path spells CTA, the first 3 bp of the reverse complement of TTAG
+    assert_eq!(gfa, "H\tVN:Z:1.0\nS\t1\tTTAG\nS\t2\tCC\nS\t3\t*\tLN:i:1\nS\t4\tTAG\n\
+        L\t1\t+\t3\t+\t0M\nL\t1\t-\t2\t-\t0M\n\
+        P\tsample#1#chr1:6-13\t2+,1+,3+\t*\nP\tsample#2#chr1:10-13\t4-\t*\n");
+
+    // Boundary nodes kept whole, with the paths extended to their ends, and the links of the input kept
+    let (_, gfa, output) = extract("chr1:6-13", true);
+    assert!(gfa.contains("P\tsample#1#chr1:4-15\t1+,2+,3+\t*\nP\tsample#2#chr1:10-14\t2-\t*\n"));
+    assert_eq!(gfa.lines().filter(|l| l.starts_with("L\t")).count(), 2);
+
+    // The extracted graph can be laced again
+    let CombinedInput { path_key_ranges, .. } = read_gfa_files(&[output.path().to_str().unwrap().to_string()], &ReadOptions::default()).unwrap();
+    let ranges: Vec<_> = path_key_ranges["sample#1#chr1"].iter().map(|r| (r.start, r.end, r.step_ends.clone())).collect();
+    assert_eq!(ranges, vec![(4, 15, vec![8, 12, 15])]);
+
+    assert_eq!(extract("chr3:0-10", false).0, 0);
+    let output = NamedTempFile::new().unwrap();
+    assert!(extract_region(laced.path().to_str().unwrap(), "chr1:10-5", false, output.path().to_str().unwrap(), &ReadOptions::default()).is_err());
+
+    // A failed extraction leaves no file behind, whether it fails before writing or once written
+    let dir = tempfile::tempdir().unwrap();
+    let files = || -> Vec<String> {
+        let mut files: Vec<String> = std::fs::read_dir(dir.path()).unwrap().map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned()).collect();
+        files.sort();
+        files
+    };
+    let broken = write_temp_gfa("S\t1\tACGT\nP\tsample#1#chr1\t1+,2+\t*\n");
+    let output = dir.path().join("out.gfa");
+    assert!(extract_region(broken.path().to_str().unwrap(), "chr1:0-6", false, output.to_str().unwrap(), &ReadOptions::default()).is_err());
+    assert!(files().is_empty());
+    // A directory cannot be replaced by the written file
+    std::fs::create_dir(&output).unwrap();
+    std::fs::write(output.join("kept"), "").unwrap();
+    assert!(extract_region(laced.path().to_str().unwrap(), "chr1:0-6", false, output.to_str().unwrap(), &ReadOptions::default()).is_err());
+    assert_eq!(files(), ["out.gfa"]);
+    assert!(output.is_dir());
+}
+
+#[test]
+fn test_diff_graphs() {
+    let old = write_temp_gfa("S\t1\tACGT\nS\t2\tGG\nS\t3\tT\nL\t1\t+\t2\t+\t0M\nL\t2\t+\t3\t+\t0M\n\
+        P\tsample#1#chr1\t1+,2+,3+\t*\nP\tsample#2#chr1\t1+,2+\t*\nP\tsample#3#chr1\t1+\t*\n");
+    // Renumbered and with reversed links, the node of sample#3 split, and sample#2 changed
+    let new = write_temp_gfa("S\t5\tGG\nS\t6\tACGT\nS\t7\tA\nS\t8\tAC\nS\t9\tGT\n\
+        L\t5\t-\t6\t-\t0M\nL\t5\t+\t7\t-\t0M\nL\t8\t+\t9\t+\t0M\n\
+        P\tsample#1#chr1\t6+,5+,7-\t*\nP\tsample#2#chr1\t6+,5+,7+\t*\nP\tsample#3#chr1\t8+,9+\t*\nP\tsample#4#chr2\t5+\t*\n");
+    let (old, new) = (old.path().to_str().unwrap(), new.path().to_str().unwrap());
+
+    assert!(diff_graphs(old, old, &ReadOptions::default()).unwrap().is_empty());
+    let diff = diff_graphs(old, new, &ReadOptions::default()).unwrap();
+    assert_eq!(diff, GraphDiff {
+        only_old: vec![],
+        only_new: vec!["sample#4#chr2".to_string()],
+        sequences: vec![("sample#2#chr1".to_string(), 6)],
+        boundaries: vec![("sample#3#chr1".to_string(), 2)],
+        nodes: (3, 5),
+        edges: (2, 3),
+        unmatched_nodes: (0, 2),
+        unmatched_edges: (0, 1),
+        coverage: vec![("chr1".to_string(), 17, 18), ("chr2".to_string(), 0, 2)],
+    });
+    assert!(!diff.is_empty());
+
+    // Renumbered, with node 2 stored reverse complemented, is no difference, while a changed link is one
+    let renumbered = write_temp_gfa("S\t3\tT\nS\t10\tACGT\nS\t20\tCC\nL\t10\t+\t20\t-\t0M\nL\t3\t-\t20\t+\t0M\n\
+        P\tsample#3#chr1\t10+\t*\nP\tsample#1#chr1\t10+,20-,3+\t*\nP\tsample#2#chr1\t10+,20-\t*\n");
+    let renumbered_path = renumbered.path().to_str().unwrap();
+    assert_eq!(diff_graphs(old, renumbered_path, &ReadOptions::default()).unwrap(), GraphDiff { nodes: (3, 3), edges: (2, 2), ..GraphDiff::default() });
+    let relinked = write_temp_gfa("S\t1\tACGT\nS\t2\tGG\nS\t3\tT\nL\t1\t+\t2\t-\t0M\nL\t2\t+\t3\t+\t0M\n\
+        P\tsample#1#chr1\t1+,2+,3+\t*\nP\tsample#2#chr1\t1+,2+\t*\nP\tsample#3#chr1\t1+\t*\n");
+    let diff = diff_graphs(old, relinked.path().to_str().unwrap(), &ReadOptions::default()).unwrap();
+    assert_eq!((diff.unmatched_nodes, diff.unmatched_edges), ((0, 0), (1, 1)));
+    assert!(!diff.is_empty());
+}
+
+#[test]
+fn test_check_roundtrip() {
+    let block_a = write_temp_gfa("S\t1\tACGT\nS\t2\tGG\nS\t3\t*\tLN:i:2\nS\t4\tC\nL\t1\t+\t2\t-\t0M\nL\t2\t-\t3\t+\t0M\n\
+        P\tsample#1#chr1:0-8\t1+,2-,3+\t*\n");
+    let block_b = write_temp_gfa("S\t1\tTTTTT\nP\tsample#1#chr1:10-15\t1+\t*\nP\tsample#1#chr2:0-5\t1+\t*\n");
+    let gfa_list: Vec<String> = [&block_a, &block_b].iter().map(|block| block.path().to_str().unwrap().to_string()).collect();
+    let CombinedInput { mut graph, mut path_key_ranges, link_overlaps, sequenceless, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+    let mut edges = canonical_edges(&graph);
+    sort_and_filter_all_ranges(&mut path_key_ranges, false);
+    for (path_key, ranges) in path_key_ranges.iter_mut() {
+        link_contiguous_ranges(path_key, ranges, &mut graph, &mut edges, false);
+    }
+
+    for options in [
+        WriteOptions::default(),
+        WriteOptions { fill_gaps: 1, no_sort: true, ..WriteOptions::default() },
+        WriteOptions { no_links: true, no_sequences: true, node_order: NodeOrder::PathOrder, fragment_naming: FragmentNaming::Pansn, ..WriteOptions::default() },
+    ] {
+        let output = write_test_gfa_file(&graph, &path_key_ranges, &sequenceless, &link_overlaps, &options).unwrap();
+        let output_path = output.path().to_str().unwrap();
+        assert_eq!(check_roundtrip(output_path, &graph, &NodeSequences::default(), &path_key_ranges, &sequenceless, &options).unwrap(), Vec::<String>::new());
+    }
+
+    // A duplicate link, a path on a dropped node, and a changed sequence
+    let output = write_test_gfa_file(&graph, &path_key_ranges, &sequenceless, &link_overlaps, &WriteOptions::default()).unwrap();
+    let output_path = output.path().to_str().unwrap();
+    let gfa = std::fs::read_to_string(output_path).unwrap().replace("S\t1\tACGT", "S\t1\tACGA")
+        + "L\t2\t+\t1\t-\t0M\nP\tsample#1#chr2:5-6\t9+\t*\n";
+    std::fs::write(output_path, gfa).unwrap();
+    let discrepancies = check_roundtrip(output_path, &graph, &NodeSequences::default(), &path_key_ranges, &sequenceless, &WriteOptions::default()).unwrap();
+    assert_eq!(discrepancies, vec![
+        "segment 1 has a different sequence",
+        "link 2+ 1- is written more than once",
+        "path 'sample#1#chr2:5-6' visits the undefined segment 9",
+    ]);
+}
+
+#[test]
+fn test_first_id() {
+    let block_a = write_temp_gfa("S\t1\tACGT\nS\t2\tGG\nS\t3\tC\nL\t1\t+\t2\t-\t0M\nL\t2\t-\t3\t+\t0M\n\
+        P\tsample#1#chr1:0-7\t1+,2-,3+\t*\nC\t1\t+\t3\t+\t1\t1M\n");
+    let block_b = write_temp_gfa("S\t1\tTTTTT\nP\tsample#1#chr1:10-15\t1+\t*\n");
+    let gfa_list: Vec<String> = [&block_a, &block_b].iter().map(|block| block.path().to_str().unwrap().to_string()).collect();
+    let CombinedInput { graph, mut path_key_ranges, link_overlaps, sequenceless, containments, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+    sort_and_filter_all_ranges(&mut path_key_ranges, false);
+    let passthrough = PassthroughLines { containments, ..PassthroughLines::default() };
+
+    for no_sort in [false, true] {
+        let write = |first_id: usize| {
+            let options = WriteOptions { fill_gaps: 1, no_sort, first_id, ..WriteOptions::default() };
+            let output = NamedTempFile::new().unwrap();
+            let output_path = output.path().to_str().unwrap();
+            let (_, max_node_id) = write_graph_to_gfa(&graph, &NodeSequences::default(), &path_key_ranges, &sequenceless, &link_overlaps, &passthrough, output_path, &None, &options, None, None, None).unwrap();
+            assert_eq!(check_roundtrip(output_path, &graph, &NodeSequences::default(), &path_key_ranges, &sequenceless, &options).unwrap(), Vec::<String>::new());
+            assert_eq!(graph_stats(output_path).unwrap().max_id, max_node_id);
+            (output, max_node_id)
+        };
+        let (output, max_node_id) = write(1);
+        let (offset_output, offset_max_node_id) = write(1001);
+
+        // Four nodes and the gap node, numbered from 1001 instead of 1
+        assert_eq!(max_node_id, 5);
+        assert_eq!(offset_max_node_id, 1005);
+        let shifted: Vec<String> = (0..=5).map(|id| (id + 1000).to_string()).collect();
+        rename_output_nodes(output.path().to_str().unwrap(), &shifted).unwrap();
+        let offset_gfa = std::fs::read_to_string(offset_output.path()).unwrap();
+        assert_eq!(std::fs::read_to_string(output.path()).unwrap(), offset_gfa);
+        assert!(offset_gfa.contains("P\tsample#1#chr1\t1001+,1002-,1003+,1005+,1004+\t*\n"));
+    }
+}
+
+#[test]
+fn test_compressed_output() {
+    let block = write_temp_gfa("S\t1\tACGT\nS\t2\tGG\nL\t1\t+\t2\t-\t0M\nP\tsample#1#chr1:0-6\t1+,2-\t*\n");
+    let CombinedInput { graph, mut path_key_ranges, link_overlaps, sequenceless, .. } = read_gfa_files(&[block.path().to_str().unwrap().to_string()], &ReadOptions::default()).unwrap();
+    sort_and_filter_all_ranges(&mut path_key_ranges, false);
+    let expected = "H\tVN:Z:1.0\nS\t1\tACGT\nS\t2\tGG\nL\t1\t+\t2\t-\t0M\nP\tsample#1#chr1\t1+,2-\t*\n";
+
+    let dir = tempfile::tempdir().unwrap();
+    for (name, magic) in [("out.gfa", &b"H\t"[..]), ("out.gfa.gz", &[0x1f, 0x8b][..]), ("out.gfa.zst", &[0x28, 0xb5, 0x2f, 0xfd][..])] {
+        let output_path = dir.path().join(name);
+        let output_path = output_path.to_str().unwrap();
+        write_graph_to_gfa(&graph, &NodeSequences::default(), &path_key_ranges, &sequenceless, &link_overlaps, &PassthroughLines::default(), output_path, &None, &WriteOptions::default(), None, None, None).unwrap();
+        assert!(std::fs::read(output_path).unwrap().starts_with(magic), "{}", name);
+        let mut text = String::new();
+        GfaInput::Path(output_path).lines(&ReadOptions::default()).unwrap().read_to_string(&mut text).unwrap();
+        assert_eq!(text, expected, "{}", name);
+        assert_eq!(check_roundtrip(output_path, &graph, &NodeSequences::default(), &path_key_ranges, &sequenceless, &WriteOptions::default()).unwrap(), Vec::<String>::new());
+
+        // Renaming keeps the compression
+        rename_output_nodes(output_path, &["".to_string(), "a".to_string(), "b".to_string()]).unwrap();
+        assert!(std::fs::read(output_path).unwrap().starts_with(magic), "{}", name);
+        let mut text = String::new();
+        GfaInput::Path(output_path).lines(&ReadOptions::default()).unwrap().read_to_string(&mut text).unwrap();
+        assert_eq!(text, expected.replace("\t1\t", "\ta\t").replace("\t2\t", "\tb\t").replace("1+,2-", "a+,b-"), "{}", name);
+    }
+}
+
+#[test]
+fn test_graph_stats() {
+    // A bubble with a sequenceless segment, a path through each branch, and an isolated segment.
+    // By the definitions of `odgi stats -S`, this graph has length 17, 5 nodes, 4 edges, and 2 paths
+    // of 3 steps. odgi counts the steps of all paths (6) where the paths are listed separately here,
+    // and has no N50 or degree histogram
+    let block = write_temp_gfa("H\tVN:Z:1.0\nS\t1\tACGT\nS\t2\t*\tLN:i:3\nS\t3\tG\nS\t4\tCC\nS\t5\tTTTTTGG\n\
+        L\t1\t+\t2\t+\t0M\nL\t1\t+\t3\t-\t0M\nL\t2\t+\t4\t+\t0M\nL\t3\t-\t4\t+\t0M\n\
+        P\tsample#1#chr1\t1+,2+,4+\t*\nP\tsample#2#chr1\t1+,3-,4+\t*\n");
+    let stats = graph_stats(block.path().to_str().unwrap()).unwrap();
+
+    assert_eq!(stats.length, 17);
+    assert_eq!(stats.node_lengths, vec![4, 3, 1, 2, 7]);
+    assert_eq!(stats.node_n50(), 4);
+    assert_eq!(stats.node_mean(), 3.4);
+    assert_eq!(stats.edges, 4);
+    assert_eq!(stats.degrees, BTreeMap::from([(0, 1), (2, 4)]));
+    assert_eq!(stats.components, 2);
+    assert_eq!(stats.max_id, 5);
+    assert_eq!(stats.paths, vec![("sample#1#chr1".to_string(), 3, 9), ("sample#2#chr1".to_string(), 3, 7)]);
+
+    let dangling = write_temp_gfa("S\t1\tA\nL\t1\t+\t2\t+\t0M\n");
+    assert!(graph_stats(dangling.path().to_str().unwrap()).is_err());
+}
+
+#[test]
+fn test_liftover() {
+    let block_a = write_temp_gfa("S\t1\tACGT\nS\t2\tGG\nL\t1\t+\t2\t+\t0M\nP\tsample#1#chr1:0-6\t1+,2+\t*\n");
+    let block_b = write_temp_gfa("S\t1\tTTT\nP\tsample#1#chr1:6-9\t1+\t*\n");
+    let block_c = write_temp_gfa("S\t1\tAAA\nP\tsample#1#chr1:12-15\t1+\t*\n");
+    let gfa_list: Vec<String> = [&block_a, &block_b, &block_c].iter()
+        .map(|block| block.path().to_str().unwrap().to_string())
+        .collect();
+    let CombinedInput { graph, mut path_key_ranges, sequenceless, gfa_names, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+    assert_eq!(gfa_names, gfa_list);
+    for (path_key, ranges) in path_key_ranges.iter_mut() {
+        sort_and_filter_ranges(path_key, ranges, false);
+    }
+
+    let write = |fill_gaps: u8| {
+        let output = NamedTempFile::new().unwrap();
+        let liftover_file = NamedTempFile::new().unwrap();
+        let mut liftover = LiftoverWriter::new(liftover_file.path().to_str().unwrap(), &gfa_names).unwrap();
+        let options = WriteOptions { fill_gaps, ..WriteOptions::default() };
+        let (split_keys, _) = write_graph_to_gfa(&graph, &NodeSequences::default(), &path_key_ranges, &sequenceless, &LinkOverlaps::default(), &PassthroughLines::default(), output.path().to_str().unwrap(), &None, &options, Some(&mut liftover), None, None).unwrap();
+        assert_eq!(split_keys, if fill_gaps == 0 { 1 } else { 0 });
+        liftover.finish().unwrap();
+        std::fs::read_to_string(liftover_file.path()).unwrap()
+    };
+
+    // Without gap filling, the last range is a separate fragment path
+    let rows = write(0);
+    let rows: Vec<&str> = rows.lines().skip(1).collect();
+    assert_eq!(rows, vec![
+        format!("sample#1#chr1:0-9\t0\t6\t{}\tsample#1#chr1:0-6\t0\t6", gfa_list[0]),
+        format!("sample#1#chr1:0-9\t6\t9\t{}\tsample#1#chr1:6-9\t6\t9", gfa_list[1]),
+        format!("sample#1#chr1:12-15\t0\t3\t{}\tsample#1#chr1:12-15\t12\t15", gfa_list[2]),
+    ]);
+
+    // The gap node shifts the offsets of the next range, and has no row
+    let rows = write(1);
+    let rows: Vec<&str> = rows.lines().skip(1).collect();
+    assert_eq!(rows.len(), 3);
+    assert!(rows[0].starts_with("sample#1#chr1\t0\t6\t"));
+    assert!(rows[1].starts_with("sample#1#chr1\t6\t9\t"));
+    assert_eq!(rows[2], format!("sample#1#chr1\t12\t15\t{}\tsample#1#chr1:12-15\t12\t15", gfa_list[2]));
+}
+
+#[test]
+fn test_range_order() {
+    // The coordinates put the first block first, the explicit order puts it second
+    let blocks: Vec<_> = [
+        "S\t1\tAAAA\nP\tsample#1#chr1:0-4\t1+\t*\nP\tother#1#chr1:0-4\t1+\t*\n",
+        "S\t1\tCCCC\nP\tsample#1#chr1:4-8\t1+\t*\nP\tother#1#chr1:4-8\t1+\t*\n",
+    ].iter().map(|block| write_temp_gfa(block)).collect();
+    let gfa_list: Vec<String> = blocks.iter().map(|block| block.path().to_str().unwrap().to_string()).collect();
+    let order_file = write_temp_gfa(&format!("# key\tgfa_id\trank\nsample#1#chr1\t{}\t1\nsample#1#chr1\t0\t2\nmissing#1#chr1\t0\t1\n", gfa_list[1]));
+    let CombinedInput { mut graph, mut path_key_ranges, link_overlaps, mut sequenceless, gfa_names, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+    let order = read_range_order(order_file.path().to_str().unwrap(), &gfa_names).unwrap();
+    assert_eq!(apply_range_order(&mut path_key_ranges, &order), vec!["missing#1#chr1".to_string()]);
+    let mut edges = canonical_edges(&graph);
+    merge_path_ranges(&mut path_key_ranges, &mut graph, &mut edges, &mut NodeSequences::default(), &mut sequenceless, None, &mut RunSummary::default(), false);
+    // The ranges are sorted by rank and keep their coordinates
+    let bounds: Vec<_> = path_key_ranges["sample#1#chr1"].iter().map(|range| (range.start, range.end)).collect();
+    assert_eq!(bounds, vec![(4, 8), (0, 4)]);
+
+    let gfa = write_test_gfa(&graph, &path_key_ranges, &sequenceless, &link_overlaps, &WriteOptions::default());
+    let segments: FxHashMap<&str, &str> = gfa.lines().filter(|line| line.starts_with("S\t"))
+        .map(|line| { let fields: Vec<&str> = line.split('\t').collect(); (fields[1], fields[2]) }).collect();
+    let paths: FxHashMap<&str, String> = gfa.lines().filter(|line| line.starts_with("P\t")).map(|line| {
+        let fields: Vec<&str> = line.split('\t').collect();
+        (fields[1], fields[2].split(',').map(|step| segments[&step[..step.len() - 1]]).collect())
+    }).collect();
+    // The explicit order wins, and keys without one keep the coordinate order
+    assert_eq!(paths["sample#1#chr1"], "CCCCAAAA");
+    assert_eq!(paths["other#1#chr1"], "AAAACCCC");
+
+    let bad_order = write_temp_gfa("sample#1#chr1\t2\t1\n");
+    assert!(read_range_order(bad_order.path().to_str().unwrap(), &gfa_names).is_err());
+}
+
+#[test]
+fn test_shard_size() {
+    // Two blocks with several segments each, separated by a gap, so that some links cross shards
+    let blocks: Vec<_> = [
+        "H\tVN:Z:1.0\nS\t1\tACGTACGT\nS\t2\tGG\nS\t3\tTTTTTT\nL\t1\t+\t2\t+\t0M\nL\t2\t+\t3\t+\t0M\nL\t1\t+\t3\t+\t0M\nP\tsample#1#chr1:0-16\t1+,2+,3+\t*\n",
+        "S\t5\tCCCCCCCC\nS\t6\tAAAA\nL\t5\t+\t6\t+\t0M\nP\tsample#1#chr1:20-32\t5+,6+\t*\nP\tother#1#chr1:0-12\t5+,6+\t*\n",
+    ].iter().map(|block| write_temp_gfa(block)).collect();
+    let gfa_list: Vec<String> = blocks.iter().map(|block| block.path().to_str().unwrap().to_string()).collect();
+    let CombinedInput { mut graph, mut path_key_ranges, link_overlaps, mut sequenceless, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+    let mut edges = canonical_edges(&graph);
+    merge_path_ranges(&mut path_key_ranges, &mut graph, &mut edges, &mut NodeSequences::default(), &mut sequenceless, None, &mut RunSummary::default(), false);
+    let write = |output_path: &str, shard_size: Option<u64>| {
+        let options = WriteOptions { fill_gaps: 1, shard_size, ..WriteOptions::default() };
+        write_graph_to_gfa(&graph, &NodeSequences::default(), &path_key_ranges, &sequenceless, &link_overlaps, &PassthroughLines::default(), output_path, &None, &options, None, None, None).unwrap();
+    };
+    let dir = tempfile::tempdir().unwrap();
+    let whole_path = dir.path().join("whole.gfa");
+    write(whole_path.to_str().unwrap(), None);
+    let whole = std::fs::read_to_string(&whole_path).unwrap();
+    let header: String = whole.lines().take_while(|line| !line.starts_with("S\t")).map(|line| format!("{}\n", line)).collect();
+    assert!(header.starts_with("H\t"));
+
+    let shard_size = 48;
+    let base = dir.path().join("sharded");
+    write(&format!("{}.gfa", base.display()), Some(shard_size));
+    let manifest: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(format!("{}.manifest.json", base.display())).unwrap()).unwrap();
+    assert_eq!(manifest["shard_size"], shard_size);
+    let shards = manifest["shards"].as_array().unwrap();
+    assert!(shards.len() > 1, "{}", manifest);
+
+    let mut lines: Vec<String> = Vec::new();
+    for (index, entry) in shards.iter().enumerate() {
+        let file = format!("{}.{:04}.gfa", base.display(), index);
+        assert_eq!(entry["file"], file);
+        let gfa = std::fs::read_to_string(&file).unwrap();
+        assert_eq!(entry["bytes"], gfa.len());
+        let body = gfa.strip_prefix(&header).unwrap();
+        let segments: FxHashSet<&str> = body.lines().filter(|line| line.starts_with("S\t")).map(|line| line.split('\t').nth(1).unwrap()).collect();
+        assert_eq!(entry["segments"], segments.len());
+        assert!(gfa.len() as u64 <= shard_size || segments.len() == 1, "{}", gfa);
+        assert_eq!(entry["first_segment"].to_string(), segments.iter().map(|id| id.parse::<usize>().unwrap()).min().unwrap().to_string());
+        assert_eq!(entry["last_segment"].to_string(), segments.iter().map(|id| id.parse::<usize>().unwrap()).max().unwrap().to_string());
+        // Every shard is self-contained for its segments
+        for line in body.lines() {
+            let fields: Vec<&str> = line.split('\t').collect();
+            match fields[0] {
+                "S" => {}
+                "L" => assert!(segments.contains(fields[1]) && segments.contains(fields[3]), "{}", line),
+                _ => panic!("Unexpected line in a shard: {}", line),
+            }
+        }
+        lines.extend(body.lines().map(String::from));
+    }
+    for (key, record) in [("links", "L"), ("paths", "P")] {
+        let file = format!("{}.{}.gfa", base.display(), key);
+        assert_eq!(manifest[key]["file"], file);
+        let gfa = std::fs::read_to_string(&file).unwrap();
+        let body = gfa.strip_prefix(&header).unwrap();
+        assert!(body.lines().all(|line| line.starts_with(record)), "{}", gfa);
+        assert_eq!(manifest[key]["records"], body.lines().count());
+        lines.extend(body.lines().map(String::from));
+    }
+    assert!(!std::fs::read_to_string(format!("{}.links.gfa", base.display())).unwrap().strip_prefix(&header).unwrap().is_empty());
+
+    // The shards hold the same records as the single output
+    let mut expected: Vec<String> = whole.lines().skip(header.lines().count()).map(String::from).collect();
+    expected.sort();
+    lines.sort();
+    assert_eq!(lines, expected);
+}
+
+#[test]
+fn test_line_ending_and_path_names() {
+    let block = write_temp_gfa("S\t1\tACGT\nS\t2\tGG\nL\t1\t+\t2\t+\t0M\nP\tsample#1#chr1:0-6\t1+,2+\t*\n");
+    let gfa_list = vec![block.path().to_str().unwrap().to_string()];
+    let CombinedInput { graph, mut path_key_ranges, sequenceless, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+    sort_and_filter_all_ranges(&mut path_key_ranges, false);
+    let write = |path_key_ranges: &FxHashMap<PathKey, Vec<RangeInfo>>, line_ending: LineEnding| {
+        let options = WriteOptions { line_ending, ..WriteOptions::default() };
+        write_test_gfa_file(&graph, path_key_ranges, &sequenceless, &LinkOverlaps::default(), &options)
+            .map(|output| (std::fs::read_to_string(output.path()).unwrap(), gfa_checksum(output.path().to_str().unwrap()).unwrap()))
+    };
+
+    let (lf, lf_checksum) = write(&path_key_ranges, LineEnding::Lf).unwrap();
+    let (crlf, crlf_checksum) = write(&path_key_ranges, LineEnding::Crlf).unwrap();
+    assert_eq!(crlf, lf.replace('\n', "\r\n"));
+    assert_eq!(crlf.matches('\n').count(), crlf.matches("\r\n").count());
+    assert_eq!(crlf_checksum, lf_checksum);
+
+    // A tab in a path name is rejected rather than splitting the P line
+    let ranges = path_key_ranges.remove("sample#1#chr1").unwrap();
+    path_key_ranges.insert("sample#1\t#chr1".into(), ranges);
+    let error = write(&path_key_ranges, LineEnding::Lf).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    assert!(error.to_string().contains("'\\t'"), "{}", error);
+}
+
+#[test]
+fn test_paths_only_fasta() {
+    let block_a = write_temp_gfa("S\t1\tACGT\nS\t2\tGG\nL\t1\t+\t2\t-\t0M\nP\tsample#1#chr1:0-6\t1+,2-\t*\n");
+    let block_b = write_temp_gfa("S\t1\tTTT\nP\tsample#1#chr1:6-9\t1+\t*\n");
+    let block_c = write_temp_gfa("S\t1\tAAA\nP\tsample#1#chr1:12-15\t1+\t*\n");
+    let gfa_list: Vec<String> = [&block_a, &block_b, &block_c].iter()
+        .map(|block| block.path().to_str().unwrap().to_string())
+        .collect();
+    let CombinedInput { graph, mut path_key_ranges, sequenceless, .. } = read_gfa_files(&gfa_list, &ReadOptions::default()).unwrap();
+    for (path_key, ranges) in path_key_ranges.iter_mut() {
+        sort_and_filter_ranges(path_key, ranges, false);
+    }
+
+    let output = NamedTempFile::new().unwrap();
+    let fasta_file = NamedTempFile::new().unwrap();
+    let mut paths_fasta = PathFastaWriter::new(fasta_file.path().to_str().unwrap()).unwrap();
+    let options = WriteOptions { paths_only: true, ..WriteOptions::default() };
+    write_graph_to_gfa(&graph, &NodeSequences::default(), &path_key_ranges, &sequenceless, &LinkOverlaps::default(), &PassthroughLines::default(), output.path().to_str().unwrap(), &None, &options, None, Some(&mut paths_fasta), None).unwrap();
+    paths_fasta.finish().unwrap();
+
+    // Only the header and the paths are written, with the same IDs as the full output
+    let gfa = std::fs::read_to_string(output.path()).unwrap();
+    assert_eq!(gfa, "H\tVN:Z:1.0\nP\tsample#1#chr1:0-9\t1+,2-,3+\t*\nP\tsample#1#chr1:12-15\t4+\t*\n");
+
+    // The sequence of the contiguous fragment spans its coordinates, with the steps in their orientation
+    let fasta = std::fs::read_to_string(fasta_file.path()).unwrap();
+    assert_eq!(fasta, ">sample#1#chr1:0-9\nACGTCCTTT\n>sample#1#chr1:12-15\nAAA\n");
+    let span = path_key_ranges["sample#1#chr1"][1].end - path_key_ranges["sample#1#chr1"][0].start;
+    assert_eq!(fasta.lines().nth(1).unwrap().len(), span);
+}
+
+#[test]
+fn test_json_log_records() {
+    let fields = [("stage", log::kv::Value::from("merge")), ("path_key", "sample#1#chr1".into()), ("overlap_bp", 42usize.into())];
+    let line = format_json_record(&log::Record::builder()
+        .level(log::Level::Debug)
+        .args(format_args!("  key {}: overlap", "sample#1#chr1"))
+        .key_values(&fields)
+        .build());
+
+    let event: serde_json::Value = serde_json::from_str(&line).unwrap();
+    assert_eq!(event["level"], "debug");
+    assert_eq!(event["stage"], "merge");
+    assert_eq!(event["path_key"], "sample#1#chr1");
+    assert_eq!(event["overlap_bp"], 42);
+    assert_eq!(event["message"], "key sample#1#chr1: overlap");
+
+    // Events without structured fields still have a stage
+    let line = format_json_record(&log::Record::builder().level(log::Level::Warn).args(format_args!("plain")).build());
+    let event: serde_json::Value = serde_json::from_str(&line).unwrap();
+    assert!(event["stage"].is_null());
+    assert_eq!(event["level"], "warn");
+}
+
+#[test]
+fn test_interned_key_order() {
+    // Keys read in reverse order, with prefixes and non-ASCII names, are written sorted by name
+    let names = ["sample#1#chr10", "sample#1#chr1", "sample#1#chr\u{e9}", "sample#1#Chr2", "other#1#chr1", "sample#10#chr1"];
+    let block = write_temp_gfa(&names.iter().rev().map(|name| format!("P\t{}:0-4\t1+\t*\n", name)).fold("S\t1\tACGT\n".to_string(), |gfa, line| gfa + &line));
+    let CombinedInput { mut graph, mut path_key_ranges, link_overlaps, mut sequenceless, .. } = read_gfa_files(&[block.path().to_str().unwrap().to_string()], &ReadOptions::default()).unwrap();
+    let mut edges = canonical_edges(&graph);
+    merge_path_ranges(&mut path_key_ranges, &mut graph, &mut edges, &mut NodeSequences::default(), &mut sequenceless, None, &mut RunSummary::default(), false);
+
+    let gfa = write_test_gfa(&graph, &path_key_ranges, &sequenceless, &link_overlaps, &WriteOptions::default());
+    let written: Vec<&str> = gfa.lines().filter_map(|line| line.strip_prefix("P\t")).map(|line| line.split('\t').next().unwrap()).collect();
+    let mut expected: Vec<String> = names.iter().map(|name| name.to_string()).collect();
+    expected.sort();
+    assert_eq!(written, expected);
+    // Interned keys order as their strings
+    let mut interned: Vec<PathKey> = names.iter().map(|&name| name.into()).collect();
+    interned.sort();
+    assert!(interned.iter().map(|key| &**key).eq(expected.iter().map(String::as_str)));
+}
+
+#[test]
+fn test_sort_path_keys() {
+    let keys: Vec<PathKey> = ["sample#1#chr1:100-200", "sample#1#chr1:9-20", "sample#1#chr10", "sample#1#chr1:9-15", "other#1#chr1"]
+        .iter().map(|&key| key.into()).collect();
+    let sorted = |order: PathOrder| {
+        let mut path_keys: Vec<&PathKey> = keys.iter().collect();
+        sort_path_keys(&mut path_keys, order, RangeSyntax::ColonDash);
+        path_keys.into_iter().map(|key| &**key).collect::<Vec<_>>()
+    };
+
+    assert_eq!(sorted(PathOrder::Name), vec!["other#1#chr1", "sample#1#chr10", "sample#1#chr1:100-200", "sample#1#chr1:9-15", "sample#1#chr1:9-20"]);
+    assert_eq!(sorted(PathOrder::Coord), vec!["other#1#chr1", "sample#1#chr1:9-15", "sample#1#chr1:9-20", "sample#1#chr1:100-200", "sample#1#chr10"]);
+    assert_eq!(sorted(PathOrder::None), keys.iter().map(|key| &**key).collect::<Vec<_>>());
+}
+
+/// Serves one response per request from a local HTTP server, returning its base URL
+#[cfg(feature = "http")]
+fn serve_http(responses: Vec<(u16, &'static str)>) -> String {
+    let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", server.server_addr().to_ip().unwrap());
+    std::thread::spawn(move || {
+        for (status, body) in responses {
+            let request = server.recv().unwrap();
+            request.respond(tiny_http::Response::from_string(body).with_status_code(status)).unwrap();
+        }
+    });
+    url
+}
+
+#[cfg(feature = "http")]
+#[test]
+fn test_read_gfa_from_url() {
+    let mut parser_builder = GFAParserBuilder::none();
+    parser_builder.paths(true);
+    let parser = parser_builder.build();
+
+    // Success
+    let url = serve_http(vec![(200, "S\t1\tACGT\nP\tsample#1#chr1:0-4\t1+\t*\n")]);
+    let gfa = read_gfa(&format!("{}/block.gfa", url), &parser, &ReadOptions::default()).unwrap();
+    assert_eq!(gfa.paths.len(), 1);
+
+    // Server errors are retried, client errors are not and report the status
+    let url = serve_http(vec![(503, ""), (404, "")]);
+    let error = fetch_url(&format!("{}/missing.gfa", url), 3).err().unwrap();
+    assert!(error.to_string().contains("HTTP status 404"), "{}", error);
+
+    // Connection dropped while the body is read
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/truncated.gfa", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = [0u8; 1024];
+        let _ = stream.read(&mut request);
+        stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 1000\r\n\r\nS\t1\tAC").unwrap();
+    });
+    assert!(read_gfa(&url, &parser, &ReadOptions::default()).is_err());
+}
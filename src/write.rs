@@ -0,0 +1,1382 @@
+//! Writing of the combined graph as GFA, with its node names and side outputs
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    path::Path,
+};
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::{Deserialize, Serialize};
+use handlegraph::{
+    handle::{Direction, Handle, NodeId},
+    handlegraph::*,
+    hashgraph::HashGraph,
+};
+use gfa::{optfields::{OptFields, OptFieldVal}, parser::GFAParser};
+use bitvec::prelude::BitVec;
+use log::{debug, info, warn};
+use rust_htslib::faidx;
+use sha2::{Digest, Sha256};
+use crate::input::{Containment, PassthroughLines, ReadOptions};
+use crate::links::{SORT_CHUNK_SIZE, canonical_link, collect_output_links, output_link_overlaps, write_links_external};
+use crate::merge::{LinkOverlaps, boundary_overlap, mark_nodes_for_removal};
+use crate::names::{RangeSyntax, split_path_name};
+use crate::open::{GfaInput, read_gfa};
+use crate::output::{AtomicFile, CrlfWriter, LineEnding, SEGMENT_BATCH, ShardedWriter};
+use crate::ranges::{PathKey, RangeInfo, range_span};
+use crate::sequences::NodeSequences;
+
+/// Maps the IDs of the used nodes to their output IDs, compacted in output order as the writers do
+/// but always numbered from 1, whatever the first ID of the output, or kept as they are with
+/// `keep_ids`. Returns the mapping and the largest output ID, the number of used nodes when compacted
+pub(crate) fn compact_id_mapping(
+    graph: &HashGraph,
+    path_key_ranges: &FxHashMap<PathKey, Vec<RangeInfo>>,
+    options: &WriteOptions
+) -> (Vec<usize>, usize) {
+    let nodes_to_remove = mark_nodes_for_removal(graph, path_key_ranges);
+    let output_nodes = output_node_order(&nodes_to_remove, path_key_ranges, options.node_order, options.sort_paths_by, options.range_syntax);
+    let mut id_mapping = vec![0; usize::from(graph.max_node_id()) + 1];
+    for (idx, &node_id) in output_nodes.iter().enumerate() {
+        id_mapping[node_id] = if options.keep_ids { node_id } else { idx + 1 };
+    }
+    let max_output_id = if options.keep_ids { output_nodes.iter().copied().max().unwrap_or(0) } else { output_nodes.len() };
+    (id_mapping, max_output_id)
+}
+
+/// Names the output nodes after their input file and segment ID, as `b{GFA_ID}_{ID}`. Nodes split
+/// from a segment when trimming overlaps are `b{GFA_ID}_{ID}_{N}`, with N numbering the pieces of
+/// the segment, and the gap nodes following the used nodes are `gap_{N}`. Indexed by output ID
+pub(crate) fn file_prefixed_node_names(
+    graph: &HashGraph,
+    path_key_ranges: &FxHashMap<PathKey, Vec<RangeInfo>>,
+    options: &WriteOptions,
+    id_translations: &[NodeId],
+    split_origins: &FxHashMap<NodeId, NodeId>
+) -> Vec<String> {
+    let (id_mapping, num_nodes) = compact_id_mapping(graph, path_key_ranges, options);
+
+    let mut names = vec![String::new(); num_nodes + 1];
+    let mut pieces: FxHashMap<(usize, u64), usize> = FxHashMap::default();
+    for (node_id, &new_id) in id_mapping.iter().enumerate().filter(|&(_, &new_id)| new_id > 0) {
+        let node_id = NodeId::from(node_id as u64);
+        let (gfa_id, segment_id) = node_source(node_id, id_translations, split_origins);
+        names[new_id] = if split_origins.contains_key(&node_id) {
+            let piece = pieces.entry((gfa_id, segment_id)).or_insert(0);
+            *piece += 1;
+            format!("b{}_{}_{}", gfa_id, segment_id, piece)
+        } else {
+            format!("b{}_{}", gfa_id, segment_id)
+        };
+    }
+    names
+}
+
+/// Origin of the nodes created by gfalace in the SF tag
+pub(crate) const GFALACE_ORIGIN: &str = "gfalace";
+
+/// Input file of each node of the combined graph, or `gfalace` for the nodes split from a
+/// segment. Indexed by node ID
+pub(crate) fn node_origins<'a>(
+    graph: &HashGraph,
+    id_translations: &[NodeId],
+    split_origins: &FxHashMap<NodeId, NodeId>,
+    gfa_names: &'a [String]
+) -> Vec<&'a str> {
+    let mut origins = vec![GFALACE_ORIGIN; usize::from(graph.max_node_id()) + 1];
+    for handle in graph.handles() {
+        let node_id = handle.id();
+        if !split_origins.contains_key(&node_id) {
+            origins[usize::from(node_id)] = gfa_names[node_source(node_id, id_translations, split_origins).0].as_str();
+        }
+    }
+    origins
+}
+
+/// Output IDs of the nodes of each input, by gfa_id, and first ID of the gap nodes created by
+/// gfalace, which follow the other nodes up to the largest output ID
+pub(crate) struct BlockGroups<'a> {
+    pub(crate) groups: Vec<Vec<usize>>,
+    pub(crate) gfa_names: &'a [String],
+    pub(crate) first_gap_id: usize,
+}
+
+/// Groups the output IDs of the nodes by input, before writing them. Nodes split from a segment
+/// belong to the input of that segment
+pub(crate) fn block_groups<'a>(
+    graph: &HashGraph,
+    path_key_ranges: &FxHashMap<PathKey, Vec<RangeInfo>>,
+    options: &WriteOptions,
+    id_translations: &[NodeId],
+    split_origins: &FxHashMap<NodeId, NodeId>,
+    gfa_names: &'a [String]
+) -> BlockGroups<'a> {
+    let (id_mapping, num_nodes) = compact_id_mapping(graph, path_key_ranges, options);
+    let mut groups = vec![Vec::new(); gfa_names.len()];
+    for (node_id, &new_id) in id_mapping.iter().enumerate().filter(|&(_, &new_id)| new_id > 0) {
+        let (gfa_id, _) = node_source(NodeId::from(node_id as u64), id_translations, split_origins);
+        groups[gfa_id].push(new_id + options.first_id - 1);
+    }
+    for group in &mut groups {
+        group.sort_unstable();
+    }
+    BlockGroups { groups, gfa_names, first_gap_id: num_nodes + options.first_id }
+}
+
+/// Formats sorted IDs as comma-separated runs, e.g. `1-4,7`
+pub(crate) fn format_id_runs(ids: &[usize]) -> String {
+    let mut runs = Vec::new();
+    let mut idx = 0;
+    while idx < ids.len() {
+        let mut end = idx;
+        while end + 1 < ids.len() && ids[end + 1] == ids[end] + 1 {
+            end += 1;
+        }
+        runs.push(if end > idx { format!("{}-{}", ids[idx], ids[end]) } else { ids[idx].to_string() });
+        idx = end + 1;
+    }
+    runs.join(",")
+}
+
+/// Writes a `# block-group` comment per input with nodes, with the gfa_id, the file, and the runs
+/// of output IDs of its nodes, then one for the gap nodes up to `max_node_id`, with gfa_id `*` and
+/// file `gfalace`. Returns the number of groups
+pub(crate) fn write_block_groups<W: Write>(out: &mut W, block_groups: &BlockGroups, max_node_id: usize) -> io::Result<usize> {
+    let mut num_groups = 0;
+    for (gfa_id, group) in block_groups.groups.iter().enumerate().filter(|(_, group)| !group.is_empty()) {
+        writeln!(out, "# block-group\t{}\t{}\t{}", gfa_id, block_groups.gfa_names[gfa_id], format_id_runs(group))?;
+        num_groups += 1;
+    }
+    let gap_nodes: Vec<usize> = (block_groups.first_gap_id..=max_node_id).collect();
+    if !gap_nodes.is_empty() {
+        writeln!(out, "# block-group\t*\t{}\t{}", GFALACE_ORIGIN, format_id_runs(&gap_nodes))?;
+        num_groups += 1;
+    }
+    Ok(num_groups)
+}
+
+/// Replaces each line of a written GFA by the result of `rewrite`, through a temporary copy
+/// compressed like the output, by its extension
+pub(crate) fn rewrite_output_lines<F: FnMut(&str) -> io::Result<String>>(gfa_path: &str, mut rewrite: F) -> io::Result<()> {
+    let mut out = AtomicFile::compressed(gfa_path)?;
+    for line in GfaInput::Path(gfa_path).lines(&ReadOptions::default())?.lines() {
+        writeln!(out, "{}", rewrite(&line?)?)?;
+    }
+    out.finish()
+}
+
+/// Returns the input file (by gfa_id) and segment ID a node of the combined graph comes from.
+/// Nodes split from a segment, possibly several times, come from that segment
+pub(crate) fn node_source(node_id: NodeId, id_translations: &[NodeId], split_origins: &FxHashMap<NodeId, NodeId>) -> (usize, u64) {
+    let mut origin = node_id;
+    while let Some(&parent) = split_origins.get(&origin) {
+        origin = parent;
+    }
+    // The block of the node is the one with the largest offset below it, as offsets may be given in any order
+    let gfa_id = id_translations.iter().enumerate()
+        .filter(|&(_, &translation)| translation < origin)
+        .max_by_key(|&(_, &translation)| translation)
+        .map_or(0, |(gfa_id, _)| gfa_id);
+    (gfa_id, u64::from(origin) - u64::from(id_translations[gfa_id]))
+}
+
+/// Renames the nodes of a GFA written with compacted numeric IDs in its S, L, and P lines, with
+/// `names` indexed by those IDs. IDs beyond `names` are gap nodes, named `gap_{N}` from 1
+pub(crate) fn rename_output_nodes(gfa_path: &str, names: &[String]) -> io::Result<()> {
+    let rename = |id: &str| -> io::Result<String> {
+        let id: usize = id.parse().map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("Invalid node ID '{}' in '{}'", id, gfa_path)))?;
+        Ok(match names.get(id) {
+            Some(name) if id > 0 => name.clone(),
+            _ => format!("gap_{}", id + 1 - names.len()),
+        })
+    };
+
+    rewrite_output_lines(gfa_path, |line| {
+        let mut fields: Vec<String> = line.split('\t').map(str::to_string).collect();
+        match fields[0].as_str() {
+            "S" if fields.len() > 1 => fields[1] = rename(&fields[1])?,
+            "L" | "C" if fields.len() > 3 => {
+                fields[1] = rename(&fields[1])?;
+                fields[3] = rename(&fields[3])?;
+            }
+            "P" if fields.len() > 2 => {
+                let steps: io::Result<Vec<String>> = fields[2].split(',').map(|step| {
+                    let (id, orientation) = step.split_at(step.len().saturating_sub(1));
+                    Ok(format!("{}{}", rename(id)?, orientation))
+                }).collect();
+                fields[2] = steps?.join(",");
+            }
+            _ => {}
+        }
+        Ok(fields.join("\t"))
+    })
+}
+
+/// Naming of the paths of the merged ranges that do not span the whole path key
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FragmentNaming {
+    /// `KEY:START-END`, or the syntax given with --range-delimiters
+    Range,
+    /// `KEY#INDEX:START-END`, with the 1-based ordinal of the fragment as an extra PanSN field
+    /// before the range
+    Pansn,
+    /// `KEY[START-END]`
+    Brackets,
+}
+
+/// Name of a fragment of a path key, the `index`-th one (from 0) in coordinate order
+pub(crate) fn fragment_name(path_key: &str, start: usize, end: usize, index: usize, naming: FragmentNaming, range_syntax: RangeSyntax) -> String {
+    match naming {
+        FragmentNaming::Range => range_syntax.format(path_key, start, end),
+        FragmentNaming::Pansn => range_syntax.format(&format!("{}#{}", path_key, index + 1), start, end),
+        FragmentNaming::Brackets => format!("{}[{}-{}]", path_key, start, end),
+    }
+}
+
+/// Groups of the sorted ranges of a path key that are written as a single path: the runs of
+/// contiguous ranges, or all the ranges when the gaps between them are filled
+pub(crate) fn path_groups(ranges: &[RangeInfo], fill_gaps: bool) -> Vec<std::ops::Range<usize>> {
+    let mut groups = Vec::new();
+    let mut start = 0;
+    for idx in 1..=ranges.len() {
+        if idx == ranges.len() || !(fill_gaps || ranges[idx - 1].is_contiguous_with(&ranges[idx])) {
+            groups.push(start..idx);
+            start = idx;
+        }
+    }
+    groups
+}
+
+/// Returns true if a group of the ranges of a path key is the whole path: it has all the ranges,
+/// from position 0, and up to the end of the sequence of the key if its length is known
+pub(crate) fn is_full_path(ranges: &[RangeInfo], group: &std::ops::Range<usize>, sequence_length: Option<usize>) -> bool {
+    let (start, end) = range_span(&ranges[group.clone()]);
+    group.len() == ranges.len()
+        && start == 0
+        && sequence_length.is_none_or(|length| end >= length)
+}
+
+/// Names of the paths of the groups of ranges of a path key: the key for the whole path, and a
+/// fragment name otherwise, with the fragments numbered in coordinate order
+pub(crate) fn path_group_names(
+    path_key: &str,
+    ranges: &[RangeInfo],
+    groups: &[std::ops::Range<usize>],
+    sequence_length: Option<usize>,
+    naming: FragmentNaming,
+    range_syntax: RangeSyntax
+) -> Vec<String> {
+    let mut fragment_idx = 0;
+    groups.iter().map(|group| {
+        if is_full_path(ranges, group, sequence_length) {
+            path_key.to_string()
+        } else {
+            fragment_idx += 1;
+            let (start, end) = range_span(&ranges[group.clone()]);
+            fragment_name(path_key, start, end, fragment_idx - 1, naming, range_syntax)
+        }
+    }).collect()
+}
+
+/// Order of the output node IDs
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum NodeOrder {
+    /// Order of the node IDs of the combined graph
+    Id,
+    /// Order of the first visit by the paths, walked in their output order
+    PathOrder,
+}
+
+/// Returns the IDs of the used nodes in the order of their output IDs. In path order, nodes are
+/// numbered as the paths visit them, in the order the paths are written (by name if unsorted),
+/// so that the nodes of a path are clustered in ID space; a node shared by several paths is
+/// numbered by the first one
+pub(crate) fn output_node_order(
+    nodes_to_remove: &BitVec,
+    path_key_ranges: &FxHashMap<PathKey, Vec<RangeInfo>>,
+    node_order: NodeOrder,
+    sort_paths_by: PathOrder,
+    range_syntax: RangeSyntax
+) -> Vec<usize> {
+    match node_order {
+        NodeOrder::Id => nodes_to_remove.iter_zeros().collect(),
+        NodeOrder::PathOrder => {
+            let mut path_keys: Vec<_> = path_key_ranges.keys().collect();
+            sort_path_keys(&mut path_keys, if sort_paths_by == PathOrder::None { PathOrder::Name } else { sort_paths_by }, range_syntax);
+
+            // Mark the visited nodes like the unused ones
+            let mut numbered = nodes_to_remove.clone();
+            let mut order = Vec::with_capacity(numbered.count_zeros());
+            for step in path_keys.into_iter().flat_map(|path_key| &path_key_ranges[path_key]).flat_map(|range| &range.steps) {
+                let node_id = usize::from(step.id());
+                if !numbered[node_id] {
+                    numbered.set(node_id, true);
+                    order.push(node_id);
+                }
+            }
+            order
+        }
+    }
+}
+
+/// Rejects the path names with control characters, such as tabs or newlines, which would corrupt
+/// the tab-delimited P lines
+pub(crate) fn check_path_name(path_name: &str) -> io::Result<()> {
+    match path_name.chars().find(|c| c.is_control()) {
+        Some(c) => Err(io::Error::new(io::ErrorKind::InvalidData, format!(
+            "Path name {:?} contains the control character {:?}", path_name, c))),
+        None => Ok(()),
+    }
+}
+
+/// Order of the paths in the output
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum PathOrder {
+    /// Lexicographic order of the names
+    Name,
+    /// Name without the coordinates, then numeric order of the coordinates
+    Coord,
+    /// No sorting (unspecified order)
+    None,
+}
+
+/// Sorts the path keys for output. In `coord` order, keys with a `:START-END` suffix
+/// are grouped by name and sorted numerically by coordinates, so that `chr1:9-20`
+/// comes before `chr1:100-200`; keys without coordinates sort by name
+pub(crate) fn sort_path_keys(path_keys: &mut [&PathKey], order: PathOrder, range_syntax: RangeSyntax) {
+    match order {
+        PathOrder::Name => path_keys.sort(),
+        PathOrder::Coord => path_keys.sort_by_cached_key(|key| {
+            match split_path_name(key, range_syntax) {
+                Some((name, start, end)) => (name, Some((start, end))),
+                None => (key.to_string(), None),
+            }
+        }),
+        PathOrder::None => {}
+    }
+}
+
+/// Options controlling how the combined graph is written
+#[derive(Debug, Clone)]
+pub(crate) struct WriteOptions<'a> {
+    /// Gap filling mode: 0=none, 1=middle gaps only, 2=all gaps
+    pub(crate) fill_gaps: u8,
+    /// Write '*' with an LN tag instead of the segment sequences
+    pub(crate) no_sequences: bool,
+    /// Skip the L lines, for consumers that rebuild adjacency from the path steps
+    pub(crate) no_links: bool,
+    /// Maximum number of steps of a single merged path
+    pub(crate) max_path_steps: usize,
+    /// Order of the paths
+    pub(crate) sort_paths_by: PathOrder,
+    /// Naming of the paths that do not span the whole path key
+    pub(crate) fragment_naming: FragmentNaming,
+    /// Syntax of the range of the fragment names
+    pub(crate) range_syntax: RangeSyntax,
+    /// Stream the segments and links through a buffered writer instead of sorting them in memory
+    pub(crate) no_sort: bool,
+    /// Number of segments and links written between flushes of the output buffer
+    pub(crate) segment_batch: usize,
+    /// Directory of the sorted chunks of links, when sorting them on disk
+    pub(crate) external_sort: Option<std::path::PathBuf>,
+    /// Number of links in each sorted chunk
+    pub(crate) sort_chunk_size: usize,
+    /// Order of the output node IDs
+    pub(crate) node_order: NodeOrder,
+    /// ID of the first output node
+    pub(crate) first_id: usize,
+    /// Write the node IDs of the combined graph, as placed by --id-offsets or --id-spacing,
+    /// instead of compacting them from `first_id`
+    pub(crate) keep_ids: bool,
+    /// Write only the header and the paths
+    pub(crate) paths_only: bool,
+    pub(crate) line_ending: LineEnding,
+    /// Largest size in bytes of an output shard, when splitting the output
+    pub(crate) shard_size: Option<u64>,
+    /// Input file of each node of the combined graph, by node ID, to tag the segments with
+    pub(crate) origins: Option<&'a [&'a str]>,
+    pub(crate) debug: bool,
+}
+
+impl Default for WriteOptions<'_> {
+    fn default() -> Self {
+        WriteOptions {
+            fill_gaps: 0,
+            no_sequences: false,
+            no_links: false,
+            max_path_steps: usize::MAX,
+            sort_paths_by: PathOrder::Name,
+            fragment_naming: FragmentNaming::Range,
+            range_syntax: RangeSyntax::ColonDash,
+            no_sort: false,
+            segment_batch: SEGMENT_BATCH,
+            external_sort: None,
+            sort_chunk_size: SORT_CHUNK_SIZE,
+            node_order: NodeOrder::Id,
+            first_id: 1,
+            keep_ids: false,
+            paths_only: false,
+            line_ending: LineEnding::Lf,
+            shard_size: None,
+            origins: None,
+            debug: false,
+        }
+    }
+}
+
+/// Writes the combined graph, followed by its `block_groups` unless split into shards, returning the
+/// number of path keys split into several paths and the largest output node ID
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn write_graph_to_gfa(
+    graph: &HashGraph,
+    sequences: &NodeSequences,
+    path_key_ranges: &FxHashMap<PathKey, Vec<RangeInfo>>,
+    sequenceless: &FxHashSet<NodeId>,
+    link_overlaps: &LinkOverlaps,
+    passthrough: &PassthroughLines,
+    output_path: &str,
+    fasta_reader: &Option<faidx::Reader>,
+    options: &WriteOptions,
+    liftover: Option<&mut LiftoverWriter>,
+    paths_fasta: Option<&mut PathFastaWriter>,
+    block_groups: Option<&BlockGroups>
+) -> std::io::Result<(usize, usize)> {
+    if let Some(shard_size) = options.shard_size {
+        let mut out = ShardedWriter::new(output_path, shard_size);
+        let written = write_graph(&mut out, graph, sequences, path_key_ranges, sequenceless, link_overlaps, passthrough, fasta_reader, options, liftover, paths_fasta)?;
+        let shards = out.finish()?;
+        info!(stage = "write", shards = shards; "Split the output into {} shards of at most {} bytes", shards, shard_size);
+        return Ok(written);
+    }
+    let mut output = AtomicFile::compressed(output_path)?;
+    let mut crlf;
+    let mut out: &mut dyn Write = match options.line_ending {
+        LineEnding::Lf => &mut output,
+        LineEnding::Crlf => {
+            crlf = CrlfWriter(&mut output);
+            &mut crlf
+        }
+    };
+    let written = write_graph(&mut out, graph, sequences, path_key_ranges, sequenceless, link_overlaps, passthrough, fasta_reader, options, liftover, paths_fasta)?;
+    if let Some(block_groups) = block_groups {
+        let num_groups = write_block_groups(&mut out, block_groups, written.1)?;
+        info!(stage = "write"; "Wrote {} block groups with the combined graph", num_groups);
+    }
+    output.finish()?;
+    Ok(written)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn write_graph<W: Write>(
+    file: &mut W,
+    graph: &HashGraph,
+    sequences: &NodeSequences,
+    path_key_ranges: &FxHashMap<PathKey, Vec<RangeInfo>>,
+    sequenceless: &FxHashSet<NodeId>,
+    link_overlaps: &LinkOverlaps,
+    passthrough: &PassthroughLines,
+    fasta_reader: &Option<faidx::Reader>,
+    options: &WriteOptions,
+    mut liftover: Option<&mut LiftoverWriter>,
+    mut paths_fasta: Option<&mut PathFastaWriter>
+) -> std::io::Result<(usize, usize)> {
+    let WriteOptions { fill_gaps, no_sequences, no_links, max_path_steps, sort_paths_by, fragment_naming, range_syntax, no_sort, segment_batch, ref external_sort, sort_chunk_size, node_order, first_id, keep_ids, paths_only, line_ending: _, shard_size: _, origins, debug } = *options;
+
+    info!(stage = "write"; "Marking unused nodes");
+    let nodes_to_remove : BitVec = mark_nodes_for_removal(graph, path_key_ranges);    
+    debug!("Marked {} nodes", nodes_to_remove.count_ones() - 1);
+    
+    // Write GFA version
+    writeln!(file, "H\tVN:Z:1.0")?;
+
+    // Write the comments of the input files
+    for comment in &passthrough.comments {
+        writeln!(file, "{}", comment)?;
+    }
+    
+    let max_id = usize::from(graph.max_node_id());
+    let mut id_mapping = vec![0; max_id + 1];
+    // Visit the nodes in output order, as the iteration order of the graph is not stable
+    let output_nodes = output_node_order(&nodes_to_remove, path_key_ranges, node_order, sort_paths_by, range_syntax);
+    // The gap nodes follow the used nodes, whose IDs are compacted or kept
+    let mut new_id = if keep_ids { output_nodes.iter().max().map_or(1, |&node_id| node_id + 1) } else { first_id + output_nodes.len() };
+    let output_ids = |(idx, &node_id): (usize, &usize)| (node_id, if keep_ids { node_id } else { first_id + idx });
+    // Each range can be preceded by a gap node, and each path key followed by one
+    let max_gap_nodes = if fill_gaps > 0 { path_key_ranges.values().map(|ranges| ranges.len() + 1).sum() } else { 0 };
+    if first_id.checked_add(output_nodes.len() + max_gap_nodes).is_none() || new_id.checked_add(max_gap_nodes).is_none() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!(
+            "The {} output nodes do not fit in the node IDs from {}", output_nodes.len() + max_gap_nodes, first_id)));
+    }
+    // The segments of the gap nodes are dropped as well when writing only the paths
+    let mut skipped = io::sink();
+    if paths_only {
+        info!(stage = "write"; "Skipping segments and edges");
+        for (node_id, output_id) in output_nodes.iter().enumerate().map(output_ids) {
+            id_mapping[node_id] = output_id;
+        }
+    } else if no_sort {
+        write_graph_streaming(file, graph, sequences, &nodes_to_remove, &output_nodes, &mut id_mapping, sequenceless, link_overlaps, no_sequences, no_links, first_id, keep_ids, segment_batch, origins)?;
+    } else {
+        // Write nodes by exluding marked ones and create the id_mapping
+        info!(stage = "write"; "Writing used nodes by compacting their IDs");
+        let mut sequence = Vec::new();
+        for (node_id, output_id) in output_nodes.iter().enumerate().map(output_ids) {
+            id_mapping[node_id] = output_id;
+            write_node(file, graph, sequences, Handle::pack(node_id as u64, false), output_id, sequenceless, no_sequences, origins, &mut sequence)?;
+            if output_id.is_multiple_of(segment_batch) {
+                file.flush()?;
+            }
+        }
+
+        // Write edges by excluding those connected to marked nodes
+        if no_links {
+            info!(stage = "write"; "Skipping edges");
+        } else if let Some(tmp_dir) = external_sort {
+            info!(stage = "write"; "Writing edges connecting used nodes, sorted in chunks in {}", tmp_dir.display());
+            let overlaps = output_link_overlaps(link_overlaps, &nodes_to_remove, &id_mapping);
+            write_links_external(file, graph, &nodes_to_remove, &id_mapping, &overlaps, tmp_dir, sort_chunk_size)?;
+        } else {
+            info!(stage = "write"; "Writing edges connecting used nodes");
+            let overlaps = output_link_overlaps(link_overlaps, &nodes_to_remove, &id_mapping);
+            for link in collect_output_links(graph, &nodes_to_remove, &id_mapping) {
+                write_link(file, link.0, link.1, overlaps.get(&link).copied().unwrap_or(0))?;
+            }
+        }
+    }
+
+    // Write the containments between written nodes, with their output IDs
+    let mut dropped_containments = 0;
+    for containment in passthrough.containments.iter().filter(|_| !paths_only) {
+        let container_id = id_mapping.get(usize::from(containment.container.id())).copied().unwrap_or(0);
+        let contained_id = id_mapping.get(usize::from(containment.contained.id())).copied().unwrap_or(0);
+        if container_id == 0 || contained_id == 0 {
+            dropped_containments += 1;
+            continue;
+        }
+        write_containment(file, (container_id, containment.container.is_reverse()), (contained_id, containment.contained.is_reverse()), containment)?;
+    }
+    if dropped_containments > 0 {
+        warn!(stage = "write"; "Dropped {} containments of segments that are not written", dropped_containments);
+    }
+
+    // Write paths by processing ranges directly
+    info!(stage = "write"; "Writing paths by merging contiguous path ranges");
+    let mut path_key_vec: Vec<_> = path_key_ranges.keys().collect();
+    sort_path_keys(&mut path_key_vec, sort_paths_by, range_syntax);
+
+    let mut start_gaps = 0;
+    let mut middle_gaps = 0;
+    let mut end_gaps = 0;
+    let mut split_keys = 0;
+
+    // Check if a valid FASTA reader is provided for end gap filling
+    if fill_gaps == 2 && fasta_reader.is_none() {
+        warn!(stage = "write"; "Cannot fill end gaps without FASTA file");
+    }
+
+    for path_key in path_key_vec {
+        let ranges = &path_key_ranges[path_key];
+
+        if debug {
+            debug!("Processing Path key '{}'", path_key);
+            
+            let mut current_start = ranges[0].start;
+            let mut current_end = ranges[0].end;
+            
+            for i in 1..ranges.len() {
+                if ranges[i-1].is_contiguous_with(&ranges[i]) {
+                    // Extend current merged range
+                    current_end = ranges[i].end;
+                } else {
+                    // Print current merged range
+                    debug!("  Merged range: start={}, end={}", 
+                        current_start, current_end);
+                    
+                    if !ranges[i-1].overlaps_with(&ranges[i]) {
+                        // Calculate and print gap
+                        let gap = ranges[i].start - current_end;
+                        debug!(stage = "write", path_key = &**path_key, position = current_end, gap_bp = gap;
+                            "    Gap to next range: {} positions", gap);
+                    } else {
+                        // Calculate and print overlap (IT SHOULD NOT HAPPEN)
+                        let overlap = current_end - ranges[i].start;
+                        debug!(stage = "write", path_key = &**path_key, position = ranges[i].start, overlap_bp = overlap;
+                            "    Overlap with next range: {} positions", overlap);
+                    }
+    
+                    // Start new merged range
+                    current_start = ranges[i].start;
+                    current_end = ranges[i].end;
+                }
+            }
+            
+            // Print final merged range
+            debug!("  Merged range: start={}, end={}", 
+                current_start, current_end);
+        }
+
+        let sequence_length = fasta_reader.as_ref().map(|reader| reader.fetch_seq_len(path_key) as usize);
+        let groups = path_groups(ranges, fill_gaps > 0);
+        let path_names = path_group_names(path_key, ranges, &groups, sequence_length, fragment_naming, range_syntax);
+        let mut num_paths = 0;
+
+        for (group, path_name) in groups.into_iter().zip(path_names) {
+            let start_range = &ranges[group.start];
+            let mut next_idx = group.start + 1;
+            let mut end_range = start_range;
+            
+            // Sources of the path, its number of steps and its last one, and the known overlaps between
+            // the steps. The steps themselves are read from the ranges when the path is written
+            let mut path_sources = Vec::new();
+            let mut num_steps = 0;
+            let mut last_step = None;
+            let mut path_overlaps = Vec::new();
+
+            // Handle initial gap if it exists and gap filling is enabled
+            if fill_gaps == 2 && start_range.start > 0 {
+                start_gaps += 1;
+
+                check_path_steps(path_key, num_steps + 1, max_path_steps)?;
+                let gap_element = create_gap_node(
+                    segment_output(file, &mut skipped, paths_only),
+                    (0, start_range.start),
+                    path_key,
+                    fasta_reader,
+                    None, // No previous element for initial gap
+                    start_range.steps.first(),
+                    &id_mapping,
+                    &mut new_id,
+                    no_sequences,
+                    no_links,
+                    origins.is_some(),
+                )?;
+                num_steps += 1;
+                last_step = Some(gap_element);
+                path_sources.push(PathSource::Gap(0, start_range.start, gap_element));
+            }
+
+            // Add first range steps
+            check_path_steps(path_key, num_steps + start_range.steps.len(), max_path_steps)?;
+            add_range_to_path(start_range, &id_mapping, link_overlaps, &mut num_steps, &mut last_step, &mut path_overlaps);
+            path_sources.push(PathSource::Range(start_range));
+            
+            // Process subsequent contiguous ranges or add gap nodes
+            while next_idx < group.end {
+                let next_range = &ranges[next_idx];
+
+                if ranges[next_idx - 1].is_contiguous_with(next_range) {
+                    // Ranges are contiguous - add steps directly
+                    check_path_steps(path_key, num_steps + next_range.steps.len(), max_path_steps)?;
+                    add_range_to_path(next_range, &id_mapping, link_overlaps, &mut num_steps, &mut last_step, &mut path_overlaps);
+                    path_sources.push(PathSource::Range(next_range));
+                    end_range = next_range;
+                    next_idx += 1;
+                } else {
+                    middle_gaps += 1;
+
+                    // Fill gap between ranges
+                    check_path_steps(path_key, num_steps + 1, max_path_steps)?;
+                    let gap_element = create_gap_node(
+                        segment_output(file, &mut skipped, paths_only),
+                        (end_range.end, next_range.start),
+                        path_key,
+                        fasta_reader,
+                        last_step.as_ref(),
+                        next_range.steps.first(),
+                        &id_mapping,
+                        &mut new_id,
+                        no_sequences,
+                        no_links,
+                        origins.is_some(),
+                    )?;
+                    num_steps += 1;
+                    last_step = Some(gap_element);
+                    path_sources.push(PathSource::Gap(end_range.end, next_range.start, gap_element));
+
+                    // Continue addint stpes of the next range
+                    check_path_steps(path_key, num_steps + next_range.steps.len(), max_path_steps)?;
+                    add_range_to_path(next_range, &id_mapping, link_overlaps, &mut num_steps, &mut last_step, &mut path_overlaps);
+                    path_sources.push(PathSource::Range(next_range));
+                    end_range = next_range;
+                    next_idx += 1;
+                }
+            }
+
+            // Handle final gap if sequence length is known and gap filling is enabled
+            if fill_gaps == 2 {
+                // Get sequence length if FASTA is provided
+                if let Some(total_length) = sequence_length {
+                    match end_range.end.cmp(&total_length) {
+                        std::cmp::Ordering::Less => {
+                            end_gaps += 1;
+
+                            check_path_steps(path_key, num_steps + 1, max_path_steps)?;
+                            let gap_element = create_gap_node(
+                                segment_output(file, &mut skipped, paths_only),
+                                (end_range.end, total_length),
+                                path_key,
+                                fasta_reader,
+                                last_step.as_ref(),
+                                None,  // No next node for final gap
+                                &id_mapping,
+                                &mut new_id,
+                                no_sequences,
+                                no_links,
+                                origins.is_some(),
+                            )?;
+                            num_steps += 1;
+                            path_sources.push(PathSource::Gap(end_range.end, total_length, gap_element));
+                        }
+                        std::cmp::Ordering::Greater => {
+                            warn!(stage = "write", path_key = &**path_key, end = end_range.end, sequence_length = total_length;
+                                "Path '{}' extends beyond sequence length ({} > {})", 
+                                path_key, end_range.end, total_length);
+                        }
+                        std::cmp::Ordering::Equal => {}
+                    }
+                }
+            }
+
+            // Write path
+            if num_steps > 0 {
+                check_path_name(&path_name)?;
+                let path_tags = merged_path_tags(path_sources.iter().filter_map(|source| match source {
+                    PathSource::Range(range) => Some(*range),
+                    PathSource::Gap(..) => None,
+                }));
+                write_path(file, &path_name, path_source_steps(&path_sources, &id_mapping), &path_overlaps, &path_tags)?;
+                debug!(stage = "write", path_key = &**path_key, path = path_name.as_str(), steps = num_steps;
+                    "  Wrote path '{}' with {} steps", path_name, num_steps);
+                num_paths += 1;
+                if let Some(liftover) = liftover.as_deref_mut() {
+                    liftover.write_path(graph, sequences, &path_name, &path_sources)?;
+                }
+                if let Some(paths_fasta) = paths_fasta.as_deref_mut() {
+                    paths_fasta.write_path(graph, sequences, link_overlaps, fasta_reader, path_key, &path_name, &path_sources)?;
+                }
+            }
+        }
+        if num_paths > 1 {
+            split_keys += 1;
+        }
+    }
+
+    // Write the unrecognized lines of the input files
+    for line in passthrough.unknown.iter().filter(|_| !paths_only) {
+        writeln!(file, "{}", line)?;
+    }
+
+    if fill_gaps == 2 {
+        info!(stage = "write", start_gaps = start_gaps, middle_gaps = middle_gaps, end_gaps = end_gaps;
+            "Filled {} gaps: {} start gaps, {} middle gaps, {} end gaps", 
+            start_gaps + middle_gaps + end_gaps, 
+            start_gaps, 
+            middle_gaps, 
+            end_gaps);
+    } else if fill_gaps == 1 {
+        info!(stage = "write", middle_gaps = middle_gaps; "Filled {} middle gaps", middle_gaps);
+    }
+
+    Ok((split_keys, new_id - 1))
+}
+
+/// Returns the hex SHA-256 of the S, L, and P lines of a GFA file, which are written in a
+/// deterministic order, so that it only depends on the graph and not on the header or comments.
+/// It can be checked with `grep -P '^[SLP]\t' OUTPUT | sha256sum`
+pub(crate) fn gfa_checksum(gfa_path: &str) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    let mut reader = GfaInput::Path(gfa_path).lines(&ReadOptions::default())?;
+    let mut line = Vec::new();
+    while reader.read_until(b'\n', &mut line)? > 0 {
+        if matches!(line.first(), Some(b'S' | b'L' | b'P')) && line.get(1) == Some(&b'\t') {
+            // The same for both line endings
+            if line.ends_with(b"\r\n") {
+                line.truncate(line.len() - 2);
+                line.push(b'\n');
+            }
+            hasher.update(&line);
+        }
+        line.clear();
+    }
+    Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// Writes the sidecar of `--checksum`: a comment with the `gfa_checksum` of the output, ignored by
+/// `sha256sum -c`, then the SHA-256 of its bytes and its file name, which `sha256sum -c` checks from
+/// the directory of the output
+pub(crate) fn write_checksum_file(gfa_path: &str, checksum_path: &str) -> io::Result<()> {
+    let mut hasher = Sha256::new();
+    let mut reader = BufReader::new(File::open(gfa_path)?);
+    loop {
+        let chunk = reader.fill_buf()?;
+        if chunk.is_empty() {
+            break;
+        }
+        hasher.update(chunk);
+        let len = chunk.len();
+        reader.consume(len);
+    }
+    let file_checksum: String = hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect();
+    let file_name = Path::new(gfa_path).file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+
+    let mut checksum_file = AtomicFile::create(checksum_path)?;
+    writeln!(checksum_file, "# SHA-256 of the S, L, and P lines: {}", gfa_checksum(gfa_path)?)?;
+    writeln!(checksum_file, "{}  {}", file_checksum, file_name)?;
+    checksum_file.finish()
+}
+
+/// Parses a GFA file written from the combined graph back and compares it with the graph: the used
+/// nodes and their sequences, the links between them modulo orientation, and the steps of the paths of
+/// each path key, with gap nodes set aside. Returns the discrepancies found, e.g. duplicate links or
+/// steps on undefined segments
+pub(crate) fn check_roundtrip(
+    gfa_path: &str,
+    graph: &HashGraph,
+    sequences: &NodeSequences,
+    path_key_ranges: &FxHashMap<PathKey, Vec<RangeInfo>>,
+    sequenceless: &FxHashSet<NodeId>,
+    options: &WriteOptions
+) -> io::Result<Vec<String>> {
+    let gfa = read_gfa(gfa_path, &GFAParser::new(), &ReadOptions::default())?;
+    let nodes_to_remove = mark_nodes_for_removal(graph, path_key_ranges);
+    let (id_mapping, num_nodes) = compact_id_mapping(graph, path_key_ranges, options);
+    // The written IDs are those of the mapping shifted by the first ID
+    let shift = options.first_id - 1;
+    let mut node_ids = vec![0; num_nodes + 1];
+    for (node_id, &new_id) in id_mapping.iter().enumerate().filter(|&(_, &new_id)| new_id > 0) {
+        node_ids[new_id] = node_id;
+    }
+    let mut discrepancies = Vec::new();
+
+    // Segments, the IDs past the used nodes being gap nodes
+    let mut defined = FxHashSet::default();
+    for segment in &gfa.segments {
+        if !defined.insert(segment.name) {
+            discrepancies.push(format!("segment {} is defined more than once", segment.name));
+        } else if segment.name <= shift || segment.name > shift + num_nodes || node_ids[segment.name - shift] == 0 {
+            if segment.name <= shift || segment.name <= shift + num_nodes || options.fill_gaps == 0 {
+                discrepancies.push(format!("segment {} is not a node of the combined graph", segment.name));
+            }
+        } else {
+            let handle = Handle::pack(node_ids[segment.name - shift] as u64, false);
+            let sequence = sequences.sequence_vec(graph, handle);
+            if segment.sequence.as_slice() == b"*" {
+                let length = match segment.optional.get_field(b"LN").map(|field| &field.value) {
+                    Some(OptFieldVal::Int(length)) => *length as usize,
+                    _ => 0,
+                };
+                if length != sequence.len() || !(options.no_sequences || sequence.is_empty() || sequenceless.contains(&handle.id())) {
+                    discrepancies.push(format!("segment {} has no sequence instead of {} bp", segment.name, sequence.len()));
+                }
+            } else if segment.sequence.as_slice() != sequence.as_slice() {
+                discrepancies.push(format!("segment {} has a different sequence", segment.name));
+            }
+        }
+    }
+    let missing = node_ids.iter().enumerate().filter(|&(new_id, &node_id)| node_id > 0 && !defined.contains(&(new_id + shift))).count();
+    if missing > 0 {
+        discrepancies.push(format!("{} nodes of the combined graph have no segment", missing));
+    }
+
+    // Links between used nodes, those of gap nodes being only checked for undefined segments
+    let mut links = FxHashSet::default();
+    for link in &gfa.links {
+        let (from, to) = ((link.from_segment, link.from_orient.is_reverse()), (link.to_segment, link.to_orient.is_reverse()));
+        if !defined.contains(&from.0) || !defined.contains(&to.0) {
+            discrepancies.push(format!("link {}{} {}{} joins undefined segments", from.0, link.from_orient, to.0, link.to_orient));
+        } else if !links.insert(canonical_link(from, to)) {
+            discrepancies.push(format!("link {}{} {}{} is written more than once", from.0, link.from_orient, to.0, link.to_orient));
+        }
+    }
+    links.retain(|(from, to)| from.0 <= shift + num_nodes && to.0 <= shift + num_nodes);
+    let expected_links: FxHashSet<_> = if options.no_links {
+        FxHashSet::default()
+    } else {
+        collect_output_links(graph, &nodes_to_remove, &id_mapping).into_iter()
+            .map(|((from, from_reverse), (to, to_reverse))| ((from + shift, from_reverse), (to + shift, to_reverse)))
+            .collect()
+    };
+    let missing_links = expected_links.difference(&links).count();
+    let extra_links = links.difference(&expected_links).count();
+    if missing_links > 0 || extra_links > 0 {
+        discrepancies.push(format!("{} links of the combined graph are missing, and {} are not in it", missing_links, extra_links));
+    }
+
+    // Path names, and the steps of the paths of each key
+    let path_key = |path_name: &str| -> String {
+        if path_key_ranges.contains_key(path_name) {
+            path_name.to_string()
+        } else {
+            split_path_name(path_name, options.range_syntax).map_or_else(|| path_name.to_string(), |(path_key, _, _)| path_key)
+        }
+    };
+    let mut path_names = FxHashSet::default();
+    let mut written_steps: FxHashMap<String, Vec<PathStep>> = FxHashMap::default();
+    for path in &gfa.paths {
+        let path_name = String::from_utf8_lossy(&path.path_name);
+        if !path_names.insert(path_name.to_string()) {
+            discrepancies.push(format!("path '{}' is written more than once", path_name));
+        }
+        let steps = written_steps.entry(path_key(&path_name)).or_default();
+        for (segment_id, orientation) in path.iter() {
+            if !defined.contains(&segment_id) {
+                discrepancies.push(format!("path '{}' visits the undefined segment {}", path_name, segment_id));
+            } else if segment_id <= shift + num_nodes {
+                steps.push((segment_id, orientation.is_reverse()));
+            }
+        }
+    }
+    for (path_key, ranges) in path_key_ranges {
+        let expected: Vec<PathStep> = ranges.iter().flat_map(|range| &range.steps)
+            .map(|step| (id_mapping[usize::from(step.id())] + shift, step.is_reverse()))
+            .collect();
+        match written_steps.remove(&**path_key) {
+            Some(steps) if steps != expected => discrepancies.push(format!("the paths of '{}' have different steps", path_key)),
+            None if !expected.is_empty() => discrepancies.push(format!("path key '{}' is not written", path_key)),
+            _ => {}
+        }
+    }
+    for path_key in written_steps.keys() {
+        discrepancies.push(format!("path key '{}' is not in the combined graph", path_key));
+    }
+    Ok(discrepancies)
+}
+
+/// Origin of a stretch of a merged path
+pub(crate) enum PathSource<'a> {
+    /// Steps of a range of an input path
+    Range(&'a RangeInfo),
+    /// Gap node between the given positions of the path key, which has no source, with its output step
+    Gap(usize, usize, PathStep),
+}
+
+/// Writes a TSV mapping the stretches of each merged path to the input paths they come from
+pub(crate) struct LiftoverWriter<'a> {
+    pub(crate) out: AtomicFile,
+    pub(crate) gfa_names: &'a [String],
+}
+
+impl<'a> LiftoverWriter<'a> {
+    pub(crate) fn new(path: &str, gfa_names: &'a [String]) -> io::Result<Self> {
+        let mut out = AtomicFile::create(path)?;
+        writeln!(out, "#merged_path\tmerged_start\tmerged_end\tsource_gfa\tsource_path\tsource_start\tsource_end")?;
+        Ok(LiftoverWriter { out, gfa_names })
+    }
+
+    /// Writes one row per range of a merged path. Offsets along the merged path are the
+    /// cumulative lengths of its steps, including gap nodes, which get no row
+    pub(crate) fn write_path(&mut self, graph: &HashGraph, sequences: &NodeSequences, path_name: &str, sources: &[PathSource]) -> io::Result<()> {
+        let mut offset = 0;
+        for source in sources {
+            match source {
+                PathSource::Range(range) => {
+                    let length: usize = range.steps.iter().map(|&handle| sequences.node_len(graph, handle)).sum();
+                    writeln!(self.out, "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                        path_name, offset, offset + length,
+                        self.gfa_names[range.gfa_id], range.path_name, range.start, range.end)?;
+                    offset += length;
+                }
+                PathSource::Gap(start, end, _) => offset += end - start,
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn finish(self) -> io::Result<()> {
+        self.out.finish()
+    }
+}
+
+/// Number of bases per line of the paths FASTA
+pub(crate) const FASTA_LINE_WIDTH: usize = 60;
+
+/// Writes the sequence of each merged path to a FASTA file
+pub(crate) struct PathFastaWriter {
+    pub(crate) out: AtomicFile,
+    pub(crate) sequence: Vec<u8>,
+}
+
+impl PathFastaWriter {
+    pub(crate) fn new(path: &str) -> io::Result<Self> {
+        Ok(PathFastaWriter { out: AtomicFile::compressed(path)?, sequence: Vec::new() })
+    }
+
+    /// Writes the record of a merged path: the sequences of its steps in order, without the bases
+    /// a step shares with the previous one through an overlapping link, and with gap nodes filled
+    /// as in the graph
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn write_path(&mut self, graph: &HashGraph, sequences: &NodeSequences, link_overlaps: &LinkOverlaps, fasta_reader: &Option<faidx::Reader>, path_key: &str, path_name: &str, sources: &[PathSource]) -> io::Result<()> {
+        self.sequence.clear();
+        for source in sources {
+            match source {
+                PathSource::Range(range) => {
+                    for (idx, &handle) in range.steps.iter().enumerate() {
+                        let step_start = self.sequence.len();
+                        sequences.extend_with_sequence(graph, handle, &mut self.sequence);
+                        if idx > 0 {
+                            let overlap = boundary_overlap(link_overlaps, range.steps[idx - 1], handle).min(self.sequence.len() - step_start);
+                            self.sequence.drain(step_start..step_start + overlap);
+                        }
+                    }
+                }
+                PathSource::Gap(start, end, _) => self.sequence.extend(gap_sequence((*start, *end), path_key, fasta_reader).bytes()),
+            }
+        }
+        writeln!(self.out, ">{}", path_name)?;
+        for line in self.sequence.chunks(FASTA_LINE_WIDTH) {
+            self.out.write_all(line)?;
+            self.out.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn finish(self) -> io::Result<()> {
+        self.out.finish()
+    }
+}
+
+/// Writes the used segments and the links between them without sorting them in memory:
+/// nodes are scanned in output order, and the links of each node are gathered from
+/// its neighbors. Besides the graph, memory is bounded by the ID mapping, the output order, and
+/// the removal bit vector (one entry per node ID), and the output is flushed periodically.
+/// The output is the same as with the sorted writer. Output IDs start from `first_id`, or are
+/// those of the graph with `keep_ids`
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn write_graph_streaming<W: Write>(
+    out: &mut W,
+    graph: &HashGraph,
+    sequences: &NodeSequences,
+    nodes_to_remove: &BitVec,
+    node_order: &[usize],
+    id_mapping: &mut [usize],
+    sequenceless: &FxHashSet<NodeId>,
+    link_overlaps: &LinkOverlaps,
+    no_sequences: bool,
+    no_links: bool,
+    first_id: usize,
+    keep_ids: bool,
+    segment_batch: usize,
+    origins: Option<&[&str]>
+) -> io::Result<()> {
+    info!(stage = "write"; "Streaming used nodes by compacting their IDs");
+    let mut sequence = Vec::new();
+    for (idx, &node_id) in node_order.iter().enumerate() {
+        let output_id = if keep_ids { node_id } else { first_id + idx };
+        id_mapping[node_id] = output_id;
+        write_node(out, graph, sequences, Handle::pack(node_id as u64, false), output_id, sequenceless, no_sequences, origins, &mut sequence)?;
+        if output_id.is_multiple_of(segment_batch) {
+            out.flush()?;
+        }
+    }
+    out.flush()?;
+
+    if no_links {
+        info!(stage = "write"; "Skipping edges");
+        return Ok(());
+    }
+    info!(stage = "write"; "Streaming edges connecting used nodes");
+    let mut written = 0;
+    let mut node_links = Vec::new();
+    for &node_id in node_order {
+        // Each link is written with the node of its canonical form that comes first
+        let from_id = id_mapping[node_id];
+        for is_reverse in [false, true] {
+            let handle = Handle::pack(node_id as u64, is_reverse);
+            for next in graph.neighbors(handle, Direction::Right) {
+                let next_id = usize::from(next.id());
+                if nodes_to_remove[next_id] {
+                    continue;
+                }
+                let link = canonical_link((from_id, is_reverse), (id_mapping[next_id], next.is_reverse()));
+                if link.0.0 == from_id {
+                    node_links.push((link, boundary_overlap(link_overlaps, handle, next)));
+                }
+            }
+        }
+        node_links.sort_unstable();
+        node_links.dedup();
+        for ((from, to), overlap) in node_links.drain(..) {
+            write_link(out, from, to, overlap)?;
+            written += 1;
+            if written % segment_batch == 0 {
+                out.flush()?;
+            }
+        }
+    }
+    out.flush()?;
+
+    Ok(())
+}
+
+/// Writes the S line of a node, tagged with its origin in `origins` if given. Segments without
+/// sequence, and empty ones, are written back as '*' with their length. The sequence is spelled
+/// into `scratch`, which callers reuse across nodes to avoid an allocation per segment
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn write_node<W: Write>(
+    out: &mut W,
+    graph: &HashGraph,
+    sequences: &NodeSequences,
+    handle: Handle,
+    new_id: usize,
+    sequenceless: &FxHashSet<NodeId>,
+    no_sequences: bool,
+    origins: Option<&[&str]>,
+    scratch: &mut Vec<u8>
+) -> io::Result<()> {
+    scratch.clear();
+    sequences.extend_with_sequence(graph, handle, scratch);
+    let origin = origins.map(|origins| origins[usize::from(handle.id())]);
+    if no_sequences || scratch.is_empty() || sequenceless.contains(&handle.id()) {
+        write!(out, "S\t{}\t*\tLN:i:{}", new_id, scratch.len())?;
+        write_origin_tag(out, origin)
+    } else {
+        write_segment(out, new_id, scratch, origin)
+    }
+}
+
+/// Writes an L line between oriented output node IDs, with the overlap in bp of the link (0 when blunt)
+pub(crate) fn write_link<W: Write>(out: &mut W, from: PathStep, to: PathStep, overlap: usize) -> io::Result<()> {
+    let from_orient = if from.1 { "-" } else { "+" };
+    let to_orient = if to.1 { "-" } else { "+" };
+    writeln!(out, "L\t{}\t{}\t{}\t{}\t{}M", from.0, from_orient, to.0, to_orient, overlap)
+}
+
+/// Writes a C line between oriented output node IDs, keeping the position, overlap, and tags of the input
+pub(crate) fn write_containment<W: Write>(out: &mut W, container: PathStep, contained: PathStep, containment: &Containment) -> io::Result<()> {
+    let container_orient = if container.1 { "-" } else { "+" };
+    let contained_orient = if contained.1 { "-" } else { "+" };
+    write!(out, "C\t{}\t{}\t{}\t{}\t{}\t{}", container.0, container_orient, contained.0, contained_orient, containment.pos, containment.overlap)?;
+    for tag in &containment.tags {
+        write!(out, "\t{}", tag)?;
+    }
+    writeln!(out)
+}
+
+/// Writes an S line with the raw sequence bytes, and the `SF:Z` tag of its origin if given.
+/// Sequences containing tabs or line breaks are rejected, since they would corrupt the GFA.
+pub(crate) fn write_segment<W: Write>(out: &mut W, id: usize, sequence: &[u8], origin: Option<&str>) -> io::Result<()> {
+    if let Some(byte) = sequence.iter().find(|&&b| b == b'\t' || b == b'\n' || b == b'\r') {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Segment {} contains an invalid character ({:?}) in its sequence", id, *byte as char)
+        ));
+    }
+
+    write!(out, "S\t{}\t", id)?;
+    out.write_all(sequence)?;
+    write_origin_tag(out, origin)
+}
+
+/// Ends an S line with the `SF:Z` tag of its origin, if given
+pub(crate) fn write_origin_tag<W: Write + ?Sized>(out: &mut W, origin: Option<&str>) -> io::Result<()> {
+    match origin {
+        Some(origin) => writeln!(out, "\tSF:Z:{}", origin),
+        None => writeln!(out),
+    }
+}
+
+/// Sequence of a gap of a path key, from the FASTA if given and otherwise a string of N's
+pub(crate) fn gap_sequence(gap_range: (usize, usize), path_key: &str, fasta_reader: &Option<faidx::Reader>) -> String {
+    let (gap_start, gap_end) = gap_range;
+    let gap_size = gap_end - gap_start;
+    if let Some(reader) = fasta_reader {
+        match reader.fetch_seq_string(path_key, gap_start, gap_end - 1) {
+            Ok(seq) => seq,
+            Err(e) => {
+                warn!(stage = "write", path_key = path_key; "Failed to fetch sequence, filling the gap with N: {}", e);
+                "N".repeat(gap_size)
+            }
+        }
+    } else {
+        "N".repeat(gap_size)
+    }
+}
+
+/// Output for the segments and links, which are dropped when writing only the paths
+pub(crate) fn segment_output<'w, W: Write>(file: &'w mut W, skipped: &'w mut io::Sink, paths_only: bool) -> &'w mut dyn Write {
+    if paths_only { skipped } else { file }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn create_gap_node<W: Write + ?Sized>(
+    file: &mut W,
+    gap_range: (usize, usize),
+    path_key: &str,
+    fasta_reader: &Option<faidx::Reader>,
+    last_element: Option<&PathStep>,
+    next_handle: Option<&Handle>,
+    id_mapping: &[usize],
+    new_id: &mut usize,
+    no_sequences: bool,
+    no_links: bool,
+    tag_origin: bool,
+) -> io::Result<PathStep> {
+    let (gap_start, gap_end) = gap_range;
+    let gap_size = gap_end - gap_start;
+    
+    if no_sequences {
+        write!(file, "S\t{}\t*\tLN:i:{}", new_id, gap_size)?;
+    } else {
+        write!(file, "S\t{}\t{}", new_id, gap_sequence(gap_range, path_key, fasta_reader))?;
+    }
+    // Gap nodes are created by gfalace
+    write_origin_tag(file, tag_origin.then_some(GFALACE_ORIGIN))?;
+
+    // Add edge from previous node if it exists
+    if let Some(&(last_id, last_rev)) = last_element.filter(|_| !no_links) {
+        let last_orient = if last_rev { "-" } else { "+" };
+        writeln!(file, "L\t{}\t{}\t{}\t+\t0M", last_id, last_orient, new_id)?;
+    }
+
+    // Add edge to next node if it exists
+    if let Some(handle) = next_handle.filter(|_| !no_links) {
+        let next_id = id_mapping[u64::from(handle.id()) as usize];
+        let next_orient = if handle.is_reverse() { "-" } else { "+" };
+        writeln!(file, "L\t{}\t+\t{}\t{}\t0M", *new_id, next_id, next_orient)?;
+    }
+
+    let path_element = (*new_id, false);
+    *new_id += 1;
+
+    Ok(path_element)
+}
+
+/// Fails if a merged path would grow to `num_steps`, beyond the maximum number of steps, which
+/// indicates malformed ranges (e.g. a self-overlapping block) rather than a legitimate path.
+/// Called before each range or gap node is appended
+pub(crate) fn check_path_steps(path_key: &str, num_steps: usize, max_path_steps: usize) -> io::Result<()> {
+    if num_steps > max_path_steps {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Merged path for key '{}' exceeds the maximum of {} steps (--max-path-steps)", path_key, max_path_steps)
+        ));
+    }
+    Ok(())
+}
+
+/// Oriented path step on the compacted output node IDs (ID, is reverse)
+pub(crate) type PathStep = (usize, bool);
+
+/// Output step of a handle of the graph
+pub(crate) fn output_step(handle: Handle, id_mapping: &[usize]) -> PathStep {
+    (id_mapping[u64::from(handle.id()) as usize], handle.is_reverse())
+}
+
+/// Counts the steps of a range appended to a path of `num_steps` steps ending on `last_step`, and
+/// records the known overlaps between them as (index of the step, overlap in bp) in `path_overlaps`.
+/// The steps are not copied: `path_source_steps` reads them from the range when the path is written
+pub(crate) fn add_range_to_path(
+    range: &RangeInfo,
+    id_mapping: &[usize],
+    link_overlaps: &LinkOverlaps,
+    num_steps: &mut usize,
+    last_step: &mut Option<PathStep>,
+    path_overlaps: &mut Vec<(usize, usize)>
+) {
+    if !link_overlaps.is_empty() {
+        for (idx, pair) in range.steps.windows(2).enumerate() {
+            let overlap = boundary_overlap(link_overlaps, pair[0], pair[1]);
+            if overlap > 0 {
+                path_overlaps.push((*num_steps + idx + 1, overlap));
+            }
+        }
+    }
+    *num_steps += range.steps.len();
+    if let Some(&handle) = range.steps.last() {
+        *last_step = Some(output_step(handle, id_mapping));
+    }
+}
+
+/// Output steps of a merged path, read by reference from its ranges and gap nodes
+pub(crate) fn path_source_steps<'a>(sources: &'a [PathSource<'a>], id_mapping: &'a [usize]) -> impl Iterator<Item = PathStep> + 'a {
+    sources.iter().flat_map(move |source| {
+        let (range_steps, gap_step): (&[Handle], _) = match source {
+            PathSource::Range(range) => (&range.steps, None),
+            PathSource::Gap(_, _, gap_step) => (&[], Some(*gap_step)),
+        };
+        range_steps.iter().map(move |&handle| output_step(handle, id_mapping)).chain(gap_step)
+    })
+}
+
+/// Name of an optional field, e.g. `SN` for `SN:Z:chr1`
+pub(crate) fn tag_name(tag: &str) -> &str {
+    tag.split(':').next().unwrap_or(tag)
+}
+
+/// Merges the optional fields of the ranges written as one path. The first range giving a tag,
+/// in path order, decides its value
+pub(crate) fn merged_path_tags<'a>(ranges: impl Iterator<Item = &'a RangeInfo>) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for range in ranges {
+        for tag in &range.tags {
+            if !tags.iter().any(|kept| tag_name(kept) == tag_name(tag)) {
+                tags.push(tag.clone());
+            }
+        }
+    }
+    tags
+}
+
+/// Warns about the path keys whose ranges give different values to the same P line tag,
+/// which keep the value of their first range. Returns the number of such keys
+pub(crate) fn path_tag_conflicts(path_key_ranges: &FxHashMap<PathKey, Vec<RangeInfo>>) -> usize {
+    let mut path_keys: Vec<&PathKey> = path_key_ranges.keys().collect();
+    path_keys.sort();
+    let mut conflicts = 0;
+    for path_key in path_keys {
+        let ranges = &path_key_ranges[path_key];
+        let kept = merged_path_tags(ranges.iter());
+        let conflicting: Vec<&str> = kept.iter()
+            .filter(|kept| ranges.iter().flat_map(|range| &range.tags).any(|tag| tag_name(tag) == tag_name(kept) && tag != *kept))
+            .map(|kept| tag_name(kept))
+            .collect();
+        if !conflicting.is_empty() {
+            warn!(stage = "merge", path_key = &**path_key;
+                "The ranges of path key '{}' have different values for the tags {}, keeping those of the first range", path_key, conflicting.join(", "));
+            conflicts += 1;
+        }
+    }
+    conflicts
+}
+
+/// Writes a P line, streaming the steps to the writer one at a time rather than
+/// building the line in memory, so long paths take no extra memory (`out` should be buffered).
+/// The overlaps field lists the known overlaps (index of the step, overlap in bp), with `0M`
+/// elsewhere, or is `*` if none is known. The tags are appended as optional fields
+pub(crate) fn write_path<W: Write>(
+    out: &mut W,
+    path_name: &str,
+    path_elements: impl IntoIterator<Item = PathStep>,
+    path_overlaps: &[(usize, usize)],
+    tags: &[String]
+) -> io::Result<()> {
+    write!(out, "P\t{}\t", path_name)?;
+    let mut num_steps = 0;
+    for (node_id, is_reverse) in path_elements {
+        if num_steps > 0 {
+            out.write_all(b",")?;
+        }
+        write!(out, "{}{}", node_id, if is_reverse { '-' } else { '+' })?;
+        num_steps += 1;
+    }
+    out.write_all(b"\t")?;
+    if path_overlaps.is_empty() {
+        out.write_all(b"*")?;
+    } else {
+        let mut known = path_overlaps.iter().peekable();
+        for idx in 1..num_steps {
+            if idx > 1 {
+                out.write_all(b",")?;
+            }
+            let overlap = known.next_if(|&&(step_idx, _)| step_idx == idx).map_or(0, |&(_, overlap)| overlap);
+            write!(out, "{}M", overlap)?;
+        }
+    }
+    for tag in tags {
+        write!(out, "\t{}", tag)?;
+    }
+    out.write_all(b"\n")
+}
@@ -0,0 +1,75 @@
+use std::path::Path;
+use std::process::{Command, Output};
+
+/// Writes a GFA named `name` in `dir`, returning its path
+fn write_gfa(dir: &Path, name: &str, content: &str) -> String {
+    let path = dir.join(name);
+    std::fs::write(&path, content).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+/// Runs gfalace with the given arguments
+fn gfalace(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_gfalace")).args(args).output().unwrap()
+}
+
+#[test]
+fn test_default_log_level() {
+    // The second block has segments but no paths, which is a warning
+    let dir = tempfile::tempdir().unwrap();
+    let block_a = write_gfa(dir.path(), "a.gfa", "S\t1\tACGT\nP\tsample#1#chr1:0-4\t1+\t*\n");
+    let block_b = write_gfa(dir.path(), "b.gfa", "S\t1\tGG\n");
+    let output = dir.path().join("out.gfa");
+    let output = output.to_str().unwrap();
+
+    // Warnings are logged by default, -q keeps only the summary, and -qq logs nothing
+    for (quiet, warning, summary) in [(None, true, true), (Some("-q"), false, true), (Some("-qq"), false, false)] {
+        let mut args = vec!["-g", &block_a, &block_b, "-o", output, "--force"];
+        args.extend(quiet);
+        let run = gfalace(&args);
+        let stderr = String::from_utf8_lossy(&run.stderr);
+        assert!(run.status.success(), "{}", stderr);
+        assert_eq!(stderr.contains("has segments but no paths"), warning, "{:?}: {}", quiet, stderr);
+        assert_eq!(stderr.contains("Summary:"), summary, "{:?}: {}", quiet, stderr);
+    }
+}
+
+#[test]
+fn test_max_gap() {
+    // A 6 bp gap between the two blocks
+    let dir = tempfile::tempdir().unwrap();
+    let block_a = write_gfa(dir.path(), "a.gfa", "S\t1\tACGT\nP\tsample#1#chr1:0-4\t1+\t*\n");
+    let block_b = write_gfa(dir.path(), "b.gfa", "S\t1\tGG\nP\tsample#1#chr1:10-12\t1+\t*\n");
+    let output = dir.path().join("out.gfa");
+    let output = output.to_str().unwrap();
+    let diagnostic = "Path key 'sample#1#chr1' has a gap of 6 bp at position 4, longer than --max-gap 5";
+
+    // A warning by default, and a failure under --strict
+    for (strict, code) in [(None, 0), (Some("--strict"), 1)] {
+        let mut args = vec!["-g", &block_a, &block_b, "-o", output, "--force", "--max-gap", "5"];
+        args.extend(strict);
+        let run = gfalace(&args);
+        let stderr = String::from_utf8_lossy(&run.stderr);
+        assert_eq!(run.status.code(), Some(code), "{:?}: {}", strict, stderr);
+        assert!(stderr.contains(diagnostic), "{:?}: {}", strict, stderr);
+    }
+
+    // Gaps up to the limit are fine
+    let run = gfalace(&["-g", &block_a, &block_b, "-o", output, "--force", "--max-gap", "6", "--strict"]);
+    assert_eq!(run.status.code(), Some(0), "{}", String::from_utf8_lossy(&run.stderr));
+    assert!(!String::from_utf8_lossy(&run.stderr).contains("longer than --max-gap"));
+}
+
+#[test]
+fn test_no_sequences_conflicts() {
+    // The FASTA outputs need the sequences that --no-sequences drops
+    let dir = tempfile::tempdir().unwrap();
+    let block = write_gfa(dir.path(), "a.gfa", "S\t1\tACGT\nP\tsample#1#chr1:0-4\t1+\t*\n");
+    let output = dir.path().join("out.gfa");
+    let paths_fasta = dir.path().join("paths.fa");
+    let run = gfalace(&["-g", &block, "-o", output.to_str().unwrap(), "--no-sequences", "--paths-fasta", paths_fasta.to_str().unwrap()]);
+    let stderr = String::from_utf8_lossy(&run.stderr);
+    assert_eq!(run.status.code(), Some(1), "{}", stderr);
+    assert!(stderr.contains("--no-sequences cannot be combined with --fasta or --paths-fasta"), "{}", stderr);
+    assert!(!output.exists() && !paths_fasta.exists());
+}